@@ -0,0 +1,47 @@
+#!/bin/bash
+# Print the --perf table of links with one thread and with all CPUs. Each workload stresses
+# one phase:
+#   parse:      300 objects with 200 functions each
+#   copy_buf:   16 objects with a 10 MB .data section each
+#   relocation: 100000 absolute relocations against 3000 output sections, whose values are
+#               computed in copy_buf
+# Usage: bench/parallel.sh [threads]
+threads=${1:-$(nproc)}
+cargo build --release || exit 1
+bold=$(realpath target/release/bold)
+dir=$(mktemp -d)
+trap 'rm -rf "$dir"' EXIT
+cd "$dir" || exit 1
+
+cat <<EOF2 | cc -o start.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov \$60, %eax
+  syscall
+EOF2
+
+mkdir parse
+for i in $(seq 0 299); do
+  seq 0 199 | awk -v i=$i '
+    BEGIN { print "  .text" }
+    { printf "  .globl fn%d_%d\nfn%d_%d:\n  call fn%d_%d\n  ret\n", i, $1, i, $1, (i + 1) % 300, $1 }
+  ' | cc -o parse/$i.o -c -x assembler -
+done
+
+mkdir copy_buf
+for i in $(seq 0 15); do
+  printf "  .data\n  .fill 0xa00000, 1, $i\n" | cc -o copy_buf/$i.o -c -x assembler -
+done
+
+mkdir relocation
+seq 0 2999 | awk '{ printf "  .section sec%d, \"aw\"\n  .globl sym%d\nsym%d:\n", $1, $1, $1
+  for (j = 0; j < 33; j++) printf "  .quad sym%d\n", ($1 * 33 + j) % 3000 }' |
+  cc -o relocation/0.o -c -x assembler -
+
+for workload in parse copy_buf relocation; do
+  for n in $(echo 1 "$threads" | tr ' ' '\n' | uniq); do
+    echo "== $workload, $n thread(s)"
+    "$bold" start.o $workload/*.o --threads=$n --perf || exit 1
+  done
+done
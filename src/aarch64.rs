@@ -0,0 +1,279 @@
+//! Relocations of AArch64. Most of them patch an immediate field of an instruction instead
+//! of writing a value of their own size, so their values are encoded into the instruction
+//! word by `encode_relocation`.
+
+use elf::{abi, relocation::Rela};
+
+use crate::{relocation::RelocationInput, target::Target};
+
+/// Page(expr) in the psABI, i.e. the address with the low 12 bits cleared
+fn page(addr: u64) -> u64 {
+    addr & !0xfff
+}
+
+/// Returns the value of the relocated field before it is encoded, or None if nothing is
+/// written. It is an error if the relocation type is not supported.
+pub fn relocation_value(input: &RelocationInput, rela: &Rela) -> Result<Option<u64>, String> {
+    let s = input.symbol_addr;
+    let a = rela.r_addend;
+    let p = input.isec_addr + rela.r_offset;
+
+    let value = match rela.r_type {
+        abi::R_AARCH64_NONE => return Ok(None),
+        abi::R_AARCH64_ABS64
+        | abi::R_AARCH64_ABS32
+        | abi::R_AARCH64_ABS16
+        | abi::R_AARCH64_MOVW_UABS_G0
+        | abi::R_AARCH64_MOVW_UABS_G0_NC
+        | abi::R_AARCH64_MOVW_UABS_G1
+        | abi::R_AARCH64_MOVW_UABS_G1_NC
+        | abi::R_AARCH64_MOVW_UABS_G2
+        | abi::R_AARCH64_MOVW_UABS_G2_NC
+        | abi::R_AARCH64_MOVW_UABS_G3 => s as i64 + a,
+        // The offset in the page of ADRP
+        abi::R_AARCH64_ADD_ABS_LO12_NC
+        | abi::R_AARCH64_LDST8_ABS_LO12_NC
+        | abi::R_AARCH64_LDST16_ABS_LO12_NC
+        | abi::R_AARCH64_LDST32_ABS_LO12_NC
+        | abi::R_AARCH64_LDST64_ABS_LO12_NC
+        | abi::R_AARCH64_LDST128_ABS_LO12_NC => (s as i64 + a) & 0xfff,
+        abi::R_AARCH64_PREL64
+        | abi::R_AARCH64_PREL32
+        | abi::R_AARCH64_PREL16
+        | abi::R_AARCH64_ADR_PREL_LO21
+        | abi::R_AARCH64_LD_PREL_LO19
+        | abi::R_AARCH64_CONDBR19
+        | abi::R_AARCH64_TSTBR14
+        | abi::R_AARCH64_JUMP26
+        | abi::R_AARCH64_CALL26 => s as i64 + a - p as i64,
+        abi::R_AARCH64_ADR_PREL_PG_HI21 | abi::R_AARCH64_ADR_PREL_PG_HI21_NC => {
+            page((s as i64 + a) as u64) as i64 - page(p) as i64
+        }
+        // The GOT entry holds S + A, so the addend doesn't apply to its address
+        abi::R_AARCH64_ADR_GOT_PAGE => page(got_entry_addr(input)?) as i64 - page(p) as i64,
+        abi::R_AARCH64_LD64_GOT_LO12_NC => (got_entry_addr(input)? & 0xfff) as i64,
+        r_type => return Err(unsupported_relocation(r_type)),
+    };
+    if let Some(align) = access_size(rela.r_type) {
+        if !(value as u64).is_multiple_of(align) {
+            return Err(format!(
+                "relocation {} is not aligned to {} bytes: {:#x}",
+                r_type_as_str(rela.r_type),
+                align,
+                value
+            ));
+        }
+    }
+    Ok(Some(value as u64))
+}
+
+fn got_entry_addr(input: &RelocationInput) -> Result<u64, String> {
+    input
+        .got_entry_addr
+        .ok_or_else(|| "GOT entry is not allocated".to_owned())
+}
+
+/// Returns the size of the access of a load or store whose offset is the value of the
+/// relocation. The instruction scales the offset by the size, so the low bits of the value
+/// are lost unless it is aligned.
+fn access_size(r_type: u32) -> Option<u64> {
+    match r_type {
+        abi::R_AARCH64_LDST16_ABS_LO12_NC => Some(2),
+        abi::R_AARCH64_LDST32_ABS_LO12_NC => Some(4),
+        abi::R_AARCH64_LDST64_ABS_LO12_NC | abi::R_AARCH64_LD64_GOT_LO12_NC => Some(8),
+        abi::R_AARCH64_LDST128_ABS_LO12_NC => Some(16),
+        _ => None,
+    }
+}
+
+/// Returns true if the relocation needs a GOT entry holding the address of the symbol
+pub fn needs_got(r_type: u32) -> bool {
+    matches!(
+        r_type,
+        abi::R_AARCH64_ADR_GOT_PAGE | abi::R_AARCH64_LD64_GOT_LO12_NC
+    )
+}
+
+fn unsupported_relocation(r_type: u32) -> String {
+    format!("unsupported relocation {}", r_type_as_str(r_type))
+}
+
+/// Returns the range of values which the relocation can hold. Relocations whose names end
+/// with _NC are not checked.
+pub fn relocation_range(r_type: u32) -> Option<(i64, i64)> {
+    let bits = |n: u32| Some((-(1 << (n - 1)), (1 << (n - 1)) - 1));
+    match r_type {
+        // Absolute values may be either signed or unsigned
+        abi::R_AARCH64_ABS32 | abi::R_AARCH64_PREL32 => Some((i32::MIN as i64, u32::MAX as i64)),
+        abi::R_AARCH64_ABS16 | abi::R_AARCH64_PREL16 => Some((i16::MIN as i64, u16::MAX as i64)),
+        abi::R_AARCH64_MOVW_UABS_G0 => Some((0, (1 << 16) - 1)),
+        abi::R_AARCH64_MOVW_UABS_G1 => Some((0, (1 << 32) - 1)),
+        abi::R_AARCH64_MOVW_UABS_G2 => Some((0, (1 << 48) - 1)),
+        abi::R_AARCH64_ADR_PREL_LO21 => bits(21),
+        abi::R_AARCH64_ADR_PREL_PG_HI21 | abi::R_AARCH64_ADR_GOT_PAGE => bits(33),
+        abi::R_AARCH64_LD_PREL_LO19 | abi::R_AARCH64_CONDBR19 => bits(21),
+        abi::R_AARCH64_TSTBR14 => bits(16),
+        abi::R_AARCH64_JUMP26 | abi::R_AARCH64_CALL26 => bits(28),
+        _ => None,
+    }
+}
+
+/// Returns the size of the relocated field, which is the whole instruction for
+/// relocations of instructions
+pub fn relocation_size(r_type: u32) -> Result<usize, String> {
+    let size = match r_type {
+        abi::R_AARCH64_NONE => 0,
+        abi::R_AARCH64_ABS64 | abi::R_AARCH64_PREL64 => 8,
+        abi::R_AARCH64_ABS32 | abi::R_AARCH64_PREL32 => 4,
+        abi::R_AARCH64_ABS16 | abi::R_AARCH64_PREL16 => 2,
+        r_type if insn_field(r_type).is_some() => 4,
+        r_type => return Err(unsupported_relocation(r_type)),
+    };
+    Ok(size)
+}
+
+/// How the value of a relocation is stored in an instruction
+enum InsnField {
+    /// The value is shifted right by `shift`, and `width` bits of it are placed at bit `lsb`
+    Imm { shift: u32, lsb: u32, width: u32 },
+    /// ADR and ADRP split the 21-bit immediate into immlo at [30:29] and immhi at [23:5].
+    /// The value is shifted right by `shift` first, i.e. 12 for the page of ADRP.
+    Adr { shift: u32 },
+}
+
+fn insn_field(r_type: u32) -> Option<InsnField> {
+    let imm = |shift, lsb, width| Some(InsnField::Imm { shift, lsb, width });
+    match r_type {
+        abi::R_AARCH64_ADD_ABS_LO12_NC | abi::R_AARCH64_LDST8_ABS_LO12_NC => imm(0, 10, 12),
+        abi::R_AARCH64_LDST16_ABS_LO12_NC => imm(1, 10, 11),
+        abi::R_AARCH64_LDST32_ABS_LO12_NC => imm(2, 10, 10),
+        abi::R_AARCH64_LDST64_ABS_LO12_NC | abi::R_AARCH64_LD64_GOT_LO12_NC => imm(3, 10, 9),
+        abi::R_AARCH64_LDST128_ABS_LO12_NC => imm(4, 10, 8),
+        abi::R_AARCH64_MOVW_UABS_G0 | abi::R_AARCH64_MOVW_UABS_G0_NC => imm(0, 5, 16),
+        abi::R_AARCH64_MOVW_UABS_G1 | abi::R_AARCH64_MOVW_UABS_G1_NC => imm(16, 5, 16),
+        abi::R_AARCH64_MOVW_UABS_G2 | abi::R_AARCH64_MOVW_UABS_G2_NC => imm(32, 5, 16),
+        abi::R_AARCH64_MOVW_UABS_G3 => imm(48, 5, 16),
+        abi::R_AARCH64_LD_PREL_LO19 | abi::R_AARCH64_CONDBR19 => imm(2, 5, 19),
+        abi::R_AARCH64_TSTBR14 => imm(2, 5, 14),
+        abi::R_AARCH64_JUMP26 | abi::R_AARCH64_CALL26 => imm(2, 0, 26),
+        abi::R_AARCH64_ADR_PREL_LO21 => Some(InsnField::Adr { shift: 0 }),
+        abi::R_AARCH64_ADR_PREL_PG_HI21
+        | abi::R_AARCH64_ADR_PREL_PG_HI21_NC
+        | abi::R_AARCH64_ADR_GOT_PAGE => Some(InsnField::Adr { shift: 12 }),
+        _ => None,
+    }
+}
+
+/// Returns the bytes written for a relocation with `value`, as a little-endian integer.
+/// `field` holds the original contents of the relocated field, whose instruction bits are
+/// kept.
+pub fn encode_relocation(r_type: u32, value: u64, field: &[u8]) -> u64 {
+    let Some(insn_field) = insn_field(r_type) else {
+        return value;
+    };
+    let insn = u32::from_le_bytes(field.try_into().expect("instruction is 4 bytes"));
+    let insn = match insn_field {
+        InsnField::Imm { shift, lsb, width } => {
+            let mask = ((1 << width) - 1) << lsb;
+            (insn & !mask) | (((value >> shift) as u32) << lsb & mask)
+        }
+        InsnField::Adr { shift } => {
+            let imm = value >> shift;
+            let immlo = (imm & 0x3) as u32;
+            let immhi = ((imm >> 2) & 0x7ffff) as u32;
+            (insn & !0x60ff_ffe0) | immlo << 29 | immhi << 5
+        }
+    };
+    insn as u64
+}
+
+pub fn r_type_as_str(r_type: u32) -> String {
+    let name = match r_type {
+        abi::R_AARCH64_NONE => "R_AARCH64_NONE",
+        abi::R_AARCH64_ABS64 => "R_AARCH64_ABS64",
+        abi::R_AARCH64_ABS32 => "R_AARCH64_ABS32",
+        abi::R_AARCH64_ABS16 => "R_AARCH64_ABS16",
+        abi::R_AARCH64_PREL64 => "R_AARCH64_PREL64",
+        abi::R_AARCH64_PREL32 => "R_AARCH64_PREL32",
+        abi::R_AARCH64_PREL16 => "R_AARCH64_PREL16",
+        abi::R_AARCH64_MOVW_UABS_G0 => "R_AARCH64_MOVW_UABS_G0",
+        abi::R_AARCH64_MOVW_UABS_G0_NC => "R_AARCH64_MOVW_UABS_G0_NC",
+        abi::R_AARCH64_MOVW_UABS_G1 => "R_AARCH64_MOVW_UABS_G1",
+        abi::R_AARCH64_MOVW_UABS_G1_NC => "R_AARCH64_MOVW_UABS_G1_NC",
+        abi::R_AARCH64_MOVW_UABS_G2 => "R_AARCH64_MOVW_UABS_G2",
+        abi::R_AARCH64_MOVW_UABS_G2_NC => "R_AARCH64_MOVW_UABS_G2_NC",
+        abi::R_AARCH64_MOVW_UABS_G3 => "R_AARCH64_MOVW_UABS_G3",
+        abi::R_AARCH64_LD_PREL_LO19 => "R_AARCH64_LD_PREL_LO19",
+        abi::R_AARCH64_ADR_PREL_LO21 => "R_AARCH64_ADR_PREL_LO21",
+        abi::R_AARCH64_ADR_PREL_PG_HI21 => "R_AARCH64_ADR_PREL_PG_HI21",
+        abi::R_AARCH64_ADR_PREL_PG_HI21_NC => "R_AARCH64_ADR_PREL_PG_HI21_NC",
+        abi::R_AARCH64_ADD_ABS_LO12_NC => "R_AARCH64_ADD_ABS_LO12_NC",
+        abi::R_AARCH64_LDST8_ABS_LO12_NC => "R_AARCH64_LDST8_ABS_LO12_NC",
+        abi::R_AARCH64_TSTBR14 => "R_AARCH64_TSTBR14",
+        abi::R_AARCH64_CONDBR19 => "R_AARCH64_CONDBR19",
+        abi::R_AARCH64_JUMP26 => "R_AARCH64_JUMP26",
+        abi::R_AARCH64_CALL26 => "R_AARCH64_CALL26",
+        abi::R_AARCH64_LDST16_ABS_LO12_NC => "R_AARCH64_LDST16_ABS_LO12_NC",
+        abi::R_AARCH64_LDST32_ABS_LO12_NC => "R_AARCH64_LDST32_ABS_LO12_NC",
+        abi::R_AARCH64_LDST64_ABS_LO12_NC => "R_AARCH64_LDST64_ABS_LO12_NC",
+        abi::R_AARCH64_LDST128_ABS_LO12_NC => "R_AARCH64_LDST128_ABS_LO12_NC",
+        abi::R_AARCH64_ADR_GOT_PAGE => "R_AARCH64_ADR_GOT_PAGE",
+        abi::R_AARCH64_LD64_GOT_LO12_NC => "R_AARCH64_LD64_GOT_LO12_NC",
+        abi::R_AARCH64_COPY => "R_AARCH64_COPY",
+        abi::R_AARCH64_GLOB_DAT => "R_AARCH64_GLOB_DAT",
+        abi::R_AARCH64_JUMP_SLOT => "R_AARCH64_JUMP_SLOT",
+        abi::R_AARCH64_RELATIVE => "R_AARCH64_RELATIVE",
+        _ => return format!("unknown ({})", r_type),
+    };
+    name.to_owned()
+}
+
+pub struct AArch64;
+
+impl Target for AArch64 {
+    fn name(&self) -> &'static str {
+        "aarch64"
+    }
+
+    fn e_machine(&self) -> u16 {
+        abi::EM_AARCH64
+    }
+
+    fn emulations(&self) -> &'static [&'static str] {
+        &["aarch64linux", "aarch64elf"]
+    }
+
+    /// Like GNU ld, AArch64 assumes 64 KiB pages so that the output runs with any page size
+    /// of the kernel
+    fn page_size(&self) -> u64 {
+        0x10000
+    }
+
+    fn relocation_value(
+        &self,
+        input: &RelocationInput,
+        rela: &Rela,
+    ) -> Result<Option<u64>, String> {
+        relocation_value(input, rela)
+    }
+
+    fn apply_relocation(&self, r_type: u32, value: u64, field: &[u8]) -> u64 {
+        encode_relocation(r_type, value, field)
+    }
+
+    fn relocation_range(&self, r_type: u32) -> Option<(i64, i64)> {
+        relocation_range(r_type)
+    }
+
+    fn relocation_size(&self, r_type: u32) -> Result<usize, String> {
+        relocation_size(r_type)
+    }
+
+    fn r_type_as_str(&self, r_type: u32) -> String {
+        r_type_as_str(r_type)
+    }
+
+    fn needs_got(&self, r_type: u32) -> bool {
+        needs_got(r_type)
+    }
+}
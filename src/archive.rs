@@ -0,0 +1,95 @@
+use std::ops::Range;
+
+const MAGIC: &[u8] = b"!<arch>\n";
+const HEADER_SIZE: usize = 60;
+
+/// Member of an archive, whose data is at `range` in the archive
+pub struct ArchiveMember {
+    pub name: String,
+    pub range: Range<usize>,
+}
+
+pub fn is_archive(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Returns the members of an archive without reading their contents. Symbol tables and the
+/// table of long names are skipped. Long names are supported in both the GNU and the BSD
+/// formats.
+pub fn parse_archive(data: &[u8]) -> Result<Vec<ArchiveMember>, String> {
+    if !is_archive(data) {
+        return Err("not an archive".to_owned());
+    }
+    let mut members = vec![];
+    let mut long_names: &[u8] = &[];
+    let mut pos = MAGIC.len();
+    while pos < data.len() {
+        let header = data
+            .get(pos..pos + HEADER_SIZE)
+            .ok_or(format!("truncated member header at {:#x}", pos))?;
+        if &header[58..] != b"`\n" {
+            return Err(format!("bad member header at {:#x}", pos));
+        }
+        let size =
+            parse_decimal(&header[48..58]).ok_or(format!("bad member size at {:#x}", pos))?;
+        let start = pos + HEADER_SIZE;
+        let end = start
+            .checked_add(size)
+            .filter(|end| *end <= data.len())
+            .ok_or(format!(
+                "member at {:#x} extends past the end of the file",
+                pos
+            ))?;
+        // Members start at even offsets
+        pos = end + end % 2;
+
+        let name = String::from_utf8_lossy(&header[..16]);
+        let name = name.trim_end_matches(' ');
+        let mut range = start..end;
+        let name = match name {
+            "/" | "/SYM64/" => continue,
+            "//" => {
+                long_names = &data[range];
+                continue;
+            }
+            _ => {
+                if let Some(len) = name.strip_prefix("#1/") {
+                    // BSD: the name is at the start of the data
+                    let len = len
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|len| *len <= range.len())
+                        .ok_or(format!("bad member name at {:#x}", start - HEADER_SIZE))?;
+                    let name = &data[start..start + len];
+                    range.start += len;
+                    let end = name.iter().position(|c| *c == 0).unwrap_or(len);
+                    String::from_utf8_lossy(&name[..end]).into_owned()
+                } else if let Some(offset) = name.strip_prefix('/') {
+                    // GNU: the name is in the table of long names, terminated by "/\n"
+                    let name = offset
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|offset| long_names.get(offset..))
+                        .ok_or(format!("bad member name at {:#x}", start - HEADER_SIZE))?;
+                    let end = name
+                        .windows(2)
+                        .position(|w| w == b"/\n")
+                        .unwrap_or(name.len());
+                    String::from_utf8_lossy(&name[..end]).into_owned()
+                } else {
+                    name.strip_suffix('/').unwrap_or(name).to_owned()
+                }
+            }
+        };
+        // BSD symbol tables
+        if name.starts_with("__.SYMDEF") {
+            continue;
+        }
+        members.push(ArchiveMember { name, range });
+    }
+    Ok(members)
+}
+
+fn parse_decimal(field: &[u8]) -> Option<usize> {
+    std::str::from_utf8(field).ok()?.trim_end().parse().ok()
+}
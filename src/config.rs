@@ -1,13 +1,346 @@
+use std::collections::BTreeMap;
+
+use crate::target;
+
+/// Page size of x86-64, which is the default that segments are aligned to
 pub const PAGE_SIZE: u64 = 0x1000;
 
+/// Order of symbols within the local and global partitions of .symtab
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortSymbols {
+    /// Input order
+    None,
+    Address,
+    Name,
+}
+
+/// Which symbols defined in a shared object bind to themselves at link time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbolic {
+    None,
+    /// -Bsymbolic
+    All,
+    /// -Bsymbolic-functions
+    Functions,
+}
+
+#[derive(Clone)]
 pub struct Config {
-    pub image_base: u64,
+    /// Address of the first segment (--image-base). By default, it depends on the target.
+    pub image_base: Option<u64>,
+    /// Emulation of GNU ld which selects the target, e.g. elf_i386 (-m). By default, the
+    /// target is the machine of the first input.
+    pub emulation: Option<String>,
+    /// Input files in command-line order
+    pub inputs: Vec<String>,
+    /// Linker script laying out the output sections (-T, --script)
+    pub script: Option<String>,
+    /// Entry point symbol or address (-e, --entry, or ENTRY() in the linker script). By
+    /// default, it is _start or start, whichever is defined.
+    pub entry: Option<String>,
+    /// Symbols to be treated as undefined, so that archive members defining them are
+    /// extracted (-u, --undefined)
+    pub undefined: Vec<String>,
+    /// Same as `undefined`, but it is an error if the symbol stays undefined
+    /// (--require-defined)
+    pub require_defined: Vec<String>,
+    /// --sort-symbols
+    pub sort_symbols: SortSymbols,
+    /// Warn about interactions of common symbols (--warn-common)
+    pub warn_common: bool,
+    /// Report undefined symbols (-z defs) or not (-z undefs). By default, they are reported
+    /// for executables but not for shared objects, which import them at runtime.
+    pub z_defs: Option<bool>,
+    /// Make some sections read-only after relocation and emit PT_GNU_RELRO (-z relro, the
+    /// default)
+    pub z_relro: bool,
+    /// Report undefined symbols as warnings instead of errors and produce the output anyway
+    /// (--warn-unresolved-symbols). This only changes the severity, so it has no effect with
+    /// -z undefs, and the last of --warn-unresolved-symbols and --error-unresolved-symbols
+    /// wins.
+    pub warn_unresolved_symbols: bool,
+    /// Number of errors after which the link is aborted, 0 for unlimited (--error-limit)
+    pub error_limit: usize,
+    /// Produce a shared object (-shared)
+    pub shared: bool,
+    /// -Bsymbolic, -Bsymbolic-functions
+    pub symbolic: Symbolic,
+    /// Name of a shared object recorded in DT_SONAME (-soname, -h)
+    pub soname: Option<String>,
+    /// Version script assigning versions to exported symbols and hiding the others
+    /// (--version-script)
+    pub version_script: Option<String>,
+    /// Number of warnings after which the rest are suppressed, 0 for unlimited
+    /// (--warning-limit)
+    pub warning_limit: usize,
+    /// Treat warnings as errors (--fatal-warnings)
+    pub fatal_warnings: bool,
+    /// Export all global symbols to .dynsym (-E, --export-dynamic)
+    pub export_dynamic: bool,
+    /// Resolve all symbols at load time instead of lazily (-z now)
+    pub z_now: bool,
+    /// Path to the dynamic linker stored in .interp (--dynamic-linker)
+    pub dynamic_linker: String,
+    /// Report orphan sections, i.e. sections which are not mapped to a well-known output
+    /// section (--verbose)
+    pub verbose: bool,
+    /// Alignment of segments in memory and in the file (-z max-page-size). By default, it
+    /// is the page size of the target.
+    pub max_page_size: Option<u64>,
+    /// Addresses of output sections given by --section-start
+    pub section_start: BTreeMap<String, u64>,
+    /// Verify that sections don't overlap after layout (--check-sections, the default)
+    pub check_sections: bool,
+    /// Reject dynamic relocations against read-only sections (-z text, the default). With
+    /// -z notext, they are emitted and the output is marked with DT_TEXTREL.
+    pub z_text: bool,
+    /// Verify the values written by relocations after they are applied (--check-relocs)
+    pub check_relocs: bool,
+    /// Rewrite instructions which load addresses from the GOT to compute them directly
+    /// when possible (--relax)
+    pub relax: bool,
+    /// Number of worker threads, or 0 to use all CPUs (--threads=N, --no-threads)
+    pub threads: usize,
+    /// Print the time spent in each phase of the link (--perf)
+    pub perf: bool,
+    /// Print statistics of the link such as memory usage (--stats)
+    pub stats: bool,
+    /// Write the layout of the output to this file as JSON (--json-report)
+    pub json_report: Option<String>,
+    /// Print the section headers of the output to stdout (--print-sections)
+    pub print_sections: bool,
+    /// Print the symbols in .symtab to stdout (--print-symbols)
+    pub print_symbols: bool,
+    /// Check in debug builds that nothing but the inputs and the options affects the
+    /// output (--reproducible, the default). --no-reproducible disables the checks.
+    pub reproducible: bool,
+    /// Link twice and fail if the outputs differ (--verify-reproducible). The second link is
+    /// done in memory and is included in --perf and --stats.
+    pub verify_reproducible: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::new()
+    }
 }
 
 impl Config {
     pub fn new() -> Config {
         Config {
-            image_base: 0x400000,
+            image_base: None,
+            emulation: None,
+            inputs: vec![],
+            script: None,
+            entry: None,
+            undefined: vec![],
+            require_defined: vec![],
+            sort_symbols: SortSymbols::None,
+            warn_common: false,
+            z_defs: None,
+            z_relro: true,
+            warn_unresolved_symbols: false,
+            error_limit: 20,
+            shared: false,
+            symbolic: Symbolic::None,
+            soname: None,
+            version_script: None,
+            warning_limit: 20,
+            fatal_warnings: false,
+            export_dynamic: false,
+            z_now: false,
+            dynamic_linker: "/lib64/ld-linux-x86-64.so.2".to_owned(),
+            verbose: false,
+            max_page_size: None,
+            section_start: BTreeMap::new(),
+            check_sections: true,
+            z_text: true,
+            check_relocs: false,
+            relax: false,
+            threads: 0,
+            perf: false,
+            stats: false,
+            json_report: None,
+            print_sections: false,
+            print_symbols: false,
+            reproducible: true,
+            verify_reproducible: false,
+        }
+    }
+
+    /// Parse command-line arguments (excluding the program name).
+    pub fn from_args(args: &[String]) -> Result<Config, String> {
+        let mut config = Config::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            // Accept both `--opt value` and `--opt=value`
+            let (opt, inline_value) = match arg.split_once('=') {
+                Some((opt, value)) if opt.starts_with("--") => (opt, Some(value.to_owned())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next().cloned())
+                    .ok_or(format!("missing argument to {}", opt))
+            };
+            match opt {
+                "-e" | "--entry" => config.entry = Some(value()?),
+                "-u" | "--undefined" => config.undefined.push(value()?),
+                "--require-defined" => config.require_defined.push(value()?),
+                "--warn-common" => config.warn_common = true,
+                "--warn-unresolved-symbols" => config.warn_unresolved_symbols = true,
+                "--error-unresolved-symbols" => config.warn_unresolved_symbols = false,
+                "--error-limit" => config.error_limit = parse_limit(opt, &value()?)?,
+                "--warning-limit" => config.warning_limit = parse_limit(opt, &value()?)?,
+                "--fatal-warnings" => config.fatal_warnings = true,
+                "--no-fatal-warnings" => config.fatal_warnings = false,
+                "--verbose" => config.verbose = true,
+                "--image-base" => {
+                    let value = value()?;
+                    config.image_base = Some(
+                        parse_number(&value)
+                            .ok_or(format!("invalid argument to {}: {}", opt, value))?,
+                    );
+                }
+                "--threads" => {
+                    config.threads = match &inline_value {
+                        Some(value) => match value.parse() {
+                            Ok(n) if n > 0 => n,
+                            _ => return Err(format!("invalid argument to {}: {}", opt, value)),
+                        },
+                        None => 0,
+                    }
+                }
+                "--no-threads" => config.threads = 1,
+                "--check-relocs" => config.check_relocs = true,
+                "--perf" => config.perf = true,
+                "--stats" => config.stats = true,
+                "--json-report" => config.json_report = Some(value()?),
+                "--print-sections" => config.print_sections = true,
+                "--print-symbols" => config.print_symbols = true,
+                "--reproducible" => config.reproducible = true,
+                "--no-reproducible" => config.reproducible = false,
+                "--verify-reproducible" => config.verify_reproducible = true,
+                "--relax" => config.relax = true,
+                "--no-relax" => config.relax = false,
+                "--check-sections" => config.check_sections = true,
+                "--no-check-sections" => config.check_sections = false,
+                "--section-start" => {
+                    let value = value()?;
+                    let (name, addr) = value
+                        .split_once('=')
+                        .and_then(|(name, addr)| Some((name, parse_number(addr)?)))
+                        .ok_or(format!("invalid argument to {}: {}", opt, value))?;
+                    config.section_start.insert(name.to_owned(), addr);
+                }
+                "-E" | "--export-dynamic" => config.export_dynamic = true,
+                "--no-export-dynamic" => config.export_dynamic = false,
+                "-dynamic-linker" | "--dynamic-linker" => config.dynamic_linker = value()?,
+                "-shared" | "--shared" => config.shared = true,
+                "-Bsymbolic" => config.symbolic = Symbolic::All,
+                "-Bsymbolic-functions" => config.symbolic = Symbolic::Functions,
+                "-h" | "-soname" | "--soname" => config.soname = Some(value()?),
+                "--version-script" => config.version_script = Some(value()?),
+                "-z" => config.parse_z_option(&value()?)?,
+                _ if opt.starts_with("-z") => config.parse_z_option(&opt[2..])?,
+                "-T" | "--script" => config.script = Some(value()?),
+                // -Ttext, -Tdata and -Tbss of GNU ld set section addresses instead
+                _ if opt.starts_with("-T")
+                    && !["-Ttext", "-Tdata", "-Tbss"]
+                        .iter()
+                        .any(|prefix| opt.starts_with(prefix)) =>
+                {
+                    config.script = Some(opt[2..].to_owned())
+                }
+                "-m" => config.set_emulation(&value()?)?,
+                _ if opt.starts_with("-m") => config.set_emulation(&opt[2..])?,
+                "--sort-symbols" => {
+                    config.sort_symbols = match value()?.as_str() {
+                        "none" => SortSymbols::None,
+                        "address" => SortSymbols::Address,
+                        "name" => SortSymbols::Name,
+                        mode => return Err(format!("unknown --sort-symbols mode: {}", mode)),
+                    }
+                }
+                _ if opt.starts_with('-') && opt.len() > 1 => {
+                    return Err(format!("unknown option: {}", arg));
+                }
+                _ => config.inputs.push(arg.clone()),
+            }
+        }
+        let page_size = config.max_page_size.unwrap_or(PAGE_SIZE);
+        if let Some(image_base) = config.image_base {
+            if !image_base.is_multiple_of(page_size) {
+                return Err(format!(
+                    "image base {:#x} is not a multiple of max-page-size {:#x}",
+                    image_base, page_size
+                ));
+            }
+        }
+        Ok(config)
+    }
+
+    fn set_emulation(&mut self, emulation: &str) -> Result<(), String> {
+        if target::from_emulation(emulation).is_none() {
+            return Err(format!("unknown emulation: {}", emulation));
+        }
+        self.emulation = Some(emulation.to_owned());
+        Ok(())
+    }
+
+    fn parse_z_option(&mut self, keyword: &str) -> Result<(), String> {
+        match keyword {
+            "defs" => self.z_defs = Some(true),
+            "undefs" => self.z_defs = Some(false),
+            "relro" => self.z_relro = true,
+            "norelro" => self.z_relro = false,
+            "now" => self.z_now = true,
+            "lazy" => self.z_now = false,
+            "text" => self.z_text = true,
+            "notext" => self.z_text = false,
+            _ if keyword.starts_with("max-page-size=") => {
+                let value = &keyword["max-page-size=".len()..];
+                self.max_page_size = Some(
+                    parse_number(value)
+                        .filter(|size| size.is_power_of_two())
+                        .ok_or(format!("invalid -z max-page-size: {}", value))?,
+                );
+            }
+            _ => return Err(format!("unknown -z option: {}", keyword)),
         }
+        Ok(())
+    }
+
+    /// Entry symbol which is reported if it is not defined
+    pub fn entry_symbol(&self) -> &str {
+        self.entry.as_deref().unwrap_or("_start")
+    }
+
+    /// Whether undefined symbols are reported, as given by -z defs and -z undefs or the
+    /// default for the output
+    pub fn reports_undefined_symbols(&self) -> bool {
+        self.z_defs.unwrap_or(!self.shared)
     }
+
+    /// Symbols which are treated as referenced before any input file is loaded
+    pub fn root_symbols(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.entry_symbol())
+            .chain(self.undefined.iter().map(String::as_str))
+            .chain(self.require_defined.iter().map(String::as_str))
+    }
+}
+
+/// Parse a decimal or 0x-prefixed hexadecimal number
+pub fn parse_number(value: &str) -> Option<u64> {
+    match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn parse_limit(opt: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid argument to {}: {}", opt, value))
 }
@@ -1,18 +1,31 @@
-use std::{borrow::Borrow, cell::RefCell, collections::HashMap, ops::Deref, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use elf::abi::SHF_TLS;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{
-    input_section::{InputSection, InputSectionId, ObjectFile, ObjectId, Symbol},
-    output_section::{get_output_section_name, OutputSection, OutputSectionId},
+    diagnostics::{Diagnostic, Diagnostics},
+    input_section::{InputSection, InputSectionId, ObjectFile, ObjectId, SymbolCell},
+    output_section::{OutputSection, OutputSectionId},
+    perf::Perf,
+    symbol_name::{Interner, SymbolName},
+    utils::align_to,
 };
 
 // https://github.com/tamaroning/mold/blob/3489a464c6577ea1ee19f6b9ae3fe46237f4e4ee/output_chunks.cc#L386
-pub const COMMON_SECTION_NAMES: [&str; 10] = [
+// Names are matched as prefixes in this order, so .data.rel.ro has to precede .data.
+pub const COMMON_SECTION_NAMES: [&str; 12] = [
     ".text",
-    ".data",
+    ".init",
+    ".fini",
     ".data.rel.ro",
+    ".data",
     ".rodata",
-    ".bss",
     ".bss.rel.ro",
+    ".bss",
     //".ctors",
     //".dtors",
     ".init_array",
@@ -21,20 +34,52 @@ pub const COMMON_SECTION_NAMES: [&str; 10] = [
     ".tdata",
 ];
 
+/// Counters which the IDs of files, sections and symbol names are taken from. Each link has
+/// its own, so that the IDs, and the order of everything iterated by ID, don't depend on the
+/// links done before in the same process.
+#[derive(Default)]
+pub struct IdSpace {
+    pub object_files: AtomicUsize,
+    pub input_sections: AtomicUsize,
+    pub output_sections: AtomicUsize,
+    /// Names of symbols, which are freed along with the rest of the link
+    pub symbol_names: Interner,
+}
+
+/// Files and sections are iterated in the order of their IDs, i.e. the order in which they
+/// were created, so that the output does not depend on hashing.
 pub struct Context {
-    file_pool: HashMap<ObjectId, ObjectFile>,
-    input_sections: HashMap<InputSectionId, InputSection>,
-    output_sections: HashMap<OutputSectionId, OutputSection>,
-    global_symbols: HashMap<String, Arc<RefCell<Symbol>>>,
+    file_pool: BTreeMap<ObjectId, ObjectFile>,
+    input_sections: BTreeMap<InputSectionId, InputSection>,
+    output_sections: BTreeMap<OutputSectionId, OutputSection>,
+    /// Output sections by their name, type and whether they are TLS
+    output_section_keys: HashMap<(String, u32, bool), OutputSectionId>,
+    global_symbols: FxHashMap<SymbolName, Arc<SymbolCell>>,
+    /// Size and alignment of common symbols
+    common_symbols: FxHashMap<SymbolName, (u64, u64)>,
+    /// Global symbols whose definitions come from DSOs
+    dso_symbols: FxHashSet<SymbolName>,
+    /// Shared with the driver, which prints the diagnostics at the end of each phase
+    pub diag: Arc<Diagnostics>,
+    /// Shared with the driver, which creates the input files before the context
+    pub ids: Arc<IdSpace>,
+    /// Shared with the driver, which returns it along with the output
+    pub perf: Arc<Perf>,
 }
 
 impl Context {
-    pub fn new() -> Context {
+    pub fn new(diag: Arc<Diagnostics>, ids: Arc<IdSpace>, perf: Arc<Perf>) -> Context {
         Context {
-            file_pool: HashMap::new(),
-            output_sections: HashMap::new(),
-            input_sections: HashMap::new(),
-            global_symbols: HashMap::new(),
+            file_pool: BTreeMap::new(),
+            output_sections: BTreeMap::new(),
+            output_section_keys: HashMap::new(),
+            input_sections: BTreeMap::new(),
+            global_symbols: FxHashMap::default(),
+            common_symbols: FxHashMap::default(),
+            dso_symbols: FxHashSet::default(),
+            diag,
+            ids,
+            perf,
         }
     }
 
@@ -50,10 +95,6 @@ impl Context {
         self.file_pool.values()
     }
 
-    pub fn files_mut(&mut self) -> impl Iterator<Item = &mut ObjectFile> {
-        self.file_pool.values_mut()
-    }
-
     pub fn get_file(&self, id: ObjectId) -> &ObjectFile {
         self.file_pool.get(&id).unwrap()
     }
@@ -62,34 +103,143 @@ impl Context {
         self.file_pool.get_mut(&id).unwrap()
     }
 
-    pub fn add_global_symbol(&mut self, symbol: Arc<RefCell<Symbol>>) {
-        let sym = symbol.deref().borrow();
+    /// Drop files which are not linked, along with their input sections
+    pub fn remove_dead_files(&mut self) {
+        let dead = self
+            .files()
+            .filter(|file| !file.is_alive())
+            .map(|file| file.get_id())
+            .collect::<Vec<_>>();
+        for id in dead {
+            let file = self.file_pool.remove(&id).unwrap();
+            log::debug!("Discard {}", file.get_file_name());
+            for isec in file.get_input_sections().iter().flatten() {
+                self.input_sections.remove(isec);
+            }
+            // Nothing refers to the file any more, but members of the same archive keep the
+            // buffer alive. Give the pages of the member back to the kernel.
+            let released = file.get_data().release();
+            self.perf.released_input_bytes.add(released as u64);
+        }
+    }
+
+    /// `from_dso` is true if the symbol is defined in a DSO. Definitions in object files
+    /// override those in DSOs, and the first DSO wins among DSOs. Returns false if the
+    /// symbol is a second non-weak definition.
+    pub fn add_global_symbol(
+        &mut self,
+        symbol: Arc<SymbolCell>,
+        from_dso: bool,
+        warn_common: bool,
+    ) -> bool {
+        let sym = symbol.read();
         assert!(sym.is_global());
         if sym.esym.get_esym().is_undefined() {
-            return;
+            return true;
         }
 
-        let name = sym.name.clone();
-        if let Some(dup) = self.global_symbols.get(&name) {
-            let dup = dup.deref().borrow();
-            if dup.esym.is_weak() {
-                log::debug!("Override weak symbol: {}", name);
+        let name = sym.name;
+        if self.global_symbols.contains_key(&name) {
+            if from_dso {
+                return true;
+            }
+            if self.dso_symbols.remove(&name) {
+                log::debug!("Override symbol defined in a DSO: {}", name);
+                std::mem::drop(sym);
+                self.global_symbols.insert(name, symbol);
+                return true;
+            }
+        } else if from_dso {
+            self.dso_symbols.insert(name);
+        }
+        if sym.esym.is_common() {
+            // The size and alignment of a common symbol are the maximum of all declarations.
+            // For common symbols, st_value holds the alignment.
+            let size = sym.esym.get_esym().st_size;
+            let align = sym.esym.get_esym().st_value;
+            if let Some((prev_size, prev_align)) = self.common_symbols.get_mut(&name) {
+                if warn_common && size > *prev_size {
+                    self.diag
+                        .warn(format!("common of {} overridden by larger common", name));
+                }
+                *prev_size = (*prev_size).max(size);
+                *prev_align = (*prev_align).max(align);
             } else {
-                log::error!("Duplicate non-weak symbol: {}", name);
-                //panic!();
+                self.common_symbols.insert(name, (size, align));
+            }
+        }
+
+        if let Some(dup) = self.global_symbols.get(&name) {
+            let dup = dup.read();
+            match (dup.esym.is_common(), sym.esym.is_common()) {
+                (true, true) => {
+                    // The first common symbol is allocated with the merged size and alignment
+                    if warn_common {
+                        self.diag.warn(format!("multiple common of {}", name));
+                    }
+                    return true;
+                }
+                (false, true) => {
+                    // A definition, even if weak, is preferred over common symbols
+                    if warn_common {
+                        self.diag
+                            .warn(format!("common of {} overridden by definition", name));
+                    }
+                    return true;
+                }
+                (true, false) => {
+                    if warn_common {
+                        self.diag
+                            .warn(format!("definition of {} overriding common", name));
+                    }
+                }
+                (false, false) => {
+                    if sym.esym.is_weak() {
+                        // The first definition wins
+                        log::debug!("Ignore weak symbol: {}", name);
+                        return true;
+                    } else if dup.esym.is_weak() {
+                        log::debug!("Override weak symbol: {}", name);
+                    } else {
+                        self.diag.error(
+                            Diagnostic::new(format!("duplicate symbol: {}", name)).symbol(name),
+                        );
+                        return false;
+                    }
+                }
             }
         } else {
             log::debug!("Add global symbol: {}", name);
         }
         std::mem::drop(sym);
         self.global_symbols.insert(name, symbol);
+        true
+    }
+
+    /// Register a reference to a symbol which is not defined in the link, so that the other
+    /// references share it. Shared objects import such symbols at runtime.
+    pub fn add_undefined_symbol(&mut self, symbol: Arc<SymbolCell>) {
+        let name = symbol.read().name;
+        self.global_symbols.entry(name).or_insert(symbol);
+    }
+
+    /// Returns the merged size and alignment of a common symbol
+    pub fn get_common_symbol(&self, name: SymbolName) -> Option<(u64, u64)> {
+        self.common_symbols.get(&name).copied()
+    }
+
+    /// Returns the name interned for this link
+    pub fn intern(&self, name: &str) -> SymbolName {
+        self.ids.symbol_names.intern(name)
     }
 
-    pub fn get_global_symbol(&self, name: &str) -> Option<&Arc<RefCell<Symbol>>> {
-        self.global_symbols.get(name)
+    pub fn get_global_symbol(&self, name: SymbolName) -> Option<&Arc<SymbolCell>> {
+        self.global_symbols.get(&name)
     }
 
-    pub fn get_global_symbols(&self) -> impl Iterator<Item = &Arc<RefCell<Symbol>>> {
+    /// Returns the global symbols in no particular order, so the order must not affect the
+    /// output
+    pub fn get_global_symbols(&self) -> impl Iterator<Item = &Arc<SymbolCell>> {
         self.global_symbols.values()
     }
 
@@ -109,100 +259,39 @@ impl Context {
         self.output_sections.get_mut(&id).unwrap()
     }
 
-    pub fn output_sections(&self) -> impl Iterator<Item = &OutputSection> {
-        self.output_sections.values()
+    /// Place the input sections of an output section one after another from `offset`, and
+    /// returns the end offset
+    pub fn set_input_section_offsets(&mut self, id: OutputSectionId, mut offset: u64) -> u64 {
+        let osec = self.output_sections.get(&id).unwrap();
+        for isec in osec.get_input_sections() {
+            let isec = self.input_sections.get_mut(isec).unwrap();
+            offset = align_to(offset, isec.get_addralign());
+            isec.set_offset(offset);
+            offset += isec.get_size();
+        }
+        offset
     }
 
-    pub fn output_sections_mut(&mut self) -> impl Iterator<Item = &mut OutputSection> {
-        self.output_sections.values_mut()
+    pub fn output_sections(&self) -> impl Iterator<Item = &OutputSection> {
+        self.output_sections.values()
     }
 
+    /// Output sections are identified by the name, the type and whether they are TLS.
+    /// Other flags are merged by OutputSection::add_input_section.
     pub fn get_or_create_output_section_mut(
         &mut self,
         name: &str,
         sh_type: u32,
         sh_flags: u64,
     ) -> &mut OutputSection {
-        let mut find = None;
-        for section in &mut self.output_sections_mut() {
-            if &section.get_name() == name
-                && section.get_sh_type() == sh_type
-                && section.get_sh_flags() == sh_flags
-            {
-                find = Some(section.get_id());
-                break;
-            }
-        }
-
-        let id = find.unwrap_or_else(|| {
+        let key = (name.to_owned(), sh_type, sh_flags & SHF_TLS as u64 != 0);
+        let id = *self.output_section_keys.entry(key).or_insert_with(|| {
             log::debug!("Create new output section: {}", name);
-            let section = OutputSection::new(name.to_string(), sh_type, sh_flags);
+            let section = OutputSection::new(name.to_string(), sh_type, sh_flags, &self.ids);
             let id = section.get_id();
             self.output_sections.insert(id, section);
             id
         });
         self.output_sections.get_mut(&id).unwrap()
     }
-
-    pub fn dump(&self) {
-        //self.dump_sections();
-        //self.dump_symbols();
-    }
-
-    fn dump_sections(&self) {
-        for file in self.files() {
-            log::debug!("Sections in '{}'", file.get_file_name());
-            for (elf_section, input_section) in file
-                .get_elf_sections()
-                .iter()
-                .zip(file.get_input_sections().iter())
-            {
-                log::debug!("\t{:?}", elf_section.name);
-                if let Some(input_section) = input_section {
-                    let input_section = self.get_input_section(*input_section);
-                    let output_section = get_output_section_name(input_section.get_name());
-                    log::debug!("\t\tOutputSection: {:?}", output_section);
-                    let num_relas = input_section.get_relas().len();
-                    log::debug!("\t\tNumber of Relas: {}", num_relas);
-                    continue;
-                }
-            }
-        }
-    }
-
-    fn dump_symbols(&self) {
-        for file in self.files() {
-            log::debug!("Symbols in '{}'", file.get_file_name());
-            for symbol in file.get_symbols().iter() {
-                if let Some(symbol) = symbol {
-                    let symbol = symbol.deref().borrow();
-                    let definiton_loc = if let Some(file_id) = symbol.file {
-                        let file = self.get_file(file_id);
-                        file.get_file_name().to_owned()
-                    } else {
-                        "undefined".to_owned()
-                    };
-                    log::debug!("\t\"{}\" ({})", symbol.name, definiton_loc);
-                }
-            }
-        }
-        let global_symbols = self
-            .global_symbols
-            .iter()
-            .map(|(_, symbol)| {
-                let symbol = symbol.deref().borrow();
-                let definiton_loc = if let Some(file_id) = symbol.file {
-                    let file = self.get_file(file_id);
-                    file.get_file_name().to_owned()
-                } else {
-                    "undefined".to_owned()
-                };
-                format!("\t\"{}\" ({})", symbol.name, definiton_loc)
-            })
-            .collect::<Vec<String>>();
-        log::debug!("Global symbols:");
-        for symbol in global_symbols {
-            log::debug!("{}", symbol);
-        }
-    }
 }
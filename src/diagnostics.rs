@@ -0,0 +1,219 @@
+use std::{collections::HashSet, fmt::Display, sync::Mutex};
+
+/// Error or warning. Fields other than the message are the location it refers to, which
+/// is also used to sort diagnostics before they are printed.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Diagnostic {
+    file: Option<String>,
+    section: Option<String>,
+    offset: Option<u64>,
+    symbol: Option<String>,
+    msg: String,
+}
+
+impl Diagnostic {
+    pub fn new(msg: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            file: None,
+            section: None,
+            offset: None,
+            symbol: None,
+            msg: msg.into(),
+        }
+    }
+
+    pub fn file(mut self, file: impl Into<String>) -> Diagnostic {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Location in an input section
+    pub fn at(self, file: &str, section: &str, offset: u64) -> Diagnostic {
+        let mut diag = self.file(file);
+        diag.section = Some(section.to_owned());
+        diag.offset = Some(offset);
+        diag
+    }
+
+    /// Symbol which the diagnostic is about. It isn't printed, so the message has to name it.
+    pub fn symbol(mut self, name: impl Display) -> Diagnostic {
+        self.symbol = Some(name.to_string());
+        self
+    }
+}
+
+impl From<String> for Diagnostic {
+    fn from(msg: String) -> Diagnostic {
+        Diagnostic::new(msg)
+    }
+}
+
+impl From<&str> for Diagnostic {
+    fn from(msg: &str) -> Diagnostic {
+        Diagnostic::new(msg)
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}", file)?;
+            match (&self.section, self.offset) {
+                (Some(section), Some(offset)) => write!(f, ":({}+{:#x})", section, offset)?,
+                (Some(section), None) => write!(f, ":({})", section)?,
+                _ => (),
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.msg)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Diagnostics reported since they were last printed
+    pending_errors: Vec<Diagnostic>,
+    pending_warnings: Vec<Diagnostic>,
+    /// All distinct diagnostics, to drop duplicates
+    seen: HashSet<(bool, Diagnostic)>,
+    /// Diagnostics which have been printed, or suppressed by the limits
+    errors: Vec<Diagnostic>,
+    warnings: Vec<Diagnostic>,
+}
+
+/// Sink of errors and warnings reported to the user. Diagnostics are collected and printed
+/// at the end of each phase by `flush`, sorted by location and without duplicates, so that
+/// the output doesn't depend on the order in which threads report them.
+pub struct Diagnostics {
+    /// Maximum number of errors printed before the link is aborted (0 means unlimited)
+    error_limit: usize,
+    /// Maximum number of warnings printed. The rest are suppressed (0 means unlimited)
+    warning_limit: usize,
+    /// Report warnings as errors (--fatal-warnings)
+    fatal_warnings: bool,
+    /// Print diagnostics when they are flushed. Otherwise they are only collected.
+    print: bool,
+    state: Mutex<State>,
+}
+
+impl Diagnostics {
+    pub fn new(error_limit: usize, warning_limit: usize, fatal_warnings: bool) -> Diagnostics {
+        Diagnostics {
+            error_limit,
+            warning_limit,
+            fatal_warnings,
+            print: true,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Diagnostics which are collected without being printed and without limits
+    pub fn silent() -> Diagnostics {
+        Diagnostics {
+            print: false,
+            ..Diagnostics::new(0, 0, false)
+        }
+    }
+
+    pub fn error(&self, diag: impl Into<Diagnostic>) {
+        let diag = diag.into();
+        let mut state = self.state.lock().unwrap();
+        if state.seen.insert((true, diag.clone())) {
+            state.pending_errors.push(diag);
+        }
+    }
+
+    pub fn warn(&self, diag: impl Into<Diagnostic>) {
+        if self.fatal_warnings {
+            self.error(diag);
+            return;
+        }
+        let diag = diag.into();
+        let mut state = self.state.lock().unwrap();
+        if state.seen.insert((false, diag.clone())) {
+            state.pending_warnings.push(diag);
+        }
+    }
+
+    /// Print the diagnostics reported since the last call. Returns false if there are more
+    /// errors than --error-limit, in which case the link has to stop.
+    pub fn flush(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let mut warnings = std::mem::take(&mut state.pending_warnings);
+        warnings.sort();
+        for diag in warnings {
+            let n = state.warnings.len();
+            if self.print {
+                if self.warning_limit == 0 || n < self.warning_limit {
+                    log::warn!(target: "bold", "{}", diag);
+                } else if n == self.warning_limit {
+                    log::warn!(
+                        target: "bold",
+                        "too many warnings emitted, suppressing the rest (use --warning-limit=0 to see all)"
+                    );
+                }
+            }
+            state.warnings.push(diag);
+        }
+
+        let mut errors = std::mem::take(&mut state.pending_errors);
+        errors.sort();
+        for diag in errors {
+            let n = state.errors.len();
+            if self.print {
+                if self.error_limit == 0 || n < self.error_limit {
+                    log::error!(target: "bold", "{}", diag);
+                } else if n == self.error_limit {
+                    log::error!(
+                        target: "bold",
+                        "too many errors emitted, stopping now (use --error-limit=0 to see all)"
+                    );
+                }
+            }
+            state.errors.push(diag);
+        }
+        self.error_limit == 0 || state.errors.len() <= self.error_limit
+    }
+
+    /// Print the remaining diagnostics and a line with the numbers of errors and warnings
+    /// if there are any
+    pub fn finish(&self) {
+        self.flush();
+        let state = self.state.lock().unwrap();
+        let (errors, warnings) = (state.errors.len(), state.warnings.len());
+        let summary = format!(
+            "{} error{}, {} warning{}",
+            errors,
+            if errors == 1 { "" } else { "s" },
+            warnings,
+            if warnings == 1 { "" } else { "s" }
+        );
+        if errors > 0 {
+            log::error!(target: "bold", "{}", summary);
+        } else if warnings > 0 {
+            log::warn!(target: "bold", "{}", summary);
+        }
+    }
+
+    /// Returns true if any error has been reported, including warnings made fatal by
+    /// --fatal-warnings
+    pub fn has_errors(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        !state.errors.is_empty() || !state.pending_errors.is_empty()
+    }
+
+    /// Returns the messages of the errors which have been flushed, including those which
+    /// were not printed because of --error-limit
+    pub fn errors(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.errors.iter().map(|diag| diag.to_string()).collect()
+    }
+
+    /// Returns the messages of the warnings which have been flushed, including those which
+    /// were not printed because of --warning-limit
+    pub fn warnings(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.warnings.iter().map(|diag| diag.to_string()).collect()
+    }
+}
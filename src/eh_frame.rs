@@ -0,0 +1,196 @@
+// Parsing of .eh_frame to build .eh_frame_hdr
+// https://refspecs.linuxfoundation.org/LSB_5.0.0/LSB-Core-generic/LSB-Core-generic/ehframechpt.html
+
+use std::collections::HashMap;
+
+use crate::diagnostics::Diagnostics;
+
+const DW_EH_PE_OMIT: u8 = 0xff;
+const DW_EH_PE_ABSPTR: u8 = 0x00;
+const DW_EH_PE_UDATA2: u8 = 0x02;
+const DW_EH_PE_UDATA4: u8 = 0x03;
+const DW_EH_PE_UDATA8: u8 = 0x04;
+const DW_EH_PE_SDATA2: u8 = 0x0a;
+const DW_EH_PE_SDATA4: u8 = 0x0b;
+const DW_EH_PE_SDATA8: u8 = 0x0c;
+const DW_EH_PE_PCREL: u8 = 0x10;
+pub const DW_EH_PE_DATAREL: u8 = 0x30;
+
+/// Encoding of pointers in .eh_frame_hdr
+pub const EH_FRAME_PTR_ENC: u8 = DW_EH_PE_PCREL | DW_EH_PE_SDATA4;
+pub const FDE_COUNT_ENC: u8 = DW_EH_PE_UDATA4;
+pub const TABLE_ENC: u8 = DW_EH_PE_DATAREL | DW_EH_PE_SDATA4;
+
+/// CIE or FDE
+struct Record {
+    /// Offset of the record in the section
+    offset: usize,
+    /// Offset of the CIE ID / CIE pointer field
+    id_offset: usize,
+    id: u32,
+    /// Offset of the end of the record
+    end: usize,
+}
+
+/// Iterate over records in .eh_frame. Zero terminators are skipped.
+fn records(data: &[u8]) -> impl Iterator<Item = Record> + '_ {
+    let mut offset = 0;
+    std::iter::from_fn(move || loop {
+        if offset + 4 > data.len() {
+            return None;
+        }
+        let start = offset;
+        let mut length = read_u32(data, offset) as usize;
+        offset += 4;
+        if length == 0 {
+            continue;
+        }
+        if length == 0xffff_ffff {
+            length = read_u64(data, offset) as usize;
+            offset += 8;
+        }
+        let id_offset = offset;
+        offset += length;
+        if offset > data.len() || id_offset + 4 > data.len() {
+            return None;
+        }
+        return Some(Record {
+            offset: start,
+            id_offset,
+            id: read_u32(data, id_offset),
+            end: offset,
+        });
+    })
+}
+
+/// Returns the number of FDEs in the contents of .eh_frame
+pub fn count_fdes(data: &[u8]) -> usize {
+    records(data).filter(|record| record.id != 0).count()
+}
+
+/// Returns pairs of the initial location and the address of each FDE in .eh_frame, which
+/// is placed at `addr`. `data` must be relocated.
+pub fn collect_fdes(data: &[u8], addr: u64, diag: &Diagnostics) -> Vec<(u64, u64)> {
+    // Offset of CIE -> pointer encoding of FDEs
+    let mut fde_encodings = HashMap::new();
+    let mut fdes = vec![];
+    for record in records(data) {
+        if record.id == 0 {
+            let encoding = parse_cie_fde_encoding(&data[record.id_offset + 4..record.end]);
+            fde_encodings.insert(record.offset, encoding);
+            continue;
+        }
+        let cie_offset = record.id_offset - record.id as usize;
+        let Some(&encoding) = fde_encodings.get(&cie_offset) else {
+            diag.warn(format!(".eh_frame: FDE at {:#x} has no CIE", record.offset));
+            continue;
+        };
+        let pc_begin_offset = record.id_offset + 4;
+        let field_addr = addr + pc_begin_offset as u64;
+        if let Some(pc_begin) = read_encoded(data, pc_begin_offset, encoding, field_addr, diag) {
+            fdes.push((pc_begin, addr + record.offset as u64));
+        }
+    }
+    fdes
+}
+
+/// Returns the pointer encoding of FDEs from the contents of a CIE following the CIE ID
+fn parse_cie_fde_encoding(data: &[u8]) -> u8 {
+    let mut offset = 0;
+    let version = data[offset];
+    offset += 1;
+    let aug_end = offset + data[offset..].iter().position(|b| *b == 0).unwrap_or(0);
+    let augmentation = &data[offset..aug_end];
+    offset = aug_end + 1;
+    read_uleb128(data, &mut offset); // code alignment factor
+    read_uleb128(data, &mut offset); // data alignment factor
+    if version == 1 {
+        offset += 1; // return address register
+    } else {
+        read_uleb128(data, &mut offset);
+    }
+    if augmentation.first() != Some(&b'z') {
+        return DW_EH_PE_ABSPTR;
+    }
+    read_uleb128(data, &mut offset); // augmentation data length
+    for c in &augmentation[1..] {
+        match c {
+            b'R' => return data[offset],
+            b'L' => offset += 1,
+            b'P' => {
+                let encoding = data[offset];
+                offset += 1 + encoded_size(encoding);
+            }
+            _ => {}
+        }
+    }
+    DW_EH_PE_ABSPTR
+}
+
+fn encoded_size(encoding: u8) -> usize {
+    match encoding & 0x0f {
+        DW_EH_PE_ABSPTR | DW_EH_PE_UDATA8 | DW_EH_PE_SDATA8 => 8,
+        DW_EH_PE_UDATA4 | DW_EH_PE_SDATA4 => 4,
+        DW_EH_PE_UDATA2 | DW_EH_PE_SDATA2 => 2,
+        _ => 0,
+    }
+}
+
+fn read_encoded(
+    data: &[u8],
+    offset: usize,
+    encoding: u8,
+    field_addr: u64,
+    diag: &Diagnostics,
+) -> Option<u64> {
+    if encoding == DW_EH_PE_OMIT {
+        return None;
+    }
+    let value = match encoding & 0x0f {
+        DW_EH_PE_ABSPTR | DW_EH_PE_UDATA8 | DW_EH_PE_SDATA8 => read_u64(data, offset),
+        DW_EH_PE_UDATA4 => read_u32(data, offset) as u64,
+        DW_EH_PE_SDATA4 => read_u32(data, offset) as i32 as u64,
+        DW_EH_PE_UDATA2 => u16::from_le_bytes([data[offset], data[offset + 1]]) as u64,
+        DW_EH_PE_SDATA2 => i16::from_le_bytes([data[offset], data[offset + 1]]) as u64,
+        _ => {
+            diag.warn(format!(
+                ".eh_frame: unsupported pointer encoding {:#x}",
+                encoding
+            ));
+            return None;
+        }
+    };
+    match encoding & 0x70 {
+        0 => Some(value),
+        DW_EH_PE_PCREL => Some(field_addr.wrapping_add(value)),
+        _ => {
+            diag.warn(format!(
+                ".eh_frame: unsupported pointer encoding {:#x}",
+                encoding
+            ));
+            None
+        }
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_uleb128(data: &[u8], offset: &mut usize) -> u64 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*offset];
+        *offset += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Layouts of the records of 32-bit and 64-bit ELF files. The linker works with the Elf64
+//! structs regardless of the class of the output, and they are converted to the layout of
+//! the class only when they are written.
+
+use elf::{
+    abi::{ELFCLASS32, ELFCLASS64},
+    file::{Class, Elf64_Ehdr},
+    section::Elf64_Shdr,
+    segment::Elf64_Phdr,
+    symbol::Elf64_Sym,
+};
+
+use crate::utils::write_to;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ElfClass {
+    Elf32,
+    Elf64,
+}
+
+impl From<Class> for ElfClass {
+    fn from(class: Class) -> ElfClass {
+        match class {
+            Class::ELF32 => ElfClass::Elf32,
+            Class::ELF64 => ElfClass::Elf64,
+        }
+    }
+}
+
+impl ElfClass {
+    /// Value of e_ident[EI_CLASS]
+    pub fn ident(self) -> u8 {
+        match self {
+            ElfClass::Elf32 => ELFCLASS32,
+            ElfClass::Elf64 => ELFCLASS64,
+        }
+    }
+
+    /// Name in diagnostics
+    pub fn name(self) -> &'static str {
+        match self {
+            ElfClass::Elf32 => "ELF32",
+            ElfClass::Elf64 => "ELF64",
+        }
+    }
+
+    /// Size of addresses, which is also the alignment of the headers and the symbol table
+    pub fn word_size(self) -> u64 {
+        match self {
+            ElfClass::Elf32 => 4,
+            ElfClass::Elf64 => 8,
+        }
+    }
+
+    pub fn ehdr_size(self) -> usize {
+        match self {
+            ElfClass::Elf32 => 52,
+            ElfClass::Elf64 => std::mem::size_of::<Elf64_Ehdr>(),
+        }
+    }
+
+    pub fn phdr_size(self) -> usize {
+        match self {
+            ElfClass::Elf32 => 32,
+            ElfClass::Elf64 => std::mem::size_of::<Elf64_Phdr>(),
+        }
+    }
+
+    pub fn shdr_size(self) -> usize {
+        match self {
+            ElfClass::Elf32 => 40,
+            ElfClass::Elf64 => std::mem::size_of::<Elf64_Shdr>(),
+        }
+    }
+
+    pub fn sym_size(self) -> usize {
+        match self {
+            ElfClass::Elf32 => 16,
+            ElfClass::Elf64 => std::mem::size_of::<Elf64_Sym>(),
+        }
+    }
+
+    /// Write `ehdr` at `offset` and return its size. Addresses and offsets are truncated
+    /// to 32 bits for ELF32.
+    pub fn write_ehdr(self, buf: &mut [u8], offset: usize, ehdr: &Elf64_Ehdr) -> usize {
+        let ElfClass::Elf32 = self else {
+            return write_to(buf, offset, ehdr);
+        };
+        let mut w = Writer::new(buf, offset);
+        w.bytes(&ehdr.e_ident);
+        w.u16(ehdr.e_type);
+        w.u16(ehdr.e_machine);
+        w.u32(ehdr.e_version);
+        w.u32(ehdr.e_entry as u32);
+        w.u32(ehdr.e_phoff as u32);
+        w.u32(ehdr.e_shoff as u32);
+        w.u32(ehdr.e_flags);
+        w.u16(ehdr.e_ehsize);
+        w.u16(ehdr.e_phentsize);
+        w.u16(ehdr.e_phnum);
+        w.u16(ehdr.e_shentsize);
+        w.u16(ehdr.e_shnum);
+        w.u16(ehdr.e_shstrndx);
+        w.len()
+    }
+
+    /// Write `phdr` at `offset` and return its size. p_flags comes after p_memsz in ELF32.
+    pub fn write_phdr(self, buf: &mut [u8], offset: usize, phdr: &Elf64_Phdr) -> usize {
+        let ElfClass::Elf32 = self else {
+            return write_to(buf, offset, phdr);
+        };
+        let mut w = Writer::new(buf, offset);
+        w.u32(phdr.p_type);
+        w.u32(phdr.p_offset as u32);
+        w.u32(phdr.p_vaddr as u32);
+        w.u32(phdr.p_paddr as u32);
+        w.u32(phdr.p_filesz as u32);
+        w.u32(phdr.p_memsz as u32);
+        w.u32(phdr.p_flags);
+        w.u32(phdr.p_align as u32);
+        w.len()
+    }
+
+    /// Write `shdr` at `offset` and return its size
+    pub fn write_shdr(self, buf: &mut [u8], offset: usize, shdr: &Elf64_Shdr) -> usize {
+        let ElfClass::Elf32 = self else {
+            return write_to(buf, offset, shdr);
+        };
+        let mut w = Writer::new(buf, offset);
+        w.u32(shdr.sh_name);
+        w.u32(shdr.sh_type);
+        w.u32(shdr.sh_flags as u32);
+        w.u32(shdr.sh_addr as u32);
+        w.u32(shdr.sh_offset as u32);
+        w.u32(shdr.sh_size as u32);
+        w.u32(shdr.sh_link);
+        w.u32(shdr.sh_info);
+        w.u32(shdr.sh_addralign as u32);
+        w.u32(shdr.sh_entsize as u32);
+        w.len()
+    }
+
+    /// Write `sym` at `offset` and return its size. st_value and st_size precede st_info in
+    /// ELF32.
+    pub fn write_sym(self, buf: &mut [u8], offset: usize, sym: &Elf64_Sym) -> usize {
+        let ElfClass::Elf32 = self else {
+            return write_to(buf, offset, sym);
+        };
+        let mut w = Writer::new(buf, offset);
+        w.u32(sym.st_name);
+        w.u32(sym.st_value as u32);
+        w.u32(sym.st_size as u32);
+        w.bytes(&[sym.st_info, sym.st_other]);
+        w.u16(sym.st_shndx);
+        w.len()
+    }
+}
+
+/// Writes little-endian fields one after another
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    start: usize,
+    offset: usize,
+}
+
+impl Writer<'_> {
+    fn new(buf: &mut [u8], offset: usize) -> Writer<'_> {
+        Writer {
+            buf,
+            start: offset,
+            offset,
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buf[self.offset..self.offset + bytes.len()].copy_from_slice(bytes);
+        self.offset += bytes.len();
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn len(&self) -> usize {
+        self.offset - self.start
+    }
+}
@@ -0,0 +1,51 @@
+use std::{fmt::Display, path::PathBuf};
+
+/// Reason why a link failed. Errors found while linking have been printed as well, so the
+/// messages they carry are for callers which inspect them.
+#[derive(Debug)]
+pub enum LinkError {
+    /// An input or the output couldn't be read or written
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// An input is not a valid object file, DSO or archive, or the linker script is invalid
+    Parse { file: String, msg: String },
+    /// Names of symbols which are referenced but not defined
+    UndefinedSymbols(Vec<String>),
+    /// Names of symbols which are defined by more than one file
+    DuplicateSymbols(Vec<String>),
+    /// Inputs use features which are not supported, e.g. relocation types. Each message
+    /// has the location in the input.
+    Unsupported(Vec<String>),
+    /// Sections overlap or are not mapped correctly (--check-sections)
+    Layout(Vec<String>),
+    /// Other errors, e.g. relocations which overflow
+    Failed(Vec<String>),
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            LinkError::Parse { file, msg } => write!(f, "{}: {}", file, msg),
+            LinkError::UndefinedSymbols(names) => {
+                write!(f, "link failed: {} symbols are not defined", names.len())
+            }
+            LinkError::DuplicateSymbols(names) => {
+                write!(
+                    f,
+                    "link failed: {} symbols are defined more than once",
+                    names.len()
+                )
+            }
+            LinkError::Unsupported(errors) => {
+                write!(f, "link failed: {} unsupported inputs", errors.len())
+            }
+            LinkError::Layout(errors) => write!(f, "link failed: {} layout errors", errors.len()),
+            LinkError::Failed(errors) => write!(f, "link failed: {} errors", errors.len()),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
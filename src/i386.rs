@@ -0,0 +1,123 @@
+//! Relocations of i386, which are stored in SHT_REL sections. Their addends are in the
+//! relocated fields and are read into r_addend when the input is parsed.
+
+use elf::{abi::EM_386, relocation::Rela};
+
+use crate::{elf_class::ElfClass, relocation::RelocationInput, target::Target};
+
+// Relocation types, which are missing in elf::abi
+pub const R_386_NONE: u32 = 0;
+pub const R_386_32: u32 = 1;
+pub const R_386_PC32: u32 = 2;
+pub const R_386_PLT32: u32 = 4;
+pub const R_386_GOTOFF: u32 = 9;
+pub const R_386_GOTPC: u32 = 10;
+
+/// Returns the value of the relocated field, or None if nothing is written. It is an error
+/// if the relocation type is not supported.
+pub fn relocation_value(input: &RelocationInput, rela: &Rela) -> Result<Option<u64>, String> {
+    let s = input.symbol_addr;
+    let a = rela.r_addend;
+    let p = input.isec_addr + rela.r_offset;
+    let got = input.got_addr;
+
+    let value = match rela.r_type {
+        R_386_NONE => return Ok(None),
+        R_386_32 => s as i64 + a,
+        // Calls are never bound to a PLT entry in a static executable
+        R_386_PC32 | R_386_PLT32 => s as i64 + a - p as i64,
+        R_386_GOTOFF => s as i64 + a - got as i64,
+        R_386_GOTPC => got as i64 + a - p as i64,
+        r_type => return Err(format!("unsupported relocation {}", r_type_as_str(r_type))),
+    };
+    Ok(Some(value as u64))
+}
+
+/// Returns true if the relocation is relative to the GOT, which therefore has to exist even
+/// without entries
+pub fn is_got_relative(r_type: u32) -> bool {
+    matches!(r_type, R_386_GOTOFF | R_386_GOTPC)
+}
+
+/// Returns the range of values which the relocation can hold. Addresses are 32 bits, so
+/// every field may be either signed or unsigned.
+pub fn relocation_range(r_type: u32) -> Option<(i64, i64)> {
+    match r_type {
+        R_386_32 | R_386_PC32 | R_386_PLT32 | R_386_GOTOFF | R_386_GOTPC => {
+            Some((i32::MIN as i64, u32::MAX as i64))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the size of the relocated field, which is also the size of the implicit addend
+pub fn relocation_size(r_type: u32) -> Result<usize, String> {
+    match r_type {
+        R_386_NONE => Ok(0),
+        R_386_32 | R_386_PC32 | R_386_PLT32 | R_386_GOTOFF | R_386_GOTPC => Ok(4),
+        r_type => Err(format!("unsupported relocation {}", r_type_as_str(r_type))),
+    }
+}
+
+pub fn r_type_as_str(r_type: u32) -> String {
+    let name = match r_type {
+        R_386_NONE => "R_386_NONE",
+        R_386_32 => "R_386_32",
+        R_386_PC32 => "R_386_PC32",
+        3 => "R_386_GOT32",
+        R_386_PLT32 => "R_386_PLT32",
+        5 => "R_386_COPY",
+        6 => "R_386_GLOB_DAT",
+        7 => "R_386_JMP_SLOT",
+        8 => "R_386_RELATIVE",
+        R_386_GOTOFF => "R_386_GOTOFF",
+        R_386_GOTPC => "R_386_GOTPC",
+        43 => "R_386_GOT32X",
+        _ => return format!("unknown ({})", r_type),
+    };
+    name.to_owned()
+}
+
+pub struct I386;
+
+impl Target for I386 {
+    fn name(&self) -> &'static str {
+        "i386"
+    }
+
+    fn e_machine(&self) -> u16 {
+        EM_386
+    }
+
+    fn emulations(&self) -> &'static [&'static str] {
+        &["elf_i386"]
+    }
+
+    fn class(&self) -> ElfClass {
+        ElfClass::Elf32
+    }
+
+    fn relocation_value(
+        &self,
+        input: &RelocationInput,
+        rela: &Rela,
+    ) -> Result<Option<u64>, String> {
+        relocation_value(input, rela)
+    }
+
+    fn relocation_range(&self, r_type: u32) -> Option<(i64, i64)> {
+        relocation_range(r_type)
+    }
+
+    fn relocation_size(&self, r_type: u32) -> Result<usize, String> {
+        relocation_size(r_type)
+    }
+
+    fn r_type_as_str(&self, r_type: u32) -> String {
+        r_type_as_str(r_type)
+    }
+
+    fn is_got_relative(&self, r_type: u32) -> bool {
+        is_got_relative(r_type)
+    }
+}
@@ -0,0 +1,114 @@
+use std::{fs::File, ops::Range, path::Path, sync::Arc};
+
+use memmap2::{Mmap, UncheckedAdvice};
+
+use crate::config::PAGE_SIZE;
+
+/// Buffer holding a whole input file
+enum Buffer {
+    /// The file is mapped into memory, so pages which are never read aren't loaded
+    Mmap(Mmap),
+    /// Contents read into memory, or created by the linker
+    Vec(Vec<u8>),
+}
+
+impl std::ops::Deref for Buffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Buffer::Mmap(mmap) => mmap,
+            Buffer::Vec(vec) => vec,
+        }
+    }
+}
+
+/// Part of an input file. Archive members and sections refer to the buffer of the file
+/// they are in instead of copying it.
+#[derive(Clone)]
+pub struct FileData {
+    buf: Arc<Buffer>,
+    range: Range<usize>,
+}
+
+impl FileData {
+    /// Map a file into memory, or read it if it can't be mapped
+    pub fn open(path: &Path) -> std::io::Result<FileData> {
+        let file = File::open(path)?;
+        // SAFETY: Input files are not expected to be modified while the linker runs
+        let buf = match unsafe { Mmap::map(&file) } {
+            Ok(mmap) => Buffer::Mmap(mmap),
+            Err(err) => {
+                log::debug!("Failed to map {}: {}", path.display(), err);
+                Buffer::Vec(std::fs::read(path)?)
+            }
+        };
+        let range = 0..buf.len();
+        Ok(FileData {
+            buf: Arc::new(buf),
+            range,
+        })
+    }
+
+    /// Returns the part at `range` relative to the start of this data
+    pub fn slice(&self, range: Range<usize>) -> FileData {
+        assert!(range.start <= range.end && range.end <= self.range.len());
+        FileData {
+            buf: Arc::clone(&self.buf),
+            range: self.range.start + range.start..self.range.start + range.end,
+        }
+    }
+
+    /// Returns the part which `data` borrows from this data. Empty slices, e.g. of NOBITS
+    /// sections, don't necessarily point into the data and give an empty part.
+    pub fn slice_of(&self, data: &[u8]) -> FileData {
+        if data.is_empty() {
+            return self.slice(0..0);
+        }
+        let start = data.as_ptr() as usize - self.as_ptr() as usize;
+        self.slice(start..start + data.len())
+    }
+
+    /// Give the pages which lie entirely within this data back to the kernel. The data
+    /// stays readable: the pages are loaded from the file again if it is read later, so this
+    /// only affects memory usage. Returns the number of bytes released.
+    pub fn release(&self) -> usize {
+        let Buffer::Mmap(mmap) = self.buf.as_ref() else {
+            return 0;
+        };
+        let page_size = PAGE_SIZE as usize;
+        let start = self.range.start.next_multiple_of(page_size);
+        let end = self.range.end / page_size * page_size;
+        if end <= start {
+            return 0;
+        }
+        // SAFETY: The mapping is read-only and backed by the file, so the pages are only
+        // reloaded from the file rather than being zero-filled
+        match unsafe { mmap.unchecked_advise_range(UncheckedAdvice::DontNeed, start, end - start) }
+        {
+            Ok(()) => end - start,
+            Err(err) => {
+                log::debug!("Failed to release input pages: {}", err);
+                0
+            }
+        }
+    }
+}
+
+impl From<Vec<u8>> for FileData {
+    fn from(data: Vec<u8>) -> FileData {
+        let range = 0..data.len();
+        FileData {
+            buf: Arc::new(Buffer::Vec(data)),
+            range,
+        }
+    }
+}
+
+impl std::ops::Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf[self.range.clone()]
+    }
+}
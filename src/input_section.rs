@@ -1,8 +1,25 @@
-use std::{cell::RefCell, collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
 
-use crate::{context::Context, output_section::OutputSectionId};
+use crate::{
+    archive::{is_archive, parse_archive},
+    context::{Context, IdSpace},
+    elf_class::ElfClass,
+    input_file::FileData,
+    output_section::OutputSectionId,
+    relocation::implicit_addend,
+    riscv,
+    symbol_name::{Interner, SymbolName},
+    target,
+    utils::{to_st_shndx, write_to},
+};
 use elf::{
-    endian::AnyEndian,
+    abi::{SHF_MERGE, SHF_STRINGS, VER_FLG_BASE},
+    endian::{AnyEndian, LittleEndian},
+    file::Class,
+    parse::{ParseAt, ParseError},
     relocation::Rela,
     section::SectionHeader,
     symbol::{Elf64_Sym, Symbol as ElfSymbolData},
@@ -12,23 +29,23 @@ use elf::{
 /// Missing constants in elf-rs
 const SHF_EXCLUDE: u64 = 0x80000000;
 
-#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, PartialOrd, Ord)]
 pub struct ObjectId {
     private: usize,
 }
 
-fn get_next_object_file_id() -> ObjectId {
-    static mut OBJECT_FILE_ID: usize = 0;
-    let id = unsafe { OBJECT_FILE_ID };
-    unsafe { OBJECT_FILE_ID += 1 };
-    ObjectId { private: id }
+fn get_next_object_file_id(ids: &IdSpace) -> ObjectId {
+    ObjectId {
+        private: ids.object_files.fetch_add(1, Ordering::Relaxed),
+    }
 }
 
 pub struct ObjectFile {
     id: ObjectId,
     file_name: String,
-    // TODO: archive file
-    data: Vec<u8>,
+    /// Contents of the file, which sections borrow their data from. For an archive member,
+    /// this is the part of the archive holding the member.
+    data: FileData,
 
     first_global: usize,
     /// All sections corresponding to each section header
@@ -38,15 +55,23 @@ pub struct ObjectFile {
     /// sections corresponding to each section header
     input_sections: Vec<Option<InputSectionId>>,
     /// symbols corresponding to each symbol table entry
-    symbols: Vec<Option<Arc<RefCell<Symbol>>>>,
+    symbols: Vec<Option<Arc<SymbolCell>>>,
     is_dso: bool,
-    in_archive: bool,
+    /// DT_SONAME of a DSO
+    soname: Option<String>,
+    /// Machine of the file, which must be the same for all inputs
+    e_machine: u16,
+    /// Flags of the file, which are specific to the machine
+    e_flags: u32,
+    class: ElfClass,
+    /// Archive members are linked only if they define a symbol referenced by a live file
+    alive: bool,
 }
 
 impl ObjectFile {
-    fn new(file_name: String, data: Vec<u8>, in_archive: bool) -> ObjectFile {
+    fn new(file_name: String, data: FileData, in_archive: bool, ids: &IdSpace) -> ObjectFile {
         ObjectFile {
-            id: get_next_object_file_id(),
+            id: get_next_object_file_id(ids),
             file_name,
             data,
             first_global: 0,
@@ -55,38 +80,58 @@ impl ObjectFile {
             input_sections: Vec::new(),
             symbols: Vec::new(),
             is_dso: false,
-            in_archive,
+            soname: None,
+            e_machine: 0,
+            e_flags: 0,
+            class: ElfClass::Elf64,
+            alive: !in_archive,
         }
     }
 
-    pub fn read_from(file_name: &str) -> Vec<ObjectFile> {
-        fn is_archive(file_name: &str) -> bool {
-            file_name.ends_with(".a")
-        }
+    /// Object file or DSO whose contents are in memory. `name` stands for the file in
+    /// diagnostics. The contents are parsed later with the other input files.
+    pub fn from_bytes(name: &str, data: Vec<u8>, ids: &IdSpace) -> ObjectFile {
+        log::debug!("Object file in memory: {} ({} bytes)", name, data.len());
+        ObjectFile::new(name.to_owned(), FileData::from(data), false, ids)
+    }
 
-        // TODO: We should use mmap here
-        if is_archive(&file_name) {
-            log::debug!("Opening archive file: {}", file_name);
-            let mut objs = vec![];
-            let mut archive = ar::Archive::new(std::fs::File::open(file_name).unwrap());
-            while let Some(Ok(mut entry)) = archive.next_entry() {
-                let mut buf = Vec::new();
-                std::io::copy(&mut entry, &mut buf).unwrap();
-                let member_file_name = std::str::from_utf8(entry.header().identifier())
-                    .unwrap()
-                    .to_string();
-                log::debug!("\t{} ({} bytes)", member_file_name, buf.len());
-                let member_file = ObjectFile::new(member_file_name, buf, true);
-                objs.push(member_file);
-            }
-            objs
+    /// Returns the object files in an input file, which is either an archive or a single
+    /// object file or DSO. Returns a message if the file is a malformed archive.
+    pub fn read_from(
+        file_name: &str,
+        data: FileData,
+        ids: &IdSpace,
+    ) -> Result<Vec<ObjectFile>, String> {
+        if is_archive(&data) {
+            Self::read_archive(file_name, &data, ids)
         } else {
-            let data = std::fs::read(file_name).expect(&format!("Failed to read {}", file_name));
             log::debug!("Opened object file: {} ({} bytes)", file_name, data.len());
-            vec![ObjectFile::new(file_name.to_string(), data, false)]
+            Ok(vec![ObjectFile::new(
+                file_name.to_string(),
+                data,
+                false,
+                ids,
+            )])
         }
     }
 
+    pub fn read_archive(
+        file_name: &str,
+        data: &FileData,
+        ids: &IdSpace,
+    ) -> Result<Vec<ObjectFile>, String> {
+        // Members are parts of the archive, so only the pages which are read are loaded
+        log::debug!("Opening archive file: {}", file_name);
+        let members = parse_archive(data).map_err(|msg| format!("malformed archive: {}", msg))?;
+        Ok(members
+            .into_iter()
+            .map(|member| {
+                log::debug!("\t{} ({} bytes)", member.name, member.range.len());
+                ObjectFile::new(member.name, data.slice(member.range), true, ids)
+            })
+            .collect())
+    }
+
     pub fn get_id(&self) -> ObjectId {
         self.id
     }
@@ -99,10 +144,6 @@ impl ObjectFile {
         self.first_global
     }
 
-    pub fn get_elf_sections(&self) -> &[Arc<ElfSection>] {
-        &self.elf_sections
-    }
-
     pub fn get_elf_symbols(&self) -> &[Arc<ElfSymbol>] {
         &self.elf_symbols
     }
@@ -111,7 +152,7 @@ impl ObjectFile {
         &self.input_sections
     }
 
-    pub fn get_symbols(&self) -> &[Option<Arc<RefCell<Symbol>>>] {
+    pub fn get_symbols(&self) -> &[Option<Arc<SymbolCell>>] {
         &self.symbols
     }
 
@@ -119,67 +160,102 @@ impl ObjectFile {
         self.is_dso
     }
 
-    pub fn is_in_archive(&self) -> bool {
-        self.in_archive
+    /// Returns the alignment of the section at `shndx`
+    pub fn get_section_alignment(&self, shndx: usize) -> u64 {
+        self.elf_sections
+            .get(shndx)
+            .map_or(1, |section| section.header.sh_addralign.max(1))
     }
 
-    pub fn parse(&mut self, ctx: &mut Context) {
-        let file = ElfBytes::<AnyEndian>::minimal_parse(&self.data).expect("Open ELF file failed");
-        self.is_dso = file.ehdr.e_type == elf::abi::ET_DYN;
+    pub fn get_e_machine(&self) -> u16 {
+        self.e_machine
+    }
 
-        let shstrtab_shdr = file.section_header_by_name(".shstrtab").unwrap().unwrap();
-        let shstrtab = file.section_data_as_strtab(&shstrtab_shdr).unwrap();
-        let section_headers = file.section_headers().unwrap();
-        // Arrange elf_sections
-        for shdr in section_headers {
-            let name = shstrtab.get(shdr.sh_name as usize).unwrap();
-            // TODO: remove clone()
-            self.elf_sections.push(Arc::new(ElfSection {
-                name: name.to_string(),
-                header: shdr,
-                data: file.section_data(&shdr).unwrap().0.to_vec(),
-            }));
-        }
+    pub fn get_e_flags(&self) -> u32 {
+        self.e_flags
+    }
 
-        // Arrange elf_symbols
-        if let Some((symtab_sec, strtab_sec)) = file.symbol_table().unwrap() {
-            // TODO: Use .dsymtab instead of .symtab for dso
-            let symtab_shdr = file.section_header_by_name(".symtab").unwrap().unwrap();
-            for sym in symtab_sec {
-                // remove string after @
-                let name = strtab_sec.get(sym.st_name as usize).unwrap();
-                let name_end = name.find('@').unwrap_or(name.len());
-                let name = name[..name_end].to_string();
-                self.elf_symbols.push(Arc::new(ElfSymbol {
-                    name: name.to_string(),
-                    sym,
-                }));
-            }
-            self.first_global = symtab_shdr.sh_info as usize;
-        }
+    pub fn get_class(&self) -> ElfClass {
+        self.class
+    }
 
-        let mut elf_rels = HashMap::new();
-        for shdr in section_headers {
-            let name = shstrtab.get(shdr.sh_name as usize).unwrap();
-            if name.starts_with(".rela") {
-                let target = name[5..].to_string();
-                let data = file.section_data_as_relas(&shdr).unwrap();
-                for rela in data {
-                    elf_rels
-                        .entry(target.clone())
-                        .or_insert(Vec::new())
-                        .push(rela);
-                }
-            }
+    /// Name recorded in DT_NEEDED of the output. It is DT_SONAME of the DSO if any, or its
+    /// file name otherwise.
+    pub fn get_soname(&self) -> &str {
+        self.soname.as_deref().unwrap_or_else(|| {
+            let name = self.file_name.as_str();
+            name.rsplit_once('/').map_or(name, |(_, base)| base)
+        })
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+
+    pub fn set_alive(&mut self) {
+        self.alive = true;
+    }
+
+    /// Names of global symbols defined in this file
+    pub fn get_defined_global_names(&self) -> impl Iterator<Item = SymbolName> + '_ {
+        self.elf_symbols
+            .iter()
+            .skip(self.first_global)
+            .filter(|esym| !esym.get_esym().is_undefined())
+            .map(|esym| esym.get_name())
+    }
+
+    /// Names of global symbols referenced but not defined in this file. Weak references are
+    /// excluded since they never cause archive members to be extracted.
+    pub fn get_undefined_global_names(&self) -> impl Iterator<Item = SymbolName> + '_ {
+        self.elf_symbols
+            .iter()
+            .skip(self.first_global)
+            .filter(|esym| esym.get_esym().is_undefined() && !esym.is_weak())
+            .map(|esym| esym.get_name())
+    }
+
+    pub fn get_data(&self) -> &FileData {
+        &self.data
+    }
+
+    /// Create input sections and symbols from the contents parsed by `ElfContents::parse`
+    pub fn initialize(&mut self, contents: ElfContents, ctx: &mut Context) {
+        let ElfContents {
+            is_dso,
+            soname,
+            e_machine,
+            e_flags,
+            class,
+            first_global,
+            elf_sections,
+            elf_symbols,
+            elf_rels,
+        } = contents;
+        self.is_dso = is_dso;
+        self.soname = soname;
+        self.e_machine = e_machine;
+        self.e_flags = e_flags;
+        self.class = class;
+        self.first_global = first_global;
+        self.elf_sections = elf_sections;
+        self.elf_symbols = elf_symbols;
+
+        // Sections of a DSO are not copied to the output. It only provides symbols.
+        if self.is_dso {
+            self.input_sections.resize(self.elf_sections.len(), None);
+            self.initialize_symbols();
+            return;
         }
 
         self.initialize_sections(ctx);
-        self.initialize_symbols(ctx);
+        self.initialize_symbols();
         self.initialize_relocations(ctx, elf_rels);
     }
 
     fn initialize_sections(&mut self, ctx: &mut Context) {
         self.input_sections.resize(self.elf_sections.len(), None);
+        let target = target::from_e_machine(self.e_machine);
         for (i, elf_section) in self.elf_sections.iter().enumerate() {
             if (elf_section.header.sh_flags & SHF_EXCLUDE) != 0
                 && (elf_section.header.sh_flags & elf::abi::SHF_ALLOC as u64) == 0
@@ -191,6 +267,7 @@ impl ObjectFile {
                 | elf::abi::SHT_REL
                 | elf::abi::SHT_RELA
                 | elf::abi::SHT_SYMTAB
+                | elf::abi::SHT_SYMTAB_SHNDX
                 | elf::abi::SHT_STRTAB => {
                     // Nothing to do
                 }
@@ -202,7 +279,6 @@ impl ObjectFile {
                         self.get_file_name()
                     );
                 }
-                elf::abi::SHT_SYMTAB_SHNDX => panic!("SHT_SYMTAB_SHNDX is not supported"),
                 elf::abi::SHT_GROUP => {
                     let shdr = elf_section.header;
                     let esym = self.elf_symbols[shdr.sh_info as usize].clone();
@@ -216,6 +292,13 @@ impl ObjectFile {
                     );
                     log::debug!("signature: \"{}\"", signature);
                 }
+                sh_type if target.is_some_and(|target| target.is_dropped_section(sh_type)) => {
+                    log::debug!(
+                        "TODO: {} is not supported, ignored ({})",
+                        elf_section.name,
+                        self.get_file_name()
+                    );
+                }
                 _ => {
                     if elf_section.name == ".note.GNU-stack" {
                         // https://github.com/tamaroning/mold/blob/c3a86f5b24343f020edfac1f683dea3648a30e61/elf/input-files.cc#L180
@@ -233,7 +316,8 @@ impl ObjectFile {
                     }
 
                     // Create a new section
-                    let input_section = InputSection::new(Arc::clone(elf_section));
+                    let input_section =
+                        InputSection::new(self.id, Arc::clone(elf_section), &ctx.ids);
                     self.input_sections[i] = Some(input_section.get_id());
                     ctx.set_input_section(input_section);
                 }
@@ -243,7 +327,7 @@ impl ObjectFile {
         }
     }
 
-    fn initialize_symbols(&mut self, ctx: &mut Context) {
+    fn initialize_symbols(&mut self) {
         self.symbols.resize(self.elf_symbols.len(), None);
 
         // Initialize local symbols
@@ -254,15 +338,7 @@ impl ObjectFile {
             if i == 0 {
                 continue;
             }
-            if elf_symbol.is_common() {
-                panic!("common local symbol?")
-            }
-            self.symbols[i] = Some(Arc::new(RefCell::new(Symbol {
-                name: elf_symbol.name.clone(),
-                file: None,
-                esym: Arc::clone(elf_symbol),
-                global: false,
-            })));
+            self.symbols[i] = Some(Arc::new(SymbolCell::new(Symbol::new(elf_symbol, false))));
         }
 
         // Initialize global symbols
@@ -270,15 +346,58 @@ impl ObjectFile {
             if i < self.first_global {
                 continue;
             }
-            let symbol = Arc::new(RefCell::new(Symbol {
-                name: elf_symbol.name.clone(),
-                file: None,
-                esym: Arc::clone(elf_symbol),
-                global: true,
-            }));
-            self.symbols[i] = Some(Arc::clone(&symbol));
-            ctx.add_global_symbol(symbol);
+            self.symbols[i] = Some(Arc::new(SymbolCell::new(Symbol::new(elf_symbol, true))));
+        }
+    }
+
+    /// Allocate space for the common symbol at `index` in the symbol table, and turn it into
+    /// an ordinary definition in a new .bss input section.
+    pub fn allocate_common_symbol(
+        &mut self,
+        index: usize,
+        size: u64,
+        align: u64,
+        ids: &IdSpace,
+    ) -> InputSection {
+        let elf_section = Arc::new(ElfSection::new(
+            ".bss".to_owned(),
+            SectionHeader {
+                sh_name: 0,
+                sh_type: elf::abi::SHT_NOBITS,
+                sh_flags: (elf::abi::SHF_WRITE | elf::abi::SHF_ALLOC) as u64,
+                sh_addr: 0,
+                sh_offset: 0,
+                sh_size: size,
+                sh_link: 0,
+                sh_info: 0,
+                sh_addralign: align.max(1),
+                sh_entsize: 0,
+            },
+            vec![],
+        ));
+        let shndx = self.elf_sections.len();
+        let input_section = InputSection::new(self.id, Arc::clone(&elf_section), ids);
+        self.elf_sections.push(elf_section);
+        self.input_sections.push(Some(input_section.get_id()));
+
+        let common = &self.elf_symbols[index];
+        let esym = Arc::new(ElfSymbol::synthetic(
+            common.get_name(),
+            &Elf64_Sym {
+                st_name: 0,
+                st_info: (common.get_esym().st_bind() << 4) | elf::abi::STT_OBJECT,
+                st_other: common.get_esym().st_vis(),
+                st_shndx: to_st_shndx(shndx),
+                st_value: 0,
+                st_size: size,
+            },
+            shndx,
+        ));
+        self.elf_symbols[index] = Arc::clone(&esym);
+        if let Some(symbol) = &self.symbols[index] {
+            symbol.write().esym = esym;
         }
+        input_section
     }
 
     fn initialize_relocations(
@@ -286,32 +405,339 @@ impl ObjectFile {
         ctx: &mut Context,
         mut elf_rels: HashMap<String, Vec<Rela>>,
     ) {
-        for isec in self.input_sections.iter_mut() {
-            if let Some(isec) = isec {
-                let isec = ctx.get_input_section_mut(*isec);
-                let name = isec.get_name();
-                if let Some(rels) = elf_rels.remove(name) {
-                    let rels = rels
-                        .into_iter()
-                        .map(|rela| {
-                            let symbol = self.symbols[rela.r_sym as usize].as_ref().unwrap();
-                            ElfRela {
-                                erela: rela,
-                                symbol: Arc::clone(symbol),
+        for isec in self.input_sections.iter_mut().flatten() {
+            let isec = ctx.get_input_section_mut(*isec);
+            let name = isec.get_name();
+            if let Some(rels) = elf_rels.remove(name) {
+                let rels = rels
+                    .into_iter()
+                    .map(|rela| {
+                        // Relocations may refer to the null symbol, which stands for 0
+                        let symbol = match &self.symbols[rela.r_sym as usize] {
+                            Some(symbol) => Arc::clone(symbol),
+                            None => {
+                                let esym = &self.elf_symbols[rela.r_sym as usize];
+                                Arc::new(SymbolCell::new(Symbol::new(esym, false)))
                             }
-                        })
-                        .collect::<Vec<_>>();
-                    isec.set_relas(rels);
+                        };
+                        ElfRela {
+                            erela: rela,
+                            symbol,
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                isec.set_relas(rels);
+            }
+            isec.convert_ctors();
+        }
+    }
+}
+
+/// Contents of an ELF file which are independent of other files
+pub struct ElfContents {
+    is_dso: bool,
+    /// DT_SONAME of a DSO
+    soname: Option<String>,
+    e_machine: u16,
+    e_flags: u32,
+    class: ElfClass,
+    first_global: usize,
+    elf_sections: Vec<Arc<ElfSection>>,
+    elf_symbols: Vec<Arc<ElfSymbol>>,
+    /// Relocations by the name of the section they apply to
+    elf_rels: HashMap<String, Vec<Rela>>,
+}
+
+impl ElfContents {
+    /// Parse an ELF file. This depends on nothing but the file itself and interns symbol
+    /// names in `names`, so that files can be parsed in parallel. Returns a message if the
+    /// file is malformed.
+    pub fn parse(data: &FileData, names: &Interner) -> Result<ElfContents, String> {
+        let file = ElfBytes::<AnyEndian>::minimal_parse(data).map_err(|err| err.to_string())?;
+        let is_dso = file.ehdr.e_type == elf::abi::ET_DYN;
+        let mut elf_sections = Vec::new();
+        let mut elf_symbols = Vec::new();
+        let mut first_global = 0;
+
+        let err = |err: ParseError| err.to_string();
+        // Section names are in the section at e_shstrndx, which LLVM shares with .strtab
+        let (section_headers, shstrtab) = file.section_headers_with_strtab().map_err(err)?;
+        let section_headers = section_headers.ok_or("no section headers")?;
+        let shstrtab = shstrtab.ok_or("no section header string table")?;
+        // Arrange elf_sections
+        for shdr in section_headers {
+            let name = shstrtab.get(shdr.sh_name as usize).map_err(err)?;
+            let section_data = file.section_data(&shdr).map_err(err)?.0;
+            elf_sections.push(Arc::new(ElfSection {
+                name: name.to_string(),
+                header: shdr,
+                data: data.slice_of(section_data),
+            }));
+        }
+
+        // Arrange elf_symbols. Only symbols in .dynsym are visible from outside of a DSO.
+        let (symtab, symtab_name) = if is_dso {
+            (file.dynamic_symbol_table().map_err(err)?, ".dynsym")
+        } else {
+            (file.symbol_table().map_err(err)?, ".symtab")
+        };
+        let versions = if is_dso {
+            file.symbol_version_table().map_err(err)?
+        } else {
+            None
+        };
+        // Section indices which don't fit in st_shndx
+        let symtab_shndx = section_headers
+            .iter()
+            .find(|shdr| shdr.sh_type == elf::abi::SHT_SYMTAB_SHNDX)
+            .map(|shdr| file.section_data(&shdr).map_err(err))
+            .transpose()?
+            .map(|(data, _)| data);
+        if let Some((symtab_sec, strtab_sec)) = symtab {
+            let symtab_shdr = file
+                .section_header_by_name(symtab_name)
+                .map_err(err)?
+                .ok_or(format!("no {}", symtab_name))?;
+            for (i, sym) in symtab_sec.iter().enumerate() {
+                let name = strtab_sec.get(sym.st_name as usize).map_err(err)?;
+                let shndx = match symtab_shndx {
+                    Some(data) if sym.st_shndx == elf::abi::SHN_XINDEX => data
+                        .get(i * 4..i * 4 + 4)
+                        .map(|index| u32::from_le_bytes(index.try_into().unwrap()) as usize)
+                        .ok_or(format!("no extended section index for symbol {}", i))?,
+                    _ => sym.st_shndx as usize,
+                };
+                if !is_dso {
+                    // remove string after @
+                    let name_end = name.find('@').unwrap_or(name.len());
+                    elf_symbols.push(Arc::new(ElfSymbol {
+                        name: names.intern(&name[..name_end]),
+                        sym,
+                        version: None,
+                        shndx,
+                    }));
+                    continue;
+                }
+                let definition = match &versions {
+                    Some(versions) if !sym.is_undefined() => {
+                        versions.get_definition(i).map_err(err)?
+                    }
+                    _ => None,
+                }
+                .filter(|def| def.flags & VER_FLG_BASE == 0);
+                let (name, version) = match definition {
+                    Some(mut def) => {
+                        let version = def
+                            .names
+                            .next()
+                            .ok_or(format!("version definition of {} has no name", name))?
+                            .map_err(err)?
+                            .to_owned();
+                        // A non-default version (foo@VER, as opposed to foo@@VER) is only
+                        // bound by references to that version, which we do not support.
+                        // Keep the suffix so that it never resolves plain references.
+                        if def.hidden {
+                            (
+                                names.intern(&format!("{}@{}", name, version)),
+                                Some(version),
+                            )
+                        } else {
+                            (names.intern(name), Some(version))
+                        }
+                    }
+                    None => (names.intern(name), None),
+                };
+                elf_symbols.push(Arc::new(ElfSymbol {
+                    name,
+                    sym,
+                    version,
+                    shndx,
+                }));
+            }
+            first_global = symtab_shdr.sh_info as usize;
+            if first_global > elf_symbols.len() {
+                return Err(format!(
+                    "{} has {} local symbols but only {} symbols",
+                    symtab_name,
+                    first_global,
+                    elf_symbols.len()
+                ));
+            }
+            // Local symbols are never merged, so they can't be common
+            if let Some(sym) = elf_symbols[..first_global]
+                .iter()
+                .find(|sym| sym.is_common())
+            {
+                return Err(format!("local symbol {} is common", sym.get_name()));
+            }
+        }
+
+        let mut soname = None;
+        if is_dso {
+            let dynstr = file
+                .section_header_by_name(".dynstr")
+                .map_err(err)?
+                .and_then(|shdr| file.section_data_as_strtab(&shdr).ok());
+            if let (Some(dynamic), Some(dynstr)) = (file.dynamic().map_err(err)?, dynstr) {
+                soname = dynamic
+                    .iter()
+                    .find(|dyn_| dyn_.d_tag == elf::abi::DT_SONAME)
+                    .and_then(|dyn_| dynstr.get(dyn_.d_val() as usize).ok())
+                    .map(|name| name.to_owned());
+            }
+        }
+
+        // Relocations in a DSO are for the dynamic linker
+        let mut elf_rels = HashMap::new();
+        for shdr in section_headers.iter().filter(|_| !is_dso) {
+            let name = shstrtab.get(shdr.sh_name as usize).map_err(err)?;
+            let (target, relas) = if let Some(target) = name.strip_prefix(".rela") {
+                let relas = file.section_data_as_relas(&shdr).map_err(err)?;
+                (target, relas.collect::<Vec<_>>())
+            } else if let Some(target) = name
+                .strip_prefix(".rel")
+                .filter(|_| shdr.sh_type == elf::abi::SHT_REL)
+            {
+                // Addends are stored in the relocated fields instead
+                let data = elf_sections
+                    .get(shdr.sh_info as usize)
+                    .ok_or(format!("{} applies to a section which doesn't exist", name))?
+                    .data();
+                // Files for unsupported machines are rejected after they are parsed
+                let Some(machine) = target::from_e_machine(file.ehdr.e_machine) else {
+                    continue;
+                };
+                let rels = file.section_data_as_rels(&shdr).map_err(err)?;
+                let relas = rels
+                    .map(|rel| {
+                        let mut rela = Rela {
+                            r_offset: rel.r_offset,
+                            r_sym: rel.r_sym,
+                            r_type: rel.r_type,
+                            r_addend: 0,
+                        };
+                        rela.r_addend = implicit_addend(machine, &rela, data)
+                            .map_err(|msg| format!("{}: {}", name, msg))?;
+                        Ok(rela)
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                (target, relas)
+            } else {
+                continue;
+            };
+            let target = target.to_string();
+            for rela in relas {
+                if rela.r_sym as usize >= elf_symbols.len() {
+                    return Err(format!(
+                        "{}: relocation refers to symbol {} which doesn't exist",
+                        name, rela.r_sym
+                    ));
                 }
+                elf_rels
+                    .entry(target.clone())
+                    .or_insert(Vec::new())
+                    .push(rela);
             }
         }
+
+        let mut contents = ElfContents {
+            is_dso,
+            soname,
+            e_machine: file.ehdr.e_machine,
+            e_flags: file.ehdr.e_flags,
+            class: file.ehdr.class.into(),
+            first_global,
+            elf_sections,
+            elf_symbols,
+            elf_rels,
+        };
+        if let Some(target) = target::from_e_machine(contents.e_machine) {
+            target.prepare_input(&mut contents)?;
+        }
+        Ok(contents)
+    }
+
+    /// Remove the part of the padding of R_RISCV_ALIGN relocations which is not needed for
+    /// the alignment, and move the symbols and relocations after it accordingly
+    pub fn remove_align_padding(&mut self) -> Result<(), String> {
+        let mut deletions = HashMap::new();
+        for (i, section) in self.elf_sections.iter_mut().enumerate() {
+            let Some(relas) = self.elf_rels.get(&section.name) else {
+                continue;
+            };
+            let section_deletions = riscv::align_deletions(relas, section.header.sh_addralign)
+                .map_err(|msg| format!("{}: {}", section.name, msg))?;
+            if section_deletions.is_empty() {
+                continue;
+            }
+            let mut header = section.header;
+            header.sh_size -= section_deletions.iter().map(|d| d.size).sum::<u64>();
+            let data = riscv::delete_padding(section.data(), &section_deletions, relas);
+            *section = Arc::new(ElfSection::new(section.name.clone(), header, data));
+            deletions.insert(i, section_deletions);
+        }
+        if deletions.is_empty() {
+            return Ok(());
+        }
+        let move_offset = |shndx: usize, offset: u64| match deletions.get(&shndx) {
+            Some(deletions) => riscv::offset_after_deletions(deletions, offset),
+            None => offset,
+        };
+
+        for esym in self.elf_symbols.iter_mut() {
+            if !deletions.contains_key(&esym.shndx) {
+                continue;
+            }
+            let mut sym = esym.sym.clone();
+            let end = move_offset(esym.shndx, sym.st_value + sym.st_size);
+            sym.st_value = move_offset(esym.shndx, sym.st_value);
+            sym.st_size = end - sym.st_value;
+            *esym = Arc::new(ElfSymbol {
+                name: esym.name,
+                sym,
+                version: esym.version.clone(),
+                shndx: esym.shndx,
+            });
+        }
+
+        // Relocations are moved with the sections they apply to. Section symbols refer to
+        // places in the section by their addends, which are moved as well.
+        for (i, section) in self.elf_sections.iter().enumerate() {
+            let Some(relas) = self.elf_rels.get_mut(&section.name) else {
+                continue;
+            };
+            for rela in relas {
+                rela.r_offset = move_offset(i, rela.r_offset);
+                let esym = &self.elf_symbols[rela.r_sym as usize];
+                if esym.sym.st_symtype() == elf::abi::STT_SECTION && rela.r_addend >= 0 {
+                    rela.r_addend = move_offset(esym.shndx, rela.r_addend as u64) as i64;
+                }
+            }
+        }
+        Ok(())
     }
 }
 
 pub struct ElfSection {
     pub name: String,
     pub header: SectionHeader,
-    pub data: Vec<u8>,
+    /// Part of the input file unless the section was created or rewritten by the linker
+    data: FileData,
+}
+
+impl ElfSection {
+    /// Create a section which owns its contents
+    pub fn new(name: String, header: SectionHeader, data: Vec<u8>) -> ElfSection {
+        ElfSection {
+            name,
+            header,
+            data: FileData::from(data),
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
 }
 
 impl std::fmt::Debug for ElfSection {
@@ -320,34 +746,49 @@ impl std::fmt::Debug for ElfSection {
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, PartialOrd, Ord)]
 pub struct InputSectionId {
     private: usize,
 }
 
-fn get_next_input_section_id() -> InputSectionId {
-    static mut INPUT_SECTION_ID: usize = 0;
-    let id = unsafe { INPUT_SECTION_ID };
-    unsafe { INPUT_SECTION_ID += 1 };
-    InputSectionId { private: id }
+fn get_next_input_section_id(ids: &IdSpace) -> InputSectionId {
+    InputSectionId {
+        private: ids.input_sections.fetch_add(1, Ordering::Relaxed),
+    }
+}
+
+/// Piece of a SHF_MERGE input section, and where its deduplicated copy is placed
+#[derive(Debug, Clone, Copy)]
+pub struct SectionFragment {
+    /// Offset in the original input section
+    pub input_offset: u64,
+    /// Input section holding the copy, and the offset of the copy in it
+    pub isec: InputSectionId,
+    pub offset: u64,
 }
 
 #[derive(Debug, Clone)]
 pub struct InputSection {
     id: InputSectionId,
+    /// The object file which the section comes from
+    file: ObjectId,
     pub elf_section: Arc<ElfSection>,
     elf_relas: Vec<ElfRela>,
+    /// Pieces sorted by input offset, if this is a merged section
+    fragments: Vec<SectionFragment>,
     /// Offset from the beginning of the output file
     offset: Option<u64>,
     output_section: Option<OutputSectionId>,
 }
 
 impl InputSection {
-    fn new(elf_section: Arc<ElfSection>) -> InputSection {
+    fn new(file: ObjectId, elf_section: Arc<ElfSection>, ids: &IdSpace) -> InputSection {
         InputSection {
-            id: get_next_input_section_id(),
+            id: get_next_input_section_id(ids),
+            file,
             elf_section,
             elf_relas: Vec::new(),
+            fragments: Vec::new(),
             offset: None,
             output_section: None,
         }
@@ -357,6 +798,10 @@ impl InputSection {
         self.id
     }
 
+    pub fn get_file(&self) -> ObjectId {
+        self.file
+    }
+
     pub fn set_relas(&mut self, elf_relas: Vec<ElfRela>) {
         self.elf_relas = elf_relas;
     }
@@ -365,6 +810,50 @@ impl InputSection {
         &self.elf_relas
     }
 
+    /// Rewrite legacy .ctors/.dtors so that they can be placed in .init_array/.fini_array.
+    /// .ctors is run from its end while .init_array is run from its start, so the entries
+    /// are reversed. The 0 and -1 sentinel words provided by crtbegin.o/crtend.o are
+    /// dropped.
+    fn convert_ctors(&mut self) {
+        let name = &self.elf_section.name;
+        let sh_type = if name == ".ctors" || name.starts_with(".ctors.") {
+            elf::abi::SHT_INIT_ARRAY
+        } else if name == ".dtors" || name.starts_with(".dtors.") {
+            elf::abi::SHT_FINI_ARRAY
+        } else {
+            return;
+        };
+
+        let mut relas = std::mem::take(&mut self.elf_relas)
+            .into_iter()
+            .map(|rela| (rela.erela.r_offset, rela))
+            .collect::<HashMap<_, _>>();
+        let mut entries = vec![];
+        for (i, word) in self.elf_section.data().chunks_exact(8).enumerate() {
+            let word = u64::from_le_bytes(word.try_into().unwrap());
+            let rela = relas.remove(&(i as u64 * 8));
+            if rela.is_none() && (word == 0 || word == u64::MAX) {
+                continue;
+            }
+            entries.push((word, rela));
+        }
+        entries.reverse();
+
+        let mut data = vec![];
+        for (i, (word, rela)) in entries.into_iter().enumerate() {
+            data.extend_from_slice(&word.to_le_bytes());
+            if let Some(mut rela) = rela {
+                rela.erela.r_offset = i as u64 * 8;
+                self.elf_relas.push(rela);
+            }
+        }
+        let mut header = self.elf_section.header;
+        header.sh_type = sh_type;
+        header.sh_size = data.len() as u64;
+        header.sh_entsize = 8;
+        self.elf_section = Arc::new(ElfSection::new(name.clone(), header, data));
+    }
+
     pub fn get_name(&self) -> &String {
         &self.elf_section.name
     }
@@ -379,6 +868,67 @@ impl InputSection {
         self.elf_section.header.sh_size
     }
 
+    /// Returns true if the section consists of pieces which can be deduplicated: null-
+    /// terminated strings (SHF_MERGE|SHF_STRINGS) or fixed-size records (SHF_MERGE with
+    /// sh_entsize, e.g. .rodata.cst8)
+    pub fn is_mergeable(&self) -> bool {
+        let header = &self.elf_section.header;
+        header.sh_flags & SHF_MERGE as u64 != 0
+            && (header.sh_flags & SHF_STRINGS as u64 != 0 || header.sh_entsize > 0)
+    }
+
+    /// Split a mergeable section into pieces. Returns pairs of the offset and the contents.
+    pub fn split_fragments(&self) -> Vec<(u64, Vec<u8>)> {
+        let header = &self.elf_section.header;
+        let entsize = header.sh_entsize.max(1) as usize;
+        let data = self.elf_section.data();
+        if header.sh_flags & SHF_STRINGS as u64 == 0 {
+            return data
+                .chunks(entsize)
+                .enumerate()
+                .map(|(i, record)| ((i * entsize) as u64, record.to_vec()))
+                .collect();
+        }
+
+        let mut pieces = vec![];
+        let mut start = 0;
+        let mut i = 0;
+        while i + entsize <= data.len() {
+            let is_null = data[i..i + entsize].iter().all(|b| *b == 0);
+            i += entsize;
+            if is_null {
+                pieces.push((start as u64, data[start..i].to_vec()));
+                start = i;
+            }
+        }
+        // An unterminated string at the end is kept as is
+        if start < data.len() {
+            pieces.push((start as u64, data[start..].to_vec()));
+        }
+        pieces
+    }
+
+    /// Replace the contents with deduplicated pieces
+    pub fn set_fragments(&mut self, data: Vec<u8>, fragments: Vec<SectionFragment>) {
+        let mut header = self.elf_section.header;
+        header.sh_size = data.len() as u64;
+        self.elf_section = Arc::new(ElfSection::new(self.elf_section.name.clone(), header, data));
+        self.fragments = fragments;
+    }
+
+    /// Returns the piece containing the offset in the original section
+    pub fn get_fragment(&self, offset: u64) -> Option<&SectionFragment> {
+        let i = self
+            .fragments
+            .partition_point(|frag| frag.input_offset <= offset);
+        self.fragments.get(i.checked_sub(1)?)
+    }
+
+    pub fn get_addralign(&self) -> u64 {
+        // 0 and 1 both mean no alignment constraint
+        self.elf_section.header.sh_addralign.max(1)
+    }
+
     pub fn get_offset(&self) -> Option<u64> {
         self.offset
     }
@@ -394,25 +944,33 @@ impl InputSection {
     pub fn set_output_section(&mut self, output_section: OutputSectionId) {
         self.output_section = Some(output_section);
     }
-
-    pub fn copy_buf(&self, buf: &mut [u8]) {
-        let offset = self.get_offset().unwrap();
-        let size = self.get_size();
-        let data = &self.elf_section.data;
-        // bss and tbss has empty elf section data
-        if !data.is_empty() {
-            buf[offset as usize..(offset + size) as usize].copy_from_slice(data);
-        }
-    }
 }
 
 #[derive(Clone)]
 pub struct ElfSymbol {
-    name: String,
+    name: SymbolName,
     sym: ElfSymbolData,
+    /// Version of a symbol defined in a DSO (e.g. GLIBC_2.2.5), from .gnu.version and
+    /// .gnu.version_d
+    version: Option<String>,
+    /// Section index, which is taken from .symtab_shndx if st_shndx is SHN_XINDEX
+    shndx: usize,
 }
 
 impl ElfSymbol {
+    /// Create a symbol which does not come from the symbol table of an input file
+    pub fn synthetic(name: SymbolName, esym: &Elf64_Sym, shndx: usize) -> ElfSymbol {
+        let mut buf = [0; std::mem::size_of::<Elf64_Sym>()];
+        write_to(&mut buf, 0, esym);
+        let sym = ElfSymbolData::parse_at(LittleEndian, Class::ELF64, &mut 0, &buf).unwrap();
+        ElfSymbol {
+            name,
+            sym,
+            version: None,
+            shndx,
+        }
+    }
+
     /// Return Elf_Sym definied in the object file
     pub fn get_esym(&self) -> &ElfSymbolData {
         &self.sym
@@ -429,16 +987,25 @@ impl ElfSymbol {
         }
     }
 
-    pub fn get_name(&self) -> &String {
-        &self.name
+    pub fn get_name(&self) -> SymbolName {
+        self.name
+    }
+
+    pub fn get_version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Returns the index of the section in which the symbol is defined
+    pub fn get_shndx(&self) -> usize {
+        self.shndx
     }
 
     pub fn is_abs(&self) -> bool {
-        self.sym.st_shndx == elf::abi::SHN_ABS as u16
+        self.sym.st_shndx == elf::abi::SHN_ABS
     }
 
     pub fn is_common(&self) -> bool {
-        self.sym.st_shndx == elf::abi::SHN_COMMON as u16
+        self.sym.st_shndx == elf::abi::SHN_COMMON
     }
 
     pub fn is_weak(&self) -> bool {
@@ -454,14 +1021,57 @@ impl std::fmt::Debug for ElfSymbol {
 
 #[derive(Debug, Clone)]
 pub struct Symbol {
-    pub name: String,
+    pub name: SymbolName,
     /// object file where the symbol is defined
     pub file: Option<ObjectId>,
+    /// Symbol table entry in the file this symbol belongs to. This is kept as is after
+    /// resolution, so attributes of a reference (e.g. weakness) are still available.
     pub esym: Arc<ElfSymbol>,
+    /// Symbol table entry of the definition which this symbol is resolved to
+    def_esym: Option<Arc<ElfSymbol>>,
     global: bool,
+    /// STV_* merged over the definition and all references to the symbol
+    visibility: u8,
+    /// Index of the GOT entry holding the address of the symbol
+    pub got_idx: Option<usize>,
+    /// Index of the GOT entry holding the TP-relative offset of the symbol (GOTTPOFF)
+    pub gottp_idx: Option<usize>,
+    /// Index of the pair of GOT entries holding the module ID and the offset of the symbol
+    /// (TLSGD)
+    pub tlsgd_idx: Option<usize>,
+    /// Index of the PLT entry of a function defined in a DSO
+    pub plt_idx: Option<usize>,
+    /// Index in .dynsym
+    pub dynsym_idx: Option<usize>,
+    /// Offset in .copyrel of the copy of a data object defined in a DSO
+    pub copyrel_offset: Option<u64>,
+    /// True if the PLT entry of a function defined in a DSO is its address
+    pub has_canonical_plt: bool,
+    /// Address in the output, set by assign_symbol_addrs once the layout is final. None if
+    /// the symbol is in a discarded section.
+    pub addr: Option<u64>,
 }
 
 impl Symbol {
+    fn new(esym: &Arc<ElfSymbol>, global: bool) -> Symbol {
+        Symbol {
+            name: esym.name,
+            file: None,
+            esym: Arc::clone(esym),
+            def_esym: None,
+            global,
+            visibility: esym.get_esym().st_vis(),
+            got_idx: None,
+            gottp_idx: None,
+            tlsgd_idx: None,
+            plt_idx: None,
+            dynsym_idx: None,
+            copyrel_offset: None,
+            has_canonical_plt: false,
+            addr: None,
+        }
+    }
+
     pub fn should_write(&self) -> bool {
         // TODO: https://github.com/tamaroning/mold/blob/3489a464c6577ea1ee19f6b9ae3fe46237f4e4ee/object_file.cc#L302
         true
@@ -470,10 +1080,61 @@ impl Symbol {
     pub fn is_global(&self) -> bool {
         self.global
     }
+
+    /// Bind this symbol to a definition
+    pub fn resolve_to(&mut self, file: ObjectId, def_esym: Arc<ElfSymbol>) {
+        self.file = Some(file);
+        self.def_esym = Some(def_esym);
+    }
+
+    /// Returns the symbol table entry of the definition, or that of this symbol itself if it
+    /// is not resolved yet.
+    pub fn get_def_esym(&self) -> &Arc<ElfSymbol> {
+        self.def_esym.as_ref().unwrap_or(&self.esym)
+    }
+
+    pub fn get_visibility(&self) -> u8 {
+        self.visibility
+    }
+
+    /// Merge the visibility of a reference into this symbol. The most constraining one wins.
+    pub fn merge_visibility(&mut self, visibility: u8) {
+        fn rank(visibility: u8) -> u8 {
+            match visibility {
+                elf::abi::STV_DEFAULT => 0,
+                elf::abi::STV_PROTECTED => 1,
+                elf::abi::STV_HIDDEN => 2,
+                elf::abi::STV_INTERNAL => 3,
+                _ => 0,
+            }
+        }
+        if rank(visibility) > rank(self.visibility) {
+            self.visibility = visibility;
+        }
+    }
+}
+
+/// Symbol shared by the files referring to it and the global symbol table, which can be
+/// accessed from multiple threads
+#[derive(Debug)]
+pub struct SymbolCell(RwLock<Symbol>);
+
+impl SymbolCell {
+    pub fn new(symbol: Symbol) -> SymbolCell {
+        SymbolCell(RwLock::new(symbol))
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, Symbol> {
+        self.0.read().unwrap()
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, Symbol> {
+        self.0.write().unwrap()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ElfRela {
     pub erela: Rela,
-    pub symbol: Arc<RefCell<Symbol>>,
+    pub symbol: Arc<SymbolCell>,
 }
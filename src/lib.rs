@@ -0,0 +1,693 @@
+//! bold is an ELF linker for x86-64, AArch64, RISC-V and i386. [`link`] links inputs into an
+//! executable in memory, and [`link_to_file`] writes it to a file as the command-line tool
+//! does.
+
+use std::{
+    hash::{BuildHasher, DefaultHasher, Hash, Hasher, RandomState},
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+};
+
+use rayon::prelude::*;
+
+use crate::{
+    context::{Context, IdSpace},
+    diagnostics::{Diagnostic, Diagnostics},
+    input_file::FileData,
+    input_section::{ElfContents, ObjectFile},
+    linker_script::{LinkerScript, VersionScript},
+    output_file::OutputFile,
+    output_section::{
+        CopyRel, EhFrameHdr, Got, GotPlt, OutputChunk, OutputEhdr, OutputPhdr, OutputSectionRef,
+        OutputShdr, Plt, Shstrtab, Strtab, Symtab,
+    },
+    perf::Perf,
+    target::Target,
+    x86_64::X86_64,
+};
+
+pub use crate::{
+    config::{Config, SortSymbols, Symbolic},
+    error::LinkError,
+};
+
+mod aarch64;
+mod archive;
+mod config;
+mod context;
+mod diagnostics;
+mod eh_frame;
+mod elf_class;
+mod error;
+mod i386;
+mod input_file;
+mod input_section;
+mod linker;
+mod linker_script;
+mod listing;
+mod output_file;
+mod output_section;
+pub mod perf;
+mod relocation;
+mod report;
+mod riscv;
+mod string_table;
+mod symbol_name;
+mod target;
+mod utils;
+mod x86_64;
+
+/// Input of a link
+pub enum Input {
+    /// File on disk, which is an object file, a DSO or an archive
+    Path(PathBuf),
+    /// Object file or DSO in memory. The name is used in diagnostics.
+    Bytes { name: String, data: Vec<u8> },
+    /// Archive in memory. The name is used in diagnostics.
+    Archive { name: String, data: Vec<u8> },
+}
+
+/// Result of a successful link
+pub struct LinkOutput {
+    /// Contents of the output. This is empty if the output was written to a file.
+    pub data: Vec<u8>,
+    /// Warnings reported during the link
+    pub warnings: Vec<String>,
+    /// Time spent in each phase and counters of the link
+    pub perf: Arc<Perf>,
+    /// Tables of the sections and symbols of the output, as requested by --print-sections
+    /// and --print-symbols. This is empty unless either is set.
+    pub listing: String,
+}
+
+/// Link the inputs and return the output in memory. `config.inputs` is not used.
+pub fn link(inputs: &[Input], config: &Config) -> Result<LinkOutput, LinkError> {
+    let perf = Arc::new(Perf::new(config.perf));
+    let _timer = perf.timer("total");
+    let (result, diag) = run(inputs, config, &perf, |filesize| {
+        Ok(OutputFile::in_memory(filesize))
+    });
+    let result = result.map(|(output, listing)| (output.into_vec().unwrap(), listing));
+    let ((data, listing), warnings) = finish(&diag, result)?;
+    Ok(LinkOutput {
+        data,
+        warnings,
+        perf,
+        listing,
+    })
+}
+
+/// Link the inputs and write the output to `path`, which is made executable unless
+/// `config.shared` is set. The file is removed if the link fails after it is created.
+/// `config.inputs` is not used.
+pub fn link_to_file(
+    inputs: &[Input],
+    config: &Config,
+    path: &Path,
+) -> Result<LinkOutput, LinkError> {
+    // The output is written to a temporary file in the same directory and renamed to `path`
+    // when it is complete, so that an interrupted link doesn't leave a truncated output.
+    // Renaming also replaces a running executable, which can't be written (ETXTBSY).
+    let perf = Arc::new(Perf::new(config.perf));
+    let _timer = perf.timer("total");
+    let tmp_path = get_tmp_path(path);
+    let mut created = false;
+    let (result, diag) = run(inputs, config, &perf, |filesize| {
+        created = true;
+        OutputFile::create(&tmp_path, filesize).map_err(|error| LinkError::Io {
+            path: path.to_owned(),
+            error,
+        })
+    });
+    let result = result.and_then(|(output, listing)| {
+        log::info!("Writing buffer to file");
+        let _timer = perf.timer("write");
+        output
+            .commit()
+            .and_then(|()| {
+                // GNU ld doesn't make shared objects executable either
+                if config.shared {
+                    Ok(())
+                } else {
+                    set_executable(&tmp_path)
+                }
+            })
+            .and_then(|()| std::fs::rename(&tmp_path, path))
+            .map(|()| listing)
+            .map_err(|error| LinkError::Io {
+                path: path.to_owned(),
+                error,
+            })
+    });
+    // Don't leave a half-written output
+    if result.is_err() && created {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    let (listing, warnings) = finish(&diag, result)?;
+
+    log::info!("Successfully wrote to {}", path.display());
+    Ok(LinkOutput {
+        data: vec![],
+        warnings,
+        perf,
+        listing,
+    })
+}
+
+/// Returns `<path>.tmp<random>`, where the output is written before it is renamed to `path`
+fn get_tmp_path(path: &Path) -> PathBuf {
+    // RandomState is seeded randomly for each process and each instance
+    let random = RandomState::new().build_hasher().finish();
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(format!(".tmp{:016x}", random));
+    path.with_file_name(name)
+}
+
+/// Add the execute bits to the permissions of the output, like `chmod +x`. Only those whose
+/// read bits are set are added, so that the umask applied when the file was created is
+/// honored.
+#[cfg(unix)]
+fn set_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    let mode = permissions.mode();
+    permissions.set_mode(mode | (mode & 0o444) >> 2);
+    std::fs::set_permissions(path, permissions)
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Print the remaining diagnostics of a link and the summary. Errors other than those
+/// reported during the link are printed here. Returns the warnings on success.
+fn finish<T>(
+    diag: &Diagnostics,
+    result: Result<T, LinkError>,
+) -> Result<(T, Vec<String>), LinkError> {
+    match &result {
+        Err(LinkError::Io { path, error }) => {
+            diag.error(Diagnostic::new(error.to_string()).file(path.to_string_lossy()))
+        }
+        Err(LinkError::Parse { file, msg }) => diag.error(Diagnostic::new(msg.clone()).file(file)),
+        _ => (),
+    }
+    diag.finish();
+    result.map(|value| (value, diag.warnings()))
+}
+
+fn read_input(input: &Input, ids: &IdSpace) -> Result<Vec<ObjectFile>, LinkError> {
+    let (name, files) = match input {
+        Input::Path(path) => {
+            let data = FileData::open(path).map_err(|error| LinkError::Io {
+                path: path.clone(),
+                error,
+            })?;
+            let name = path.to_string_lossy();
+            let files = ObjectFile::read_from(&name, data, ids);
+            (name.into_owned(), files)
+        }
+        Input::Bytes { name, data } => (
+            name.clone(),
+            Ok(vec![ObjectFile::from_bytes(name, data.clone(), ids)]),
+        ),
+        Input::Archive { name, data } => (
+            name.clone(),
+            ObjectFile::read_archive(name, &FileData::from(data.clone()), ids),
+        ),
+    };
+    files.map_err(|msg| LinkError::Parse { file: name, msg })
+}
+
+/// Read a linker script or a version script with `parse`
+fn read_script<T>(path: &str, parse: fn(&str) -> Result<T, String>) -> Result<T, LinkError> {
+    let script = std::fs::read_to_string(path).map_err(|error| LinkError::Io {
+        path: path.into(),
+        error,
+    })?;
+    parse(&script).map_err(|msg| LinkError::Parse {
+        file: path.to_owned(),
+        msg,
+    })
+}
+
+/// Run the whole link. `create_output` is called with the size of the output once the
+/// layout is fixed. Returns the output with all chunks written and the listing of
+/// --print-sections and --print-symbols, and the diagnostics to be passed to `finish`.
+fn run(
+    inputs: &[Input],
+    config: &Config,
+    perf: &Arc<Perf>,
+    create_output: impl FnOnce(u64) -> Result<OutputFile, LinkError>,
+) -> (Result<(OutputFile, String), LinkError>, Arc<Diagnostics>) {
+    let diag = Arc::new(Diagnostics::new(
+        config.error_limit,
+        config.warning_limit,
+        config.fatal_warnings,
+    ));
+    let result =
+        run_phases(inputs, config, &diag, perf, create_output).and_then(|(mut output, listing)| {
+            if config.verify_reproducible {
+                verify_reproducible(inputs, config, &diag, perf, output.buf_mut())?;
+            }
+            Ok((output, listing))
+        });
+    (result, diag)
+}
+
+/// Link the inputs again in memory and check that the output is the same as `data`
+/// (--verify-reproducible). Diagnostics of the second link are the same as those of the
+/// first, so they are not printed.
+fn verify_reproducible(
+    inputs: &[Input],
+    config: &Config,
+    diag: &Diagnostics,
+    perf: &Arc<Perf>,
+    data: &[u8],
+) -> Result<(), LinkError> {
+    let _timer = perf.timer("verify_reproducible");
+    let config = Config {
+        json_report: None,
+        print_sections: false,
+        print_symbols: false,
+        ..config.clone()
+    };
+    let (output, _) = run_phases(
+        inputs,
+        &config,
+        &Arc::new(Diagnostics::silent()),
+        perf,
+        |filesize| Ok(OutputFile::in_memory(filesize)),
+    )?;
+    let second = output.into_vec().unwrap();
+
+    let hash = |data: &[u8]| {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        hasher.finish()
+    };
+    let (hash1, hash2) = (hash(data), hash(&second));
+    if hash1 == hash2 && data == second {
+        log::info!("Output is reproducible (hash {:016x})", hash1);
+        return Ok(());
+    }
+    let offset = data
+        .iter()
+        .zip(&second)
+        .position(|(a, b)| a != b)
+        .unwrap_or(data.len().min(second.len()));
+    let msg = format!(
+        "output is not reproducible: linking again gave a different output \
+         (hash {:016x} vs {:016x}, size {} vs {}, first difference at offset {:#x})",
+        hash1,
+        hash2,
+        data.len(),
+        second.len(),
+        offset
+    );
+    diag.error(msg);
+    diag.flush();
+    Err(LinkError::Failed(diag.errors()))
+}
+
+/// Returns the target of the link, which is given by -m or is the machine of the first
+/// input, and e_flags of the output. Inputs for other machines or classes or with
+/// incompatible flags are unsupported, and so is dynamic linking on targets which don't
+/// implement it.
+fn select_target(
+    files: &[ObjectFile],
+    config: &Config,
+    diag: &Diagnostics,
+) -> Result<(&'static dyn Target, u32), LinkError> {
+    let emulation = match &config.emulation {
+        Some(emulation) => Some(target::from_emulation(emulation).ok_or_else(|| {
+            LinkError::Unsupported(vec![format!("unknown emulation: {}", emulation)])
+        })?),
+        None => None,
+    };
+    let target = emulation
+        .or_else(|| {
+            files
+                .first()
+                .and_then(|file| target::from_e_machine(file.get_e_machine()))
+        })
+        .unwrap_or(&X86_64);
+    let mut e_flags = None;
+    let mut unsupported = vec![];
+    let mut report = |msg: Diagnostic| {
+        unsupported.push(msg.to_string());
+        diag.error(msg);
+    };
+    for file in files {
+        let msg = match target::from_e_machine(file.get_e_machine()) {
+            None => format!("unsupported machine {}", file.get_e_machine()),
+            Some(other) if other.e_machine() != target.e_machine() => format!(
+                "{} object is incompatible with {} output",
+                other.name(),
+                target.name()
+            ),
+            Some(_) if file.get_class() != target.class() => format!(
+                "{} object is incompatible with {} output",
+                file.get_class().name(),
+                target.name()
+            ),
+            Some(_) if file.is_dso() && !target.supports_dynamic_linking() => {
+                format!("dynamic linking is not supported on {}", target.name())
+            }
+            Some(_) => match target.merge_e_flags(e_flags, file.get_e_flags()) {
+                Ok(flags) => {
+                    e_flags = Some(flags);
+                    continue;
+                }
+                Err(msg) => msg,
+            },
+        };
+        report(Diagnostic::new(msg).file(file.get_file_name()));
+    }
+    if config.shared && !target.supports_dynamic_linking() {
+        report(Diagnostic::new(format!(
+            "shared objects are not supported on {}",
+            target.name()
+        )));
+    }
+    if unsupported.is_empty() {
+        Ok((target, e_flags.unwrap_or(0)))
+    } else {
+        Err(LinkError::Unsupported(unsupported))
+    }
+}
+
+fn run_phases(
+    inputs: &[Input],
+    config: &Config,
+    diag: &Arc<Diagnostics>,
+    perf: &Arc<Perf>,
+    create_output: impl FnOnce(u64) -> Result<OutputFile, LinkError>,
+) -> Result<(OutputFile, String), LinkError> {
+    // Print the diagnostics of a phase, and stop if there are too many errors
+    let end_phase = || {
+        if diag.flush() {
+            Ok(())
+        } else {
+            Err(LinkError::Failed(diag.errors()))
+        }
+    };
+
+    let script = config
+        .script
+        .as_deref()
+        .map(|path| read_script(path, LinkerScript::parse))
+        .transpose()?;
+    let version_script = config
+        .version_script
+        .as_deref()
+        .map(|path| read_script(path, VersionScript::parse))
+        .transpose()?;
+    // ENTRY() of the linker script applies unless --entry is given
+    let script_config;
+    let config = match script.as_ref().and_then(|script| script.entry.as_ref()) {
+        Some(entry) if config.entry.is_none() => {
+            script_config = Config {
+                entry: Some(entry.clone()),
+                ..config.clone()
+            };
+            &script_config
+        }
+        _ => config,
+    };
+    let ids = Arc::new(IdSpace::default());
+    let mut read_timer = perf.timer("read");
+    let mut files = vec![];
+    for input in inputs {
+        files.extend(read_input(input, &ids)?);
+    }
+    // IDs are numbered from 0 in each link
+    debug_assert!(
+        !config.reproducible || ids.object_files.load(Ordering::Relaxed) == files.len(),
+        "object file IDs are shared with another link"
+    );
+    let input_size = files
+        .iter()
+        .map(|file| file.get_data().len() as u64)
+        .sum::<u64>();
+    read_timer.add_bytes(input_size);
+    perf.input_bytes.add(input_size);
+    drop(read_timer);
+
+    let mut ctx = Context::new(Arc::clone(diag), ids, Arc::clone(perf));
+
+    // Parse files in parallel, then register their sections and symbols in order. Files
+    // hold symbols which can't be shared between threads, so only their data is passed.
+    let mut parse_timer = perf.timer("parse");
+    parse_timer.add_bytes(input_size);
+    let contents = files
+        .iter()
+        .map(|file| (file.get_file_name(), file.get_data()))
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|(file_name, data)| {
+            log::debug!("Parsing {}", file_name);
+            ElfContents::parse(data, &ctx.ids.symbol_names)
+        })
+        .collect::<Vec<_>>();
+    for (file, contents) in files.iter_mut().zip(contents) {
+        let contents = contents.map_err(|msg| LinkError::Parse {
+            file: file.get_file_name().to_owned(),
+            msg,
+        })?;
+        file.initialize(contents, &mut ctx);
+    }
+    drop(parse_timer);
+    let (target, e_flags) = select_target(&files, config, diag)?;
+
+    // Set priorities to files
+    // What is this?
+
+    for file in files {
+        ctx.set_object_file(file);
+    }
+
+    // Like GNU ld, the headers are not loaded with a linker script, which would have to make
+    // room for them
+    let load_headers = script.is_none();
+    let mut linker = linker::Linker::new(ctx, config, target, e_flags, script, version_script);
+
+    let ehdr = OutputChunk::Ehdr(OutputEhdr::new(load_headers));
+    let shdr = OutputChunk::Shdr(OutputShdr::new());
+    let phdr = OutputChunk::Phdr(OutputPhdr::new(load_headers));
+    let symtab = OutputChunk::Symtab(Symtab::new());
+    let strtab = OutputChunk::Strtab(Strtab::new());
+    let shstrtab = OutputChunk::Shstrtab(Shstrtab::new());
+
+    // Extract archive members which are needed to resolve symbols
+    log::info!("Marking live objects");
+    linker.mark_live_objects();
+
+    // Register (un)defined symbols
+    log::info!("Resolving symbols");
+    let duplicates = linker.register_global_symbols();
+    if !duplicates.is_empty() {
+        return Err(LinkError::DuplicateSymbols(duplicates));
+    }
+    linker.convert_common_symbols();
+    linker.resolve_symbols();
+    linker.define_script_symbols();
+    linker.apply_version_script();
+    let mut undefined = linker.report_unresolved_symbols();
+    undefined.extend(linker.check_required_symbols());
+    if !undefined.is_empty() {
+        return Err(LinkError::UndefinedSymbols(undefined));
+    }
+    linker.import_undefined_symbols();
+    end_phase()?;
+
+    // Eliminate unused archive members
+    // What is this?
+
+    // Eliminate duplicate comdat groups
+    // What is this?
+
+    // Bin input sections into output sections
+    // mold: bin_sections
+    log::info!("Merging sections");
+    let output_sections = linker.bin_input_sections();
+    linker.sort_init_fini();
+    linker.merge_sections();
+
+    // Assign offsets to input sections
+    // mold: set_isec_offsets
+    log::info!("Assigning isec offsets");
+    linker.assign_isec_offsets();
+    end_phase()?;
+
+    // Add sections to the section lists
+    // mold: https://github.com/tamaroning/mold/blob/3489a464c6577ea1ee19f6b9ae3fe46237f4e4ee/main.cc#L1214
+    for output_section in output_sections {
+        let output_section = linker.get_ctx().get_output_section(output_section);
+        linker
+            .chunks
+            .push(OutputChunk::Section(OutputSectionRef::from(output_section)));
+    }
+
+    // Beyond this point, no new symbols will be added to the result.
+
+    // TODO: Convert weak symbols to absolute symbols with value 0
+    // mold: https://github.com/tamaroning/mold/blob/3489a464c6577ea1ee19f6b9ae3fe46237f4e4ee/main.cc#L1236
+
+    // TODO: Make sure that all symbols have been resolved
+    // mold: check_duplicate_symbols
+
+    // TODO: Copy shared object name strings to .dynstr.
+    // mold: https://github.com/tamaroning/mold/blob/3489a464c6577ea1ee19f6b9ae3fe46237f4e4ee/main.cc#L1249
+
+    // Copy DT_RUNPATH strings to .dynstr.
+    // mold: https://github.com/tamaroning/mold/blob/3489a464c6577ea1ee19f6b9ae3fe46237f4e4ee/main.cc#L1254
+
+    // Add headers and sections that have to be at the beginning
+    // or the ending of a file.
+    // mold: https://github.com/tamaroning/mold/blob/3489a464c6577ea1ee19f6b9ae3fe46237f4e4ee/main.cc#L1256
+    linker.chunks.insert(0, ehdr);
+    linker.chunks.insert(1, phdr);
+    linker.chunks.insert(2, shdr);
+    linker.chunks.push(symtab);
+    linker.chunks.push(strtab);
+    linker.chunks.push(shstrtab);
+    linker.index_chunks();
+    if linker
+        .get_ctx()
+        .output_sections()
+        .any(|osec| osec.get_name() == ".eh_frame")
+    {
+        linker
+            .chunks
+            .push(OutputChunk::EhFrameHdr(EhFrameHdr::new()));
+    }
+
+    // Scan relocations to find symbols that need entries in .got and .plt
+    // mold: scan_rels
+    let mut got = Got::new(linker.get_target().got_entry_size());
+    let mut plt = Plt::new(linker.get_target());
+    let mut copyrel = CopyRel::new();
+    let unsupported = linker.scan_relocations(&mut got, &mut plt, &mut copyrel);
+    if !unsupported.is_empty() {
+        return Err(LinkError::Unsupported(unsupported));
+    }
+    end_phase()?;
+    if !got.is_empty() || got.is_referenced {
+        linker.chunks.push(OutputChunk::Got(got));
+    }
+    if !plt.is_empty() {
+        linker.chunks.push(OutputChunk::Plt(plt));
+        linker.chunks.push(OutputChunk::GotPlt(GotPlt::new()));
+    }
+    if !copyrel.is_empty() {
+        linker.chunks.push(OutputChunk::CopyRel(copyrel));
+    }
+    linker.create_dynamic_chunks();
+
+    // Put symbols to .dynsym and sort them, and fill .gnu.version and .gnu.version_r.
+    // Beyond this point, no symbol should be added to .dynsym.
+    // mold: export_dynamic, fill_symbol_versions
+    linker.export_dynamic();
+
+    // Dynamic relocations refer to .dynsym, so their number is known only now
+    linker.create_rela_chunks();
+    linker.create_symtab_shndx();
+
+    // Sort the sections by section flags so that we'll have to create
+    // as few segments as possible.
+    // mold: https://github.com/tamaroning/mold/blob/3489a464c6577ea1ee19f6b9ae3fe46237f4e4ee/main.cc#L1224
+    linker.sort_chunks();
+
+    // TODO: Compute .symtab and .strtab sizes for each file.
+    // mold: ObjectFile::compute_symtab
+
+    // TODO: delete empty output sections
+
+    // FIXME: update_shdr should be called here?
+
+    // Set section indices
+    log::debug!("Setting section indices");
+    linker.set_section_indices();
+
+    // TODO: eh_frame
+    // mold: https://github.com/tamaroning/mold/blob/3489a464c6577ea1ee19f6b9ae3fe46237f4e4ee/main.cc#L1283
+
+    linker.update_shdr();
+
+    log::debug!("Assigning osec offsets");
+    let filesize = linker.assign_osec_offsets();
+    log::debug!("File size: {}", filesize);
+    if config.check_sections {
+        let errors = linker.check_sections();
+        if !errors.is_empty() {
+            return Err(LinkError::Layout(errors));
+        }
+    }
+    linker.report_missing_entry();
+    end_phase()?;
+
+    // mold: https://github.com/tamaroning/mold/blob/c3a86f5b24343f020edfac1f683dea3648a30e61/elf/main.cc#L629
+    linker.fix_synthetic_symbols();
+    linker.assign_symbol_addrs();
+
+    // Create the output. Chunks are written to it in place.
+    let mut output = create_output(filesize)?;
+    let buf = output.buf_mut();
+
+    log::debug!("Chunks:");
+    for chunk in linker.chunks.iter() {
+        let shndx = chunk.get_common().shndx;
+        log::debug!(
+            "\t[{}]: {}",
+            shndx.map(|x| x.to_string()).unwrap_or("-".to_string()),
+            chunk.as_string(linker.get_ctx())
+        );
+    }
+
+    // Copy input sections to the output file
+    log::info!("Copying sections to buffer");
+    linker.copy_buf(buf);
+    linker.relocation(buf);
+    // Relocation errors are reported again if the values are recomputed
+    if config.check_relocs && !linker.get_ctx().diag.has_errors() {
+        linker.check_relocations(buf);
+    }
+    linker.write_eh_frame_hdr(buf);
+
+    end_phase()?;
+
+    // Warnings made fatal by --fatal-warnings don't stop the link where they are reported
+    if diag.has_errors() {
+        return Err(LinkError::Failed(diag.errors()));
+    }
+
+    if let Some(path) = &config.json_report {
+        let _timer = perf.timer("report");
+        std::fs::write(path, linker.get_report().to_string()).map_err(|error| LinkError::Io {
+            path: PathBuf::from(path),
+            error,
+        })?;
+    }
+    let mut listing = String::new();
+    if config.print_sections {
+        listing.push_str(&linker.get_section_listing().to_string());
+    }
+    if config.print_symbols {
+        if !listing.is_empty() {
+            listing.push('\n');
+        }
+        listing.push_str(&linker.get_symbol_listing().to_string());
+    }
+
+    let num_unresolved = linker.get_num_unresolved_symbols();
+    if num_unresolved > 0 && config.reports_undefined_symbols() {
+        diag.warn(format!(
+            "{} undefined symbols were resolved to 0",
+            num_unresolved
+        ));
+    }
+    Ok((output, listing))
+}
@@ -1,35 +1,191 @@
-use std::{cell::RefCell, collections::HashSet, ops::Deref, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::{Deref, Range},
+    sync::Arc,
+};
 
 use elf::{
-    abi::{PF_R, PF_W, PF_X, PT_LOAD, SHF_ALLOC, SHF_EXECINSTR, SHF_TLS, SHF_WRITE, SHT_NOBITS},
+    abi::{
+        DF_1_NOW, DF_BIND_NOW, DF_SYMBOLIC, DF_TEXTREL, DT_DEBUG, DT_FINI, DT_FINI_ARRAY,
+        DT_FINI_ARRAYSZ, DT_FLAGS, DT_FLAGS_1, DT_GNU_HASH, DT_INIT, DT_INIT_ARRAY,
+        DT_INIT_ARRAYSZ, DT_JMPREL, DT_NEEDED, DT_NULL, DT_PLTGOT, DT_PLTREL, DT_PLTRELSZ,
+        DT_PREINIT_ARRAY, DT_PREINIT_ARRAYSZ, DT_RELA, DT_RELACOUNT, DT_RELAENT, DT_RELASZ,
+        DT_SONAME, DT_STRSZ, DT_STRTAB, DT_SYMBOLIC, DT_SYMENT, DT_SYMTAB, DT_TEXTREL, DT_VERDEF,
+        DT_VERDEFNUM, DT_VERNEED, DT_VERNEEDNUM, DT_VERSYM, ET_DYN, ET_EXEC, PF_R, PF_W, PF_X,
+        PT_DYNAMIC, PT_GNU_EH_FRAME, PT_GNU_RELRO, PT_INTERP, PT_LOAD, PT_PHDR, PT_TLS, SHF_ALLOC,
+        SHF_EXECINSTR, SHF_TLS, SHF_WRITE, SHN_ABS, SHN_COMMON, SHN_UNDEF, SHN_XINDEX,
+        SHT_FINI_ARRAY, SHT_INIT_ARRAY, SHT_NOBITS, SHT_PREINIT_ARRAY, STB_GLOBAL, STT_FUNC,
+        STT_GNU_IFUNC, STT_NOTYPE, STT_SECTION, STT_TLS, STV_DEFAULT, STV_HIDDEN, STV_PROTECTED,
+        VER_NDX_GLOBAL, VER_NDX_LOCAL,
+    },
+    relocation::Elf64_Rela,
     section::Elf64_Shdr,
     segment::Elf64_Phdr,
     symbol::Elf64_Sym,
 };
+use rayon::prelude::*;
+use rustc_hash::FxHashMap;
 
 use crate::{
-    config::{Config, PAGE_SIZE},
+    config::{parse_number, Config, SortSymbols, Symbolic},
     context::Context,
-    dummy,
-    input_section::{InputSection, InputSectionId, Symbol},
-    output_section::{get_output_section_name, ChunkInfo, OutputChunk, OutputSectionId},
-    relocation::{relocation_size, relocation_value, RelValue},
-    utils::{align_to, is_c_identifier},
+    diagnostics::Diagnostic,
+    dummy, eh_frame,
+    input_section::{
+        ElfRela, ElfSymbol, InputSection, InputSectionId, ObjectFile, ObjectId, SectionFragment,
+        Symbol, SymbolCell,
+    },
+    linker_script::{Assignment, LinkerScript, SectionsCommand, SymbolVersion, VersionScript},
+    listing::{hex, sh_flags_string, sh_type_name, st_bind_name, st_type_name, Column, Table},
+    output_section::{
+        get_output_section_name, gnu_hash, is_orphan_section, ChunkInfo, CopyRel, DefinedVersion,
+        DynRela, DynStr, DynSym, Dynamic, GnuHash, Got, GotPlt, Interp, NeededVersion, OutputChunk,
+        OutputSectionId, Plt, SectionImage, SymtabShndx, Verdef, Verneed, Versym,
+    },
+    relocation::{check_relocation_data, RelValue, RelaxedInsn, RelocationInput},
+    report::{Json, REPORT_VERSION},
+    string_table::StringTable,
+    symbol_name::SymbolName,
+    target::{DynamicRelocTypes, Target, TlsReloc},
+    utils::{align_to, is_c_identifier, split_disjoint_mut, to_st_shndx, SHN_LORESERVE},
 };
 
+/// Conventional order of output sections, which is applied among sections with the same
+/// permissions. Notes come before all of them, and .symtab, .strtab and .shstrtab after
+/// all the others.
+const SECTION_ORDER: &[&str] = &[
+    // Read-only
+    ".gnu.hash",
+    ".dynsym",
+    ".dynstr",
+    ".gnu.version",
+    ".gnu.version_d",
+    ".gnu.version_r",
+    ".rela.dyn",
+    ".rela.plt",
+    ".rodata",
+    ".eh_frame_hdr",
+    ".eh_frame",
+    // Executable
+    ".init",
+    ".plt",
+    ".text",
+    ".fini",
+    // RELRO
+    ".preinit_array",
+    ".init_array",
+    ".fini_array",
+    ".data.rel.ro",
+    ".dynamic",
+    ".got",
+    ".bss.rel.ro",
+    // Writable
+    ".got.plt",
+    ".data",
+    ".copyrel",
+    ".bss",
+    // Non-allocated
+    ".comment",
+];
+
+/// The TLS initialization image, i.e. .tdata followed by .tbss, which is copied to the TLS
+/// block of each thread
+#[derive(Debug, Clone, Copy, Default)]
+struct TlsTemplate {
+    addr: u64,
+    offset: u64,
+    /// Size of the initialized part
+    filesz: u64,
+    memsz: u64,
+    align: u64,
+}
+
+impl TlsTemplate {
+    /// Offset of the thread pointer from the start of the template. On x86-64, the thread
+    /// pointer points to the end of the TLS block.
+    fn tp_offset(&self) -> u64 {
+        align_to(self.memsz, self.align)
+    }
+}
+
+/// Entry of .symtab with the file defining the symbol
+struct SymtabEntry {
+    name: SymbolName,
+    esym: Elf64_Sym,
+    /// Section index stored in .symtab_shndx
+    xindex: u32,
+    file: Option<ObjectId>,
+}
+
+/// Symbol defined by an assignment in the linker script
+struct ScriptSymbol {
+    /// Output section the assignment is in. The symbol is absolute if it is outside of
+    /// output sections.
+    section: Option<String>,
+    /// Defined by PROVIDE() or PROVIDE_HIDDEN() rather than a plain assignment
+    provided: bool,
+    hidden: bool,
+    /// Set by fix_synthetic_symbols once the layout is final
+    value: u64,
+}
+
+/// Symbol defined by the linker at the start of .got.plt, or .got if there is no PLT
+const GLOBAL_OFFSET_TABLE: &str = "_GLOBAL_OFFSET_TABLE_";
+/// Symbol defined by the linker at the start of .dynamic
+const DYNAMIC: &str = "_DYNAMIC";
+
 pub struct Linker<'ctx> {
     ctx: Context,
     // Move this to the main function
     pub chunks: Vec<OutputChunk>,
     pub config: &'ctx Config,
+    /// Architecture of the inputs and the output
+    target: &'static dyn Target,
+    /// e_flags of the output, which is specific to the target
+    e_flags: u32,
+    /// Layout given by -T
+    script: Option<LinkerScript>,
+    /// Symbols which the linker script defines, decided by define_script_symbols
+    script_symbols: FxHashMap<SymbolName, ScriptSymbol>,
+    /// Given by --version-script
+    version_script: Option<VersionScript>,
+    /// Versions of the global symbols defined in the output, decided by
+    /// apply_version_script
+    symbol_versions: FxHashMap<SymbolName, SymbolVersion>,
+    /// Names of non-weak undefined symbols without definitions
+    unresolved_symbols: Vec<SymbolName>,
+    /// Position in `chunks` of the chunk of each output section. Rebuilt by
+    /// `index_chunks` whenever chunks are inserted or reordered.
+    osec_chunk_index: FxHashMap<OutputSectionId, usize>,
+    /// Whether the addresses of symbols have been stored by assign_symbol_addrs
+    symbol_addrs_assigned: bool,
+    /// Set by assign_osec_offsets once TLS sections are laid out
+    tls_template: Option<TlsTemplate>,
 }
 
 impl Linker<'_> {
-    pub fn new<'ctx>(ctx: Context, config: &'ctx Config) -> Linker<'ctx> {
+    pub fn new<'ctx>(
+        ctx: Context,
+        config: &'ctx Config,
+        target: &'static dyn Target,
+        e_flags: u32,
+        script: Option<LinkerScript>,
+        version_script: Option<VersionScript>,
+    ) -> Linker<'ctx> {
         Linker {
             ctx,
             chunks: vec![],
             config,
+            target,
+            e_flags,
+            script,
+            script_symbols: FxHashMap::default(),
+            version_script,
+            symbol_versions: FxHashMap::default(),
+            unresolved_symbols: vec![],
+            osec_chunk_index: FxHashMap::default(),
+            symbol_addrs_assigned: false,
+            tls_template: None,
         }
     }
 
@@ -37,58 +193,146 @@ impl Linker<'_> {
         &self.ctx
     }
 
-    /// Resolve all symbols
-    pub fn resolve_symbols(&mut self) {
-        // https://github.com/tamaroning/mold/blob/3489a464c6577ea1ee19f6b9ae3fe46237f4e4ee/object_file.cc#L536
+    pub fn get_target(&self) -> &'static dyn Target {
+        self.target
+    }
 
-        // Set file to symbols defined in the object file
-        let mut num_defined = 0;
-        for file in self.ctx.files() {
-            let object_id = file.get_id();
-            for (i, symbol) in file.get_symbols().iter().enumerate() {
-                let esym = &file.get_elf_symbols()[i];
-                if esym.get_esym().is_undefined() {
-                    continue;
+    /// Alignment of segments, which is -z max-page-size or the page size of the target
+    fn page_size(&self) -> u64 {
+        self.config.max_page_size.unwrap_or(self.target.page_size())
+    }
+
+    /// Address of the first segment, which is --image-base or the default of the target.
+    /// Shared objects are linked at 0 and relocated to their load address.
+    fn image_base(&self) -> u64 {
+        let default = if self.config.shared {
+            0
+        } else {
+            self.target.default_image_base()
+        };
+        self.config.image_base.unwrap_or(default)
+    }
+
+    /// Returns the types of dynamic relocations. Only targets supporting dynamic linking
+    /// produce dynamic output.
+    fn dynamic_reloc_types(&self) -> &'static DynamicRelocTypes {
+        self.target
+            .dynamic_reloc_types()
+            .expect("dynamic linking is not supported")
+    }
+
+    /// Decide which archive members are linked. A member is extracted if it defines a symbol
+    /// referenced by a live file or one of the root symbols such as the entry symbol.
+    pub fn mark_live_objects(&mut self) {
+        let files = self.ctx.files().collect::<Vec<_>>();
+
+        // Symbols defined by live files never cause extraction
+        let mut defined = HashSet::new();
+        // Symbols defined by archive members. The first member on the command line wins.
+        let mut lazy_symbols = HashMap::new();
+        let mut names = self
+            .config
+            .root_symbols()
+            .map(|name| self.ctx.intern(name))
+            .collect::<Vec<_>>();
+        for file in files {
+            if file.is_alive() {
+                defined.extend(file.get_defined_global_names());
+                names.extend(file.get_undefined_global_names());
+            } else {
+                for name in file.get_defined_global_names() {
+                    lazy_symbols.entry(name).or_insert(file.get_id());
                 }
-                let Some(symbol) = symbol else {
-                    continue;
-                };
-                let symbol = symbol.deref();
-                symbol.borrow_mut().file = Some(object_id);
-                // TODO: visibility?
-                num_defined += 1;
             }
         }
 
-        // Symbols defined in other object files
-        let mut num_resolved = 0;
-        let mut unresolved = HashSet::new();
-        for file in self.ctx.files() {
-            for (i, symbol) in file.get_symbols().iter().enumerate() {
-                if let Some(symbol) = symbol {
-                    if i < file.get_first_global() {
-                        continue;
-                    }
+        while let Some(name) = names.pop() {
+            if defined.contains(&name) {
+                continue;
+            }
+            let Some(&id) = lazy_symbols.get(&name) else {
+                continue;
+            };
+            let file = self.ctx.get_file_mut(id);
+            log::debug!("Extract {} for {}", file.get_file_name(), name);
+            file.set_alive();
+            defined.extend(file.get_defined_global_names());
+            names.extend(file.get_undefined_global_names());
+        }
+
+        self.ctx.remove_dead_files();
+    }
+
+    /// Register global symbols of live files in command-line order. Returns the names of
+    /// symbols which are defined more than once.
+    pub fn register_global_symbols(&mut self) -> Vec<String> {
+        let files = self.ctx.files().collect::<Vec<_>>();
+        let symbols = files
+            .iter()
+            .flat_map(|file| {
+                file.get_symbols()[file.get_first_global()..]
+                    .iter()
+                    .flatten()
+                    .map(|symbol| (Arc::clone(symbol), file.is_dso()))
+            })
+            .collect::<Vec<_>>();
+        let mut duplicates = vec![];
+        for (symbol, from_dso) in symbols {
+            let name = symbol.read().name;
+            if !self
+                .ctx
+                .add_global_symbol(symbol, from_dso, self.config.warn_common)
+            {
+                duplicates.push(name.to_string());
+            }
+        }
+        duplicates
+    }
+
+    /// Resolve all symbols
+    pub fn resolve_symbols(&mut self) {
+        let _timer = self.ctx.perf.timer("resolve_symbols");
+        // https://github.com/tamaroning/mold/blob/3489a464c6577ea1ee19f6b9ae3fe46237f4e4ee/object_file.cc#L536
+
+        // Both passes work on files in parallel. Each file only binds its own symbols, and
+        // which definition wins was decided by register_global_symbols, so the result doesn't
+        // depend on the order in which files are processed.
+        let files = self.ctx.files().collect::<Vec<_>>();
+
+        // Set file to symbols defined in the object file
+        let num_defined = files
+            .par_iter()
+            .map(|file| {
+                let object_id = file.get_id();
+                let mut num_defined = 0;
+                for (i, symbol) in file.get_symbols().iter().enumerate() {
                     let esym = &file.get_elf_symbols()[i];
-                    if !esym.get_esym().is_undefined() {
+                    if esym.get_esym().is_undefined() {
                         continue;
                     }
-                    let name = esym.get_name();
-                    let Some(global_symbol) = self.ctx.get_global_symbol(name).map(Arc::clone)
-                    else {
-                        unresolved.insert(name.to_owned());
+                    let Some(symbol) = symbol else {
                         continue;
                     };
-                    let defined_file = global_symbol.deref().borrow().file;
-                    let defined_esym = Arc::clone(&global_symbol.deref().borrow().esym);
-                    assert!(defined_file.is_some());
-                    let mut symbol = symbol.deref().borrow_mut();
-                    symbol.file = defined_file;
-                    symbol.esym = defined_esym;
-                    num_resolved += 1;
+                    let symbol = symbol.deref();
+                    symbol.write().resolve_to(object_id, Arc::clone(esym));
+                    num_defined += 1;
                 }
-            }
-        }
+                num_defined
+            })
+            .sum::<usize>();
+
+        // Bind global symbols to the definition registered to the global symbol table. This
+        // covers references to symbols defined in other object files as well as definitions
+        // overridden by another file (e.g. weak ones).
+        let results = files
+            .par_iter()
+            .map(|file| self.bind_global_symbols(file))
+            .collect::<Vec<_>>();
+        let num_resolved = results.iter().map(|(n, _)| n).sum::<usize>();
+        let unresolved = results
+            .into_iter()
+            .flat_map(|(_, unresolved)| unresolved)
+            .collect::<HashSet<_>>();
 
         log::info!(
             "Summary: Defined: {} symbols, Resolved: {} references, Unresolved: {} symbols",
@@ -97,22 +341,287 @@ impl Linker<'_> {
             unresolved.len()
         );
 
-        if !unresolved.is_empty() {
-            log::warn!("Unresolved symbols:");
-            for symbol in unresolved {
-                log::warn!("\t{}", symbol);
+        let mut unresolved = unresolved.into_iter().collect::<Vec<_>>();
+        unresolved.sort();
+        self.unresolved_symbols = unresolved;
+    }
+
+    /// Bind the global symbols of a file to their definitions. Returns the number of
+    /// symbols bound to a definition in another file, and the names of undefined symbols.
+    fn bind_global_symbols(&self, file: &ObjectFile) -> (usize, Vec<SymbolName>) {
+        let mut num_resolved = 0;
+        let mut unresolved = vec![];
+        for (i, symbol) in file.get_symbols().iter().enumerate() {
+            if i < file.get_first_global() {
+                continue;
+            }
+            let Some(symbol) = symbol else {
+                continue;
+            };
+            let esym = &file.get_elf_symbols()[i];
+            let name = esym.get_name();
+            let Some(global_symbol) = self.ctx.get_global_symbol(name).map(Arc::clone) else {
+                // Weak references are allowed to stay undefined. References from DSOs
+                // are left to the dynamic linker.
+                if esym.get_esym().is_undefined()
+                    && !esym.is_weak()
+                    && !file.is_dso()
+                    && name != GLOBAL_OFFSET_TABLE
+                    && name != DYNAMIC
+                {
+                    unresolved.push(name);
+                }
+                continue;
+            };
+            if Arc::ptr_eq(&global_symbol, symbol) {
+                // This is the definition itself
+                continue;
+            }
+            if !esym.get_esym().is_undefined() {
+                log::debug!(
+                    "{} in {} is overridden by another definition",
+                    name,
+                    file.get_file_name()
+                );
+            }
+            let defined_file = global_symbol.read().file.unwrap();
+            let defined_esym = Arc::clone(global_symbol.read().get_def_esym());
+            let visibility = symbol.read().get_visibility();
+            global_symbol.write().merge_visibility(visibility);
+            symbol.write().resolve_to(defined_file, defined_esym);
+            num_resolved += 1;
+        }
+        (num_resolved, unresolved)
+    }
+
+    /// Look up the versions of the global symbols defined in input files in the version
+    /// script. This must be called after symbols are resolved.
+    pub fn apply_version_script(&mut self) {
+        let Some(version_script) = &self.version_script else {
+            return;
+        };
+        let mut versions = FxHashMap::default();
+        for file in self.ctx.files().filter(|file| !file.is_dso()) {
+            for esym in &file.get_elf_symbols()[file.get_first_global()..] {
+                if esym.get_esym().is_undefined() {
+                    continue;
+                }
+                let name = esym.get_name();
+                if let Some(version) = version_script.find_version(name.as_str()) {
+                    versions.insert(name, version);
+                }
             }
         }
+        self.symbol_versions = versions;
     }
 
-    pub fn convert_common_symbols(&mut self) {
-        let bss = self.ctx.get_or_create_output_section_mut(
-            ".bss",
-            SHT_NOBITS,
-            (SHF_WRITE | SHF_ALLOC) as u64,
-        );
+    /// Returns the index in .gnu.version of a symbol defined in the output. Versions in the
+    /// version script other than the anonymous one are numbered from 2.
+    fn get_version_index(&self, name: SymbolName) -> u16 {
+        let Some(SymbolVersion::Global(i)) = self.symbol_versions.get(&name) else {
+            return VER_NDX_GLOBAL;
+        };
+        match &self.version_script.as_ref().unwrap().versions[*i].name {
+            Some(_) => VER_NDX_GLOBAL + 1 + *i as u16,
+            None => VER_NDX_GLOBAL,
+        }
+    }
+
+    /// Returns true if the version script hides the symbol
+    fn is_hidden_by_version_script(&self, name: SymbolName) -> bool {
+        self.symbol_versions.get(&name) == Some(&SymbolVersion::Local)
+    }
 
+    /// Decide which symbols the assignments in the linker script define. A plain assignment
+    /// always defines the symbol, overriding a definition in an input file. PROVIDE() only
+    /// defines it if it is referenced and nothing else defines it.
+    pub fn define_script_symbols(&mut self) {
+        let Some(script) = &self.script else {
+            return;
+        };
+        // References from input files, including weak ones, and from the script itself
+        let mut referenced = self
+            .config
+            .root_symbols()
+            .map(|name| self.ctx.intern(name))
+            .collect::<HashSet<_>>();
         for file in self.ctx.files() {
+            referenced.extend(
+                file.get_elf_symbols()[file.get_first_global()..]
+                    .iter()
+                    .filter(|esym| esym.get_esym().is_undefined())
+                    .map(|esym| esym.get_name()),
+            );
+        }
+        for (_, assignment) in script.assignments() {
+            referenced.extend(
+                assignment
+                    .expr
+                    .get_symbols()
+                    .into_iter()
+                    .map(|name| self.ctx.intern(name)),
+            );
+        }
+
+        let mut symbols = FxHashMap::default();
+        for (osec, assignment) in script.assignments() {
+            let name = self.ctx.intern(&assignment.symbol);
+            if assignment.provide
+                && (!referenced.contains(&name)
+                    || self.ctx.get_global_symbol(name).is_some()
+                    || symbols.contains_key(&name))
+            {
+                continue;
+            }
+            symbols.insert(
+                name,
+                ScriptSymbol {
+                    section: osec.map(|osec| osec.name.clone()),
+                    provided: assignment.provide,
+                    hidden: assignment.hidden,
+                    value: 0,
+                },
+            );
+        }
+        self.unresolved_symbols
+            .retain(|name| !symbols.contains_key(name));
+        self.script_symbols = symbols;
+    }
+
+    /// Report undefined symbols left after resolution. References to them are resolved to 0.
+    /// Returns their names if they are errors.
+    pub fn report_unresolved_symbols(&self) -> Vec<String> {
+        if !self.config.reports_undefined_symbols() {
+            return vec![];
+        }
+        let unresolved_symbols = self.get_unresolved_symbols();
+        for name in &unresolved_symbols {
+            if self.config.warn_unresolved_symbols {
+                self.ctx
+                    .diag
+                    .warn(Diagnostic::new(format!("undefined symbol: {}", name)).symbol(name));
+            } else {
+                self.ctx
+                    .diag
+                    .error(Diagnostic::new(format!("undefined symbol: {}", name)).symbol(name));
+            }
+        }
+        if self.config.warn_unresolved_symbols {
+            return vec![];
+        }
+        unresolved_symbols
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    pub fn get_num_unresolved_symbols(&self) -> usize {
+        self.get_unresolved_symbols().len()
+    }
+
+    /// __tls_get_addr doesn't need to be defined if all calls to it are relaxed away
+    fn get_unresolved_symbols(&self) -> Vec<SymbolName> {
+        self.unresolved_symbols
+            .iter()
+            .copied()
+            .filter(|name| *name != "__tls_get_addr" || !self.are_tls_calls_relaxed())
+            .collect()
+    }
+
+    fn are_tls_calls_relaxed(&self) -> bool {
+        self.ctx.files().all(|file| {
+            file.get_input_sections().iter().flatten().all(|isec_id| {
+                let isec = self.ctx.get_input_section(*isec_id);
+                let relas = isec.get_relas();
+                relas.iter().enumerate().all(|(i, rel)| {
+                    rel.symbol.read().name != "__tls_get_addr"
+                        || i > 0 && self.is_tls_call_relaxed(isec, i - 1)
+                })
+            })
+        })
+    }
+
+    /// Returns true if the relocation at `idx` starts a general-dynamic or local-dynamic
+    /// sequence whose call to __tls_get_addr is relaxed away
+    fn is_tls_call_relaxed(&self, isec: &InputSection, idx: usize) -> bool {
+        let r_type = isec.get_relas()[idx].erela.r_type;
+        match self.target.tls_reloc(r_type) {
+            Some(kind @ (TlsReloc::Gd | TlsReloc::Ld)) => {
+                self.relax_tls(isec, idx, kind, 0, 0).is_some()
+            }
+            _ => false,
+        }
+    }
+
+    /// Make sure that symbols given by --require-defined are defined. Returns the names of
+    /// those which are missing. A missing entry symbol is reported by report_missing_entry.
+    pub fn check_required_symbols(&self) -> Vec<String> {
+        let mut missing = vec![];
+        for name in &self.config.require_defined {
+            let name_id = self.ctx.intern(name);
+            if self.ctx.get_global_symbol(name_id).is_none()
+                && !self.script_symbols.contains_key(&name_id)
+            {
+                self.ctx.diag.error(
+                    Diagnostic::new(format!("required symbol {} is not defined", name))
+                        .symbol(name),
+                );
+                missing.push(name.clone());
+            }
+        }
+        missing
+    }
+
+    /// In a shared object, undefined symbols are left to the dynamic linker unless they are
+    /// hidden. Register a reference to each as the global symbol, so that all references
+    /// share its .dynsym, GOT and PLT entries. The reference is non-weak if any is.
+    pub fn import_undefined_symbols(&mut self) {
+        if !self.config.shared {
+            return;
+        }
+        let mut imports = FxHashMap::<SymbolName, Arc<SymbolCell>>::default();
+        let mut visibilities = FxHashMap::default();
+        for file in self.ctx.files().filter(|file| !file.is_dso()) {
+            for (i, symbol) in file.get_symbols().iter().enumerate() {
+                let esym = &file.get_elf_symbols()[i];
+                let name = esym.get_name();
+                let Some(symbol) = symbol.as_ref().filter(|_| i >= file.get_first_global()) else {
+                    continue;
+                };
+                if !esym.get_esym().is_undefined()
+                    || self.ctx.get_global_symbol(name).is_some()
+                    || self.script_symbols.contains_key(&name)
+                    || name == GLOBAL_OFFSET_TABLE
+                    || name == DYNAMIC
+                {
+                    continue;
+                }
+                let visibility = visibilities.entry(name).or_insert(STV_DEFAULT);
+                if symbol.read().get_visibility() != STV_DEFAULT {
+                    *visibility = symbol.read().get_visibility();
+                }
+                let import = imports.entry(name).or_insert_with(|| Arc::clone(symbol));
+                if import.read().esym.is_weak() && !esym.is_weak() {
+                    *import = Arc::clone(symbol);
+                }
+            }
+        }
+        for (name, symbol) in imports {
+            if visibilities[&name] != STV_DEFAULT {
+                continue;
+            }
+            self.ctx.add_undefined_symbol(symbol);
+            self.unresolved_symbols
+                .retain(|unresolved| *unresolved != name);
+        }
+    }
+
+    /// Allocate common symbols which are not overridden by real definitions in .bss
+    pub fn convert_common_symbols(&mut self) {
+        // mold: https://github.com/tamaroning/mold/blob/3489a464c6577ea1ee19f6b9ae3fe46237f4e4ee/object_file.cc#L668
+        let files = self.ctx.files().collect::<Vec<_>>();
+        let mut commons = vec![];
+        for file in files {
             for (i, symbol) in file.get_symbols().iter().enumerate() {
                 if i < file.get_first_global() {
                     continue;
@@ -120,48 +629,99 @@ impl Linker<'_> {
                 let Some(symbol) = symbol else {
                     continue;
                 };
-                let esym = &file.get_elf_symbols()[i];
-                if !esym.is_common() {
+                if !file.get_elf_symbols()[i].is_common() {
                     continue;
                 }
-                if symbol.borrow().file == Some(file.get_id()) {
-                    continue;
+                let name = symbol.read().name;
+                if let Some(global_symbol) = self.ctx.get_global_symbol(name) {
+                    if Arc::ptr_eq(global_symbol, symbol) {
+                        commons.push((file.get_id(), i, name));
+                    }
                 }
-
-                // TODO: ?
-                // https://github.com/tamaroning/mold/blob/3489a464c6577ea1ee19f6b9ae3fe46237f4e4ee/object_file.cc#L668
             }
         }
+
+        let ids = Arc::clone(&self.ctx.ids);
+        for (file_id, i, name) in commons {
+            let (size, align) = self.ctx.get_common_symbol(name).unwrap();
+            log::debug!(
+                "Allocate common symbol {} (size: {}, align: {})",
+                name,
+                size,
+                align
+            );
+            let file = self.ctx.get_file_mut(file_id);
+            let input_section = file.allocate_common_symbol(i, size, align, &ids);
+            self.ctx.set_input_section(input_section);
+        }
     }
 
     pub fn bin_input_sections(&mut self) -> Vec<OutputSectionId> {
+        let _timer = self.ctx.perf.timer("bin_input_sections");
+        // Keep the command-line order so that fragments of .init/.fini from crti.o, user
+        // objects and crtn.o are concatenated in this order
+        let files = self.ctx.files().collect::<Vec<_>>();
         let mut input_sections = vec![];
-        for file in self.ctx.files_mut() {
-            for input_section in file.get_input_sections().iter() {
-                if let Some(input_section) = input_section {
-                    input_sections.push(*input_section);
-                }
+        for file in files {
+            for input_section in file.get_input_sections().iter().flatten() {
+                input_sections.push((file.get_file_name().to_owned(), *input_section));
             }
         }
+        // Inputs selected by the linker script are in the order of the descriptions they
+        // match, and unmatched ones come after them
+        if let Some(script) = &self.script {
+            input_sections.sort_by_cached_key(|(file_name, input_section)| {
+                let name = self.ctx.get_input_section(*input_section).get_name();
+                script
+                    .find_output_section(file_name, name)
+                    .map_or(usize::MAX, |(_, pos)| pos)
+            });
+        }
 
         let mut chunks = vec![];
-        for input_section_id in input_sections {
+        for (file_name, input_section_id) in input_sections {
             let input_section = self.ctx.get_input_section(input_section_id);
-            let output_section_name = get_output_section_name(input_section.get_name());
             let sh_type = input_section.elf_section.header.sh_type;
+            let name = input_section.get_name().to_owned();
+            // The linker script takes precedence over the default mapping
+            let script_osec = self
+                .script
+                .as_ref()
+                .and_then(|script| script.find_output_section(&file_name, &name))
+                .map(|(osec, _)| osec);
+            let is_orphan = script_osec.is_none() && is_orphan_section(&name, sh_type);
+            let output_section_name = script_osec
+                .unwrap_or_else(|| get_output_section_name(&name, sh_type))
+                .to_owned();
             let sh_flags = input_section.elf_section.header.sh_flags;
+            let addralign = input_section.get_addralign();
+            let entsize = input_section.elf_section.header.sh_entsize;
             let output_section =
                 self.ctx
                     .get_or_create_output_section_mut(&output_section_name, sh_type, sh_flags);
             let osec_id = output_section.get_id();
 
-            if output_section.get_input_sections_mut().is_empty() {
+            let is_new = output_section.get_input_sections_mut().is_empty();
+            if is_new {
                 let section = &output_section;
                 chunks.push(section.get_id());
             }
-            output_section
-                .get_input_sections_mut()
-                .push(input_section_id);
+            let same_perms =
+                output_section.add_input_section(input_section_id, addralign, sh_flags, entsize);
+            if !same_perms {
+                self.ctx.diag.warn(
+                    Diagnostic::new(format!(
+                        "{} has different permissions from other inputs of {}",
+                        name, output_section_name
+                    ))
+                    .file(file_name),
+                );
+            }
+            if is_new && is_orphan && self.config.verbose {
+                self.ctx
+                    .diag
+                    .warn(format!("orphan section: {}", output_section_name));
+            }
 
             let input_section = self.ctx.get_input_section_mut(input_section_id);
             input_section.set_output_section(osec_id);
@@ -169,158 +729,1505 @@ impl Linker<'_> {
         chunks
     }
 
-    pub fn assign_isec_offsets(&mut self) {
-        let _ = self.assign_osec_offsets();
-    }
-
-    pub fn update_shdr(&mut self) {
-        // Set sh_name to all shdrs
-        fn calc_sh_name_from_shstrtab(shstrtab_content: &[u8], section_name: &str) -> usize {
-            let mut section_name = unsafe { section_name.to_string().as_bytes_mut() }.to_vec();
-            section_name.push(0);
-            let mut sh_name = 0;
-            let mut i = 0;
-            while i < shstrtab_content.len() {
-                if shstrtab_content[i..].starts_with(&section_name) {
-                    sh_name = i;
-                    break;
-                }
-                i += 1;
-            }
-            sh_name
-        }
-        let shstrtab_content = self.get_shstrtab_content();
-        for chunk in self.chunks.iter_mut() {
-            if !chunk.is_header() {
-                let name = chunk.get_section_name(&self.ctx);
-                let shdr = &mut chunk.get_common_mut().shdr;
-                shdr.sh_name = calc_sh_name_from_shstrtab(&shstrtab_content, &name) as u32;
+    /// Sort inputs of .init_array, .fini_array and .preinit_array by priority, so that e.g.
+    /// `.init_array.00101` comes before `.init_array.00102` and the unsuffixed
+    /// `.init_array`. .fini_array is sorted in the same way because the runtime calls it in
+    /// reverse order.
+    // mold: sort_init_fini
+    pub fn sort_init_fini(&mut self) {
+        fn get_priority(name: &str) -> u32 {
+            let Some((prefix, suffix)) = name.rsplit_once('.') else {
+                return 65536;
+            };
+            match suffix.parse::<u32>() {
+                // .ctors.N and .dtors.N are run in the reverse order
+                Ok(n) if prefix == ".ctors" || prefix == ".dtors" => 65535u32.saturating_sub(n),
+                Ok(n) => n,
+                Err(_) => 65536,
             }
         }
 
-        // Call update_shdr for all chunks
-        let num_shdrs = self.get_shdrs().len();
-        let num_phdrs = self.create_phdr().len();
-        let shstrtab_size = shstrtab_content.len() as u64;
-        let (symtab_content, strtab_content) = self.get_symtab();
-        let strtab_shndx = self
-            .chunks
-            .iter()
-            .find_map(|chunk| {
-                if let OutputChunk::Strtab(chunk) = chunk {
-                    Some(chunk.common.shndx.unwrap() as u32)
-                } else {
-                    None
-                }
+        let osec_ids = self
+            .ctx
+            .output_sections()
+            .filter(|osec| {
+                matches!(
+                    osec.get_name().as_str(),
+                    ".init_array" | ".fini_array" | ".preinit_array"
+                )
             })
-            .unwrap();
+            .map(|osec| osec.get_id())
+            .collect::<Vec<_>>();
+        for osec_id in osec_ids {
+            let mut isecs = self
+                .ctx
+                .get_output_section(osec_id)
+                .get_input_sections()
+                .clone();
+            // Stable sort keeps the input order within the same priority
+            isecs.sort_by_key(|isec| get_priority(self.ctx.get_input_section(*isec).get_name()));
+            *self
+                .ctx
+                .get_output_section_mut(osec_id)
+                .get_input_sections_mut() = isecs;
+        }
+    }
 
-        for chunk in self.chunks.iter_mut() {
-            match chunk {
-                OutputChunk::Ehdr(_) => (/* Do nothing */),
-                OutputChunk::Shdr(shdr) => {
-                    shdr.update_shdr(num_shdrs);
-                }
-                OutputChunk::Phdr(phdr) => {
-                    phdr.update_shdr(num_phdrs);
+    /// Deduplicate strings and constants of SHF_MERGE input sections within each output
+    /// section. The first input section containing a piece keeps it, and the others refer to
+    /// that copy.
+    // mold: MergedSection
+    pub fn merge_sections(&mut self) {
+        let osec_ids = self
+            .ctx
+            .output_sections()
+            .map(|osec| osec.get_id())
+            .collect::<Vec<_>>();
+        for osec_id in osec_ids {
+            // (entsize, alignment, contents) -> (input section, offset)
+            let mut pool: HashMap<(u64, u64, Vec<u8>), (InputSectionId, u64)> = HashMap::new();
+            let isecs = self
+                .ctx
+                .get_output_section(osec_id)
+                .get_input_sections()
+                .clone();
+            for isec_id in isecs {
+                let isec = self.ctx.get_input_section(isec_id);
+                // Sections with relocations can't be split safely
+                if !isec.is_mergeable() || !isec.get_relas().is_empty() {
+                    continue;
                 }
-                OutputChunk::Section(_) => (/* Do nothing */),
-                OutputChunk::Symtab(symtab) => {
-                    symtab.update_shdr(symtab_content.len() as u64, strtab_shndx)
+                let entsize = isec.elf_section.header.sh_entsize.max(1);
+                let align = isec.get_addralign();
+                let mut data = vec![];
+                let mut fragments = vec![];
+                // Each piece keeps the alignment of the section
+                for (input_offset, piece) in isec.split_fragments() {
+                    let (owner, offset) =
+                        *pool
+                            .entry((entsize, align, piece))
+                            .or_insert_with_key(|(_, _, piece)| {
+                                data.resize(align_to(data.len() as u64, align) as usize, 0);
+                                let offset = data.len() as u64;
+                                data.extend_from_slice(piece);
+                                (isec_id, offset)
+                            });
+                    fragments.push(SectionFragment {
+                        input_offset,
+                        isec: owner,
+                        offset,
+                    });
                 }
-                OutputChunk::Strtab(strtab) => strtab.update_shdr(strtab_content.len() as u64),
-                OutputChunk::Shstrtab(shstrtab) => shstrtab.update_shdr(shstrtab_size),
+                log::debug!(
+                    "Merged {}: {} -> {} bytes",
+                    isec.get_name(),
+                    isec.get_size(),
+                    data.len()
+                );
+                self.ctx
+                    .get_input_section_mut(isec_id)
+                    .set_fragments(data, fragments);
             }
         }
     }
 
-    pub fn set_section_indices(&mut self) {
-        // shndx = 0 is reserved for SHN_UNDEF
-        let mut shndx = 1;
-        for chunk in self.chunks.iter_mut() {
-            if !chunk.is_header() {
-                let common = chunk.get_common_mut();
-                common.shndx = Some(shndx);
-                shndx += 1;
+    /// Find relocations which need GOT or PLT entries or copies of data objects in DSOs and
+    /// allocate them. Returns the errors for relocations of unsupported types.
+    // mold: scan_rels
+    pub fn scan_relocations(
+        &self,
+        got: &mut Got,
+        plt: &mut Plt,
+        copyrel: &mut CopyRel,
+    ) -> Vec<String> {
+        let _timer = self.ctx.perf.timer("scan_relocations");
+        let mut unsupported = vec![];
+        for file in self.ctx.files() {
+            for isec_id in file.get_input_sections().iter().flatten() {
+                let isec = self.ctx.get_input_section(*isec_id);
+                let mut is_call_relaxed = false;
+                for (i, rel) in isec.get_relas().iter().enumerate() {
+                    // The call to __tls_get_addr is gone in a relaxed TLS sequence
+                    if std::mem::take(&mut is_call_relaxed) {
+                        continue;
+                    }
+                    if let Err(msg) = self.target.check_relocation_type(rel.erela.r_type) {
+                        let diag = Diagnostic::new(msg).at(
+                            file.get_file_name(),
+                            isec.get_name(),
+                            rel.erela.r_offset,
+                        );
+                        unsupported.push(diag.to_string());
+                        self.ctx.diag.error(diag);
+                        continue;
+                    }
+                    let symbol = self.get_canonical_symbol(&rel.symbol);
+                    let r_type = rel.erela.r_type;
+                    if self.target.is_got_relative(r_type)
+                        || symbol.read().name == GLOBAL_OFFSET_TABLE
+                    {
+                        got.is_referenced = true;
+                    }
+                    if self.needs_dynamic_reloc(isec, rel)
+                        && !self.is_writable(isec)
+                        && self.config.z_text
+                    {
+                        let name = rel.symbol.read().name;
+                        self.ctx.diag.error(
+                            Diagnostic::new(format!(
+                                "relocation {} against symbol '{}' cannot be used in read-only \
+                                 section {}; recompile with -fPIC",
+                                self.target.r_type_as_str(r_type),
+                                name,
+                                isec.get_name()
+                            ))
+                            .at(file.get_file_name(), isec.get_name(), rel.erela.r_offset)
+                            .symbol(name),
+                        );
+                    } else if self.target.is_absolute_relocation(r_type)
+                        && !self.needs_dynamic_reloc(isec, rel)
+                        && isec.elf_section.header.sh_flags & SHF_ALLOC as u64 != 0
+                        && self.depends_on_load_address(&symbol.read())
+                    {
+                        // Only word-sized fields can hold the relocated address
+                        let name = rel.symbol.read().name;
+                        self.ctx.diag.error(
+                            Diagnostic::new(format!(
+                                "relocation {} against symbol '{}' cannot be used when making \
+                                 a shared object; recompile with -fPIC",
+                                self.target.r_type_as_str(r_type),
+                                name
+                            ))
+                            .at(file.get_file_name(), isec.get_name(), rel.erela.r_offset)
+                            .symbol(name),
+                        );
+                    }
+                    if let Some(kind) = self.target.tls_reloc(r_type) {
+                        // The values are not known yet, and they don't affect whether the
+                        // instructions can be relaxed
+                        let is_relaxed = self.relax_tls(isec, i, kind, 0, 0).is_some();
+                        match kind {
+                            // The call to __tls_get_addr is relaxed too
+                            TlsReloc::Gd | TlsReloc::Ld if is_relaxed => is_call_relaxed = true,
+                            TlsReloc::Gd => got.add_tlsgd_symbol(&symbol),
+                            TlsReloc::Ld => got.add_tlsld(),
+                            TlsReloc::GotTpOff if !is_relaxed => got.add_gottp_symbol(&symbol),
+                            // Relaxed to initial-exec
+                            TlsReloc::Desc
+                                if is_relaxed && self.is_defined_in_dso(&symbol.read()) =>
+                            {
+                                got.add_gottp_symbol(&symbol)
+                            }
+                            _ => {}
+                        }
+                    } else if self.target.needs_got(r_type) {
+                        // Relaxed instructions don't use the GOT
+                        if self.relax_gotpcrelx(isec, rel).is_none() {
+                            got.add_got_symbol(&symbol);
+                        }
+                    } else if self.target.is_call(r_type) && self.needs_plt(&symbol.read()) {
+                        // Calls which may be bound elsewhere at runtime go through the PLT
+                        plt.add_symbol(&symbol);
+                    } else if self.refers_to_dso_directly(isec, rel) {
+                        if self.is_function(&symbol.read()) {
+                            // The address of the function is its PLT entry everywhere, so
+                            // that it compares equal across modules
+                            plt.add_canonical_symbol(&symbol);
+                        } else {
+                            self.add_copyrel(copyrel, &symbol, file.get_file_name(), isec, rel);
+                        }
+                    }
+                }
             }
         }
+        self.add_copyrel_aliases(copyrel);
+        unsupported
     }
 
-    pub fn assign_osec_offsets(&mut self) -> u64 {
-        let mut file_ofs = 0;
-        let mut vaddr = self.config.image_base;
-
-        for chunk in self.chunks.iter_mut() {
-            if chunk.get_common().should_be_loaded() {
-                vaddr = align_to(vaddr, PAGE_SIZE);
-            }
-
-            if vaddr % PAGE_SIZE > file_ofs % PAGE_SIZE {
-                file_ofs += vaddr % PAGE_SIZE - file_ofs % PAGE_SIZE;
-            } else if vaddr % PAGE_SIZE < file_ofs % PAGE_SIZE {
-                file_ofs = align_to(file_ofs, PAGE_SIZE) + vaddr % PAGE_SIZE;
-            }
-
-            // Align to sh_addralign
-            let sh_addralign = chunk.get_common().shdr.sh_addralign;
-            file_ofs = align_to(file_ofs, sh_addralign);
-            vaddr = align_to(vaddr, sh_addralign);
-
-            chunk.set_offset(&mut self.ctx, file_ofs);
+    /// Returns true if the relocation refers to a symbol in a DSO directly rather than
+    /// through the GOT, PLT or a dynamic relocation. The executable needs its own copy of a
+    /// data object, or a canonical PLT entry of a function.
+    fn refers_to_dso_directly(&self, isec: &InputSection, rel: &ElfRela) -> bool {
+        if self.config.shared
+            || self.target.dynamic_reloc_types().is_none()
+            || isec.elf_section.header.sh_flags & SHF_ALLOC as u64 == 0
+            || self.target.is_got_relative(rel.erela.r_type)
+            || self.needs_dynamic_reloc(isec, rel)
+        {
+            return false;
+        }
+        let symbol = self.get_canonical_symbol(&rel.symbol);
+        let symbol = symbol.read();
+        self.is_defined_in_dso(&symbol)
+    }
 
-            // Make sure to get sh_size after `chunk.set_offset` because we set a value to sh_size in it
-            if chunk.get_common().shdr.sh_flags & SHF_ALLOC as u64 != 0 {
-                chunk.get_common_mut().shdr.sh_addr = vaddr;
-            }
+    fn is_function(&self, symbol: &Symbol) -> bool {
+        matches!(
+            symbol.get_def_esym().get_esym().st_symtype(),
+            STT_FUNC | STT_GNU_IFUNC
+        )
+    }
 
-            let is_bss = chunk.get_common().shdr.sh_type == SHT_NOBITS;
-            if !is_bss {
-                file_ofs += chunk.get_common_mut().shdr.sh_size;
-            }
-            let is_tbss = chunk.get_common().shdr.sh_flags & SHF_TLS as u64 != 0;
-            if !is_tbss {
-                vaddr += chunk.get_common_mut().shdr.sh_size;
-            }
+    /// Reserve space for a copy of the data object in .copyrel. The copy is aligned like the
+    /// object in the DSO, which is no more than its section alignment.
+    // mold: add_copyrel_symbol
+    fn add_copyrel(
+        &self,
+        copyrel: &mut CopyRel,
+        symbol: &Arc<SymbolCell>,
+        file_name: &str,
+        isec: &InputSection,
+        rel: &ElfRela,
+    ) {
+        let (size, align) = {
+            let sym = symbol.read();
+            let esym = sym.get_def_esym();
+            let dso = self.ctx.get_file(sym.file.unwrap());
+            let st_value = esym.get_esym().st_value;
+            let align = 1u64 << st_value.trailing_zeros().min(63);
+            let align = align.min(dso.get_section_alignment(esym.get_shndx()));
+            (esym.get_esym().st_size, align)
+        };
+        if size == 0 {
+            let name = symbol.read().name;
+            self.ctx.diag.error(
+                Diagnostic::new(format!(
+                    "cannot create a copy relocation for symbol '{}' of unknown size; \
+                     recompile with -fPIC",
+                    name
+                ))
+                .at(file_name, isec.get_name(), rel.erela.r_offset)
+                .symbol(name),
+            );
+            return;
         }
-        file_ofs
+        copyrel.add_symbol(symbol, size, align);
     }
 
-    pub fn fix_synthetic_symbols(&mut self) {
-        // `__start_` and `__stop_` symbols
-        for osec in self.ctx.output_sections() {
-            let name = osec.get_name();
-            if is_c_identifier(&name) {
-                // TODO: register symbols
+    /// The DSO has to refer to the copy too, including by the other names of the object.
+    /// Global symbols at the same address as a copied object in its DSO share the copy.
+    fn add_copyrel_aliases(&self, copyrel: &mut CopyRel) {
+        for symbol in copyrel.syms.clone() {
+            let (file, st_value, shndx, offset) = {
+                let sym = symbol.read();
+                let esym = sym.get_def_esym();
+                (
+                    sym.file.unwrap(),
+                    esym.get_esym().st_value,
+                    esym.get_shndx(),
+                    sym.copyrel_offset.unwrap(),
+                )
+            };
+            let dso = self.ctx.get_file(file);
+            for esym in &dso.get_elf_symbols()[dso.get_first_global()..] {
+                if esym.get_esym().is_undefined()
+                    || esym.get_esym().st_value != st_value
+                    || esym.get_shndx() != shndx
+                {
+                    continue;
+                }
+                let Some(alias) = self.ctx.get_global_symbol(esym.get_name()) else {
+                    continue;
+                };
+                if alias.read().file == Some(file) {
+                    copyrel.add_alias(alias, offset);
+                }
             }
         }
     }
 
-    pub fn copy_buf(&mut self, buf: &mut [u8]) {
-        // copy all shdrs to buf
-        let e_shoff = self
-            .chunks
+    /// Returns true if the output is loaded by the dynamic linker
+    pub fn is_dynamic(&self) -> bool {
+        self.config.shared || self.ctx.files().any(|file| file.is_dso())
+    }
+
+    /// Create .interp, .dynstr and .dynamic if the output is dynamically linked. Every DSO
+    /// on the command line is recorded in DT_NEEDED in command-line order.
+    pub fn create_dynamic_chunks(&mut self) {
+        if !self.is_dynamic() {
+            return;
+        }
+        let dsos = self
+            .ctx
+            .files()
+            .filter(|file| file.is_dso())
+            .collect::<Vec<_>>();
+        let mut dynstr = DynStr::new();
+        let needed = dsos
             .iter()
-            .find_map(|chunk| {
-                if let OutputChunk::Shdr(chunk) = chunk {
-                    Some(chunk.common.shdr.sh_offset)
-                } else {
-                    None
-                }
-            })
-            .unwrap();
+            .map(|file| dynstr.add_string(file.get_soname()))
+            .collect();
+        if !self.config.shared {
+            let interp = Interp::new(self.config.dynamic_linker.clone());
+            self.chunks.push(OutputChunk::Interp(interp));
+        }
+        let soname = self
+            .config
+            .soname
+            .as_ref()
+            .filter(|_| self.config.shared)
+            .map(|soname| dynstr.add_string(soname));
+        self.chunks.push(OutputChunk::DynStr(dynstr));
+        self.chunks
+            .push(OutputChunk::Dynamic(Dynamic::new(needed, soname)));
+    }
 
-        let e_shnum = self.get_shdrs().len() as u16;
-        let e_shstrndx = self
+    /// Put symbols to .dynsym: functions and variables imported from DSOs, followed by
+    /// exported symbols. Symbols are exported if they are referenced by DSOs, or if they
+    /// have default or protected visibility and -shared or --export-dynamic is given, unless
+    /// the version script hides them. Also create .gnu.hash for the exported ones,
+    /// .gnu.version_d if the version script defines versions, and .gnu.version_r if any
+    /// imported symbol is versioned, as well as .gnu.version for either.
+    // mold: export_dynamic, fill_symbol_versions
+    pub fn export_dynamic(&mut self) {
+        let Some(dynstr_pos) = self
             .chunks
             .iter()
-            .find_map(|chunk| {
-                if let OutputChunk::Shstrtab(chunk) = chunk {
-                    Some(chunk.common.shndx.unwrap() as u16)
-                } else {
-                    None
-                }
+            .position(|chunk| matches!(chunk, OutputChunk::DynStr(_)))
+        else {
+            return;
+        };
+        let files = self.ctx.files().collect::<Vec<_>>();
+        let export_all = self.config.shared || self.config.export_dynamic;
+
+        let mut referenced_by_dso = HashSet::new();
+        for file in files.iter().filter(|file| file.is_dso()) {
+            referenced_by_dso.extend(file.get_undefined_global_names());
+        }
+
+        let mut seen = HashSet::new();
+        let mut imports = vec![];
+        let mut exports = vec![];
+        for file in files.iter().filter(|file| !file.is_dso()) {
+            for (i, esym) in file.get_elf_symbols().iter().enumerate() {
+                if i < file.get_first_global() {
+                    continue;
+                }
+                let Some(symbol) = self.ctx.get_global_symbol(esym.get_name()) else {
+                    continue;
+                };
+                if seen.contains(&esym.get_name()) {
+                    continue;
+                }
+                let sym = symbol.read();
+                let is_canonical = sym.copyrel_offset.is_some() || sym.has_canonical_plt;
+                let is_imported = self.is_defined_in_dso(&sym) || self.is_undefined_import(&sym);
+                if is_imported && !is_canonical {
+                    if esym.get_esym().is_undefined() {
+                        imports.push(Arc::clone(symbol));
+                        seen.insert(sym.name);
+                    }
+                    continue;
+                }
+                // DSOs refer to copied objects and functions with canonical PLT entries in
+                // the executable, so the dynamic linker has to find them in .gnu.hash
+                if is_canonical {
+                    exports.push(Arc::clone(symbol));
+                    seen.insert(sym.name);
+                    continue;
+                }
+                let is_def = sym.file == Some(file.get_id()) && !esym.get_esym().is_undefined();
+                let is_visible = matches!(sym.get_visibility(), STV_DEFAULT | STV_PROTECTED)
+                    && !self.is_hidden_by_version_script(sym.name);
+                if is_def && is_visible && (export_all || referenced_by_dso.contains(&sym.name)) {
+                    exports.push(Arc::clone(symbol));
+                    seen.insert(sym.name);
+                }
+            }
+        }
+
+        if let Some(copyrel) = self.get_copyrel() {
+            for alias in &copyrel.aliases {
+                if seen.insert(alias.read().name) {
+                    exports.push(Arc::clone(alias));
+                }
+            }
+        }
+
+        // .gnu.hash requires exported symbols to be sorted by bucket
+        let num_exported = exports.len();
+        let hash = |symbol: &Arc<SymbolCell>| gnu_hash(&symbol.read().name);
+        exports.sort_by_key(|symbol| GnuHash::get_bucket(hash(symbol), num_exported));
+        let hashes = exports.iter().map(hash).collect::<Vec<_>>();
+        let symoffset = 1 + imports.len();
+
+        // Versions of imported symbols, grouped by DSO
+        let mut needed_versions = BTreeMap::<ObjectId, Vec<String>>::new();
+        let sym_versions = imports
+            .iter()
+            .map(|symbol| {
+                let sym = symbol.read();
+                let file = sym.file?;
+                let version = sym.esym.get_version()?.to_owned();
+                let versions = needed_versions.entry(file).or_default();
+                if !versions.contains(&version) {
+                    versions.push(version.clone());
+                }
+                Some((file, version))
+            })
+            .collect::<Vec<_>>();
+
+        let OutputChunk::DynStr(dynstr) = &mut self.chunks[dynstr_pos] else {
+            unreachable!();
+        };
+        let syms = imports.into_iter().chain(exports).collect::<Vec<_>>();
+        let mut names = vec![];
+        for (i, symbol) in syms.iter().enumerate() {
+            let mut sym = symbol.write();
+            sym.dynsym_idx = Some(i + 1);
+            names.push(dynstr.add_string(&sym.name));
+        }
+
+        // Version indices 0 and 1 are reserved for local and unversioned symbols, so
+        // defined versions are numbered from 2, followed by required versions. The base
+        // version with index 1 names the output itself.
+        let mut defs = vec![];
+        let named_versions = self
+            .version_script
+            .iter()
+            .flat_map(|script| &script.versions)
+            .filter_map(|node| Some((node.name.as_ref()?, &node.parents)))
+            .collect::<Vec<_>>();
+        if !named_versions.is_empty() {
+            let base = self.config.soname.as_deref().unwrap_or_default();
+            defs.push(DefinedVersion {
+                name: base,
+                names: vec![dynstr.add_string(base)],
+            });
+        }
+        for (name, parents) in named_versions {
+            let names = std::iter::once(name)
+                .chain(parents)
+                .map(|name| dynstr.add_string(name))
+                .collect();
+            defs.push(DefinedVersion { name, names });
+        }
+        let first_needed = VER_NDX_GLOBAL + defs.len().max(1) as u16;
+        let mut version_indices = HashMap::new();
+        let mut needs = vec![];
+        for (file, versions) in &needed_versions {
+            let soname = self.ctx.get_file(*file).get_soname();
+            let versions = versions
+                .iter()
+                .map(|version| {
+                    let index = first_needed + version_indices.len() as u16;
+                    version_indices.insert((*file, version.as_str()), index);
+                    NeededVersion {
+                        index,
+                        name: version,
+                        name_offset: dynstr.add_string(version),
+                    }
+                })
+                .collect::<Vec<_>>();
+            needs.push((dynstr.add_string(soname), versions));
+        }
+        let mut versym = vec![VER_NDX_LOCAL];
+        for (i, symbol) in syms.iter().enumerate() {
+            versym.push(match sym_versions.get(i) {
+                Some(Some((file, version))) => version_indices[&(*file, version.as_str())],
+                Some(None) => VER_NDX_GLOBAL,
+                None => self.get_version_index(symbol.read().name),
+            });
+        }
+        if !needs.is_empty() {
+            let verneed = OutputChunk::Verneed(Verneed::new(&needs));
+            self.chunks.insert(dynstr_pos + 1, verneed);
+        }
+        if !defs.is_empty() {
+            let verdef = OutputChunk::Verdef(Verdef::new(&defs));
+            self.chunks.insert(dynstr_pos + 1, verdef);
+        }
+        if !needs.is_empty() || !defs.is_empty() {
+            let versym = OutputChunk::Versym(Versym::new(versym));
+            self.chunks.insert(dynstr_pos + 1, versym);
+        }
+
+        let gnu_hash = OutputChunk::GnuHash(GnuHash::new(symoffset, &hashes));
+        let dynsym = OutputChunk::DynSym(DynSym::new(syms, names));
+        self.chunks.insert(dynstr_pos, dynsym);
+        self.chunks.insert(dynstr_pos, gnu_hash);
+        self.index_chunks();
+    }
+
+    /// Create .rela.dyn and .rela.plt. This must be called after export_dynamic since the
+    /// entries refer to .dynsym indices. Only the number of entries is fixed here, and the
+    /// contents are computed after addresses are assigned.
+    pub fn create_rela_chunks(&mut self) {
+        if !self.is_dynamic() {
+            return;
+        }
+        if !self.get_rela_dyn_entries().is_empty() {
+            self.chunks.push(OutputChunk::RelaDyn(DynRela::new(false)));
+        }
+        if self.get_plt().is_some() {
+            self.chunks.push(OutputChunk::RelaPlt(DynRela::new(true)));
+        }
+    }
+
+    /// Create .symtab_shndx if some sections have indices which don't fit in st_shndx. This
+    /// must be called after all the other sections are created.
+    pub fn create_symtab_shndx(&mut self) {
+        let num_sections = self
+            .chunks
+            .iter()
+            .filter(|chunk| !chunk.is_header())
+            .count();
+        if num_sections >= SHN_LORESERVE as usize {
+            self.chunks
+                .push(OutputChunk::SymtabShndx(SymtabShndx::new()));
+        }
+    }
+
+    /// Returns the contents of .rela.dyn: GLOB_DAT for GOT entries of imported symbols,
+    /// TPOFF, DTPMOD and DTPOFF for TLS entries of them and COPY for copied data objects.
+    /// RELATIVE entries come first so that DT_RELACOUNT can tell the dynamic linker how many
+    /// there are.
+    fn get_rela_dyn_entries(&self) -> Vec<Elf64_Rela> {
+        // Static links of other targets have no dynamic relocations
+        let Some(types) = self.target.dynamic_reloc_types() else {
+            return vec![];
+        };
+        let rela = |r_offset: u64, dynsym_idx: usize, r_type: u32, r_addend: i64| Elf64_Rela {
+            r_offset,
+            r_info: ((dynsym_idx as u64) << 32) | r_type as u64,
+            r_addend,
+        };
+        let mut entries = vec![];
+        for (isec, rel) in self.get_dynamic_relocs() {
+            let r_offset = self.get_isec_addr(isec.get_id()) + rel.erela.r_offset;
+            let r_addend = rel.erela.r_addend;
+            let dynsym_idx = {
+                let symbol = self.get_canonical_symbol(&rel.symbol);
+                let symbol = symbol.read();
+                self.is_dynamic_symbol(&symbol)
+                    .then(|| symbol.dynsym_idx.unwrap())
+            };
+            if let Some(dynsym_idx) = dynsym_idx {
+                entries.push(rela(r_offset, dynsym_idx, rel.erela.r_type, r_addend));
+            } else {
+                // The dynamic linker adds the load address of the shared object
+                let symbol = rel.symbol.read();
+                let s = self
+                    .get_section_symbol_addr(&symbol, r_addend)
+                    .or_else(|| self.get_symbol_addr(&symbol))
+                    .unwrap_or(0);
+                entries.push(rela(r_offset, 0, types.relative, s as i64 + r_addend));
+            }
+        }
+        if let Some(copyrel) = self.get_copyrel() {
+            for symbol in &copyrel.syms {
+                let symbol = symbol.read();
+                let r_offset = self.get_copyrel_addr(&symbol).unwrap();
+                entries.push(rela(r_offset, symbol.dynsym_idx.unwrap(), types.copy, 0));
+            }
+        }
+        let Some(got) = self.get_got() else {
+            return entries;
+        };
+        let mut add = |idx: usize, r_type: u32, dynsym_idx: usize, r_addend: u64| {
+            let r_offset = got.get_entry_addr(idx);
+            entries.push(rela(r_offset, dynsym_idx, r_type, r_addend as i64));
+        };
+        for symbol in &got.got_syms {
+            let symbol = symbol.read();
+            let idx = symbol.got_idx.unwrap();
+            if self.is_dynamic_symbol(&symbol) {
+                add(idx, types.glob_dat, symbol.dynsym_idx.unwrap(), 0);
+            } else if self.depends_on_load_address(&symbol) {
+                let addr = self.get_symbol_addr(&symbol).unwrap_or(0);
+                add(idx, types.relative, 0, addr);
+            }
+        }
+        // TLS symbols of a shared object are at offsets in its TLS block, whose module ID
+        // and TP-relative offset are only known at runtime
+        for symbol in &got.gottp_syms {
+            let symbol = symbol.read();
+            let idx = symbol.gottp_idx.unwrap();
+            if self.is_dynamic_symbol(&symbol) {
+                add(idx, types.tpoff, symbol.dynsym_idx.unwrap(), 0);
+            } else if self.config.shared {
+                let offset = self.get_symbol_addr(&symbol).unwrap_or(0);
+                add(idx, types.tpoff, 0, offset);
+            }
+        }
+        for symbol in &got.tlsgd_syms {
+            let symbol = symbol.read();
+            let idx = symbol.tlsgd_idx.unwrap();
+            if self.is_dynamic_symbol(&symbol) {
+                let dynsym_idx = symbol.dynsym_idx.unwrap();
+                add(idx, types.dtpmod, dynsym_idx, 0);
+                add(idx + 1, types.dtpoff, dynsym_idx, 0);
+            } else if self.config.shared {
+                add(idx, types.dtpmod, 0, 0);
+            }
+        }
+        if let Some(idx) = got.tlsld_idx.filter(|_| self.config.shared) {
+            add(idx, types.dtpmod, 0, 0);
+        }
+        entries.sort_by_key(|rela| rela.r_info as u32 != types.relative);
+        entries
+    }
+
+    /// Returns the contents of .rela.plt: a JUMP_SLOT for the .got.plt entry of each PLT
+    /// entry
+    fn get_rela_plt_entries(&self) -> Vec<Elf64_Rela> {
+        let Some(plt) = self.get_plt() else {
+            return vec![];
+        };
+        let got_plt_addr = self.get_got_plt_addr().unwrap_or(0);
+        let jump_slot = self.dynamic_reloc_types().jump_slot;
+        plt.syms
+            .iter()
+            .map(|symbol| {
+                let symbol = symbol.read();
+                let dynsym_idx = symbol.dynsym_idx.unwrap() as u64;
+                Elf64_Rela {
+                    r_offset: GotPlt::get_entry_addr_of(got_plt_addr, symbol.plt_idx.unwrap()),
+                    r_info: (dynsym_idx << 32) | jump_slot as u64,
+                    r_addend: 0,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns pairs of the tag and the value of each entry in .dynamic, terminated by
+    /// DT_NULL. The number of entries does not depend on the layout, so this can be called
+    /// before addresses are assigned to compute the size.
+    // mold: DynamicSection::create_dynamic_section
+    fn get_dynamic_entries(&self) -> Vec<(i64, u64)> {
+        let Some(dynamic) = self.chunks.iter().find_map(|chunk| match chunk {
+            OutputChunk::Dynamic(chunk) => Some(chunk),
+            _ => None,
+        }) else {
+            return vec![];
+        };
+        let mut entries = vec![];
+        for needed in &dynamic.needed {
+            entries.push((DT_NEEDED, *needed as u64));
+        }
+        if let Some(soname) = dynamic.soname {
+            entries.push((DT_SONAME, soname as u64));
+        }
+        for (tag, name) in [(DT_INIT, "_init"), (DT_FINI, "_fini")] {
+            let symbol = self.ctx.get_global_symbol(self.ctx.intern(name));
+            let file = symbol.and_then(|symbol| symbol.read().file);
+            if file.is_some_and(|file| !self.ctx.get_file(file).is_dso()) {
+                entries.push((tag, self.get_global_symbol_addr(name).unwrap()));
+            }
+        }
+        for (sh_type, tag, size_tag) in [
+            (SHT_PREINIT_ARRAY, DT_PREINIT_ARRAY, DT_PREINIT_ARRAYSZ),
+            (SHT_INIT_ARRAY, DT_INIT_ARRAY, DT_INIT_ARRAYSZ),
+            (SHT_FINI_ARRAY, DT_FINI_ARRAY, DT_FINI_ARRAYSZ),
+        ] {
+            let shdr = self.chunks.iter().find_map(|chunk| match chunk {
+                OutputChunk::Section(chunk) if chunk.common.shdr.sh_type == sh_type => {
+                    Some(&chunk.common.shdr)
+                }
+                _ => None,
+            });
+            if let Some(shdr) = shdr {
+                entries.push((tag, shdr.sh_addr));
+                entries.push((size_tag, shdr.sh_size));
+            }
+        }
+        for chunk in &self.chunks {
+            let shdr = &chunk.get_common().shdr;
+            match chunk {
+                OutputChunk::GnuHash(_) => entries.push((DT_GNU_HASH, shdr.sh_addr)),
+                OutputChunk::DynSym(_) => {
+                    entries.push((DT_SYMTAB, shdr.sh_addr));
+                    entries.push((DT_SYMENT, shdr.sh_entsize));
+                }
+                OutputChunk::DynStr(_) => {
+                    entries.push((DT_STRTAB, shdr.sh_addr));
+                    entries.push((DT_STRSZ, shdr.sh_size));
+                }
+                OutputChunk::GotPlt(_) => entries.push((DT_PLTGOT, shdr.sh_addr)),
+                OutputChunk::Versym(_) => entries.push((DT_VERSYM, shdr.sh_addr)),
+                OutputChunk::Verdef(_) => {
+                    entries.push((DT_VERDEF, shdr.sh_addr));
+                    entries.push((DT_VERDEFNUM, shdr.sh_info as u64));
+                }
+                OutputChunk::Verneed(_) => {
+                    entries.push((DT_VERNEED, shdr.sh_addr));
+                    entries.push((DT_VERNEEDNUM, shdr.sh_info as u64));
+                }
+                OutputChunk::RelaDyn(_) => {
+                    entries.push((DT_RELA, shdr.sh_addr));
+                    entries.push((DT_RELASZ, shdr.sh_size));
+                    entries.push((DT_RELAENT, shdr.sh_entsize));
+                    let relative = self.dynamic_reloc_types().relative;
+                    let num_relative = self
+                        .get_rela_dyn_entries()
+                        .iter()
+                        .filter(|rela| rela.r_info as u32 == relative)
+                        .count();
+                    if num_relative > 0 {
+                        entries.push((DT_RELACOUNT, num_relative as u64));
+                    }
+                }
+                OutputChunk::RelaPlt(_) => {
+                    entries.push((DT_JMPREL, shdr.sh_addr));
+                    entries.push((DT_PLTRELSZ, shdr.sh_size));
+                    entries.push((DT_PLTREL, DT_RELA as u64));
+                }
+                _ => {}
+            }
+        }
+        if !self.config.shared {
+            // Filled by the dynamic linker for debuggers
+            entries.push((DT_DEBUG, 0));
+        }
+
+        let mut flags = 0;
+        let mut flags_1 = 0;
+        if self.config.shared && self.config.symbolic == Symbolic::All {
+            entries.push((DT_SYMBOLIC, 0));
+            flags |= DF_SYMBOLIC;
+        }
+        // The dynamic linker makes read-only segments writable while relocating them
+        if self
+            .get_dynamic_relocs()
+            .any(|(isec, _)| !self.is_writable(isec))
+        {
+            entries.push((DT_TEXTREL, 0));
+            flags |= DF_TEXTREL;
+        }
+        if self.config.z_now {
+            flags |= DF_BIND_NOW;
+            flags_1 |= DF_1_NOW;
+        }
+        if flags != 0 {
+            entries.push((DT_FLAGS, flags as u64));
+        }
+        if flags_1 != 0 {
+            entries.push((DT_FLAGS_1, flags_1 as u64));
+        }
+        entries.push((DT_NULL, 0));
+        entries
+    }
+
+    pub fn assign_isec_offsets(&mut self) {
+        let _ = self.assign_osec_offsets();
+    }
+
+    pub fn update_shdr(&mut self) {
+        let _timer = self.ctx.perf.timer("update_shdr");
+        // Set sh_name to all shdrs
+        let shstrtab = self.get_shstrtab();
+        for chunk in self.chunks.iter_mut() {
+            if !chunk.is_header() {
+                let name = chunk.get_section_name(&self.ctx);
+                chunk.get_common_mut().shdr.sh_name = shstrtab.get(&name);
+            }
+        }
+
+        // Call update_shdr for all chunks
+        let class = self.target.class();
+        let num_shdrs = self.get_shdrs().len();
+        let num_phdrs = self.create_phdr().len();
+        let shstrtab_size = shstrtab.content().len() as u64;
+        let (symtab_content, _, strtab, first_global) = self.get_symtab();
+        let num_plt_entries = self.get_plt().map_or(0, |plt| plt.syms.len());
+        let num_dynamic_entries = self.get_dynamic_entries().len();
+        let dynstr_shndx = self.chunks.iter().find_map(|chunk| match chunk {
+            OutputChunk::DynStr(chunk) => Some(chunk.common.shndx.unwrap() as u32),
+            _ => None,
+        });
+        let dynsym_shndx = self
+            .get_dynsym()
+            .map(|chunk| chunk.common.shndx.unwrap() as u32);
+        let got_plt_shndx = self.chunks.iter().find_map(|chunk| match chunk {
+            OutputChunk::GotPlt(chunk) => Some(chunk.common.shndx.unwrap() as u32),
+            _ => None,
+        });
+        let num_rela_dyn = self.get_rela_dyn_entries().len();
+        let num_rela_plt = self.get_rela_plt_entries().len();
+        let num_fdes = self
+            .get_eh_frame_isecs()
+            .map(|isec| eh_frame::count_fdes(isec.elf_section.data()))
+            .sum();
+        let strtab_shndx = self
+            .chunks
+            .iter()
+            .find_map(|chunk| {
+                if let OutputChunk::Strtab(chunk) = chunk {
+                    Some(chunk.common.shndx.unwrap() as u32)
+                } else {
+                    None
+                }
+            })
+            .unwrap();
+        let symtab_shndx_idx = self
+            .chunks
+            .iter()
+            .find_map(|chunk| match chunk {
+                OutputChunk::Symtab(chunk) => Some(chunk.common.shndx.unwrap() as u32),
+                _ => None,
+            })
+            .unwrap();
+
+        for chunk in self.chunks.iter_mut() {
+            match chunk {
+                OutputChunk::Ehdr(ehdr) => ehdr.update_shdr(class),
+                OutputChunk::Shdr(shdr) => {
+                    shdr.update_shdr(num_shdrs, class);
+                }
+                OutputChunk::Phdr(phdr) => {
+                    phdr.update_shdr(num_phdrs, class);
+                }
+                OutputChunk::Section(_) => (/* Do nothing */),
+                OutputChunk::Symtab(symtab) => symtab.update_shdr(
+                    symtab_content.len() as u64,
+                    strtab_shndx,
+                    first_global,
+                    class,
+                ),
+                OutputChunk::SymtabShndx(symtab_shndx) => {
+                    symtab_shndx.update_shdr(symtab_content.len() as u64, symtab_shndx_idx)
+                }
+                OutputChunk::Strtab(chunk) => chunk.update_shdr(strtab.content().len() as u64),
+                OutputChunk::Shstrtab(shstrtab) => shstrtab.update_shdr(shstrtab_size),
+                OutputChunk::EhFrameHdr(eh_frame_hdr) => eh_frame_hdr.update_shdr(num_fdes),
+                OutputChunk::Got(got) => got.update_shdr(),
+                OutputChunk::CopyRel(_) => (/* Do nothing */),
+                OutputChunk::Plt(plt) => plt.update_shdr(),
+                OutputChunk::GotPlt(got_plt) => got_plt.update_shdr(num_plt_entries),
+                OutputChunk::Interp(_) => (/* Do nothing */),
+                OutputChunk::DynStr(dynstr) => dynstr.update_shdr(),
+                OutputChunk::Dynamic(dynamic) => {
+                    dynamic.update_shdr(num_dynamic_entries, dynstr_shndx.unwrap())
+                }
+                OutputChunk::DynSym(dynsym) => dynsym.update_shdr(dynstr_shndx.unwrap()),
+                OutputChunk::GnuHash(gnu_hash) => gnu_hash.update_shdr(dynsym_shndx.unwrap()),
+                OutputChunk::Versym(versym) => versym.update_shdr(dynsym_shndx.unwrap()),
+                OutputChunk::Verdef(verdef) => verdef.update_shdr(dynstr_shndx.unwrap()),
+                OutputChunk::Verneed(verneed) => verneed.update_shdr(dynstr_shndx.unwrap()),
+                OutputChunk::RelaDyn(rela) => {
+                    rela.update_shdr(num_rela_dyn, dynsym_shndx.unwrap(), 0)
+                }
+                OutputChunk::RelaPlt(rela) => {
+                    rela.update_shdr(num_rela_plt, dynsym_shndx.unwrap(), got_plt_shndx.unwrap())
+                }
+            }
+        }
+    }
+
+    /// Sort chunks so that sections with the same permissions are adjacent and as few
+    /// segments as possible are needed. The order is: headers, read-only, executable,
+    /// RELRO, writable, TLS data, TLS bss, bss and non-allocated sections, with the section
+    /// header table at the end. Sections with the same permissions are ordered by
+    /// SECTION_ORDER. Output sections described by the linker script come right after the
+    /// headers in the order of the script instead.
+    pub fn sort_chunks(&mut self) {
+        let relro = self.config.z_relro;
+        let get_rank = |chunk: &OutputChunk| -> u32 {
+            match chunk {
+                OutputChunk::Ehdr(_) => return 0,
+                OutputChunk::Phdr(_) | OutputChunk::Interp(_) => return 1,
+                OutputChunk::Shdr(_) => return 10,
+                _ => {}
+            }
+            let shdr = &chunk.get_common().shdr;
+            let flags = shdr.sh_flags;
+            let is_bss = shdr.sh_type == SHT_NOBITS;
+            if flags & SHF_ALLOC as u64 == 0 {
+                9
+            } else if flags & SHF_TLS as u64 != 0 {
+                if is_bss {
+                    7
+                } else {
+                    6
+                }
+            } else if relro && chunk.is_relro(&self.ctx) {
+                4
+            } else if is_bss {
+                8
+            } else if flags & SHF_WRITE as u64 != 0 {
+                5
+            } else if flags & SHF_EXECINSTR as u64 != 0 {
+                3
+            } else {
+                2
+            }
+        };
+        // Within the same rank, well-known sections are sorted in the conventional order.
+        // Stable sort keeps the discovery order among the others.
+        let get_name_rank = |chunk: &OutputChunk| -> usize {
+            if chunk.is_header() {
+                return 0;
+            }
+            let name = chunk.get_section_name(&self.ctx);
+            if name.starts_with(".note") {
+                return 0;
+            }
+            match SECTION_ORDER.iter().position(|known| *known == name) {
+                Some(i) => i + 1,
+                None if matches!(
+                    name.as_str(),
+                    ".symtab" | ".symtab_shndx" | ".strtab" | ".shstrtab"
+                ) =>
+                {
+                    SECTION_ORDER.len() + 2
+                }
+                None => SECTION_ORDER.len() + 1,
+            }
+        };
+        // Ties are broken by the current order, in which output sections have to be in the
+        // order they were created
+        debug_assert!(
+            !self.config.reproducible
+                || self
+                    .chunks
+                    .iter()
+                    .filter_map(|chunk| match chunk {
+                        OutputChunk::Section(osec) => Some(osec.get_id()),
+                        _ => None,
+                    })
+                    .is_sorted(),
+            "output sections are out of order before sorting"
+        );
+        let get_script_rank = |chunk: &OutputChunk| -> Option<usize> {
+            let OutputChunk::Section(_) = chunk else {
+                return None;
+            };
+            self.script
+                .as_ref()?
+                .get_command_index(&chunk.get_section_name(&self.ctx))
+        };
+        self.chunks.sort_by_cached_key(|chunk| {
+            let rank = get_rank(chunk);
+            match get_script_rank(chunk) {
+                Some(script_rank) => (2, script_rank, 0),
+                None if rank <= 1 => (rank, 0, get_name_rank(chunk)),
+                None => (3, rank as usize, get_name_rank(chunk)),
+            }
+        });
+    }
+
+    pub fn set_section_indices(&mut self) {
+        // shndx = 0 is reserved for SHN_UNDEF
+        let mut shndx = 1;
+        for chunk in self.chunks.iter_mut() {
+            if !chunk.is_header() {
+                let common = chunk.get_common_mut();
+                common.shndx = Some(shndx);
+                shndx += 1;
+            }
+        }
+        self.index_chunks();
+    }
+
+    /// Record the position of the chunk of each output section. This must be called again
+    /// after chunks are inserted before existing ones or sorted.
+    pub fn index_chunks(&mut self) {
+        self.osec_chunk_index = self
+            .chunks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, chunk)| match chunk {
+                OutputChunk::Section(chunk) => Some((chunk.get_id(), i)),
+                _ => None,
+            })
+            .collect();
+    }
+
+    /// Assign file offsets and addresses to chunks, and return the size of the output file.
+    /// NOBITS sections take no space in the file and non-allocated sections have no
+    /// address, so the file ends at the last byte of the section header table.
+    pub fn assign_osec_offsets(&mut self) -> u64 {
+        let _timer = self.ctx.perf.timer("assign_offsets");
+        let mut file_ofs = 0;
+        let mut vaddr = self.image_base();
+        let page_size = self.page_size();
+
+        let mut segment_flags = None;
+        let mut in_relro = false;
+
+        // The start of the TLS template is aligned for the strictest TLS section so that
+        // the thread pointer is aligned in every thread
+        let is_tls = |shdr: &Elf64_Shdr| shdr.sh_flags & SHF_TLS as u64 != 0;
+        let tls_align = self
+            .chunks
+            .iter()
+            .map(|chunk| &chunk.get_common().shdr)
+            .filter(|shdr| is_tls(shdr))
+            .map(|shdr| shdr.sh_addralign)
+            .max();
+        let mut seen_tls = false;
+        let section_starts = self
+            .chunks
+            .iter()
+            .map(|chunk| self.get_section_start(chunk))
+            .collect::<Vec<_>>();
+        let script_commands = self.get_script_commands();
+
+        for ((chunk, section_start), script_commands) in self
+            .chunks
+            .iter_mut()
+            .zip(section_starts)
+            .zip(script_commands)
+        {
+            let mut sh_addralign = chunk.get_common().shdr.sh_addralign;
+            if is_tls(&chunk.get_common().shdr) && !seen_tls {
+                sh_addralign = tls_align.unwrap();
+                seen_tls = true;
+            }
+            if !chunk.get_common().should_be_loaded() {
+                file_ofs = align_to(file_ofs, sh_addralign);
+                chunk.set_offset(&mut self.ctx, file_ofs);
+                file_ofs += chunk.get_common().shdr.sh_size;
+                continue;
+            }
+
+            // A new segment starts at a page boundary when the permissions change
+            let flags = to_phdr_flags(&chunk.get_common().shdr);
+            if segment_flags != Some(flags) {
+                vaddr = align_to(vaddr, page_size);
+                segment_flags = Some(flags);
+            }
+            // The end of RELRO is padded to a page boundary so that mprotect() doesn't
+            // make the following data read-only
+            let is_relro = self.config.z_relro && chunk.is_relro(&self.ctx);
+            if in_relro && !is_relro {
+                vaddr = align_to(vaddr, page_size);
+            }
+            in_relro = is_relro;
+            // A section placed by --section-start starts a new segment, see create_phdr
+            if let Some(addr) = section_start {
+                vaddr = addr;
+            }
+            // The linker script moves the location counter, which starts a new segment too
+            // if it sets the address
+            if let (Some(script), Some(commands)) = (&self.script, script_commands) {
+                vaddr = script.get_address(commands, vaddr);
+            }
+
+            // File offsets and addresses must be congruent modulo the page size
+            let mut offset = file_ofs;
+            if vaddr % page_size > offset % page_size {
+                offset += vaddr % page_size - offset % page_size;
+            } else if vaddr % page_size < offset % page_size {
+                offset = align_to(offset, page_size) + vaddr % page_size;
+            }
+
+            // Align to sh_addralign. .tbss doesn't occupy the address space, so its padding
+            // doesn't move the following sections either.
+            let is_tbss = {
+                let shdr = &chunk.get_common().shdr;
+                shdr.sh_type == SHT_NOBITS && is_tls(shdr)
+            };
+            offset = align_to(offset, sh_addralign);
+            let addr = align_to(vaddr, sh_addralign);
+            if !is_tbss {
+                vaddr = addr;
+            }
+
+            chunk.set_offset(&mut self.ctx, offset);
+            chunk.get_common_mut().shdr.sh_addr = addr;
+
+            // Make sure to get sh_size after `chunk.set_offset` because we set a value to sh_size in it
+            let shdr = &chunk.get_common().shdr;
+            let is_bss = shdr.sh_type == SHT_NOBITS;
+            // Empty sections don't pull the following contents to their offset either
+            if !is_bss && shdr.sh_size > 0 {
+                file_ofs = offset + shdr.sh_size;
+            }
+            if !is_tbss {
+                vaddr += shdr.sh_size;
+            }
+        }
+        self.tls_template = self.layout_tls_template();
+        file_ofs
+    }
+
+    /// Warn if the entry symbol is not defined. Like GNU ld, the entry point then defaults
+    /// to the start of .text, or the image base if .text is empty, so this has to be called
+    /// after assign_osec_offsets.
+    pub fn report_missing_entry(&self) {
+        // Shared objects don't need an entry point
+        if self.config.shared && self.config.entry.is_none() {
+            return;
+        }
+        if self.find_entry_addr().is_none() {
+            let entry = self.config.entry_symbol();
+            self.ctx.diag.warn(
+                Diagnostic::new(format!(
+                    "cannot find entry symbol {}; defaulting to {:#x}",
+                    entry,
+                    self.get_entry_addr()
+                ))
+                .symbol(entry),
+            );
+        }
+    }
+
+    /// Returns the address of the entry symbol given by --entry or ENTRY(), or the address
+    /// itself if it is a number and no such symbol exists. Without either, the entry symbol
+    /// is _start, or start as a fallback.
+    fn find_entry_addr(&self) -> Option<u64> {
+        match &self.config.entry {
+            Some(entry) => self
+                .get_global_symbol_addr(entry)
+                .or_else(|| parse_number(entry)),
+            None => ["_start", "start"]
+                .into_iter()
+                .find_map(|name| self.get_global_symbol_addr(name)),
+        }
+    }
+
+    fn get_entry_addr(&self) -> u64 {
+        if let Some(addr) = self.find_entry_addr() {
+            return addr;
+        }
+        if self.config.shared {
+            return 0;
+        }
+        self.chunks
+            .iter()
+            .filter(|chunk| matches!(chunk, OutputChunk::Section(_)))
+            .filter(|chunk| chunk.get_common().shdr.sh_size > 0)
+            .find(|chunk| chunk.get_section_name(&self.ctx) == ".text")
+            .map_or(self.image_base(), |chunk| chunk.get_common().shdr.sh_addr)
+    }
+
+    /// Verify the layout made by assign_osec_offsets: sections must not overlap each other
+    /// either in memory or in the file, and allocated sections must be mapped by a PT_LOAD
+    /// at the address they were assigned. Returns the errors, which are reported as well.
+    pub fn check_sections(&self) -> Vec<String> {
+        let mut errors = vec![];
+        for name in self.config.section_start.keys() {
+            let found = self.chunks.iter().any(|chunk| {
+                matches!(chunk, OutputChunk::Section(_))
+                    && chunk.get_section_name(&self.ctx) == *name
+            });
+            if !found {
+                self.ctx
+                    .diag
+                    .warn(format!("--section-start: no such section: {}", name));
+            }
+        }
+
+        let name = |chunk: &OutputChunk| match chunk {
+            OutputChunk::Ehdr(_) => "ELF header".to_owned(),
+            OutputChunk::Phdr(_) => "program headers".to_owned(),
+            OutputChunk::Shdr(_) => "section headers".to_owned(),
+            _ => format!("section {}", chunk.get_section_name(&self.ctx)),
+        };
+        let is_tbss =
+            |shdr: &Elf64_Shdr| shdr.sh_type == SHT_NOBITS && shdr.sh_flags & SHF_TLS as u64 != 0;
+
+        // Ranges are reported inclusively like GNU ld does
+        let mut check_overlaps = |kind: &str, mut ranges: Vec<(u64, u64, &OutputChunk)>| {
+            ranges.sort_by_key(|(start, end, _)| (*start, *end));
+            let mut last: Option<(u64, u64, &OutputChunk)> = None;
+            for (start, end, chunk) in ranges {
+                if let Some((prev_start, prev_end, prev)) = last {
+                    if start < prev_end {
+                        errors.push(format!(
+                            "{} {} [{:#x}, {:#x}] overlaps {} {} [{:#x}, {:#x}]",
+                            name(chunk),
+                            kind,
+                            start,
+                            end - 1,
+                            name(prev),
+                            kind,
+                            prev_start,
+                            prev_end - 1
+                        ));
+                        if end <= prev_end {
+                            continue;
+                        }
+                    }
+                }
+                last = Some((start, end, chunk));
+            }
+        };
+        let non_empty = || {
+            self.chunks
+                .iter()
+                .filter(|chunk| chunk.get_common().shdr.sh_size > 0)
+        };
+        check_overlaps(
+            "address",
+            non_empty()
+                .filter(|chunk| chunk.get_common().should_be_loaded())
+                .filter(|chunk| !is_tbss(&chunk.get_common().shdr))
+                .map(|chunk| {
+                    let shdr = &chunk.get_common().shdr;
+                    (shdr.sh_addr, shdr.sh_addr + shdr.sh_size, chunk)
+                })
+                .collect(),
+        );
+        check_overlaps(
+            "file offset",
+            non_empty()
+                .filter(|chunk| chunk.get_common().shdr.sh_type != SHT_NOBITS)
+                .map(|chunk| {
+                    let shdr = &chunk.get_common().shdr;
+                    (shdr.sh_offset, shdr.sh_offset + shdr.sh_size, chunk)
+                })
+                .collect(),
+        );
+
+        let page_size = self.page_size();
+        let phdrs = self.create_phdr();
+        for chunk in non_empty().filter(|chunk| chunk.get_common().should_be_loaded()) {
+            let shdr = &chunk.get_common().shdr;
+            if is_tbss(shdr) {
+                continue;
+            }
+            let is_nobits = shdr.sh_type == SHT_NOBITS;
+            if !is_nobits && shdr.sh_addr % page_size != shdr.sh_offset % page_size {
+                errors.push(format!(
+                    "{} has address {:#x} and file offset {:#x} which are not congruent modulo the page size",
+                    name(chunk),
+                    shdr.sh_addr,
+                    shdr.sh_offset
+                ));
+            }
+            let is_mapped = phdrs
+                .iter()
+                .filter(|phdr| phdr.p_type == PT_LOAD)
+                .any(|phdr| {
+                    phdr.p_vaddr <= shdr.sh_addr
+                        && shdr.sh_addr + shdr.sh_size <= phdr.p_vaddr + phdr.p_memsz
+                        && (is_nobits
+                            || shdr.sh_offset.wrapping_sub(phdr.p_offset)
+                                == shdr.sh_addr - phdr.p_vaddr
+                                && shdr.sh_offset + shdr.sh_size <= phdr.p_offset + phdr.p_filesz)
+                });
+            if !is_mapped {
+                errors.push(format!(
+                    "{} [{:#x}, {:#x}] is not mapped by any PT_LOAD segment",
+                    name(chunk),
+                    shdr.sh_addr,
+                    shdr.sh_addr + shdr.sh_size - 1
+                ));
+            }
+        }
+        for msg in &errors {
+            self.ctx.diag.error(msg.as_str());
+        }
+        errors
+    }
+
+    /// Returns the address given to the chunk by --section-start
+    fn get_section_start(&self, chunk: &OutputChunk) -> Option<u64> {
+        match chunk {
+            OutputChunk::Section(_) => {
+                let name = chunk.get_section_name(&self.ctx);
+                self.config.section_start.get(&name).copied()
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the commands of the linker script which place each chunk: those since the
+    /// previous output section in the output up to the description of the chunk. Assignments
+    /// to the location counter are thus not lost if the sections described before them have
+    /// no inputs.
+    fn get_script_commands(&self) -> Vec<Option<Range<usize>>> {
+        let mut next = 0;
+        self.chunks
+            .iter()
+            .map(|chunk| {
+                let OutputChunk::Section(_) = chunk else {
+                    return None;
+                };
+                let name = chunk.get_section_name(&self.ctx);
+                let idx = self.script.as_ref()?.get_command_index(&name)?;
+                // Only the first of output sections with the same name but different types
+                if idx < next {
+                    return None;
+                }
+                let commands = next..idx + 1;
+                next = idx + 1;
+                Some(commands)
+            })
+            .collect()
+    }
+
+    /// Returns the TLS template laid out by assign_osec_offsets. TLS sections are adjacent
+    /// after sorting chunks. .tbss is laid out after .tdata in the template even though its
+    /// addresses alias the following sections.
+    fn layout_tls_template(&self) -> Option<TlsTemplate> {
+        let mut tls: Option<TlsTemplate> = None;
+        for chunk in &self.chunks {
+            let shdr = &chunk.get_common().shdr;
+            if shdr.sh_flags & SHF_TLS as u64 == 0 {
+                continue;
+            }
+            let tls = tls.get_or_insert(TlsTemplate {
+                addr: shdr.sh_addr,
+                offset: shdr.sh_offset,
+                filesz: 0,
+                memsz: 0,
+                align: 1,
+            });
+            tls.memsz = shdr.sh_addr + shdr.sh_size - tls.addr;
+            if shdr.sh_type != SHT_NOBITS {
+                tls.filesz = shdr.sh_offset + shdr.sh_size - tls.offset;
+            }
+            tls.align = tls.align.max(shdr.sh_addralign);
+        }
+        tls
+    }
+
+    fn has_tls_sections(&self) -> bool {
+        self.chunks
+            .iter()
+            .any(|chunk| chunk.get_common().shdr.sh_flags & SHF_TLS as u64 != 0)
+    }
+
+    pub fn fix_synthetic_symbols(&mut self) {
+        self.eval_script_symbols();
+
+        // `__start_` and `__stop_` symbols
+        for osec in self.ctx.output_sections() {
+            let name = osec.get_name();
+            if is_c_identifier(&name) {
+                // TODO: register symbols
+            }
+        }
+    }
+
+    /// Evaluate the symbol assignments of the linker script in order now that the layout is
+    /// final. The location counter at an assignment in an output section is the end of the
+    /// inputs selected by the descriptions before it.
+    fn eval_script_symbols(&mut self) {
+        let Some(script) = &self.script else {
+            return;
+        };
+        let mut values = FxHashMap::default();
+        let mut dot = 0;
+        // Position in the script of the first input section description of the section
+        let mut num_inputs = 0;
+        for command in &script.commands {
+            match command {
+                SectionsCommand::SetLocation(expr) => {
+                    dot = expr.eval(dot, &|name| self.get_script_expr_symbol(name, &values));
+                }
+                SectionsCommand::Assign(assignment) => {
+                    self.eval_script_assignment(assignment, dot, &mut values);
+                }
+                SectionsCommand::OutputSection(osec) => {
+                    let range = self.get_osec_range(&osec.name);
+                    for (num_before, assignment) in &osec.assignments {
+                        let dot = match range {
+                            None => dot,
+                            Some((_, end)) if *num_before == osec.inputs.len() => end,
+                            Some((start, _)) => self
+                                .get_script_inputs_end(script, &osec.name, num_inputs + num_before)
+                                .unwrap_or(start),
+                        };
+                        self.eval_script_assignment(assignment, dot, &mut values);
+                    }
+                    num_inputs += osec.inputs.len();
+                    if let Some((_, end)) = range {
+                        dot = end;
+                    }
+                }
+            }
+        }
+        for (name, value) in values {
+            self.script_symbols.get_mut(&name).unwrap().value = value;
+        }
+    }
+
+    /// Evaluate an assignment of the linker script if it defines the symbol. `values` holds
+    /// the values assigned so far.
+    fn eval_script_assignment(
+        &self,
+        assignment: &Assignment,
+        dot: u64,
+        values: &mut FxHashMap<SymbolName, u64>,
+    ) {
+        let name = self.ctx.intern(&assignment.symbol);
+        // PROVIDE() is ignored if the symbol is not needed or assigned without it
+        let Some(symbol) = self.script_symbols.get(&name) else {
+            return;
+        };
+        if symbol.provided != assignment.provide {
+            return;
+        }
+        let value = assignment
+            .expr
+            .eval(dot, &|name| self.get_script_expr_symbol(name, values));
+        values.insert(name, value);
+    }
+
+    /// Returns the value of a symbol in an expression of the linker script. Symbols assigned
+    /// by the script take the value assigned before the expression.
+    fn get_script_expr_symbol(&self, name: &str, values: &FxHashMap<SymbolName, u64>) -> u64 {
+        let name = self.ctx.intern(name);
+        if let Some(value) = values.get(&name) {
+            return *value;
+        }
+        if let Some(symbol) = self.ctx.get_global_symbol(name) {
+            return self.get_symbol_addr(&symbol.read()).unwrap_or(0);
+        }
+        self.ctx.diag.error(
+            Diagnostic::new(format!("undefined symbol in the linker script: {}", name))
+                .symbol(name),
+        );
+        0
+    }
+
+    /// Returns the start and end addresses of the output sections named `name`
+    fn get_osec_range(&self, name: &str) -> Option<(u64, u64)> {
+        self.chunks
+            .iter()
+            .filter(|chunk| {
+                matches!(chunk, OutputChunk::Section(_))
+                    && chunk.get_section_name(&self.ctx) == name
+            })
+            .map(|chunk| {
+                let shdr = &chunk.get_common().shdr;
+                (shdr.sh_addr, shdr.sh_addr + shdr.sh_size)
+            })
+            .reduce(|(start, end), (other_start, other_end)| {
+                (start.min(other_start), end.max(other_end))
+            })
+    }
+
+    /// Returns the end of the inputs of the output sections named `name` which are selected
+    /// by the input section descriptions before `pos` in the linker script
+    fn get_script_inputs_end(&self, script: &LinkerScript, name: &str, pos: usize) -> Option<u64> {
+        self.ctx
+            .output_sections()
+            .filter(|osec| osec.get_name() == name)
+            .flat_map(|osec| osec.get_input_sections())
+            .filter(|id| {
+                let isec = self.ctx.get_input_section(**id);
+                let file_name = self.ctx.get_file(isec.get_file()).get_file_name();
+                script
+                    .find_output_section(file_name, isec.get_name())
+                    .is_some_and(|(_, isec_pos)| isec_pos < pos)
+            })
+            .map(|id| self.get_isec_addr(*id) + self.ctx.get_input_section(*id).get_size())
+            .max()
+    }
+
+    pub fn copy_buf(&self, buf: &mut [u8]) {
+        let mut timer = self.ctx.perf.timer("copy_buf");
+        timer.add_bytes(buf.len() as u64);
+        // copy all shdrs to buf
+        let e_shoff = self
+            .chunks
+            .iter()
+            .find_map(|chunk| {
+                if let OutputChunk::Shdr(chunk) = chunk {
+                    Some(chunk.common.shdr.sh_offset)
+                } else {
+                    None
+                }
             })
             .unwrap();
+
+        let num_shdrs = self.get_shdrs().len();
+        let shstrndx = self.get_shstrndx();
         let e_phoff = self
             .chunks
             .iter()
@@ -332,61 +2239,484 @@ impl Linker<'_> {
                 }
             })
             .unwrap();
-        let e_entry = self.get_global_symbol_addr("_start").unwrap_or(0);
-        let shstrtab_content = self.get_shstrtab_content();
-        let (symtab_content, strtab_content) = self.get_symtab();
+        let e_entry = self.get_entry_addr();
+        let class = self.target.class();
+        let shstrtab = self.get_shstrtab();
+        let (symtab_content, symtab_shndx_content, strtab, _) = self.get_symtab();
         let shdrs = self.get_shdrs();
         let phdrs = self.create_phdr();
+        let got_entries = self.get_got_entries();
+        let got_plt_lazy_addrs = self.get_plt().map_or(vec![], |plt| {
+            (0..plt.syms.len())
+                .map(|idx| self.target.plt_lazy_addr(plt.get_entry_addr(idx)))
+                .collect()
+        });
+        let got_plt_addr = self.get_got_plt_addr().unwrap_or(0);
+        let dynamic_addr = self.get_dynamic_addr().unwrap_or(0);
+        let dynamic_entries = self.get_dynamic_entries();
+        let dynsym_content = self.get_dynsym_content();
+        let rela_dyn_entries = self.get_rela_dyn_entries();
+        let rela_plt_entries = self.get_rela_plt_entries();
+        // Output sections with relocations applied. Those of non-allocated sections are
+        // applied by relocation.
+        let images = self
+            .chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                OutputChunk::Section(chunk) => {
+                    let osec = self.ctx.get_output_section(chunk.get_id());
+                    let image = osec.get_image(&self.ctx, &chunk.common.shdr)?;
+                    let is_alloc = chunk.common.shdr.sh_flags & SHF_ALLOC as u64 != 0;
+                    Some((image, is_alloc.then_some(chunk.get_id())))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        self.write_section_images(images, buf);
         // copy all other sections and headers
-        for chunk in self.chunks.iter_mut() {
+        for chunk in self.chunks.iter() {
             match chunk {
                 // FIXME: dummy
                 OutputChunk::Ehdr(chunk) => chunk.copy_buf(
                     buf,
+                    if self.config.shared { ET_DYN } else { ET_EXEC },
                     e_entry,
                     e_phoff,
                     e_shoff,
                     phdrs.len() as u16,
-                    e_shnum,
-                    e_shstrndx,
+                    num_shdrs,
+                    shstrndx,
+                    self.target.e_machine(),
+                    self.e_flags,
+                    class,
                 ),
                 OutputChunk::Shdr(chunk) => {
-                    chunk.copy_buf(buf, e_shoff as usize, &shdrs);
+                    chunk.copy_buf(buf, e_shoff as usize, &shdrs, class);
                 }
                 OutputChunk::Phdr(chunk) => {
-                    chunk.copy_buf(buf, &phdrs);
-                }
-                OutputChunk::Section(chunk) => {
-                    // TODO: apply relocation
-                    // mold: apply_reloc_alloc
-                    let chunk = self.ctx.get_output_section(chunk.get_id());
-                    chunk.copy_buf(&self.ctx, buf);
+                    chunk.copy_buf(buf, &phdrs, class);
                 }
+                OutputChunk::Section(_) => (),
                 OutputChunk::Strtab(chunk) => {
-                    chunk.copy_buf(buf, &strtab_content);
+                    chunk.copy_buf(buf, strtab.content());
                 }
                 OutputChunk::Symtab(chunk) => {
-                    chunk.copy_buf(buf, &symtab_content);
+                    chunk.copy_buf(buf, &symtab_content, class);
+                }
+                OutputChunk::SymtabShndx(chunk) => {
+                    chunk.copy_buf(buf, &symtab_shndx_content);
                 }
                 OutputChunk::Shstrtab(chunk) => {
-                    chunk.copy_buf(buf, &shstrtab_content);
+                    chunk.copy_buf(buf, shstrtab.content());
+                }
+                // Written by write_eh_frame_hdr since .eh_frame has to be relocated first
+                OutputChunk::EhFrameHdr(_) => (),
+                OutputChunk::Got(chunk) => {
+                    chunk.copy_buf(buf, &got_entries);
+                }
+                // Filled by the dynamic linker
+                OutputChunk::CopyRel(_) => (),
+                OutputChunk::Plt(chunk) => {
+                    chunk.copy_buf(buf, got_plt_addr, self.target);
+                }
+                OutputChunk::GotPlt(chunk) => {
+                    chunk.copy_buf(buf, dynamic_addr, &got_plt_lazy_addrs);
+                }
+                OutputChunk::Interp(chunk) => {
+                    chunk.copy_buf(buf);
+                }
+                OutputChunk::DynStr(chunk) => {
+                    chunk.copy_buf(buf);
+                }
+                OutputChunk::Dynamic(chunk) => {
+                    chunk.copy_buf(buf, &dynamic_entries);
+                }
+                OutputChunk::DynSym(chunk) => {
+                    chunk.copy_buf(buf, &dynsym_content);
+                }
+                OutputChunk::GnuHash(chunk) => {
+                    chunk.copy_buf(buf);
+                }
+                OutputChunk::Versym(chunk) => {
+                    chunk.copy_buf(buf);
+                }
+                OutputChunk::Verdef(chunk) => {
+                    chunk.copy_buf(buf);
+                }
+                OutputChunk::Verneed(chunk) => {
+                    chunk.copy_buf(buf);
+                }
+                OutputChunk::RelaDyn(chunk) => {
+                    chunk.copy_buf(buf, &rela_dyn_entries);
+                }
+                OutputChunk::RelaPlt(chunk) => {
+                    chunk.copy_buf(buf, &rela_plt_entries);
                 }
             }
         }
     }
 
-    pub fn relocation(&self, buf: &mut [u8]) {
-        let relocation_data = self.get_relocation_data();
-        for relval in relocation_data {
-            let RelValue {
-                file_ofs,
-                value,
-                size,
-            } = relval;
-            log::debug!("Relocation: {:#x} -> {:#x}", file_ofs, value);
-            let value = value.to_le_bytes();
-            buf[file_ofs..file_ofs + size].copy_from_slice(&value[0..size]);
+    /// Apply the relocations of non-allocated sections such as debug info. Those of
+    /// allocated sections are applied by copy_buf.
+    // mold: apply_reloc_nonalloc
+    pub fn relocation(&self, buf: &mut [u8]) {
+        let _timer = self.ctx.perf.timer("relocation");
+        let images = self
+            .chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                OutputChunk::Section(chunk)
+                    if chunk.common.shdr.sh_flags & SHF_ALLOC as u64 == 0 =>
+                {
+                    let osec = self.ctx.get_output_section(chunk.get_id());
+                    let mut image = osec.get_image(&self.ctx, &chunk.common.shdr)?;
+                    // The contents have been copied by copy_buf
+                    image.fill = None;
+                    for (_, data, _) in image.isecs.iter_mut() {
+                        *data = &[];
+                    }
+                    Some((image, Some(chunk.get_id())))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        self.write_section_images(images, buf);
+    }
+
+    /// Write output sections to their parts of the output in parallel. The relocations of
+    /// the output section paired with each image, if any, are computed and applied as well.
+    fn write_section_images(
+        &self,
+        images: Vec<(SectionImage, Option<OutputSectionId>)>,
+        buf: &mut [u8],
+    ) {
+        let ranges = images
+            .iter()
+            .map(|(image, _)| image.offset..image.offset + image.size)
+            .collect::<Vec<_>>();
+        split_disjoint_mut(buf, &ranges)
+            .into_par_iter()
+            .zip(images)
+            .for_each(|(buf, (mut image, osec_id))| {
+                if let Some(osec_id) = osec_id {
+                    self.add_relocation_data(osec_id, &mut image);
+                }
+                image.write(buf);
+            });
+    }
+
+    /// Compute the relocation values of the input sections in the image of an output
+    /// section. Values which don't fit in their input section are reported and dropped.
+    // mold: apply_reloc_alloc
+    fn add_relocation_data(&self, osec_id: OutputSectionId, image: &mut SectionImage) {
+        let osec = self.ctx.get_output_section(osec_id);
+        for (isec_id, (_, _, relocation_data)) in
+            osec.get_input_sections().iter().zip(image.isecs.iter_mut())
+        {
+            let isec = self.ctx.get_input_section(*isec_id);
+            *relocation_data = self.get_relocation_data(*isec_id);
+            relocation_data.retain(
+                |RelValue {
+                     offset,
+                     value,
+                     size,
+                 }| {
+                    if offset + size > isec.get_size() as usize {
+                        self.ctx.diag.error(
+                            Diagnostic::new("relocation is outside the section").at(
+                                self.ctx.get_file(isec.get_file()).get_file_name(),
+                                isec.get_name(),
+                                *offset as u64,
+                            ),
+                        );
+                        return false;
+                    }
+                    log::debug!("Relocation: {:#x} -> {:#x}", offset, value);
+                    true
+                },
+            );
+        }
+    }
+
+    /// Verify the values written by relocations in the output, which catches overlapping
+    /// or misplaced writes (--check-relocs). Returns false if a check fails.
+    pub fn check_relocations(&self, buf: &[u8]) -> bool {
+        let mut ok = true;
+        for chunk in self.chunks.iter() {
+            let OutputChunk::Section(chunk) = chunk else {
+                continue;
+            };
+            let osec = self.ctx.get_output_section(chunk.get_id());
+            for isec_id in osec.get_input_sections() {
+                let relocation_data = self.get_relocation_data(*isec_id);
+                if relocation_data.is_empty() {
+                    continue;
+                }
+                let isec = self.ctx.get_input_section(*isec_id);
+                let start = isec.get_offset().unwrap() as usize;
+                let data = buf
+                    .get(start..start + isec.get_size() as usize)
+                    .unwrap_or(&[]);
+                if let Err(msg) = check_relocation_data(data, &relocation_data) {
+                    self.ctx.diag.error(format!(
+                        "--check-relocs: {}:({}): {}",
+                        self.ctx.get_file(isec.get_file()).get_file_name(),
+                        isec.get_name(),
+                        msg
+                    ));
+                    ok = false;
+                }
+            }
+        }
+        ok
+    }
+
+    fn get_eh_frame_isecs(&self) -> impl Iterator<Item = &InputSection> {
+        self.ctx
+            .output_sections()
+            .filter(|osec| osec.get_name() == ".eh_frame")
+            .flat_map(|osec| osec.get_input_sections())
+            .map(|isec| self.ctx.get_input_section(*isec))
+    }
+
+    /// Fill .eh_frame_hdr with the FDEs of the relocated .eh_frame
+    pub fn write_eh_frame_hdr(&self, buf: &mut [u8]) {
+        let Some(eh_frame_hdr) = self.chunks.iter().find_map(|chunk| match chunk {
+            OutputChunk::EhFrameHdr(chunk) => Some(chunk),
+            _ => None,
+        }) else {
+            return;
+        };
+        let mut eh_frame_addr = None;
+        let mut fdes = vec![];
+        for chunk in &self.chunks {
+            if let OutputChunk::Section(osec_ref) = chunk {
+                if self.ctx.get_output_section(osec_ref.get_id()).get_name() != ".eh_frame" {
+                    continue;
+                }
+                let shdr = &osec_ref.common.shdr;
+                eh_frame_addr.get_or_insert(shdr.sh_addr);
+                let start = shdr.sh_offset as usize;
+                let data = &buf[start..start + shdr.sh_size as usize];
+                fdes.extend(eh_frame::collect_fdes(data, shdr.sh_addr, &self.ctx.diag));
+            }
+        }
+        eh_frame_hdr.copy_buf(buf, eh_frame_addr.unwrap_or(0), &mut fdes, &self.ctx.diag);
+    }
+
+    /// Returns the layout of the output for --json-report. See the report module for the
+    /// schema.
+    pub fn get_report(&self) -> Json {
+        let sections = self
+            .chunks
+            .iter()
+            .filter(|chunk| !chunk.is_header())
+            .map(|chunk| {
+                let common = chunk.get_common();
+                let shdr = &common.shdr;
+                let inputs = match chunk {
+                    OutputChunk::Section(osec) => self
+                        .ctx
+                        .get_output_section(osec.get_id())
+                        .get_input_sections()
+                        .iter()
+                        .map(|id| {
+                            let isec = self.ctx.get_input_section(*id);
+                            let file = self.ctx.get_file(isec.get_file());
+                            Json::Object(vec![
+                                ("file", file.get_file_name().into()),
+                                ("section", isec.get_name().as_str().into()),
+                                ("address", self.get_isec_addr(*id).into()),
+                                ("size", isec.get_size().into()),
+                            ])
+                        })
+                        .collect(),
+                    _ => vec![],
+                };
+                Json::Object(vec![
+                    ("index", common.shndx.map(|shndx| shndx as u64).into()),
+                    ("name", chunk.get_section_name(&self.ctx).into()),
+                    ("type", u64::from(shdr.sh_type).into()),
+                    ("flags", shdr.sh_flags.into()),
+                    ("address", shdr.sh_addr.into()),
+                    ("offset", shdr.sh_offset.into()),
+                    ("size", shdr.sh_size.into()),
+                    ("align", shdr.sh_addralign.into()),
+                    ("inputs", Json::Array(inputs)),
+                ])
+            })
+            .collect();
+
+        // The same symbols as .symtab
+        let mut symbols = vec![];
+        for (symbol_ref, def_esym) in self.get_symbols() {
+            let sym = symbol_ref.read();
+            if def_esym.is_common() {
+                continue;
+            }
+            let (address, section) = if def_esym.is_abs() {
+                (def_esym.get_esym().st_value, None)
+            } else {
+                let Some(shndx) = self.get_output_shndx(&sym) else {
+                    continue;
+                };
+                (self.get_symbol_addr(&sym).unwrap_or(0), Some(shndx as u64))
+            };
+            let esym = def_esym.get_esym();
+            let file = sym.file.map(|id| self.ctx.get_file(id).get_file_name());
+            symbols.push(Json::Object(vec![
+                ("name", sym.name.as_str().into()),
+                ("address", address.into()),
+                ("size", esym.st_size.into()),
+                ("type", u64::from(esym.st_symtype()).into()),
+                ("binding", u64::from(esym.st_bind()).into()),
+                ("section", section.into()),
+                ("file", file.into()),
+            ]));
+        }
+
+        let segments = self
+            .create_phdr()
+            .iter()
+            .map(|phdr| {
+                Json::Object(vec![
+                    ("type", u64::from(phdr.p_type).into()),
+                    ("flags", u64::from(phdr.p_flags).into()),
+                    ("offset", phdr.p_offset.into()),
+                    ("address", phdr.p_vaddr.into()),
+                    ("file_size", phdr.p_filesz.into()),
+                    ("memory_size", phdr.p_memsz.into()),
+                    ("align", phdr.p_align.into()),
+                ])
+            })
+            .collect();
+
+        let stats = self
+            .ctx
+            .perf
+            .counters()
+            .map(|(name, value)| (name, value.into()))
+            .collect();
+
+        Json::Object(vec![
+            ("format", "bold-link-report".into()),
+            ("version", REPORT_VERSION.into()),
+            ("entry", self.get_entry_addr().into()),
+            ("sections", Json::Array(sections)),
+            ("symbols", Json::Array(symbols)),
+            ("segments", Json::Array(segments)),
+            ("stats", Json::Object(stats)),
+        ])
+    }
+
+    /// Returns the table of section headers printed by --print-sections. Inputs is the
+    /// number of input sections in each output section, or - for sections created by the
+    /// linker.
+    pub fn get_section_listing(&self) -> Table {
+        const COLUMNS: &[Column] = &[
+            Column::right("Nr"),
+            Column::left("Name"),
+            Column::left("Type"),
+            Column::left("Flags"),
+            Column::left("Address"),
+            Column::left("Offset"),
+            Column::left("Size"),
+            Column::right("Align"),
+            Column::right("Inputs"),
+        ];
+        let mut table = Table::new(COLUMNS);
+        let chunks = self.chunks.iter().filter(|chunk| !chunk.is_header());
+        // The null section header has no chunk
+        for (i, (shdr, chunk)) in self
+            .get_shdrs()
+            .iter()
+            .zip(std::iter::once(None).chain(chunks.map(Some)))
+            .enumerate()
+        {
+            let inputs = match chunk {
+                Some(OutputChunk::Section(osec)) => self
+                    .ctx
+                    .get_output_section(osec.get_id())
+                    .get_input_sections()
+                    .len()
+                    .to_string(),
+                _ => "-".to_owned(),
+            };
+            table.push(vec![
+                i.to_string(),
+                chunk.map_or(String::new(), |chunk| chunk.get_section_name(&self.ctx)),
+                sh_type_name(shdr.sh_type),
+                sh_flags_string(shdr.sh_flags),
+                hex(shdr.sh_addr),
+                hex(shdr.sh_offset),
+                hex(shdr.sh_size),
+                shdr.sh_addralign.to_string(),
+                inputs,
+            ]);
+        }
+        table
+    }
+
+    /// Returns the table of the symbols in .symtab printed by --print-symbols, in the same
+    /// order
+    pub fn get_symbol_listing(&self) -> Table {
+        const COLUMNS: &[Column] = &[
+            Column::left("Value"),
+            Column::right("Size"),
+            Column::left("Bind"),
+            Column::left("Type"),
+            Column::left("Section"),
+            Column::left("Name"),
+            Column::left("File"),
+        ];
+        let section_names = self
+            .chunks
+            .iter()
+            .filter_map(|chunk| Some((chunk.get_common().shndx?, chunk)))
+            .collect::<HashMap<_, _>>();
+        let mut table = Table::new(COLUMNS);
+        for entry in self.get_symtab_entries().0 {
+            let esym = &entry.esym;
+            let section = match esym.st_shndx {
+                SHN_UNDEF => "UND".to_owned(),
+                SHN_ABS => "ABS".to_owned(),
+                SHN_COMMON => "COM".to_owned(),
+                shndx => {
+                    let shndx = if shndx == SHN_XINDEX {
+                        entry.xindex as usize
+                    } else {
+                        shndx as usize
+                    };
+                    section_names[&shndx].get_section_name(&self.ctx)
+                }
+            };
+            let file = entry
+                .file
+                .map_or("", |id| self.ctx.get_file(id).get_file_name());
+            table.push(vec![
+                hex(esym.st_value),
+                esym.st_size.to_string(),
+                st_bind_name(esym.st_info >> 4),
+                st_type_name(esym.st_info & 0xf),
+                section,
+                entry.name.to_string(),
+                file.to_owned(),
+            ]);
         }
+        table
+    }
+
+    fn get_shstrndx(&self) -> usize {
+        self.chunks
+            .iter()
+            .find_map(|chunk| {
+                if let OutputChunk::Shstrtab(chunk) = chunk {
+                    Some(chunk.common.shndx.unwrap())
+                } else {
+                    None
+                }
+            })
+            .unwrap()
     }
 
     fn get_shdrs(&self) -> Vec<Elf64_Shdr> {
@@ -396,138 +2726,379 @@ impl Linker<'_> {
                 shdrs.push(chunk.get_common().get_elf64_shdr());
             }
         }
+        // The number of sections and the index of .shstrtab which don't fit in the ELF
+        // header are stored in the first section header
+        if shdrs.len() >= SHN_LORESERVE as usize {
+            shdrs[0].sh_size = shdrs.len() as u64;
+        }
+        let shstrndx = self.get_shstrndx();
+        if shstrndx >= SHN_LORESERVE as usize {
+            shdrs[0].sh_link = shstrndx as u32;
+        }
         shdrs
     }
 
-    fn get_shstrtab_content(&self) -> Vec<u8> {
-        let mut content = vec![0];
-        for chunk in &self.chunks {
-            if !chunk.is_header() {
-                let name = chunk.get_section_name(&self.ctx);
-                content.extend_from_slice(name.as_bytes());
-                content.push(0);
-            }
-        }
-        content
+    /// Returns .shstrtab holding the names of all sections
+    fn get_shstrtab(&self) -> StringTable {
+        let names = self
+            .chunks
+            .iter()
+            .filter(|chunk| !chunk.is_header())
+            .map(|chunk| chunk.get_section_name(&self.ctx))
+            .collect::<Vec<_>>();
+        StringTable::with_suffix_sharing(names.iter().map(|name| name.as_str()))
     }
 
-    fn get_symbols(&self) -> Vec<&Arc<RefCell<Symbol>>> {
+    /// Returns symbols to be written to .symtab, paired with the symbol table entry of the
+    /// file defining them.
+    fn get_symbols(&self) -> Vec<(&Arc<SymbolCell>, &Arc<ElfSymbol>)> {
         let mut symbols = vec![];
         for file in self.ctx.files() {
-            for sym in file.get_symbols() {
-                if let Some(symbol_ref) = sym {
-                    let symbol = symbol_ref.borrow();
-                    if symbol.file == Some(file.get_id()) {
-                        if symbol.should_write() && symbol.file == Some(file.get_id()) {
-                            symbols.push(symbol_ref);
-                        }
-                    }
+            for (i, symbol_ref) in file.get_symbols().iter().enumerate() {
+                let Some(symbol_ref) = symbol_ref else {
+                    continue;
+                };
+                // Only the definition is written. Undefined references are resolved to it, and
+                // their esym no longer describes this file's entry.
+                let esym = &file.get_elf_symbols()[i];
+                if esym.get_esym().is_undefined() {
+                    continue;
+                }
+                let symbol = symbol_ref.read();
+                if symbol.should_write() && symbol.file == Some(file.get_id()) {
+                    symbols.push((symbol_ref, esym));
                 }
             }
         }
         symbols
     }
 
-    fn get_symtab(&self) -> (Vec<Elf64_Sym>, Vec<u8>) {
-        let mut symtab_content = vec![dummy!(Elf64_Sym)];
-        let mut strtab_content = vec![0];
+    /// Returns the index of the output section containing the definition of the symbol
+    fn get_output_shndx(&self, symbol: &Symbol) -> Option<usize> {
+        let file = self.ctx.get_file(symbol.file?);
+        let shndx = symbol.get_def_esym().get_shndx();
+        let isec = self
+            .ctx
+            .get_input_section(file.get_input_sections()[shndx]?);
+        let common = self.get_common_from_osec(isec.get_output_section())?;
+        common.shndx
+    }
+
+    /// Returns the contents of .dynsym. Imported symbols are undefined, except for copied
+    /// data objects, which are defined in .copyrel. The value of an imported function with
+    /// a canonical PLT entry is the address of the entry.
+    fn get_dynsym_content(&self) -> Vec<Elf64_Sym> {
+        let Some(dynsym) = self.get_dynsym() else {
+            return vec![];
+        };
+        let mut content = vec![dummy!(Elf64_Sym)];
+        for (symbol, name) in dynsym.syms.iter().zip(&dynsym.names) {
+            let sym = symbol.read();
+            let def_esym = sym.get_def_esym();
+            let mut esym = def_esym.get();
+            esym.st_name = *name;
+            if let Some(addr) = self.get_copyrel_addr(&sym) {
+                esym.st_other = STV_DEFAULT;
+                esym.st_shndx = self
+                    .get_copyrel()
+                    .and_then(|copyrel| copyrel.common.shndx)
+                    .map_or(SHN_UNDEF, to_st_shndx);
+                esym.st_value = addr;
+            } else if self.is_defined_in_dso(&sym) || self.is_undefined_import(&sym) {
+                esym.st_other = STV_DEFAULT;
+                esym.st_shndx = SHN_UNDEF;
+                esym.st_value = 0;
+                if sym.has_canonical_plt {
+                    esym.st_value = self.get_plt_entry_addr(&sym).unwrap_or(0);
+                }
+            } else {
+                esym.st_other = sym.get_visibility();
+                if !def_esym.is_abs() {
+                    esym.st_value = self.get_symbol_addr(&sym).unwrap_or(0);
+                    // There is no .dynsym counterpart of .symtab_shndx, but the dynamic
+                    // loader only tells defined symbols from undefined ones
+                    esym.st_shndx = self.get_output_shndx(&sym).map_or(SHN_UNDEF, to_st_shndx);
+                }
+            }
+            content.push(esym);
+        }
+        content
+    }
+
+    /// Returns the entries of .symtab other than the null one in their order, and the index
+    /// of the first global symbol. st_name is not set yet.
+    fn get_symtab_entries(&self) -> (Vec<SymtabEntry>, usize) {
+        // Local symbols must precede global ones
+        let mut locals = vec![];
+        let mut globals = vec![];
         let symbols = self.get_symbols();
-        for symbol_ref in symbols {
-            let sym = symbol_ref.borrow_mut();
-            let mut esym = sym.esym.get();
-            esym.st_name = strtab_content.len() as u32;
-            if sym.esym.is_abs() {
+        for (symbol_ref, def_esym) in symbols {
+            let sym = symbol_ref.read();
+            // Definitions overridden by the linker script are written below
+            if sym.is_global() && self.script_symbols.contains_key(&sym.name) {
+                continue;
+            }
+            // st_size and st_info are taken from the defining entry as is. st_other is the
+            // visibility merged over all references.
+            let mut esym = def_esym.get();
+            esym.st_other = sym.get_visibility();
+            // Section index stored in .symtab_shndx
+            let mut xindex = 0;
+            if def_esym.is_abs() {
                 // Keep esym.st_value
-                // Keep esym.st_shndx
-            } else if sym.esym.is_common() {
-                log::error!("common: {}, ignored", sym.name);
+                // Keep esym.st_shndx (SHN_ABS)
+            } else if def_esym.is_common() {
+                self.ctx.diag.warn(
+                    Diagnostic::new(format!("common symbol {} is not allocated", sym.name))
+                        .symbol(sym.name),
+                );
             } else {
                 esym.st_value = self.get_symbol_addr(&sym).unwrap_or(0);
-                let file = self.ctx.get_file(sym.file.unwrap());
-                let shndx = sym.esym.get_esym().st_shndx as usize;
-                let Some(isec) = file.get_input_sections()[shndx] else {
-                    let esec = &file.get_elf_sections()[shndx];
-                    log::debug!("{} in section `{}`, ignored", sym.name, esec.name);
+                let Some(shndx) = self.get_output_shndx(&sym) else {
+                    log::debug!("{} is not in any output section, ignored", sym.name);
                     continue;
                 };
-                let isec = self.ctx.get_input_section(isec);
-                let osec_id = isec.get_output_section();
-                let common = self.get_common_from_osec(osec_id);
-                esym.st_shndx = common.map(|chunk| chunk.shndx.unwrap() as u16).unwrap();
+                esym.st_shndx = to_st_shndx(shndx);
+                if esym.st_shndx == SHN_XINDEX {
+                    xindex = shndx as u32;
+                }
             }
 
-            /* TODO: remove
-            log::debug!(
-                "Symbol: {} (st_value: {:#x}, st_shndx: {})",
-                sym.name,
-                esym.st_value,
-                esym.st_shndx
-            );
-            */
-            symtab_content.push(esym);
-            strtab_content.extend_from_slice(sym.name.as_bytes());
-            strtab_content.push(0);
+            let entry = SymtabEntry {
+                name: sym.name,
+                esym,
+                xindex,
+                file: sym.file,
+            };
+            if sym.is_global() {
+                globals.push(entry);
+            } else {
+                locals.push(entry);
+            }
         }
-        (symtab_content, strtab_content)
-    }
 
-    fn create_phdr(&self) -> Vec<Elf64_Phdr> {
-        fn to_phdr_flags(shdr: &Elf64_Shdr) -> u32 {
-            let mut ret = PF_R;
-            if shdr.sh_flags & SHF_WRITE as u64 != 0 {
-                ret |= PF_W;
-            }
-            if shdr.sh_flags & SHF_EXECINSTR as u64 != 0 {
-                ret |= PF_X;
-            }
-            ret
-        }
-
-        fn new_phdr(
-            p_type: u32,
-            p_flags: u32,
-            p_align: u64,
-            chunk_shdr: &Elf64_Shdr,
-        ) -> Elf64_Phdr {
-            Elf64_Phdr {
-                p_type,
-                p_flags,
-                p_offset: chunk_shdr.sh_offset,
-                p_vaddr: chunk_shdr.sh_addr,
-                p_paddr: chunk_shdr.sh_addr,
-                p_filesz: if chunk_shdr.sh_type == SHT_NOBITS {
-                    0
+        // Symbols defined by the linker script are relative to the output section their
+        // assignment is in
+        let mut script_symbols = self.script_symbols.iter().collect::<Vec<_>>();
+        script_symbols.sort_by_key(|(name, _)| **name);
+        for (name, symbol) in script_symbols {
+            let shndx = symbol.section.as_deref().and_then(|section| {
+                self.chunks.iter().find_map(|chunk| match chunk {
+                    OutputChunk::Section(_) if chunk.get_section_name(&self.ctx) == section => {
+                        chunk.get_common().shndx
+                    }
+                    _ => None,
+                })
+            });
+            let st_shndx = shndx.map_or(SHN_ABS, to_st_shndx);
+            globals.push(SymtabEntry {
+                name: *name,
+                esym: Elf64_Sym {
+                    st_name: 0,
+                    st_info: (STB_GLOBAL << 4) | STT_NOTYPE,
+                    st_other: if symbol.hidden {
+                        STV_HIDDEN
+                    } else {
+                        STV_DEFAULT
+                    },
+                    st_shndx,
+                    st_value: symbol.value,
+                    st_size: 0,
+                },
+                xindex: if st_shndx == SHN_XINDEX {
+                    shndx.unwrap() as u32
                 } else {
-                    chunk_shdr.sh_size
+                    0
                 },
-                p_memsz: chunk_shdr.sh_size,
-                p_align,
+                file: None,
+            });
+        }
+
+        for symbols in [&mut locals, &mut globals] {
+            match self.config.sort_symbols {
+                SortSymbols::None => (),
+                SortSymbols::Address => symbols.sort_by_key(|entry| entry.esym.st_value),
+                SortSymbols::Name => symbols.sort_by_key(|entry| entry.name),
+            }
+        }
+        let first_global = 1 + locals.len();
+        locals.extend(globals);
+        (locals, first_global)
+    }
+
+    /// Returns the contents of .symtab, .symtab_shndx and .strtab, and the index of the first
+    /// global symbol
+    fn get_symtab(&self) -> (Vec<Elf64_Sym>, Vec<u32>, StringTable, usize) {
+        let (entries, first_global) = self.get_symtab_entries();
+        let strtab =
+            StringTable::with_suffix_sharing(entries.iter().map(|entry| entry.name.as_str()));
+        let mut symtab_content = vec![dummy!(Elf64_Sym)];
+        let mut symtab_shndx_content = vec![0];
+        for mut entry in entries {
+            entry.esym.st_name = strtab.get(&entry.name);
+            symtab_content.push(entry.esym);
+            symtab_shndx_content.push(entry.xindex);
+        }
+        (symtab_content, symtab_shndx_content, strtab, first_global)
+    }
+
+    fn create_phdr(&self) -> Vec<Elf64_Phdr> {
+        let mut phdrs: Vec<Elf64_Phdr> = vec![];
+
+        // Create PT_PHDR. It must precede any PT_LOAD.
+        for chunk in &self.chunks {
+            if let OutputChunk::Phdr(chunk) = chunk {
+                if !chunk.common.should_be_loaded() {
+                    continue;
+                }
+                let shdr = &chunk.common.shdr;
+                phdrs.push(Elf64_Phdr {
+                    p_type: PT_PHDR,
+                    p_flags: PF_R,
+                    p_offset: shdr.sh_offset,
+                    p_vaddr: shdr.sh_addr,
+                    p_paddr: shdr.sh_addr,
+                    p_filesz: shdr.sh_size,
+                    p_memsz: shdr.sh_size,
+                    p_align: shdr.sh_addralign,
+                });
+            }
+        }
+
+        // Create PT_INTERP. It must also precede any PT_LOAD.
+        for chunk in &self.chunks {
+            if let OutputChunk::Interp(chunk) = chunk {
+                phdrs.push(to_phdr(PT_INTERP, PF_R, &chunk.common.shdr));
+            }
+        }
+
+        // Create PT_LOAD. Consecutive chunks with the same permissions share a segment unless
+        // a chunk is placed elsewhere by --section-start or the linker script. This doesn't
+        // depend on the layout so that the number of program headers is known beforehand.
+        let mut segment_flags = None;
+        let mut last_load: Option<usize> = None;
+        for (chunk, script_commands) in self.chunks.iter().zip(self.get_script_commands()) {
+            if !chunk.get_common().should_be_loaded() {
+                continue;
+            }
+            let shdr = &chunk.get_common().shdr;
+            let flags = to_phdr_flags(shdr);
+            let is_nobits = shdr.sh_type == SHT_NOBITS;
+            let sets_address = match (&self.script, script_commands) {
+                (Some(script), Some(commands)) => script.sets_address(commands),
+                _ => false,
+            };
+            if segment_flags != Some(flags)
+                || self.get_section_start(chunk).is_some()
+                || sets_address
+            {
+                segment_flags = Some(flags);
+                last_load = Some(phdrs.len());
+                phdrs.push(Elf64_Phdr {
+                    p_type: PT_LOAD,
+                    p_flags: flags,
+                    p_offset: shdr.sh_offset,
+                    p_vaddr: shdr.sh_addr,
+                    p_paddr: shdr.sh_addr,
+                    p_filesz: if is_nobits { 0 } else { shdr.sh_size },
+                    p_memsz: shdr.sh_size,
+                    p_align: self.page_size(),
+                });
+                continue;
+            }
+            // .tbss does not occupy memory of the segment
+            let is_tbss = is_nobits && shdr.sh_flags & SHF_TLS as u64 != 0;
+            if is_tbss {
+                continue;
+            }
+            let phdr = &mut phdrs[last_load.unwrap()];
+            phdr.p_memsz = shdr.sh_addr + shdr.sh_size - phdr.p_vaddr;
+            if !is_nobits {
+                phdr.p_filesz = shdr.sh_offset + shdr.sh_size - phdr.p_offset;
+            }
+        }
+
+        // Create PT_TLS covering the TLS template. Before the layout, it is only counted to
+        // size the program header table.
+        if self.has_tls_sections() {
+            let tls = self.tls_template.unwrap_or_default();
+            phdrs.push(Elf64_Phdr {
+                p_type: PT_TLS,
+                p_flags: PF_R,
+                p_offset: tls.offset,
+                p_vaddr: tls.addr,
+                p_paddr: tls.addr,
+                p_filesz: tls.filesz,
+                p_memsz: tls.memsz,
+                p_align: tls.align,
+            });
+        }
+
+        // Create PT_DYNAMIC
+        for chunk in &self.chunks {
+            if let OutputChunk::Dynamic(chunk) = chunk {
+                phdrs.push(to_phdr(PT_DYNAMIC, PF_R | PF_W, &chunk.common.shdr));
             }
         }
 
-        let mut phdrs = vec![];
-        // Create PT_LOAD
+        // Create PT_GNU_EH_FRAME
         for chunk in &self.chunks {
-            if chunk.get_common().should_be_loaded() {
+            if let OutputChunk::EhFrameHdr(chunk) = chunk {
+                let shdr = &chunk.common.shdr;
+                phdrs.push(Elf64_Phdr {
+                    p_type: PT_GNU_EH_FRAME,
+                    p_flags: PF_R,
+                    p_offset: shdr.sh_offset,
+                    p_vaddr: shdr.sh_addr,
+                    p_paddr: shdr.sh_addr,
+                    p_filesz: shdr.sh_size,
+                    p_memsz: shdr.sh_size,
+                    p_align: shdr.sh_addralign,
+                });
+            }
+        }
+
+        // Create PT_GNU_RELRO. Its end is padded to a page boundary by assign_osec_offsets.
+        if self.config.z_relro {
+            let mut relro: Option<Elf64_Phdr> = None;
+            for chunk in &self.chunks {
+                if !chunk.is_relro(&self.ctx) {
+                    continue;
+                }
                 let shdr = &chunk.get_common().shdr;
-                let phdr = new_phdr(PT_LOAD, to_phdr_flags(shdr), PAGE_SIZE, shdr);
-                phdrs.push(phdr);
+                let phdr = relro.get_or_insert(Elf64_Phdr {
+                    p_type: PT_GNU_RELRO,
+                    p_flags: PF_R,
+                    p_offset: shdr.sh_offset,
+                    p_vaddr: shdr.sh_addr,
+                    p_paddr: shdr.sh_addr,
+                    p_filesz: 0,
+                    p_memsz: 0,
+                    p_align: 1,
+                });
+                let end = align_to(shdr.sh_addr + shdr.sh_size, self.page_size());
+                phdr.p_memsz = end - phdr.p_vaddr;
+                phdr.p_filesz = phdr.p_memsz;
             }
+            phdrs.extend(relro);
         }
+
+        // The loader maps segments with mmap, which requires the address and the offset to
+        // be congruent
+        debug_assert!(phdrs
+            .iter()
+            .all(|phdr| phdr.p_align <= 1
+                || phdr.p_vaddr % phdr.p_align == phdr.p_offset % phdr.p_align));
         phdrs
     }
 
     fn get_common_from_osec(&self, id: OutputSectionId) -> Option<&ChunkInfo> {
-        self.chunks
-            .iter()
-            .find(|chunk| {
-                if let OutputChunk::Section(chunk) = chunk {
-                    chunk.get_id() == id
-                } else {
-                    false
-                }
-            })
-            .map(|chunk| chunk.get_common())
+        let chunk = &self.chunks[*self.osec_chunk_index.get(&id)?];
+        debug_assert!(
+            matches!(chunk, OutputChunk::Section(chunk) if chunk.get_id() == id),
+            "chunk index is out of sync with the chunk list"
+        );
+        Some(chunk.get_common())
     }
 
     fn get_isec_addr(&self, id: InputSectionId) -> u64 {
@@ -540,47 +3111,695 @@ impl Linker<'_> {
         osec_addr + (isec_file_ofs - osec_file_ofs)
     }
 
+    /// Store the address of every symbol, so that relocations referring to the same symbol
+    /// don't derive it again. This must be called after addresses are assigned to chunks.
+    pub fn assign_symbol_addrs(&mut self) {
+        let _timer = self.ctx.perf.timer("assign_symbol_addrs");
+        let symbols = self
+            .ctx
+            .files()
+            .flat_map(|file| file.get_symbols().iter().flatten())
+            .chain(self.ctx.get_global_symbols())
+            .collect::<Vec<_>>();
+        symbols.into_par_iter().for_each(|symbol| {
+            let addr = self.compute_symbol_addr(&symbol.read());
+            symbol.write().addr = addr;
+        });
+        self.symbol_addrs_assigned = true;
+    }
+
     fn get_symbol_addr(&self, symbol: &Symbol) -> Option<u64> {
-        let file = self.ctx.get_file(symbol.file.unwrap());
-        let shndx = symbol.esym.get_esym().st_shndx as usize;
-        file.get_input_sections()[shndx].map(|isec_id| {
-            let isec_addr = self.get_isec_addr(isec_id);
-            isec_addr + symbol.esym.get_esym().st_value
+        // Symbols without a definition are cheap to resolve. They include the null symbols
+        // created for each relocation, which are not in any symbol table.
+        if !self.symbol_addrs_assigned || symbol.file.is_none() {
+            return self.compute_symbol_addr(symbol);
+        }
+        debug_assert_eq!(symbol.addr, self.compute_symbol_addr(symbol));
+        symbol.addr
+    }
+
+    fn compute_symbol_addr(&self, symbol: &Symbol) -> Option<u64> {
+        // Symbols assigned by the linker script override definitions in input files
+        if symbol.is_global() {
+            if let Some(script_symbol) = self.script_symbols.get(&symbol.name) {
+                return Some(script_symbol.value);
+            }
+        }
+        // Absolute symbols are not relative to any section
+        // Undefined symbols (e.g. weak references) resolve to 0, and those imported by a
+        // shared object are called through their PLT entries
+        let Some(file) = symbol.file else {
+            if symbol.name == GLOBAL_OFFSET_TABLE {
+                return Some(self.get_got_base());
+            }
+            if symbol.name == DYNAMIC {
+                return Some(self.get_dynamic_addr().unwrap_or(0));
+            }
+            return Some(self.get_plt_entry_addr(symbol).unwrap_or(0));
+        };
+        let esym = symbol.get_def_esym();
+        if esym.is_abs() {
+            return Some(esym.get_esym().st_value);
+        }
+        let file = self.ctx.get_file(file);
+        // Functions in DSOs are referred to by their PLT entries, and copied data objects by
+        // their copies
+        if file.is_dso() {
+            if let Some(addr) = self.get_copyrel_addr(symbol) {
+                return Some(addr);
+            }
+            return Some(self.get_plt_entry_addr(symbol).unwrap_or(0));
+        }
+        let shndx = esym.get_shndx();
+        let addr = file.get_input_sections()[shndx]
+            .map(|isec_id| self.get_isec_offset_addr(isec_id, esym.get_esym().st_value))?;
+        // The value of a TLS symbol is its offset in the TLS template
+        if esym.get_esym().st_symtype() == STT_TLS {
+            let tls_addr = self.tls_template.map_or(0, |tls| tls.addr);
+            return Some(addr.wrapping_sub(tls_addr));
+        }
+        Some(addr)
+    }
+
+    /// Returns the address of an offset in the original contents of an input section. For
+    /// merged sections, it is the address of the deduplicated copy.
+    fn get_isec_offset_addr(&self, id: InputSectionId, offset: u64) -> u64 {
+        match self.ctx.get_input_section(id).get_fragment(offset) {
+            Some(frag) => self.get_isec_addr(frag.isec) + frag.offset + offset - frag.input_offset,
+            None => self.get_isec_addr(id) + offset,
+        }
+    }
+
+    /// For a relocation against a section symbol, the addend rather than the symbol selects
+    /// the piece of a merged section. Returns the symbol address adjusted accordingly, so
+    /// that adding the addend yields the address of the piece.
+    fn get_section_symbol_addr(&self, symbol: &Symbol, addend: i64) -> Option<u64> {
+        let esym = symbol.get_def_esym();
+        if esym.get_esym().st_symtype() != STT_SECTION {
+            return None;
+        }
+        let file = self.ctx.get_file(symbol.file?);
+        let isec_id = file.get_input_sections()[esym.get_shndx()]?;
+        let offset = esym.get_esym().st_value.wrapping_add(addend as u64);
+        self.ctx.get_input_section(isec_id).get_fragment(offset)?;
+        Some(
+            self.get_isec_offset_addr(isec_id, offset)
+                .wrapping_sub(addend as u64),
+        )
+    }
+
+    /// Returns the address of the PLT entry allocated to the symbol by scan_relocations
+    fn get_plt_entry_addr(&self, symbol: &Symbol) -> Option<u64> {
+        if !symbol.is_global() {
+            return None;
+        }
+        let plt_idx = self
+            .ctx
+            .get_global_symbol(symbol.name)
+            .and_then(|symbol| symbol.read().plt_idx)?;
+        Some(self.get_plt()?.get_entry_addr(plt_idx))
+    }
+
+    /// Returns the address of the copy of a data object in a DSO allocated by
+    /// scan_relocations
+    fn get_copyrel_addr(&self, symbol: &Symbol) -> Option<u64> {
+        if !symbol.is_global() {
+            return None;
+        }
+        let offset = self
+            .ctx
+            .get_global_symbol(symbol.name)
+            .and_then(|symbol| symbol.read().copyrel_offset)?;
+        Some(self.get_copyrel()?.common.shdr.sh_addr + offset)
+    }
+
+    /// Returns true if a PLT32 call to the symbol has to go through a PLT entry. Calls to
+    /// undefined symbols (e.g. weak references) are resolved to 0 directly, except in a
+    /// shared object, which imports them.
+    fn needs_plt(&self, symbol: &Symbol) -> bool {
+        self.is_dynamic_symbol(symbol)
+    }
+
+    /// Returns true if the dynamic linker binds references to the symbol through .dynsym:
+    /// symbols defined in DSOs, preemptible definitions in a shared object and symbols which
+    /// a shared object imports
+    fn is_dynamic_symbol(&self, symbol: &Symbol) -> bool {
+        (symbol.file.is_some() || self.is_undefined_import(symbol)) && self.is_preemptible(symbol)
+    }
+
+    /// Returns true if the symbol is not defined in the link and registered by
+    /// import_undefined_symbols
+    fn is_undefined_import(&self, symbol: &Symbol) -> bool {
+        self.config.shared
+            && symbol.file.is_none()
+            && symbol.is_global()
+            && self.ctx.get_global_symbol(symbol.name).is_some()
+    }
+
+    /// Returns true if the address of a symbol changes with the load address of the output,
+    /// i.e. it is in a section of a shared object or imported by it
+    fn depends_on_load_address(&self, symbol: &Symbol) -> bool {
+        self.config.shared
+            && (symbol.file.is_some() && !symbol.get_def_esym().is_abs()
+                || self.is_undefined_import(symbol))
+    }
+
+    /// Returns true if references to the symbol may be bound to another definition at
+    /// runtime, so that they have to go through the GOT or PLT. Symbols with non-default
+    /// visibility (e.g. STV_PROTECTED) always bind to the definition in this link.
+    fn is_preemptible(&self, symbol: &Symbol) -> bool {
+        if !symbol.is_global()
+            || symbol.get_visibility() != STV_DEFAULT
+            || self.script_symbols.contains_key(&symbol.name)
+        {
+            return false;
+        }
+        let Some(file) = symbol.file else {
+            return true;
+        };
+        if self.ctx.get_file(file).is_dso() {
+            return true;
+        }
+        if self.is_hidden_by_version_script(symbol.name) {
+            return false;
+        }
+        // Definitions in an executable can't be preempted. Those in a shared object can,
+        // unless -Bsymbolic(-functions) binds them locally.
+        self.config.shared
+            && match self.config.symbolic {
+                Symbolic::None => true,
+                Symbolic::All => false,
+                Symbolic::Functions => symbol.get_def_esym().get_esym().st_symtype() != STT_FUNC,
+            }
+    }
+
+    /// References to a global symbol from each file are distinct `Symbol`s. Returns the one
+    /// registered to the global symbol table, which carries the GOT indices.
+    fn get_canonical_symbol(&self, symbol: &Arc<SymbolCell>) -> Arc<SymbolCell> {
+        let sym = symbol.read();
+        if sym.is_global() {
+            if let Some(global_symbol) = self.ctx.get_global_symbol(sym.name) {
+                return Arc::clone(global_symbol);
+            }
+        }
+        Arc::clone(symbol)
+    }
+
+    /// Returns the address of _GLOBAL_OFFSET_TABLE_, which is the start of .got.plt if it
+    /// exists
+    fn get_got_base(&self) -> u64 {
+        let got = self.get_got().map(|got| got.common.shdr.sh_addr);
+        self.get_got_plt_addr().or(got).unwrap_or(0)
+    }
+
+    fn get_plt(&self) -> Option<&Plt> {
+        self.chunks.iter().find_map(|chunk| match chunk {
+            OutputChunk::Plt(chunk) => Some(chunk),
+            _ => None,
         })
     }
 
-    fn get_global_symbol_addr(&self, name: &str) -> Option<u64> {
-        self.ctx.get_global_symbol(name).map(|symbol| {
-            let symbol = symbol.deref().borrow();
-            self.get_symbol_addr(&symbol).unwrap_or(0)
+    fn get_copyrel(&self) -> Option<&CopyRel> {
+        self.chunks.iter().find_map(|chunk| match chunk {
+            OutputChunk::CopyRel(chunk) => Some(chunk),
+            _ => None,
+        })
+    }
+
+    fn get_got_plt_addr(&self) -> Option<u64> {
+        self.chunks.iter().find_map(|chunk| match chunk {
+            OutputChunk::GotPlt(chunk) => Some(chunk.common.shdr.sh_addr),
+            _ => None,
         })
     }
 
-    /// Returns [(file_ofs, u64)]
-    fn get_relocation_data(&self) -> Vec<RelValue> {
+    fn get_dynsym(&self) -> Option<&DynSym> {
+        self.chunks.iter().find_map(|chunk| match chunk {
+            OutputChunk::DynSym(chunk) => Some(chunk),
+            _ => None,
+        })
+    }
+
+    fn get_dynamic_addr(&self) -> Option<u64> {
+        self.chunks.iter().find_map(|chunk| match chunk {
+            OutputChunk::Dynamic(chunk) => Some(chunk.common.shdr.sh_addr),
+            _ => None,
+        })
+    }
+
+    fn get_got(&self) -> Option<&Got> {
+        self.chunks.iter().find_map(|chunk| match chunk {
+            OutputChunk::Got(chunk) => Some(chunk),
+            _ => None,
+        })
+    }
+
+    /// Returns the TP-relative offset of a TLS symbol, whose value is the offset in the TLS
+    /// template
+    fn get_tpoff(&self, symbol: &Symbol) -> u64 {
+        let tp_offset = self.tls_template.map_or(0, |tls| tls.tp_offset());
+        self.get_symbol_addr(symbol)
+            .unwrap_or(0)
+            .wrapping_sub(tp_offset)
+    }
+
+    /// Returns pairs of the index and the value of each GOT entry. TLS variables defined in
+    /// objects are in the main executable, whose module ID is 1. Entries for symbols
+    /// imported from DSOs are left zero and filled through .rela.dyn.
+    fn get_got_entries(&self) -> Vec<(usize, u64)> {
+        let Some(got) = self.get_got() else {
+            return vec![];
+        };
+        let addr = |symbol: &Arc<SymbolCell>| self.get_symbol_addr(&symbol.read()).unwrap_or(0);
+        let is_imported = |symbol: &Arc<SymbolCell>| self.is_dynamic_symbol(&symbol.read());
+        // The module ID of the executable is 1, and that of a shared object is filled
+        // through .rela.dyn
+        let module_id = if self.config.shared { 0 } else { 1 };
+        let mut entries = vec![];
+        for symbol in &got.got_syms {
+            let idx = symbol.read().got_idx.unwrap();
+            if is_imported(symbol) {
+                entries.push((idx, 0));
+            } else {
+                entries.push((idx, addr(symbol)));
+            }
+        }
+        for symbol in &got.gottp_syms {
+            let idx = symbol.read().gottp_idx.unwrap();
+            if is_imported(symbol) || self.config.shared {
+                entries.push((idx, 0));
+            } else {
+                entries.push((idx, self.get_tpoff(&symbol.read())));
+            }
+        }
+        for symbol in &got.tlsgd_syms {
+            let idx = symbol.read().tlsgd_idx.unwrap();
+            if is_imported(symbol) {
+                entries.push((idx, 0));
+                entries.push((idx + 1, 0));
+            } else {
+                entries.push((idx, module_id));
+                entries.push((idx + 1, addr(symbol)));
+            }
+        }
+        if let Some(idx) = got.tlsld_idx {
+            entries.push((idx, module_id));
+            entries.push((idx + 1, 0));
+        }
+        entries
+    }
+
+    /// Returns the address of the GOT entry which a relocation of `r_type` refers to
+    fn get_got_entry_addr(&self, symbol: &Arc<SymbolCell>, r_type: u32) -> Option<u64> {
+        let (got_idx, gottp_idx, tlsgd_idx) = {
+            let symbol = self.get_canonical_symbol(symbol);
+            let symbol = symbol.read();
+            (symbol.got_idx, symbol.gottp_idx, symbol.tlsgd_idx)
+        };
+        // Most relocations don't use the GOT, so it is looked up only when needed
+        let idx = if self.target.needs_got(r_type) {
+            got_idx
+        } else {
+            match self.target.tls_reloc(r_type)? {
+                TlsReloc::GotTpOff => gottp_idx,
+                TlsReloc::Gd => tlsgd_idx,
+                TlsReloc::Ld => self.get_got()?.tlsld_idx,
+                _ => None,
+            }
+        }?;
+        Some(self.get_got()?.get_entry_addr(idx))
+    }
+
+    /// Returns the address of the GOT entry holding the TP-relative offset of the symbol
+    fn get_gottp_entry_addr(&self, symbol: &Arc<SymbolCell>) -> Option<u64> {
+        let symbol = self.get_canonical_symbol(symbol);
+        let idx = symbol.read().gottp_idx?;
+        Some(self.get_got()?.get_entry_addr(idx))
+    }
+
+    /// Returns the instruction which a GOTPCRELX relocation is relaxed to. The symbol must
+    /// be defined in the output and can't be preempted.
+    fn relax_gotpcrelx(&self, isec: &InputSection, rel: &ElfRela) -> Option<RelaxedInsn> {
+        if !self.config.relax {
+            return None;
+        }
+        let symbol = self.get_canonical_symbol(&rel.symbol);
+        let symbol = symbol.read();
+        if symbol.file.is_none()
+            || self.is_defined_in_dso(&symbol)
+            || self.is_preemptible(&symbol)
+            || symbol.get_def_esym().is_abs()
+            || symbol.get_def_esym().get_esym().st_symtype() == STT_GNU_IFUNC
+        {
+            return None;
+        }
+        let data = isec.elf_section.data();
+        let r_offset = rel.erela.r_offset as usize;
+        self.target
+            .relax_gotpcrelx(rel.erela.r_type, data.get(..r_offset)?)
+    }
+
+    /// TLS offsets are known at link time in an executable, so TLSGD and TLSLD sequences
+    /// can be relaxed to local-exec
+    fn can_relax_tls(&self) -> bool {
+        self.config.relax && !self.config.shared
+    }
+
+    /// Returns the values of the PC-relative HI20 relocations of an input section by the
+    /// address of their instructions, which RISC-V PCREL_LO12 relocations refer to with
+    /// their symbols
+    fn get_pcrel_hi20_values(&self, isec: &InputSection, isec_addr: u64) -> HashMap<u64, u64> {
+        isec.get_relas()
+            .iter()
+            .filter(|rel| self.target.is_pcrel_hi(rel.erela.r_type))
+            .filter_map(|rel| {
+                let symbol = rel.symbol.read();
+                let s = self
+                    .get_section_symbol_addr(&symbol, rel.erela.r_addend)
+                    .or_else(|| self.get_symbol_addr(&symbol))?;
+                let p = isec_addr + rel.erela.r_offset;
+                Some((p, (s as i64 + rel.erela.r_addend - p as i64) as u64))
+            })
+            .collect()
+    }
+
+    /// Returns the offset and the instructions which the TLS relocation at `idx` is relaxed
+    /// to, including the following call to __tls_get_addr for general-dynamic and
+    /// local-dynamic sequences. `tpoff` is the TP-relative offset of the symbol.
+    fn relax_tls(
+        &self,
+        isec: &InputSection,
+        idx: usize,
+        kind: TlsReloc,
+        tpoff: u64,
+        isec_addr: u64,
+    ) -> Option<(usize, Vec<u8>)> {
+        // TP-relative offsets are only known at link time in an executable
+        if self.config.shared {
+            return None;
+        }
+        let relas = isec.get_relas();
+        let rel = &relas[idx];
+        let data = isec.elf_section.data();
+        let (is_local, in_dso) = {
+            let symbol = self.get_canonical_symbol(&rel.symbol);
+            let symbol = symbol.read();
+            let in_dso = self.is_defined_in_dso(&symbol);
+            (symbol.file.is_some() && !in_dso, in_dso)
+        };
+        match kind {
+            TlsReloc::GotTpOff if self.config.relax && is_local => {
+                self.target.relax_gottpoff(data, &rel.erela, tpoff)
+            }
+            // TLS descriptors are not supported, so the sequence is always relaxed: to
+            // local-exec if the symbol is defined in the executable, and to initial-exec
+            // otherwise
+            TlsReloc::Desc if in_dso => {
+                let got_entry_addr = self.get_gottp_entry_addr(&rel.symbol).unwrap_or(0);
+                let p = isec_addr + rel.erela.r_offset;
+                self.target
+                    .relax_tlsdesc_to_ie(data, &rel.erela, got_entry_addr, p)
+            }
+            TlsReloc::Desc => self.target.relax_tlsdesc_to_le(data, &rel.erela, tpoff),
+            TlsReloc::DescCall => self.target.relax_tlsdesc_call(data, &rel.erela),
+            TlsReloc::Gd | TlsReloc::Ld if self.can_relax_tls() => {
+                let call = relas.get(idx + 1)?;
+                if call.symbol.read().name != "__tls_get_addr" {
+                    return None;
+                }
+                if kind == TlsReloc::Ld {
+                    self.target.relax_tlsld(data, &rel.erela, &call.erela)
+                } else if is_local {
+                    self.target
+                        .relax_tlsgd(data, &rel.erela, &call.erela, tpoff)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if the relocation is left to the dynamic linker. Absolute 64-bit
+    /// references to symbols in DSOs are resolved at load time, which needs neither a copy
+    /// of the object nor a canonical PLT entry. In a shared object, the load address is
+    /// added to the others.
+    fn needs_dynamic_reloc(&self, isec: &InputSection, rel: &ElfRela) -> bool {
+        let is_absolute = self
+            .target
+            .dynamic_reloc_types()
+            .is_some_and(|types| rel.erela.r_type == types.absolute);
+        if !is_absolute || isec.elf_section.header.sh_flags & SHF_ALLOC as u64 == 0 {
+            return false;
+        }
+        let symbol = self.get_canonical_symbol(&rel.symbol);
+        let symbol = symbol.read();
+        self.is_dynamic_symbol(&symbol) || self.depends_on_load_address(&symbol)
+    }
+
+    /// Returns the relocations which are emitted to .rela.dyn as they are
+    fn get_dynamic_relocs(&self) -> impl Iterator<Item = (&InputSection, &ElfRela)> {
+        self.ctx
+            .files()
+            .flat_map(|file| file.get_input_sections().iter().flatten())
+            .map(|isec_id| self.ctx.get_input_section(*isec_id))
+            .flat_map(|isec| isec.get_relas().iter().map(move |rel| (isec, rel)))
+            .filter(|(isec, rel)| self.needs_dynamic_reloc(isec, rel))
+    }
+
+    /// Returns true if the output section of the input section is writable
+    fn is_writable(&self, isec: &InputSection) -> bool {
+        let osec = self
+            .get_common_from_osec(isec.get_output_section())
+            .unwrap();
+        osec.shdr.sh_flags & SHF_WRITE as u64 != 0
+    }
+
+    fn is_defined_in_dso(&self, symbol: &Symbol) -> bool {
+        symbol
+            .file
+            .is_some_and(|file| self.ctx.get_file(file).is_dso())
+    }
+
+    fn get_global_symbol_addr(&self, name: &str) -> Option<u64> {
+        let name = self.ctx.intern(name);
+        self.ctx
+            .get_global_symbol(name)
+            .map(|symbol| {
+                let symbol = symbol.read();
+                self.get_symbol_addr(&symbol).unwrap_or(0)
+            })
+            .or_else(|| self.script_symbols.get(&name).map(|symbol| symbol.value))
+    }
+
+    /// Returns the values written by the relocations of an input section, at offsets from
+    /// the start of the section
+    fn get_relocation_data(&self, isec_id: InputSectionId) -> Vec<RelValue> {
         let mut ret = Vec::new();
-        for file in self.ctx.files() {
-            for isec_id in file.get_input_sections() {
-                if let Some(isec_id) = isec_id {
-                    let isec_addr = self.get_isec_addr(*isec_id);
-                    let isec = self.ctx.get_input_section(*isec_id);
-                    for rel in isec.get_relas() {
-                        let symbol = rel.symbol.deref().borrow();
-                        log::debug!("Relocation: {:?}", symbol.name);
-                        let symbol_addr = self.get_symbol_addr(&symbol).unwrap();
-                        if let Some(value) = relocation_value(symbol_addr, isec_addr, &rel.erela) {
-                            let isec_file_ofs = isec.get_offset().unwrap();
-                            let file_ofs = (isec_file_ofs + rel.erela.r_offset) as usize;
-                            ret.push(RelValue {
-                                file_ofs,
-                                value,
-                                size: relocation_size(&rel.erela),
-                            });
-                        }
-                    }
+        let isec = self.ctx.get_input_section(isec_id);
+        if isec.get_relas().is_empty() {
+            return ret;
+        }
+        let file = self.ctx.get_file(isec.get_file());
+        let tp_offset = self.tls_template.map_or(0, |tls| tls.tp_offset());
+        let got_addr = self.get_got_base();
+        let isec_addr = self.get_isec_addr(isec_id);
+        let is_code = isec.elf_section.header.sh_flags & SHF_EXECINSTR as u64 != 0;
+        let pcrel_hi20_values = self.get_pcrel_hi20_values(isec, isec_addr);
+        let mut is_call_relaxed = false;
+        for (i, rel) in isec.get_relas().iter().enumerate() {
+            if std::mem::take(&mut is_call_relaxed) {
+                continue;
+            }
+            // The dynamic linker writes the value
+            if self.needs_dynamic_reloc(isec, rel) {
+                continue;
+            }
+            // A copy, so that no lock is held while other symbols are looked up
+            let symbol = rel.symbol.read().clone();
+            log::debug!("Relocation: {:?}", symbol.name);
+            let Some(symbol_addr) = self
+                .get_section_symbol_addr(&symbol, rel.erela.r_addend)
+                .or_else(|| self.get_symbol_addr(&symbol))
+            else {
+                // Non-allocated sections such as debug info may refer to discarded code
+                if isec.elf_section.header.sh_flags & SHF_ALLOC as u64 != 0 {
+                    self.ctx.diag.error(
+                        Diagnostic::new(format!(
+                            "relocation {} refers to a symbol in a discarded section",
+                            self.target.r_type_as_str(rel.erela.r_type)
+                        ))
+                        .at(
+                            file.get_file_name(),
+                            isec.get_name(),
+                            rel.erela.r_offset,
+                        ),
+                    );
+                }
+                continue;
+            };
+            let r_offset = rel.erela.r_offset as usize;
+            let tls_reloc = self.target.tls_reloc(rel.erela.r_type);
+            if let Some(kind) = tls_reloc {
+                let tpoff = self.get_tpoff(&symbol);
+                if let Some((start, insns)) = self.relax_tls(isec, i, kind, tpoff, isec_addr) {
+                    push_bytes(&mut ret, start, &insns);
+                    // The call to __tls_get_addr is rewritten too
+                    is_call_relaxed = matches!(kind, TlsReloc::Gd | TlsReloc::Ld);
+                    continue;
+                }
+                // TLS descriptors are not supported, and LdOffset relocations in code rely
+                // on all local-dynamic sequences being relaxed
+                let must_relax = match kind {
+                    TlsReloc::Desc | TlsReloc::DescCall => !self.config.shared,
+                    TlsReloc::Ld => self.can_relax_tls(),
+                    _ => false,
+                };
+                if must_relax {
+                    self.ctx.diag.error(
+                        Diagnostic::new(format!(
+                            "cannot relax {}: unexpected instructions",
+                            self.target.r_type_as_str(rel.erela.r_type)
+                        ))
+                        .at(
+                            file.get_file_name(),
+                            isec.get_name(),
+                            rel.erela.r_offset,
+                        ),
+                    );
+                    continue;
+                }
+            }
+            if let Some(insn) = self.relax_gotpcrelx(isec, rel) {
+                let insn_ofs = rel.erela.r_offset - insn.start as u64;
+                let bytes = insn.encode(symbol_addr, rel.erela.r_addend, isec_addr + insn_ofs);
+                push_bytes(&mut ret, insn_ofs as usize, &bytes);
+                continue;
+            }
+            // After TLSLD is relaxed, code adds DTPOFF32 to the thread pointer instead
+            // of the address of the TLS block. Like GNU ld, DTPOFF64 and data such as
+            // debug info keep referring to the TLS block.
+            let dtp_offset =
+                if tls_reloc == Some(TlsReloc::LdOffset) && is_code && self.can_relax_tls() {
+                    tp_offset
+                } else {
+                    0
+                };
+            let input = RelocationInput {
+                symbol_addr,
+                // Common symbols are defined with their merged size
+                symbol_size: symbol.get_def_esym().get_esym().st_size,
+                isec_addr,
+                got_entry_addr: self.get_got_entry_addr(&rel.symbol, rel.erela.r_type),
+                plt_entry_addr: self.get_plt_entry_addr(&symbol),
+                got_addr,
+                tp_offset,
+                dtp_offset,
+                pcrel_hi20_value: pcrel_hi20_values.get(&symbol_addr).copied(),
+            };
+            let value = self
+                .target
+                .relocation_value(&input, &rel.erela)
+                .and_then(|value| {
+                    Ok(value.zip(Some(self.target.relocation_size(rel.erela.r_type)?)))
+                });
+            let (value, size) = match value {
+                Ok(Some(value)) => value,
+                Ok(None) => continue,
+                Err(msg) => {
+                    self.ctx.diag.error(Diagnostic::new(msg).at(
+                        file.get_file_name(),
+                        isec.get_name(),
+                        rel.erela.r_offset,
+                    ));
+                    continue;
                 }
+            };
+            if let Some((min, max)) = self.target.relocation_range(rel.erela.r_type) {
+                let value = value as i64;
+                if value < min || max < value {
+                    self.ctx.diag.error(
+                        Diagnostic::new(format!(
+                            "relocation {} out of range: symbol '{}' ({:#x}): {} is not in \
+                             [{}, {}]",
+                            self.target.r_type_as_str(rel.erela.r_type),
+                            symbol.name,
+                            symbol_addr,
+                            value,
+                            min,
+                            max
+                        ))
+                        .at(file.get_file_name(), isec.get_name(), rel.erela.r_offset)
+                        .symbol(symbol.name),
+                    );
+                }
+            }
+            // On RISC-V, relocations at the same offset apply to the result of the previous
+            // one, e.g. ADD32 and SUB32 for the difference of two labels
+            let prev = ret
+                .last()
+                .filter(|prev| {
+                    self.target.composes_relocations()
+                        && prev.offset == r_offset
+                        && prev.size == size
+                })
+                .map(|prev| prev.value.to_le_bytes());
+            if prev.is_some() {
+                ret.pop();
             }
+            let field = match &prev {
+                Some(prev) => Some(&prev[..size]),
+                None => isec.elf_section.data().get(r_offset..r_offset + size),
+            };
+            let value = match field {
+                Some(field) => self.target.apply_relocation(rel.erela.r_type, value, field),
+                // Reported by add_relocation_data
+                None => value,
+            };
+            ret.push(RelValue {
+                offset: r_offset,
+                value,
+                size,
+            });
         }
         ret
     }
 }
+
+/// Write rewritten instructions as relocations of up to 8 bytes
+fn push_bytes(ret: &mut Vec<RelValue>, offset: usize, bytes: &[u8]) {
+    for (i, chunk) in bytes.chunks(8).enumerate() {
+        let mut value = [0; 8];
+        value[..chunk.len()].copy_from_slice(chunk);
+        ret.push(RelValue {
+            offset: offset + i * 8,
+            value: u64::from_le_bytes(value),
+            size: chunk.len(),
+        });
+    }
+}
+
+fn to_phdr_flags(shdr: &Elf64_Shdr) -> u32 {
+    let mut ret = PF_R;
+    if shdr.sh_flags & SHF_WRITE as u64 != 0 {
+        ret |= PF_W;
+    }
+    if shdr.sh_flags & SHF_EXECINSTR as u64 != 0 {
+        ret |= PF_X;
+    }
+    ret
+}
+
+/// Returns a segment covering exactly the chunk
+fn to_phdr(p_type: u32, p_flags: u32, shdr: &Elf64_Shdr) -> Elf64_Phdr {
+    Elf64_Phdr {
+        p_type,
+        p_flags,
+        p_offset: shdr.sh_offset,
+        p_vaddr: shdr.sh_addr,
+        p_paddr: shdr.sh_addr,
+        p_filesz: shdr.sh_size,
+        p_memsz: shdr.sh_size,
+        p_align: shdr.sh_addralign,
+    }
+}
@@ -0,0 +1,728 @@
+//! Linker scripts given by -T. Only the core of SECTIONS is supported: output section
+//! descriptions which select input sections by glob, assignments to the location counter
+//! and symbols, PROVIDE(), PROVIDE_HIDDEN() and ALIGN(), as well as ENTRY(). Other commands
+//! are rejected with the line they are on.
+//!
+//! Version scripts given by --version-script share the syntax of globs. They consist of
+//! version nodes listing the symbols which are exported with each version and those which
+//! are hidden. `extern "C++"` blocks are not supported.
+
+use std::ops::Range;
+
+#[derive(Debug)]
+pub struct LinkerScript {
+    /// Commands in SECTIONS in order
+    pub commands: Vec<SectionsCommand>,
+    /// Entry symbol or address given by ENTRY()
+    pub entry: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum SectionsCommand {
+    /// `. = expr;`
+    SetLocation(Expr),
+    Assign(Assignment),
+    OutputSection(OutputSectionDesc),
+}
+
+/// `symbol = expr;`, `PROVIDE(symbol = expr);` or `PROVIDE_HIDDEN(symbol = expr);`
+#[derive(Debug)]
+pub struct Assignment {
+    pub symbol: String,
+    pub expr: Expr,
+    /// The symbol is only defined if it is referenced and no input file defines it
+    pub provide: bool,
+    pub hidden: bool,
+}
+
+/// `name [addr] : [ALIGN(align)] { inputs }`
+#[derive(Debug)]
+pub struct OutputSectionDesc {
+    pub name: String,
+    pub addr: Option<Expr>,
+    pub align: Option<Expr>,
+    pub inputs: Vec<InputSectionDesc>,
+    /// Symbol assignments with the number of input section descriptions before each
+    pub assignments: Vec<(usize, Assignment)>,
+}
+
+/// `file(sections...)`, which selects the input sections whose file and section names
+/// match the globs
+#[derive(Debug)]
+pub struct InputSectionDesc {
+    pub file: String,
+    pub sections: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum Expr {
+    Number(u64),
+    /// The location counter
+    Dot,
+    /// Value of a symbol, which is known only after the layout
+    Symbol(String),
+    /// ALIGN(align) aligns the location counter, and ALIGN(expr, align) the expression
+    Align(Box<Expr>, Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Returns the value of the expression with the location counter at `dot`. `symbol`
+    /// returns the value of a symbol.
+    pub fn eval(&self, dot: u64, symbol: &dyn Fn(&str) -> u64) -> u64 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Dot => dot,
+            Expr::Symbol(name) => symbol(name),
+            Expr::Align(expr, align) => align_up(expr.eval(dot, symbol), align.eval(dot, symbol)),
+            Expr::Add(lhs, rhs) => lhs.eval(dot, symbol).wrapping_add(rhs.eval(dot, symbol)),
+            Expr::Sub(lhs, rhs) => lhs.eval(dot, symbol).wrapping_sub(rhs.eval(dot, symbol)),
+            Expr::Mul(lhs, rhs) => lhs.eval(dot, symbol).wrapping_mul(rhs.eval(dot, symbol)),
+        }
+    }
+
+    /// Returns the names of the symbols which the expression refers to
+    pub fn get_symbols(&self) -> Vec<&str> {
+        match self {
+            Expr::Number(_) | Expr::Dot => vec![],
+            Expr::Symbol(name) => vec![name.as_str()],
+            Expr::Align(lhs, rhs)
+            | Expr::Add(lhs, rhs)
+            | Expr::Sub(lhs, rhs)
+            | Expr::Mul(lhs, rhs) => {
+                let mut symbols = lhs.get_symbols();
+                symbols.extend(rhs.get_symbols());
+                symbols
+            }
+        }
+    }
+}
+
+/// Addresses are computed before symbols have values, so they can't refer to symbols
+fn no_symbol(name: &str) -> u64 {
+    unreachable!("{} in an address is rejected by the parser", name)
+}
+
+/// Version script assigning versions to the symbols exported from a shared object
+#[derive(Debug, Default)]
+pub struct VersionScript {
+    /// Version nodes in order. An anonymous node is the only one.
+    pub versions: Vec<VersionNode>,
+}
+
+/// `name { global: globs; local: globs; } parents;`, or `{ ... };` for the anonymous node,
+/// which hides symbols without versioning the others
+#[derive(Debug)]
+pub struct VersionNode {
+    /// None for the anonymous node
+    pub name: Option<String>,
+    /// Versions which this one inherits from
+    pub parents: Vec<String>,
+    /// Globs of the symbols exported with this version
+    pub global: Vec<String>,
+    /// Globs of the symbols which are not exported
+    pub local: Vec<String>,
+}
+
+/// Version assigned to a symbol by a version script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolVersion {
+    /// The symbol matches a `local:` glob, so it is not exported
+    Local,
+    /// Index in `versions` of the node with the `global:` glob which the symbol matches
+    Global(usize),
+}
+
+impl VersionScript {
+    /// Parse a version script. Errors have the line number where parsing stopped.
+    pub fn parse(script: &str) -> Result<VersionScript, String> {
+        let mut parser = Parser {
+            src: script,
+            pos: 0,
+        };
+        let mut script = VersionScript::default();
+        parser
+            .parse_version_script(&mut script)
+            .map_err(|msg| format!("line {}: {}", parser.line(), msg))?;
+        Ok(script)
+    }
+
+    /// Returns the version of a symbol, or None if no glob matches it. Like GNU ld, names
+    /// without wildcards take precedence over globs, which take precedence over `*`. Among
+    /// matches of the same kind, `global:` wins.
+    pub fn find_version(&self, name: &str) -> Option<SymbolVersion> {
+        let precedence = |glob: &str| match glob {
+            "*" => 2,
+            _ if glob.contains(['*', '?', '[']) => 1,
+            _ => 0,
+        };
+        (0..3).find_map(|level| {
+            let matches = |globs: &[String]| {
+                globs
+                    .iter()
+                    .any(|glob| precedence(glob) == level && glob_match(glob, name))
+            };
+            self.versions
+                .iter()
+                .position(|node| matches(&node.global))
+                .map(SymbolVersion::Global)
+                .or_else(|| {
+                    let is_local = self.versions.iter().any(|node| matches(&node.local));
+                    is_local.then_some(SymbolVersion::Local)
+                })
+        })
+    }
+}
+
+/// Round `value` up to a multiple of `align`, which unlike section alignments doesn't have
+/// to be a power of two
+fn align_up(value: u64, align: u64) -> u64 {
+    match align {
+        0 => value,
+        align => value.div_ceil(align) * align,
+    }
+}
+
+impl LinkerScript {
+    /// Parse a script. Errors have the line number where parsing stopped.
+    pub fn parse(script: &str) -> Result<LinkerScript, String> {
+        let mut parser = Parser {
+            src: script,
+            pos: 0,
+        };
+        let mut script = LinkerScript {
+            commands: vec![],
+            entry: None,
+        };
+        parser
+            .parse_script(&mut script)
+            .map_err(|msg| format!("line {}: {}", parser.line(), msg))?;
+        Ok(script)
+    }
+
+    pub fn output_sections(&self) -> impl Iterator<Item = (usize, &OutputSectionDesc)> {
+        self.commands
+            .iter()
+            .enumerate()
+            .filter_map(|(i, command)| match command {
+                SectionsCommand::OutputSection(osec) => Some((i, osec)),
+                _ => None,
+            })
+    }
+
+    /// Returns the symbol assignments in order with the output section they are in
+    pub fn assignments(&self) -> impl Iterator<Item = (Option<&OutputSectionDesc>, &Assignment)> {
+        self.commands.iter().flat_map(|command| match command {
+            SectionsCommand::Assign(assignment) => vec![(None, assignment)],
+            SectionsCommand::OutputSection(osec) => osec
+                .assignments
+                .iter()
+                .map(|(_, assignment)| (Some(osec), assignment))
+                .collect(),
+            SectionsCommand::SetLocation(_) => vec![],
+        })
+    }
+
+    /// Returns the output section which the first matching input section description puts
+    /// the input section into, and the position of the description in the script, which
+    /// orders the inputs of the output section
+    pub fn find_output_section(
+        &self,
+        file_name: &str,
+        section_name: &str,
+    ) -> Option<(&str, usize)> {
+        self.output_sections()
+            .flat_map(|(_, osec)| osec.inputs.iter().map(move |input| (osec, input)))
+            .enumerate()
+            .find(|(_, (_, input))| {
+                glob_match(&input.file, file_name)
+                    && input
+                        .sections
+                        .iter()
+                        .any(|pattern| glob_match(pattern, section_name))
+            })
+            .map(|(pos, (osec, _))| (osec.name.as_str(), pos))
+    }
+
+    /// Returns the index of the command describing the output section
+    pub fn get_command_index(&self, name: &str) -> Option<usize> {
+        self.output_sections()
+            .find(|(_, osec)| osec.name == name)
+            .map(|(i, _)| i)
+    }
+
+    /// Returns true if `commands` set the address of the output section they end with
+    /// instead of letting it follow the previous section
+    pub fn sets_address(&self, commands: Range<usize>) -> bool {
+        self.commands[commands].iter().any(|command| match command {
+            SectionsCommand::SetLocation(_) => true,
+            SectionsCommand::Assign(_) => false,
+            SectionsCommand::OutputSection(osec) => osec.addr.is_some(),
+        })
+    }
+
+    /// Run `commands`, which end with an output section, with the location counter at `dot`
+    /// and return the address of the section
+    pub fn get_address(&self, commands: Range<usize>, mut dot: u64) -> u64 {
+        for command in &self.commands[commands] {
+            match command {
+                SectionsCommand::SetLocation(expr) => dot = expr.eval(dot, &no_symbol),
+                SectionsCommand::Assign(_) => (),
+                SectionsCommand::OutputSection(osec) => {
+                    if let Some(addr) = &osec.addr {
+                        dot = addr.eval(dot, &no_symbol);
+                    }
+                    if let Some(align) = &osec.align {
+                        dot = align_up(dot, align.eval(dot, &no_symbol));
+                    }
+                }
+            }
+        }
+        dot
+    }
+}
+
+/// Returns true if `name` matches `pattern`, in which `*` matches any string, `?` any
+/// character and `[...]` a set of characters
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    // Position to retry from when a `*` has to match one more character
+    let mut backtrack = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => {
+                p += 1;
+                n += 1;
+                continue;
+            }
+            Some(b'[') => {
+                if let Some((matched, len)) = match_class(&pattern[p..], name[n]) {
+                    if matched {
+                        p += len;
+                        n += 1;
+                        continue;
+                    }
+                } else if name[n] == b'[' {
+                    p += 1;
+                    n += 1;
+                    continue;
+                }
+            }
+            Some(&c) if c == name[n] => {
+                p += 1;
+                n += 1;
+                continue;
+            }
+            _ => {}
+        }
+        let Some((star, matched)) = backtrack else {
+            return false;
+        };
+        backtrack = Some((star, matched + 1));
+        p = star + 1;
+        n = matched + 1;
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Match `c` against the class at the start of `pattern`, e.g. `[a-z]` or `[!0-9]`. Returns
+/// whether it matched and the length of the class, or None if the class is not closed.
+fn match_class(pattern: &[u8], c: u8) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some(b'!' | b'^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let lo = *pattern.get(i)?;
+        if lo == b']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        if pattern.get(i + 1) == Some(&b'-') && pattern.get(i + 2).is_some_and(|&hi| hi != b']') {
+            matched |= (lo..=pattern[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= lo == c;
+            i += 1;
+        }
+    }
+}
+
+/// Characters of globs in input section descriptions besides those of words
+const GLOB_CHARS: &str = "*?[]!^-$/+~";
+
+/// Characters of globs of symbol names in version scripts besides those of words
+const SYMBOL_GLOB_CHARS: &str = "*?[]!^-$";
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn line(&self) -> usize {
+        self.src[..self.pos].matches('\n').count() + 1
+    }
+
+    /// Skip whitespace and comments, and return the next character
+    fn peek(&mut self) -> Option<char> {
+        loop {
+            let rest = &self.src[self.pos..];
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if !trimmed.starts_with("/*") {
+                return trimmed.chars().next();
+            }
+            match trimmed[2..].find("*/") {
+                Some(end) => self.pos += end + 4,
+                None => self.pos = self.src.len(),
+            }
+        }
+    }
+
+    /// Read the longest word of alphanumeric characters, `_`, `.` and `extra`
+    fn word(&mut self, extra: &str) -> &'a str {
+        self.peek();
+        let rest = &self.src[self.pos..];
+        let len = rest
+            .find(|c: char| {
+                !(c.is_ascii_alphanumeric() || c == '_' || c == '.' || extra.contains(c))
+            })
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn consume(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.consume(c) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(found) => Err(format!("expected '{}', found '{}'", c, found)),
+            None => Err(format!("expected '{}', found end of file", c)),
+        }
+    }
+
+    fn unexpected(&mut self) -> String {
+        match self.peek() {
+            Some(c) => format!("unexpected '{}'", c),
+            None => "unexpected end of file".to_owned(),
+        }
+    }
+
+    fn parse_script(&mut self, script: &mut LinkerScript) -> Result<(), String> {
+        while self.peek().is_some() {
+            match self.word("") {
+                "SECTIONS" => {
+                    self.expect('{')?;
+                    while !self.consume('}') {
+                        script.commands.push(self.parse_sections_command()?);
+                    }
+                }
+                "ENTRY" => {
+                    self.expect('(')?;
+                    let entry = self.word("$");
+                    if entry.is_empty() {
+                        return Err(self.unexpected());
+                    }
+                    self.expect(')')?;
+                    self.consume(';');
+                    script.entry = Some(entry.to_owned());
+                }
+                "" => return Err(self.unexpected()),
+                command => return Err(format!("unsupported command: {}", command)),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_version_script(&mut self, script: &mut VersionScript) -> Result<(), String> {
+        while self.peek().is_some() {
+            let name = match self.peek() {
+                Some('{') => None,
+                _ => match self.word("") {
+                    "" => return Err(self.unexpected()),
+                    name => Some(name.to_owned()),
+                },
+            };
+            let has_anonymous = script.versions.iter().any(|node| node.name.is_none());
+            if has_anonymous || (name.is_none() && !script.versions.is_empty()) {
+                return Err("anonymous version cannot be combined with other versions".to_owned());
+            }
+            if let Some(name) = &name {
+                if script
+                    .versions
+                    .iter()
+                    .any(|node| node.name.as_ref() == Some(name))
+                {
+                    return Err(format!("duplicate version: {}", name));
+                }
+            }
+            let mut node = VersionNode {
+                name,
+                parents: vec![],
+                global: vec![],
+                local: vec![],
+            };
+            self.expect('{')?;
+            // Symbols before `global:` and `local:` are global
+            let mut is_global = true;
+            while !self.consume('}') {
+                let word = self.word(SYMBOL_GLOB_CHARS);
+                match (word, self.peek()) {
+                    ("", _) => return Err(self.unexpected()),
+                    ("global" | "local", Some(':')) => {
+                        self.expect(':')?;
+                        is_global = word == "global";
+                    }
+                    ("extern", _) => return Err("unsupported extern in a version".to_owned()),
+                    (glob, _) => {
+                        self.expect(';')?;
+                        let globs = if is_global {
+                            &mut node.global
+                        } else {
+                            &mut node.local
+                        };
+                        globs.push(glob.to_owned());
+                    }
+                }
+            }
+            while !self.consume(';') {
+                let parent = self.word("");
+                if parent.is_empty() || node.name.is_none() {
+                    return Err(self.unexpected());
+                }
+                // Versions can only inherit from those defined before them
+                if !script
+                    .versions
+                    .iter()
+                    .any(|node| node.name.as_deref() == Some(parent))
+                {
+                    return Err(format!("undefined version: {}", parent));
+                }
+                node.parents.push(parent.to_owned());
+            }
+            script.versions.push(node);
+        }
+        Ok(())
+    }
+
+    fn parse_sections_command(&mut self) -> Result<SectionsCommand, String> {
+        let name = self.word("$-/");
+        if name.is_empty() {
+            return Err(self.unexpected());
+        }
+        if name == "." {
+            self.expect('=')?;
+            let expr = self.parse_address()?;
+            self.expect(';')?;
+            return Ok(SectionsCommand::SetLocation(expr));
+        }
+        if let Some(assignment) = self.parse_assignment(name)? {
+            return Ok(SectionsCommand::Assign(assignment));
+        }
+        if self.peek() == Some('(') {
+            return Err(format!("unsupported command: {}", name));
+        }
+        if name == "/DISCARD/" {
+            return Err("unsupported output section: /DISCARD/".to_owned());
+        }
+        let addr = match self.peek() {
+            Some(':') => None,
+            _ => Some(self.parse_address()?),
+        };
+        self.expect(':')?;
+        let align = match self.peek() {
+            Some('{') => None,
+            _ => match self.word("") {
+                "ALIGN" => {
+                    self.expect('(')?;
+                    let align = self.parse_address()?;
+                    self.expect(')')?;
+                    Some(align)
+                }
+                "" => None,
+                attr => return Err(format!("unsupported output section attribute: {}", attr)),
+            },
+        };
+        self.expect('{')?;
+        let mut inputs = vec![];
+        let mut assignments = vec![];
+        while !self.consume('}') {
+            let word = self.word(GLOB_CHARS);
+            if word == "." && self.peek() == Some('=') {
+                return Err("unsupported assignment to . in an output section".to_owned());
+            }
+            match self.parse_assignment(word)? {
+                Some(assignment) => assignments.push((inputs.len(), assignment)),
+                None => inputs.push(self.parse_input_section_desc(word)?),
+            }
+        }
+        Ok(SectionsCommand::OutputSection(OutputSectionDesc {
+            name: name.to_owned(),
+            addr,
+            align,
+            inputs,
+            assignments,
+        }))
+    }
+
+    /// Parse the rest of a symbol assignment starting with `name`, which is the symbol or
+    /// PROVIDE(_HIDDEN). Returns None if `name` doesn't start an assignment.
+    fn parse_assignment(&mut self, name: &str) -> Result<Option<Assignment>, String> {
+        let (symbol, provide, hidden) = match (name, self.peek()) {
+            ("", _) => return Ok(None),
+            (_, Some('=')) => (name, false, false),
+            ("PROVIDE" | "PROVIDE_HIDDEN", Some('(')) => {
+                self.expect('(')?;
+                let symbol = self.word("$");
+                if symbol.is_empty() {
+                    return Err(self.unexpected());
+                }
+                (symbol, true, name == "PROVIDE_HIDDEN")
+            }
+            _ => return Ok(None),
+        };
+        if symbol == "." {
+            return Err(format!("unsupported assignment to . in {}", name));
+        }
+        self.expect('=')?;
+        let expr = self.parse_expr()?;
+        if provide {
+            self.expect(')')?;
+        }
+        self.expect(';')?;
+        Ok(Some(Assignment {
+            symbol: symbol.to_owned(),
+            expr,
+            provide,
+            hidden,
+        }))
+    }
+
+    /// Parse the input section description starting with `file`, which is the glob of
+    /// file names or KEEP
+    fn parse_input_section_desc(&mut self, file: &str) -> Result<InputSectionDesc, String> {
+        match (file, self.peek()) {
+            ("", _) => return Err(self.unexpected()),
+            // Sections are never garbage-collected, so they are always kept
+            ("KEEP", Some('(')) => {
+                self.expect('(')?;
+                let file = self.word(GLOB_CHARS);
+                let desc = self.parse_input_section_desc(file)?;
+                self.expect(')')?;
+                return Ok(desc);
+            }
+            ("SORT" | "SORT_BY_NAME" | "SORT_BY_ALIGNMENT" | "EXCLUDE_FILE", _) => {
+                return Err(format!("unsupported command: {}", file));
+            }
+            _ => {}
+        }
+        self.expect('(')?;
+        let mut sections = vec![];
+        while !self.consume(')') {
+            let pattern = self.word(GLOB_CHARS);
+            if pattern.is_empty() {
+                return Err(self.unexpected());
+            }
+            sections.push(pattern.to_owned());
+        }
+        Ok(InputSectionDesc {
+            file: file.to_owned(),
+            sections,
+        })
+    }
+
+    /// Parse an expression of an address, which is computed before symbols have values
+    fn parse_address(&mut self) -> Result<Expr, String> {
+        let expr = self.parse_expr()?;
+        match expr.get_symbols().first() {
+            Some(symbol) => Err(format!("unsupported symbol in an address: {}", symbol)),
+            None => Ok(expr),
+        }
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_term()?;
+        loop {
+            lhs = if self.consume('+') {
+                Expr::Add(Box::new(lhs), Box::new(self.parse_term()?))
+            } else if self.consume('-') {
+                Expr::Sub(Box::new(lhs), Box::new(self.parse_term()?))
+            } else {
+                return Ok(lhs);
+            };
+        }
+    }
+
+    fn parse_term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_factor()?;
+        while self.consume('*') {
+            lhs = Expr::Mul(Box::new(lhs), Box::new(self.parse_factor()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_factor(&mut self) -> Result<Expr, String> {
+        if self.consume('(') {
+            let expr = self.parse_expr()?;
+            self.expect(')')?;
+            return Ok(expr);
+        }
+        let word = self.word("");
+        match word {
+            "." => Ok(Expr::Dot),
+            "ALIGN" => {
+                self.expect('(')?;
+                let first = self.parse_expr()?;
+                let expr = if self.consume(',') {
+                    Expr::Align(Box::new(first), Box::new(self.parse_expr()?))
+                } else {
+                    Expr::Align(Box::new(Expr::Dot), Box::new(first))
+                };
+                self.expect(')')?;
+                Ok(expr)
+            }
+            "" => Err(self.unexpected()),
+            _ if word.starts_with(|c: char| c.is_ascii_digit()) => parse_number(word)
+                .map(Expr::Number)
+                .ok_or(format!("invalid number: {}", word)),
+            _ if self.peek() == Some('(') => Err(format!("unsupported expression: {}", word)),
+            _ => Ok(Expr::Symbol(word.to_owned())),
+        }
+    }
+}
+
+/// Parse a decimal or 0x-prefixed hexadecimal number, optionally followed by K or M
+fn parse_number(word: &str) -> Option<u64> {
+    let (digits, multiplier) = match word.as_bytes().last() {
+        Some(b'K' | b'k') => (&word[..word.len() - 1], 1024),
+        Some(b'M' | b'm') => (&word[..word.len() - 1], 1024 * 1024),
+        _ => (word, 1),
+    };
+    let value = match digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    value.checked_mul(multiplier)
+}
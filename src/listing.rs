@@ -0,0 +1,163 @@
+//! Human-readable listings of the output (--print-sections, --print-symbols)
+
+use std::fmt::{self, Display};
+
+use elf::abi::{
+    SHF_ALLOC, SHF_EXECINSTR, SHF_GROUP, SHF_INFO_LINK, SHF_LINK_ORDER, SHF_MERGE, SHF_STRINGS,
+    SHF_TLS, SHF_WRITE, SHT_DYNAMIC, SHT_DYNSYM, SHT_FINI_ARRAY, SHT_GNU_HASH, SHT_GNU_VERNEED,
+    SHT_GNU_VERSYM, SHT_HASH, SHT_INIT_ARRAY, SHT_NOBITS, SHT_NOTE, SHT_NULL, SHT_PREINIT_ARRAY,
+    SHT_PROGBITS, SHT_REL, SHT_RELA, SHT_STRTAB, SHT_SYMTAB, SHT_SYMTAB_SHNDX, STB_GLOBAL,
+    STB_GNU_UNIQUE, STB_LOCAL, STB_WEAK, STT_COMMON, STT_FILE, STT_FUNC, STT_GNU_IFUNC, STT_NOTYPE,
+    STT_OBJECT, STT_SECTION, STT_TLS,
+};
+
+/// Column of a table
+pub struct Column {
+    title: &'static str,
+    right: bool,
+}
+
+impl Column {
+    pub const fn left(title: &'static str) -> Column {
+        Column {
+            title,
+            right: false,
+        }
+    }
+
+    pub const fn right(title: &'static str) -> Column {
+        Column { title, right: true }
+    }
+}
+
+/// Table whose columns are as wide as their widest cell. Cells are separated by two
+/// spaces, and the last column is not padded.
+pub struct Table {
+    columns: &'static [Column],
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(columns: &'static [Column]) -> Table {
+        Table {
+            columns,
+            rows: vec![],
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let widths = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                self.rows
+                    .iter()
+                    .map(|row| row[i].len())
+                    .fold(column.title.len(), usize::max)
+            })
+            .collect::<Vec<_>>();
+        let header = self.columns.iter().map(|column| column.title.to_owned());
+        for row in std::iter::once(header.collect()).chain(self.rows.iter().cloned()) {
+            let mut line = String::new();
+            for (i, cell) in row.iter().enumerate() {
+                let last = i + 1 == row.len();
+                if i > 0 {
+                    line.push_str("  ");
+                }
+                if self.columns[i].right {
+                    line.push_str(&format!("{:>1$}", cell, widths[i]));
+                } else if last {
+                    line.push_str(cell);
+                } else {
+                    line.push_str(&format!("{:1$}", cell, widths[i]));
+                }
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Address, offset or size, which are written with a fixed width
+pub fn hex(value: u64) -> String {
+    format!("{:016x}", value)
+}
+
+pub fn sh_type_name(sh_type: u32) -> String {
+    let name = match sh_type {
+        SHT_NULL => "NULL",
+        SHT_PROGBITS => "PROGBITS",
+        SHT_SYMTAB => "SYMTAB",
+        SHT_STRTAB => "STRTAB",
+        SHT_RELA => "RELA",
+        SHT_HASH => "HASH",
+        SHT_DYNAMIC => "DYNAMIC",
+        SHT_NOTE => "NOTE",
+        SHT_NOBITS => "NOBITS",
+        SHT_REL => "REL",
+        SHT_DYNSYM => "DYNSYM",
+        SHT_INIT_ARRAY => "INIT_ARRAY",
+        SHT_FINI_ARRAY => "FINI_ARRAY",
+        SHT_PREINIT_ARRAY => "PREINIT_ARRAY",
+        SHT_SYMTAB_SHNDX => "SYMTAB_SHNDX",
+        SHT_GNU_HASH => "GNU_HASH",
+        SHT_GNU_VERNEED => "VERNEED",
+        SHT_GNU_VERSYM => "VERSYM",
+        _ => return format!("{:#x}", sh_type),
+    };
+    name.to_owned()
+}
+
+/// Flags in the letters used by readelf
+pub fn sh_flags_string(sh_flags: u64) -> String {
+    const LETTERS: [(u32, char); 9] = [
+        (SHF_WRITE, 'W'),
+        (SHF_ALLOC, 'A'),
+        (SHF_EXECINSTR, 'X'),
+        (SHF_MERGE, 'M'),
+        (SHF_STRINGS, 'S'),
+        (SHF_INFO_LINK, 'I'),
+        (SHF_LINK_ORDER, 'L'),
+        (SHF_GROUP, 'G'),
+        (SHF_TLS, 'T'),
+    ];
+    LETTERS
+        .iter()
+        .filter(|(flag, _)| sh_flags & *flag as u64 != 0)
+        .map(|(_, letter)| *letter)
+        .collect()
+}
+
+pub fn st_type_name(st_type: u8) -> String {
+    let name = match st_type {
+        STT_NOTYPE => "NOTYPE",
+        STT_OBJECT => "OBJECT",
+        STT_FUNC => "FUNC",
+        STT_SECTION => "SECTION",
+        STT_FILE => "FILE",
+        STT_COMMON => "COMMON",
+        STT_TLS => "TLS",
+        STT_GNU_IFUNC => "IFUNC",
+        _ => return st_type.to_string(),
+    };
+    name.to_owned()
+}
+
+pub fn st_bind_name(st_bind: u8) -> String {
+    let name = match st_bind {
+        STB_LOCAL => "LOCAL",
+        STB_GLOBAL => "GLOBAL",
+        STB_WEAK => "WEAK",
+        STB_GNU_UNIQUE => "UNIQUE",
+        _ => return st_bind.to_string(),
+    };
+    name.to_owned()
+}
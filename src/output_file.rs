@@ -0,0 +1,74 @@
+use std::{fs::File, io::Write, path::Path};
+
+use memmap2::MmapMut;
+
+/// Output file which chunks are written to in place
+pub enum OutputFile {
+    /// The file is mapped into memory and written back by the kernel
+    Mmap(MmapMut),
+    /// Fallback for files which can't be mapped. The buffer is written out on commit.
+    Buffer { file: File, buf: Vec<u8> },
+    /// Output which is returned to the caller instead of being written to a file
+    Memory(Vec<u8>),
+}
+
+impl OutputFile {
+    /// Create a new file, failing if it exists. The output is written to a temporary file
+    /// which is renamed to the destination once it is complete.
+    pub fn create(path: &Path, filesize: u64) -> std::io::Result<OutputFile> {
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        match Self::map(&file, filesize) {
+            Ok(mmap) => Ok(OutputFile::Mmap(mmap)),
+            Err(err) => {
+                log::debug!("Failed to map the output file: {}", err);
+                Ok(OutputFile::Buffer {
+                    file,
+                    buf: vec![0; filesize as usize],
+                })
+            }
+        }
+    }
+
+    pub fn in_memory(filesize: u64) -> OutputFile {
+        OutputFile::Memory(vec![0; filesize as usize])
+    }
+
+    fn map(file: &File, filesize: u64) -> std::io::Result<MmapMut> {
+        file.set_len(filesize)?;
+        // SAFETY: The file was just created by us and is not modified by anything else
+        // while it is mapped
+        unsafe { MmapMut::map_mut(file) }
+    }
+
+    pub fn buf_mut(&mut self) -> &mut [u8] {
+        match self {
+            OutputFile::Mmap(mmap) => mmap,
+            OutputFile::Buffer { buf, .. } | OutputFile::Memory(buf) => buf,
+        }
+    }
+
+    /// Returns the contents of an output kept in memory
+    pub fn into_vec(self) -> Option<Vec<u8>> {
+        match self {
+            OutputFile::Memory(buf) => Some(buf),
+            _ => None,
+        }
+    }
+
+    /// Flush the contents to the file
+    pub fn commit(self) -> std::io::Result<()> {
+        match self {
+            OutputFile::Mmap(mmap) => mmap.flush(),
+            OutputFile::Buffer { mut file, buf } => {
+                // The file was extended by a failed mapping attempt
+                file.set_len(0)?;
+                file.write_all(&buf)
+            }
+            OutputFile::Memory(_) => Ok(()),
+        }
+    }
+}
@@ -1,16 +1,29 @@
 use elf::{
-    abi::{SHF_ALLOC, SHT_STRTAB},
+    abi::{
+        SHF_ALLOC, SHF_COMPRESSED, SHF_EXECINSTR, SHF_GROUP, SHF_INFO_LINK, SHF_MERGE, SHF_STRINGS,
+        SHF_TLS, SHF_WRITE, SHT_DYNAMIC, SHT_DYNSYM, SHT_FINI_ARRAY, SHT_GNU_HASH, SHT_GNU_VERDEF,
+        SHT_GNU_VERNEED, SHT_GNU_VERSYM, SHT_INIT_ARRAY, SHT_NOBITS, SHT_PREINIT_ARRAY,
+        SHT_PROGBITS, SHT_RELA, SHT_STRTAB, VER_DEF_CURRENT, VER_FLG_BASE, VER_NEED_CURRENT,
+    },
     file::Elf64_Ehdr,
+    relocation::Elf64_Rela,
     section::Elf64_Shdr,
     segment::Elf64_Phdr,
     symbol::Elf64_Sym,
 };
 
+use std::sync::{atomic::Ordering, Arc};
+
 use crate::{
-    context::{Context, COMMON_SECTION_NAMES},
-    dummy,
-    input_section::InputSectionId,
-    utils::write_to,
+    context::{Context, IdSpace, COMMON_SECTION_NAMES},
+    diagnostics::Diagnostics,
+    dummy, eh_frame,
+    elf_class::ElfClass,
+    input_section::{InputSectionId, SymbolCell},
+    relocation::RelValue,
+    string_table::StringTable,
+    target::Target,
+    utils::{align_to, write_to, SHN_LORESERVE},
 };
 
 pub enum OutputChunk {
@@ -20,7 +33,23 @@ pub enum OutputChunk {
     Section(OutputSectionRef),
     Strtab(Strtab),
     Symtab(Symtab),
+    SymtabShndx(SymtabShndx),
     Shstrtab(Shstrtab),
+    EhFrameHdr(EhFrameHdr),
+    Got(Got),
+    CopyRel(CopyRel),
+    Plt(Plt),
+    GotPlt(GotPlt),
+    Interp(Interp),
+    DynStr(DynStr),
+    Dynamic(Dynamic),
+    DynSym(DynSym),
+    GnuHash(GnuHash),
+    RelaDyn(DynRela),
+    RelaPlt(DynRela),
+    Versym(Versym),
+    Verdef(Verdef),
+    Verneed(Verneed),
 }
 
 impl OutputChunk {
@@ -32,11 +61,27 @@ impl OutputChunk {
             OutputChunk::Section(chunk) => &chunk.common,
             OutputChunk::Strtab(chunk) => &chunk.common,
             OutputChunk::Symtab(chunk) => &chunk.common,
+            OutputChunk::SymtabShndx(chunk) => &chunk.common,
             OutputChunk::Shstrtab(chunk) => &chunk.common,
+            OutputChunk::EhFrameHdr(chunk) => &chunk.common,
+            OutputChunk::Got(chunk) => &chunk.common,
+            OutputChunk::CopyRel(chunk) => &chunk.common,
+            OutputChunk::Plt(chunk) => &chunk.common,
+            OutputChunk::GotPlt(chunk) => &chunk.common,
+            OutputChunk::Interp(chunk) => &chunk.common,
+            OutputChunk::DynStr(chunk) => &chunk.common,
+            OutputChunk::Dynamic(chunk) => &chunk.common,
+            OutputChunk::DynSym(chunk) => &chunk.common,
+            OutputChunk::GnuHash(chunk) => &chunk.common,
+            OutputChunk::RelaDyn(chunk) => &chunk.common,
+            OutputChunk::RelaPlt(chunk) => &chunk.common,
+            OutputChunk::Versym(chunk) => &chunk.common,
+            OutputChunk::Verdef(chunk) => &chunk.common,
+            OutputChunk::Verneed(chunk) => &chunk.common,
         }
     }
 
-    pub fn get_common_mut<'a>(&'a mut self) -> &'a mut ChunkInfo {
+    pub fn get_common_mut(&mut self) -> &mut ChunkInfo {
         match self {
             OutputChunk::Ehdr(chunk) => &mut chunk.common,
             OutputChunk::Shdr(chunk) => &mut chunk.common,
@@ -44,7 +89,23 @@ impl OutputChunk {
             OutputChunk::Section(chunk) => &mut chunk.common,
             OutputChunk::Strtab(chunk) => &mut chunk.common,
             OutputChunk::Symtab(chunk) => &mut chunk.common,
+            OutputChunk::SymtabShndx(chunk) => &mut chunk.common,
             OutputChunk::Shstrtab(chunk) => &mut chunk.common,
+            OutputChunk::EhFrameHdr(chunk) => &mut chunk.common,
+            OutputChunk::Got(chunk) => &mut chunk.common,
+            OutputChunk::CopyRel(chunk) => &mut chunk.common,
+            OutputChunk::Plt(chunk) => &mut chunk.common,
+            OutputChunk::GotPlt(chunk) => &mut chunk.common,
+            OutputChunk::Interp(chunk) => &mut chunk.common,
+            OutputChunk::DynStr(chunk) => &mut chunk.common,
+            OutputChunk::Dynamic(chunk) => &mut chunk.common,
+            OutputChunk::DynSym(chunk) => &mut chunk.common,
+            OutputChunk::GnuHash(chunk) => &mut chunk.common,
+            OutputChunk::RelaDyn(chunk) => &mut chunk.common,
+            OutputChunk::RelaPlt(chunk) => &mut chunk.common,
+            OutputChunk::Versym(chunk) => &mut chunk.common,
+            OutputChunk::Verdef(chunk) => &mut chunk.common,
+            OutputChunk::Verneed(chunk) => &mut chunk.common,
         }
     }
 
@@ -59,32 +120,76 @@ impl OutputChunk {
             }
             OutputChunk::Strtab(_) => ".strtab".to_owned(),
             OutputChunk::Symtab(_) => ".symtab".to_owned(),
+            OutputChunk::SymtabShndx(_) => ".symtab_shndx".to_owned(),
             OutputChunk::Shstrtab(_) => ".shstrtab".to_owned(),
+            OutputChunk::EhFrameHdr(_) => ".eh_frame_hdr".to_owned(),
+            OutputChunk::Got(_) => ".got".to_owned(),
+            OutputChunk::CopyRel(_) => ".copyrel".to_owned(),
+            OutputChunk::Plt(_) => ".plt".to_owned(),
+            OutputChunk::GotPlt(_) => ".got.plt".to_owned(),
+            OutputChunk::Interp(_) => ".interp".to_owned(),
+            OutputChunk::DynStr(_) => ".dynstr".to_owned(),
+            OutputChunk::Dynamic(_) => ".dynamic".to_owned(),
+            OutputChunk::DynSym(_) => ".dynsym".to_owned(),
+            OutputChunk::GnuHash(_) => ".gnu.hash".to_owned(),
+            OutputChunk::RelaDyn(_) => ".rela.dyn".to_owned(),
+            OutputChunk::RelaPlt(_) => ".rela.plt".to_owned(),
+            OutputChunk::Versym(_) => ".gnu.version".to_owned(),
+            OutputChunk::Verdef(_) => ".gnu.version_d".to_owned(),
+            OutputChunk::Verneed(_) => ".gnu.version_r".to_owned(),
         }
     }
 
-    pub fn set_offset(&mut self, ctx: &mut Context, mut offset: u64) {
+    pub fn set_offset(&mut self, ctx: &mut Context, offset: u64) {
         match self {
             OutputChunk::Ehdr(chunk) => chunk.common.shdr.sh_offset = offset,
             OutputChunk::Shdr(chunk) => chunk.common.shdr.sh_offset = offset,
             OutputChunk::Phdr(chunk) => chunk.common.shdr.sh_offset = offset,
             OutputChunk::Section(osec_ref) => {
-                let osec = ctx.get_output_section_mut(osec_ref.get_id());
                 osec_ref.common.shdr.sh_offset = offset;
-                let offset_start = offset;
-
-                for input_section in osec.input_sections.clone() {
-                    let input_section = ctx.get_input_section_mut(input_section);
-                    input_section.set_offset(offset);
-                    offset += input_section.get_size();
-                }
-
-                osec_ref.common.shdr.sh_size = offset - offset_start;
+                // Offsets in a NOBITS section are virtual. They only determine addresses and
+                // take no space in the file.
+                let end = ctx.set_input_section_offsets(osec_ref.get_id(), offset);
+                osec_ref.common.shdr.sh_size = end - offset;
             }
             OutputChunk::Strtab(chunk) => chunk.common.shdr.sh_offset = offset,
             OutputChunk::Symtab(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::SymtabShndx(chunk) => chunk.common.shdr.sh_offset = offset,
             OutputChunk::Shstrtab(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::EhFrameHdr(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::Got(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::CopyRel(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::Plt(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::GotPlt(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::Interp(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::DynStr(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::Dynamic(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::DynSym(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::GnuHash(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::RelaDyn(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::RelaPlt(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::Versym(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::Verdef(chunk) => chunk.common.shdr.sh_offset = offset,
+            OutputChunk::Verneed(chunk) => chunk.common.shdr.sh_offset = offset,
+        }
+    }
+
+    /// Returns true if the chunk is made read-only after relocation (RELRO)
+    pub fn is_relro(&self, ctx: &Context) -> bool {
+        let OutputChunk::Section(_) = self else {
+            return matches!(self, OutputChunk::Got(_) | OutputChunk::Dynamic(_));
+        };
+        let shdr = &self.get_common().shdr;
+        if shdr.sh_flags & SHF_WRITE as u64 == 0 || shdr.sh_flags & SHF_TLS as u64 != 0 {
+            return false;
         }
+        let name = self.get_section_name(ctx);
+        matches!(
+            shdr.sh_type,
+            SHT_INIT_ARRAY | SHT_FINI_ARRAY | SHT_PREINIT_ARRAY
+        ) || name == ".got"
+            || name == ".dynamic"
+            || name.ends_with(".rel.ro")
     }
 
     pub fn is_header(&self) -> bool {
@@ -105,7 +210,23 @@ impl OutputChunk {
             }
             OutputChunk::Strtab(_) => "Strtab ".to_owned(),
             OutputChunk::Symtab(_) => "Symtab ".to_owned(),
+            OutputChunk::SymtabShndx(_) => "SymtabShndx ".to_owned(),
             OutputChunk::Shstrtab(_) => "Shstrtab ".to_owned(),
+            OutputChunk::EhFrameHdr(_) => "EhFrameHdr ".to_owned(),
+            OutputChunk::Got(_) => "Got ".to_owned(),
+            OutputChunk::CopyRel(_) => "CopyRel ".to_owned(),
+            OutputChunk::Plt(_) => "Plt ".to_owned(),
+            OutputChunk::GotPlt(_) => "GotPlt ".to_owned(),
+            OutputChunk::Interp(_) => "Interp ".to_owned(),
+            OutputChunk::DynStr(_) => "DynStr ".to_owned(),
+            OutputChunk::Dynamic(_) => "Dynamic ".to_owned(),
+            OutputChunk::DynSym(_) => "DynSym ".to_owned(),
+            OutputChunk::GnuHash(_) => "GnuHash ".to_owned(),
+            OutputChunk::RelaDyn(_) => "RelaDyn ".to_owned(),
+            OutputChunk::RelaPlt(_) => "RelaPlt ".to_owned(),
+            OutputChunk::Versym(_) => "Versym ".to_owned(),
+            OutputChunk::Verdef(_) => "Verdef ".to_owned(),
+            OutputChunk::Verneed(_) => "Verneed ".to_owned(),
         }) + &self.get_common().as_string()
     }
 }
@@ -160,24 +281,34 @@ pub struct OutputEhdr {
 }
 
 impl OutputEhdr {
-    pub fn new() -> OutputEhdr {
+    /// Headers are mapped into the first read-only segment if `is_loaded`
+    pub fn new(is_loaded: bool) -> OutputEhdr {
         let mut common = ChunkInfo::new();
-        common.shdr.sh_size = std::mem::size_of::<Elf64_Ehdr>() as u64;
+        if is_loaded {
+            common.shdr.sh_flags = SHF_ALLOC as u64;
+        }
         OutputEhdr { common }
     }
-}
 
-impl OutputEhdr {
+    pub fn update_shdr(&mut self, class: ElfClass) {
+        self.common.shdr.sh_size = class.ehdr_size() as u64;
+        self.common.shdr.sh_addralign = class.word_size();
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn copy_buf(
         &self,
         buf: &mut [u8],
+        e_type: u16,
         e_entry: u64,
         e_phoff: u64,
         e_shoff: u64,
         e_phnum: u16,
-        e_shnum: u16,
-        e_shstrndx: u16,
+        num_shdrs: usize,
+        shstrndx: usize,
+        e_machine: u16,
+        e_flags: u32,
+        class: ElfClass,
     ) {
         use elf::abi::*;
 
@@ -186,27 +317,32 @@ impl OutputEhdr {
         ehdr.e_ident[EI_MAG1] = ELFMAG1;
         ehdr.e_ident[EI_MAG2] = ELFMAG2;
         ehdr.e_ident[EI_MAG3] = ELFMAG3;
-        ehdr.e_ident[EI_CLASS] = ELFCLASS64;
+        ehdr.e_ident[EI_CLASS] = class.ident();
         ehdr.e_ident[EI_DATA] = ELFDATA2LSB;
         ehdr.e_ident[EI_VERSION] = EV_CURRENT;
-        ehdr.e_type = ET_EXEC; // FIXME: PIE
-        ehdr.e_machine = EM_X86_64;
+        ehdr.e_type = e_type;
+        ehdr.e_machine = e_machine;
+        ehdr.e_flags = e_flags;
         ehdr.e_version = EV_CURRENT as u32;
         ehdr.e_entry = e_entry;
         ehdr.e_phoff = e_phoff;
         ehdr.e_shoff = e_shoff;
-        ehdr.e_ehsize = std::mem::size_of::<Elf64_Ehdr>() as u16;
-        ehdr.e_phentsize = std::mem::size_of::<Elf64_Phdr>() as u16;
+        ehdr.e_ehsize = class.ehdr_size() as u16;
+        ehdr.e_phentsize = class.phdr_size() as u16;
         ehdr.e_phnum = e_phnum;
-        ehdr.e_shentsize = std::mem::size_of::<Elf64_Shdr>() as u16;
-        ehdr.e_shnum = e_shnum;
-        ehdr.e_shstrndx = e_shstrndx;
-
-        let view = &ehdr as *const _ as *const u8;
-        let offset = self.common.shdr.sh_offset as usize;
-        let size = std::mem::size_of::<Elf64_Ehdr>();
-        let data = unsafe { std::slice::from_raw_parts(view, size) };
-        buf[offset..offset + size].copy_from_slice(data);
+        ehdr.e_shentsize = class.shdr_size() as u16;
+        // If they don't fit, the real values are stored in the first section header
+        ehdr.e_shnum = if num_shdrs < SHN_LORESERVE as usize {
+            num_shdrs as u16
+        } else {
+            0
+        };
+        ehdr.e_shstrndx = if shstrndx < SHN_LORESERVE as usize {
+            shstrndx as u16
+        } else {
+            SHN_XINDEX
+        };
+        class.write_ehdr(buf, self.common.shdr.sh_offset as usize, &ehdr);
     }
 }
 
@@ -220,15 +356,14 @@ impl OutputShdr {
         OutputShdr { common }
     }
 
-    pub fn update_shdr(&mut self, num_shdrs: usize) {
-        self.common.shdr.sh_size = (num_shdrs * std::mem::size_of::<Elf64_Shdr>()) as u64;
+    pub fn update_shdr(&mut self, num_shdrs: usize, class: ElfClass) {
+        self.common.shdr.sh_size = (num_shdrs * class.shdr_size()) as u64;
     }
 
-    pub fn copy_buf(&self, buf: &mut [u8], e_shoff: usize, data: &[Elf64_Shdr]) {
+    pub fn copy_buf(&self, buf: &mut [u8], e_shoff: usize, data: &[Elf64_Shdr], class: ElfClass) {
         let mut offset = e_shoff;
         for shdr in data {
-            let size = write_to(buf, offset, shdr);
-            offset += size;
+            offset += class.write_shdr(buf, offset, shdr);
         }
     }
 }
@@ -238,26 +373,29 @@ pub struct OutputPhdr {
 }
 
 impl OutputPhdr {
-    pub fn new() -> OutputPhdr {
-        let common = ChunkInfo::new();
+    /// The program headers are loaded with the ELF header if `is_loaded`
+    pub fn new(is_loaded: bool) -> OutputPhdr {
+        let mut common = ChunkInfo::new();
+        if is_loaded {
+            common.shdr.sh_flags = SHF_ALLOC as u64;
+        }
         OutputPhdr { common }
     }
 
-    pub fn update_shdr(&mut self, num_entry: usize) {
-        self.common.shdr.sh_size = (num_entry * std::mem::size_of::<Elf64_Phdr>()) as u64;
+    pub fn update_shdr(&mut self, num_entry: usize, class: ElfClass) {
+        self.common.shdr.sh_size = (num_entry * class.phdr_size()) as u64;
+        self.common.shdr.sh_addralign = class.word_size();
     }
 
-    pub fn copy_buf(&self, buf: &mut [u8], data: &[Elf64_Phdr]) {
+    pub fn copy_buf(&self, buf: &mut [u8], data: &[Elf64_Phdr], class: ElfClass) {
         let mut offset = self.common.shdr.sh_offset as usize;
-        // Other symbols
         for phdr in data {
-            let size = write_to(buf, offset, phdr);
-            offset += size;
+            offset += class.write_phdr(buf, offset, phdr);
         }
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, PartialOrd, Ord)]
 pub struct OutputSectionId {
     private: usize,
 }
@@ -273,6 +411,8 @@ impl OutputSectionRef {
         let mut common = ChunkInfo::new();
         common.shdr.sh_type = osec.sh_type;
         common.shdr.sh_flags = osec.sh_flags;
+        common.shdr.sh_addralign = osec.sh_addralign;
+        common.shdr.sh_entsize = osec.sh_entsize;
         OutputSectionRef {
             id: osec.get_id(),
             common,
@@ -284,11 +424,10 @@ impl OutputSectionRef {
     }
 }
 
-fn get_next_output_section_id() -> OutputSectionId {
-    static mut OUTPUT_SECTION_ID: usize = 0;
-    let id = unsafe { OUTPUT_SECTION_ID };
-    unsafe { OUTPUT_SECTION_ID += 1 };
-    OutputSectionId { private: id }
+fn get_next_output_section_id(ids: &IdSpace) -> OutputSectionId {
+    OutputSectionId {
+        private: ids.output_sections.fetch_add(1, Ordering::Relaxed),
+    }
 }
 
 #[derive(Debug)]
@@ -298,16 +437,27 @@ pub struct OutputSection {
     input_sections: Vec<InputSectionId>,
     sh_type: u32,
     sh_flags: u64,
+    /// Maximum alignment of the input sections
+    sh_addralign: u64,
+    /// Entry size shared by all input sections, or 0 if they disagree
+    sh_entsize: u64,
 }
 
 impl OutputSection {
-    pub fn new(name: String, sh_type: u32, sh_flags: u64) -> OutputSection {
+    /// Flags of inputs which are meaningless in the output
+    const IGNORED_FLAGS: u64 = (SHF_GROUP | SHF_COMPRESSED) as u64;
+    /// Flags which the output has only if all inputs have them
+    const COMMON_FLAGS: u64 = (SHF_MERGE | SHF_STRINGS) as u64;
+
+    pub fn new(name: String, sh_type: u32, sh_flags: u64, ids: &IdSpace) -> OutputSection {
         OutputSection {
-            id: get_next_output_section_id(),
+            id: get_next_output_section_id(ids),
             name,
             input_sections: vec![],
             sh_type,
-            sh_flags,
+            sh_flags: sh_flags & !Self::IGNORED_FLAGS,
+            sh_addralign: 1,
+            sh_entsize: 0,
         }
     }
 
@@ -327,19 +477,72 @@ impl OutputSection {
         &mut self.input_sections
     }
 
-    pub fn get_sh_type(&self) -> u32 {
-        self.sh_type
-    }
+    /// Returns false if the permissions of the input differ from those of the previous
+    /// inputs. The output is then writable or executable if any of the inputs is.
+    pub fn add_input_section(
+        &mut self,
+        id: InputSectionId,
+        addralign: u64,
+        sh_flags: u64,
+        entsize: u64,
+    ) -> bool {
+        let is_first = self.input_sections.is_empty();
+        self.input_sections.push(id);
+        self.sh_addralign = self.sh_addralign.max(addralign);
+        if is_first {
+            self.sh_entsize = entsize;
+        } else if self.sh_entsize != entsize {
+            self.sh_entsize = 0;
+        }
 
-    pub fn get_sh_flags(&self) -> u64 {
-        self.sh_flags
+        let sh_flags = sh_flags & !Self::IGNORED_FLAGS;
+        let perms = (SHF_WRITE | SHF_EXECINSTR) as u64;
+        let same_perms = self.sh_flags & perms == sh_flags & perms;
+        let common = self.sh_flags & sh_flags & Self::COMMON_FLAGS;
+        self.sh_flags = (self.sh_flags | sh_flags) & !Self::COMMON_FLAGS | common;
+        // Mergeable entries of different sizes cannot be merged with each other
+        if self.sh_entsize == 0 {
+            self.sh_flags &= !Self::COMMON_FLAGS;
+        }
+        // .ctors and .dtors converted to arrays don't have the entry size of arrays
+        if matches!(
+            self.sh_type,
+            SHT_INIT_ARRAY | SHT_FINI_ARRAY | SHT_PREINIT_ARRAY
+        ) {
+            self.sh_entsize = 8;
+        }
+        same_perms
     }
 
-    pub fn copy_buf(&self, ctx: &Context, buf: &mut [u8]) {
-        for input_section in self.input_sections.iter() {
-            let input_section = ctx.get_input_section(*input_section);
-            input_section.copy_buf(buf);
+    /// Returns the contents of the section in the file without relocations applied, or None
+    /// if it occupies no space in the file
+    pub fn get_image<'a>(&self, ctx: &'a Context, shdr: &Elf64_Shdr) -> Option<SectionImage<'a>> {
+        // The offsets of NOBITS sections may overlap the following sections in the file, and
+        // those of empty sections may be past the end of the file
+        if self.sh_type == SHT_NOBITS || shdr.sh_size == 0 {
+            return None;
         }
+        // Fill padding between code with int3
+        let fill = if self.sh_flags & SHF_EXECINSTR as u64 != 0 {
+            Some(0xcc)
+        } else {
+            None
+        };
+        let isecs = self
+            .input_sections
+            .iter()
+            .map(|isec| {
+                let isec = ctx.get_input_section(*isec);
+                let offset = isec.get_offset().unwrap() - shdr.sh_offset;
+                (offset as usize, isec.elf_section.data(), vec![])
+            })
+            .collect();
+        Some(SectionImage {
+            offset: shdr.sh_offset as usize,
+            size: shdr.sh_size as usize,
+            fill,
+            isecs,
+        })
     }
 
     fn as_string(&self) -> String {
@@ -351,15 +554,90 @@ impl OutputSection {
     }
 }
 
-pub fn get_output_section_name(input_section: &String) -> String {
+/// Contents of an output section in the file. They are collected up front, so that
+/// sections can be written to their own parts of the output in parallel.
+pub struct SectionImage<'a> {
+    /// File offset of the section
+    pub offset: usize,
+    pub size: usize,
+    /// Byte to fill the gaps between input sections with. Without it, the gaps are left
+    /// as they are, which is zero in a newly created output file.
+    pub fill: Option<u8>,
+    /// Offset of each input section relative to the section in ascending order, its data
+    /// (empty if there is nothing to copy) and relocation values to write over it
+    pub isecs: Vec<(usize, &'a [u8], Vec<RelValue>)>,
+}
+
+impl SectionImage<'_> {
+    /// `buf` is the part of the output which the section occupies
+    pub fn write(&self, buf: &mut [u8]) {
+        // Every byte is written once, so only the gaps are filled
+        let mut end = 0;
+        for (offset, data, relocation_data) in self.isecs.iter() {
+            debug_assert!(end <= *offset);
+            if let Some(fill) = self.fill {
+                buf[end..*offset].fill(fill);
+            }
+            end = offset + data.len();
+            buf[*offset..end].copy_from_slice(data);
+            for RelValue {
+                offset: rel_offset,
+                value,
+                size,
+            } in relocation_data
+            {
+                let start = offset + rel_offset;
+                buf[start..start + size].copy_from_slice(&value.to_le_bytes()[..*size]);
+            }
+        }
+        if let Some(fill) = self.fill {
+            buf[end..].fill(fill);
+        }
+    }
+}
+
+/// Returns the name of the output section which an input section is binned into. Sections
+/// with unknown names ("orphans") and debug sections keep their own names; sort_chunks
+/// places them by flags.
+pub fn get_output_section_name(input_section: &str, sh_type: u32) -> &str {
+    get_common_output_section_name(input_section, sh_type).unwrap_or(input_section)
+}
+
+/// Returns true if the input section is neither a well-known section nor a debug section
+pub fn is_orphan_section(input_section: &str, sh_type: u32) -> bool {
+    get_common_output_section_name(input_section, sh_type).is_none()
+        && !input_section.starts_with(".debug_")
+}
+
+/// Returns None if the input section is an orphan
+fn get_common_output_section_name(input_section: &str, sh_type: u32) -> Option<&'static str> {
+    // .ctors and .dtors are converted by InputSection::convert_ctors
+    if sh_type == SHT_INIT_ARRAY && input_section.starts_with(".ctors") {
+        return Some(".init_array");
+    }
+    if sh_type == SHT_FINI_ARRAY && input_section.starts_with(".dtors") {
+        return Some(".fini_array");
+    }
     for common_section_name in &COMMON_SECTION_NAMES {
-        if *input_section == **common_section_name
+        if input_section == *common_section_name
             || input_section.starts_with(&format!("{common_section_name}."))
         {
-            return common_section_name.to_string();
+            // NOBITS and PROGBITS inputs never share an output section. Route them to the
+            // counterpart of the section if their type doesn't match the name.
+            let is_nobits = sh_type == SHT_NOBITS;
+            let name = match (*common_section_name, is_nobits) {
+                (".bss", false) => ".data",
+                (".tbss", false) => ".tdata",
+                (".bss.rel.ro", false) => ".data.rel.ro",
+                (".data", true) => ".bss",
+                (".tdata", true) => ".tbss",
+                (".data.rel.ro", true) => ".bss.rel.ro",
+                (name, _) => name,
+            };
+            return Some(name);
         }
     }
-    input_section.clone()
+    None
 }
 
 pub struct Shstrtab {
@@ -391,23 +669,55 @@ impl Symtab {
     pub fn new() -> Symtab {
         let mut common = ChunkInfo::new();
         common.shdr.sh_type = elf::abi::SHT_SYMTAB;
-        common.shdr.sh_entsize = std::mem::size_of::<elf::symbol::Elf64_Sym>() as u64;
-        common.shdr.sh_addralign = 8;
-        // NULL symbol
-        common.shdr.sh_size = std::mem::size_of::<elf::symbol::Elf64_Sym>() as u64;
         Symtab { common }
     }
 
-    pub fn update_shdr(&mut self, num_sym: u64, strtab_shndx: u32) {
-        self.common.shdr.sh_size = num_sym * std::mem::size_of::<elf::symbol::Elf64_Sym>() as u64;
+    pub fn update_shdr(
+        &mut self,
+        num_sym: u64,
+        strtab_shndx: u32,
+        first_global: usize,
+        class: ElfClass,
+    ) {
+        self.common.shdr.sh_entsize = class.sym_size() as u64;
+        self.common.shdr.sh_addralign = class.word_size();
+        self.common.shdr.sh_size = num_sym * self.common.shdr.sh_entsize;
         self.common.shdr.sh_link = strtab_shndx;
+        // One greater than the index of the last local symbol
+        self.common.shdr.sh_info = first_global as u32;
     }
 
-    pub fn copy_buf(&self, buf: &mut [u8], data: &[Elf64_Sym]) {
+    pub fn copy_buf(&self, buf: &mut [u8], data: &[Elf64_Sym], class: ElfClass) {
         let mut offset = self.common.shdr.sh_offset as usize;
         for sym in data {
-            let size = write_to(buf, offset, sym);
-            offset += size;
+            offset += class.write_sym(buf, offset, sym);
+        }
+    }
+}
+
+/// Section indices of symbols in .symtab whose st_shndx is SHN_XINDEX, and 0 for the others
+pub struct SymtabShndx {
+    pub common: ChunkInfo,
+}
+
+impl SymtabShndx {
+    pub fn new() -> SymtabShndx {
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = elf::abi::SHT_SYMTAB_SHNDX;
+        common.shdr.sh_entsize = 4;
+        common.shdr.sh_addralign = 4;
+        SymtabShndx { common }
+    }
+
+    pub fn update_shdr(&mut self, num_sym: u64, symtab_shndx: u32) {
+        self.common.shdr.sh_size = num_sym * 4;
+        self.common.shdr.sh_link = symtab_shndx;
+    }
+
+    pub fn copy_buf(&self, buf: &mut [u8], data: &[u32]) {
+        let mut offset = self.common.shdr.sh_offset as usize;
+        for shndx in data {
+            offset += write_to(buf, offset, shndx);
         }
     }
 }
@@ -432,3 +742,739 @@ impl Strtab {
         buf[offset..offset + data.len()].copy_from_slice(data);
     }
 }
+
+/// Binary search table of FDEs in .eh_frame, used by unwinders
+pub struct EhFrameHdr {
+    pub common: ChunkInfo,
+}
+
+impl EhFrameHdr {
+    const HEADER_SIZE: u64 = 12;
+
+    pub fn new() -> EhFrameHdr {
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_PROGBITS;
+        common.shdr.sh_flags = SHF_ALLOC as u64;
+        common.shdr.sh_addralign = 4;
+        EhFrameHdr { common }
+    }
+
+    pub fn update_shdr(&mut self, num_fdes: usize) {
+        self.common.shdr.sh_size = Self::HEADER_SIZE + num_fdes as u64 * 8;
+    }
+
+    /// `fdes` are pairs of the initial location and the address of each FDE
+    pub fn copy_buf(
+        &self,
+        buf: &mut [u8],
+        eh_frame_addr: u64,
+        fdes: &mut [(u64, u64)],
+        diag: &Diagnostics,
+    ) {
+        let offset = self.common.shdr.sh_offset as usize;
+        let addr = self.common.shdr.sh_addr;
+        let num_fdes = (self.common.shdr.sh_size - Self::HEADER_SIZE) as usize / 8;
+        if fdes.len() != num_fdes {
+            diag.warn(format!(
+                ".eh_frame_hdr: expected {} FDEs, but found {}",
+                num_fdes,
+                fdes.len()
+            ));
+        }
+        fdes.sort_unstable();
+        let fdes = &fdes[..fdes.len().min(num_fdes)];
+
+        buf[offset] = 1; // version
+        buf[offset + 1] = eh_frame::EH_FRAME_PTR_ENC;
+        buf[offset + 2] = eh_frame::FDE_COUNT_ENC;
+        buf[offset + 3] = eh_frame::TABLE_ENC;
+        let eh_frame_ptr = eh_frame_addr.wrapping_sub(addr + 4) as i32;
+        write_to(buf, offset + 4, &eh_frame_ptr);
+        write_to(buf, offset + 8, &(fdes.len() as u32));
+        for (i, (pc_begin, fde_addr)) in fdes.iter().enumerate() {
+            let entry = offset + Self::HEADER_SIZE as usize + i * 8;
+            write_to(buf, entry, &(pc_begin.wrapping_sub(addr) as i32));
+            write_to(buf, entry + 4, &(fde_addr.wrapping_sub(addr) as i32));
+        }
+    }
+}
+
+/// .got synthesized by the linker. Entries are allocated by Linker::scan_relocations.
+pub struct Got {
+    pub common: ChunkInfo,
+    /// Symbols whose addresses are stored in the GOT
+    pub got_syms: Vec<Arc<SymbolCell>>,
+    /// Symbols whose TP-relative offsets are stored in the GOT
+    pub gottp_syms: Vec<Arc<SymbolCell>>,
+    /// Symbols whose module IDs and offsets are stored in the GOT
+    pub tlsgd_syms: Vec<Arc<SymbolCell>>,
+    /// Index of the module ID and offset pair shared by all TLSLD relocations
+    pub tlsld_idx: Option<usize>,
+    num_entries: usize,
+    /// Whether relocations refer to the GOT itself, e.g. through _GLOBAL_OFFSET_TABLE_
+    pub is_referenced: bool,
+    entry_size: u64,
+}
+
+impl Got {
+    pub fn new(entry_size: u64) -> Got {
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_PROGBITS;
+        common.shdr.sh_flags = (SHF_ALLOC | SHF_WRITE) as u64;
+        common.shdr.sh_addralign = entry_size;
+        common.shdr.sh_entsize = entry_size;
+        Got {
+            common,
+            got_syms: vec![],
+            gottp_syms: vec![],
+            tlsgd_syms: vec![],
+            tlsld_idx: None,
+            num_entries: 0,
+            is_referenced: false,
+            entry_size,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_entries == 0
+    }
+
+    pub fn add_got_symbol(&mut self, symbol: &Arc<SymbolCell>) {
+        if symbol.read().got_idx.is_none() {
+            symbol.write().got_idx = Some(self.num_entries);
+            self.num_entries += 1;
+            self.got_syms.push(Arc::clone(symbol));
+        }
+    }
+
+    pub fn add_gottp_symbol(&mut self, symbol: &Arc<SymbolCell>) {
+        if symbol.read().gottp_idx.is_none() {
+            symbol.write().gottp_idx = Some(self.num_entries);
+            self.num_entries += 1;
+            self.gottp_syms.push(Arc::clone(symbol));
+        }
+    }
+
+    pub fn add_tlsgd_symbol(&mut self, symbol: &Arc<SymbolCell>) {
+        if symbol.read().tlsgd_idx.is_none() {
+            symbol.write().tlsgd_idx = Some(self.num_entries);
+            self.num_entries += 2;
+            self.tlsgd_syms.push(Arc::clone(symbol));
+        }
+    }
+
+    pub fn add_tlsld(&mut self) {
+        if self.tlsld_idx.is_none() {
+            self.tlsld_idx = Some(self.num_entries);
+            self.num_entries += 2;
+        }
+    }
+
+    pub fn get_entry_addr(&self, idx: usize) -> u64 {
+        self.common.shdr.sh_addr + idx as u64 * self.entry_size
+    }
+
+    pub fn update_shdr(&mut self) {
+        self.common.shdr.sh_size = self.num_entries as u64 * self.entry_size;
+    }
+
+    /// `entries` are pairs of the index and the value of each GOT entry
+    pub fn copy_buf(&self, buf: &mut [u8], entries: &[(usize, u64)]) {
+        let offset = self.common.shdr.sh_offset as usize;
+        let size = self.entry_size as usize;
+        for (idx, value) in entries {
+            let entry = offset + idx * size;
+            buf[entry..entry + size].copy_from_slice(&value.to_le_bytes()[..size]);
+        }
+    }
+}
+
+/// .plt synthesized by the linker for calls to functions defined in DSOs. Each entry jumps
+/// to the address stored in the corresponding .got.plt entry, which initially points back
+/// to the entry to resolve the function lazily.
+pub struct Plt {
+    pub common: ChunkInfo,
+    /// Symbols which have PLT entries
+    pub syms: Vec<Arc<SymbolCell>>,
+    header_size: u64,
+    entry_size: u64,
+}
+
+impl Plt {
+    pub fn new(target: &dyn Target) -> Plt {
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_PROGBITS;
+        common.shdr.sh_flags = (SHF_ALLOC | SHF_EXECINSTR) as u64;
+        common.shdr.sh_addralign = 16;
+        common.shdr.sh_entsize = target.plt_entry_size();
+        Plt {
+            common,
+            syms: vec![],
+            header_size: target.plt_header_size(),
+            entry_size: target.plt_entry_size(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.syms.is_empty()
+    }
+
+    pub fn add_symbol(&mut self, symbol: &Arc<SymbolCell>) {
+        if symbol.read().plt_idx.is_none() {
+            symbol.write().plt_idx = Some(self.syms.len());
+            self.syms.push(Arc::clone(symbol));
+        }
+    }
+
+    /// Allocate an entry which is the address of the function in the executable and the
+    /// DSOs, because the executable refers to the function directly
+    pub fn add_canonical_symbol(&mut self, symbol: &Arc<SymbolCell>) {
+        self.add_symbol(symbol);
+        symbol.write().has_canonical_plt = true;
+    }
+
+    pub fn get_entry_addr(&self, idx: usize) -> u64 {
+        self.common.shdr.sh_addr + self.header_size + idx as u64 * self.entry_size
+    }
+
+    pub fn update_shdr(&mut self) {
+        self.common.shdr.sh_size = self.header_size + self.syms.len() as u64 * self.entry_size;
+    }
+
+    pub fn copy_buf(&self, buf: &mut [u8], got_plt_addr: u64, target: &dyn Target) {
+        let offset = self.common.shdr.sh_offset as usize;
+        let addr = self.common.shdr.sh_addr;
+        let header_size = self.header_size as usize;
+        target.write_plt_header(&mut buf[offset..offset + header_size], addr, got_plt_addr);
+        for idx in 0..self.syms.len() {
+            let entry_addr = self.get_entry_addr(idx);
+            let entry = offset + (entry_addr - addr) as usize;
+            let got_entry_addr = GotPlt::get_entry_addr_of(got_plt_addr, idx);
+            target.write_plt_entry(
+                &mut buf[entry..entry + self.entry_size as usize],
+                entry_addr,
+                got_entry_addr,
+                addr,
+                idx,
+            );
+        }
+    }
+}
+
+/// .copyrel holding copies of data objects defined in DSOs, which an executable refers to
+/// directly. Like .bss, it takes no space in the file. The dynamic linker copies the
+/// initial values from the DSOs, and the DSOs refer to the copies too.
+pub struct CopyRel {
+    pub common: ChunkInfo,
+    /// Symbols copied by a COPY relocation each
+    pub syms: Vec<Arc<SymbolCell>>,
+    /// Symbols at the same address in a DSO as one of `syms`, which share its copy
+    pub aliases: Vec<Arc<SymbolCell>>,
+}
+
+impl CopyRel {
+    pub fn new() -> CopyRel {
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_NOBITS;
+        common.shdr.sh_flags = (SHF_ALLOC | SHF_WRITE) as u64;
+        CopyRel {
+            common,
+            syms: vec![],
+            aliases: vec![],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.syms.is_empty()
+    }
+
+    /// Reserve `size` bytes aligned to `align` for a copy of the symbol
+    pub fn add_symbol(&mut self, symbol: &Arc<SymbolCell>, size: u64, align: u64) {
+        if symbol.read().copyrel_offset.is_none() {
+            let offset = align_to(self.common.shdr.sh_size, align);
+            symbol.write().copyrel_offset = Some(offset);
+            self.common.shdr.sh_size = offset + size;
+            self.common.shdr.sh_addralign = self.common.shdr.sh_addralign.max(align);
+            self.syms.push(Arc::clone(symbol));
+        }
+    }
+
+    /// Make `alias` refer to the copy at `offset`
+    pub fn add_alias(&mut self, alias: &Arc<SymbolCell>, offset: u64) {
+        if alias.read().copyrel_offset.is_none() {
+            alias.write().copyrel_offset = Some(offset);
+            self.aliases.push(Arc::clone(alias));
+        }
+    }
+}
+
+/// .got.plt holding the addresses which PLT entries jump to. The first three entries are
+/// reserved for the address of .dynamic and the dynamic linker.
+pub struct GotPlt {
+    pub common: ChunkInfo,
+}
+
+impl GotPlt {
+    const NUM_RESERVED: usize = 3;
+
+    pub fn new() -> GotPlt {
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_PROGBITS;
+        common.shdr.sh_flags = (SHF_ALLOC | SHF_WRITE) as u64;
+        common.shdr.sh_addralign = 8;
+        common.shdr.sh_entsize = 8;
+        GotPlt { common }
+    }
+
+    /// Returns the address of the entry for the PLT entry at `idx`
+    pub fn get_entry_addr_of(got_plt_addr: u64, idx: usize) -> u64 {
+        got_plt_addr + (Self::NUM_RESERVED + idx) as u64 * 8
+    }
+
+    pub fn update_shdr(&mut self, num_plt_entries: usize) {
+        self.common.shdr.sh_size = (Self::NUM_RESERVED + num_plt_entries) as u64 * 8;
+    }
+
+    /// `lazy_addrs` are the addresses which the entries hold until the functions are
+    /// resolved, which are in their PLT entries
+    pub fn copy_buf(&self, buf: &mut [u8], dynamic_addr: u64, lazy_addrs: &[u64]) {
+        let offset = self.common.shdr.sh_offset as usize;
+        write_to(buf, offset, &dynamic_addr);
+        write_to(buf, offset + 8, &0u64);
+        write_to(buf, offset + 16, &0u64);
+        for (idx, lazy_addr) in lazy_addrs.iter().enumerate() {
+            let entry = offset + (Self::NUM_RESERVED + idx) * 8;
+            write_to(buf, entry, lazy_addr);
+        }
+    }
+}
+
+/// .interp holding the path to the dynamic linker
+pub struct Interp {
+    pub common: ChunkInfo,
+    path: String,
+}
+
+impl Interp {
+    pub fn new(path: String) -> Interp {
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_PROGBITS;
+        common.shdr.sh_flags = SHF_ALLOC as u64;
+        common.shdr.sh_size = path.len() as u64 + 1;
+        Interp { common, path }
+    }
+
+    pub fn copy_buf(&self, buf: &mut [u8]) {
+        let offset = self.common.shdr.sh_offset as usize;
+        buf[offset..offset + self.path.len()].copy_from_slice(self.path.as_bytes());
+        buf[offset + self.path.len()] = 0;
+    }
+}
+
+/// .dynstr holding names referred to by .dynamic
+/// Offsets of names in .dynstr are handed out as they are added, so strings are not
+/// suffix-shared
+pub struct DynStr {
+    pub common: ChunkInfo,
+    strings: StringTable,
+}
+
+impl DynStr {
+    pub fn new() -> DynStr {
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_STRTAB;
+        common.shdr.sh_flags = SHF_ALLOC as u64;
+        DynStr {
+            common,
+            strings: StringTable::new(),
+        }
+    }
+
+    /// Returns the offset of the string, adding it if it is not in the table yet
+    pub fn add_string(&mut self, string: &str) -> u32 {
+        self.strings.add(string)
+    }
+
+    pub fn update_shdr(&mut self) {
+        self.common.shdr.sh_size = self.strings.content().len() as u64;
+    }
+
+    pub fn copy_buf(&self, buf: &mut [u8]) {
+        let offset = self.common.shdr.sh_offset as usize;
+        let content = self.strings.content();
+        buf[offset..offset + content.len()].copy_from_slice(content);
+    }
+}
+
+/// .dynamic holding information for the dynamic linker. Entries are computed by
+/// Linker::get_dynamic_entries.
+pub struct Dynamic {
+    pub common: ChunkInfo,
+    /// Offsets of the names of DSOs in .dynstr
+    pub needed: Vec<u32>,
+    /// Offset of the name of the output in .dynstr, given by -soname
+    pub soname: Option<u32>,
+}
+
+impl Dynamic {
+    const ENTRY_SIZE: u64 = 16;
+
+    pub fn new(needed: Vec<u32>, soname: Option<u32>) -> Dynamic {
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_DYNAMIC;
+        common.shdr.sh_flags = (SHF_ALLOC | SHF_WRITE) as u64;
+        common.shdr.sh_addralign = 8;
+        common.shdr.sh_entsize = Self::ENTRY_SIZE;
+        Dynamic {
+            common,
+            needed,
+            soname,
+        }
+    }
+
+    pub fn update_shdr(&mut self, num_entries: usize, dynstr_shndx: u32) {
+        self.common.shdr.sh_size = num_entries as u64 * Self::ENTRY_SIZE;
+        self.common.shdr.sh_link = dynstr_shndx;
+    }
+
+    /// `entries` are pairs of the tag and the value of each entry, including DT_NULL
+    pub fn copy_buf(&self, buf: &mut [u8], entries: &[(i64, u64)]) {
+        let offset = self.common.shdr.sh_offset as usize;
+        for (i, (tag, value)) in entries.iter().enumerate() {
+            let entry = offset + i * Self::ENTRY_SIZE as usize;
+            write_to(buf, entry, tag);
+            write_to(buf, entry + 8, value);
+        }
+    }
+}
+
+/// .dynsym holding symbols imported from DSOs followed by exported ones. Entries are
+/// computed by Linker::get_dynsym_content.
+pub struct DynSym {
+    pub common: ChunkInfo,
+    /// Symbols in the table except for the null symbol at index 0
+    pub syms: Vec<Arc<SymbolCell>>,
+    /// Offsets of the names of `syms` in .dynstr
+    pub names: Vec<u32>,
+}
+
+impl DynSym {
+    pub fn new(syms: Vec<Arc<SymbolCell>>, names: Vec<u32>) -> DynSym {
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_DYNSYM;
+        common.shdr.sh_flags = SHF_ALLOC as u64;
+        common.shdr.sh_entsize = std::mem::size_of::<Elf64_Sym>() as u64;
+        common.shdr.sh_addralign = 8;
+        // Only the null symbol is local
+        common.shdr.sh_info = 1;
+        DynSym {
+            common,
+            syms,
+            names,
+        }
+    }
+
+    pub fn update_shdr(&mut self, dynstr_shndx: u32) {
+        self.common.shdr.sh_size = (self.syms.len() as u64 + 1) * self.common.shdr.sh_entsize;
+        self.common.shdr.sh_link = dynstr_shndx;
+    }
+
+    pub fn copy_buf(&self, buf: &mut [u8], content: &[Elf64_Sym]) {
+        let mut offset = self.common.shdr.sh_offset as usize;
+        for esym in content {
+            offset += write_to(buf, offset, esym);
+        }
+    }
+}
+
+/// Hash function of .gnu.hash
+pub fn gnu_hash(name: &str) -> u32 {
+    name.bytes()
+        .fold(5381u32, |h, c| h.wrapping_mul(33).wrapping_add(c as u32))
+}
+
+/// .gnu.hash to look up exported symbols in .dynsym. Exported symbols must be at the end of
+/// .dynsym and sorted by bucket (see GnuHash::get_bucket).
+pub struct GnuHash {
+    pub common: ChunkInfo,
+    content: Vec<u8>,
+}
+
+impl GnuHash {
+    const BLOOM_SHIFT: u32 = 26;
+
+    /// Returns the number of buckets for `num_exported` symbols
+    pub fn get_num_buckets(num_exported: usize) -> u32 {
+        num_exported as u32 / 8 + 1
+    }
+
+    pub fn get_bucket(hash: u32, num_exported: usize) -> u32 {
+        hash % Self::get_num_buckets(num_exported)
+    }
+
+    /// `hashes` are the hashes of the exported symbols, which start at `symoffset` in .dynsym
+    pub fn new(symoffset: usize, hashes: &[u32]) -> GnuHash {
+        let num_buckets = Self::get_num_buckets(hashes.len());
+        let num_bloom = (hashes.len() * 12 / 64).max(1).next_power_of_two();
+        let mut bloom = vec![0u64; num_bloom];
+        let mut buckets = vec![0u32; num_buckets as usize];
+        let mut chains = vec![0u32; hashes.len()];
+        for (i, hash) in hashes.iter().enumerate() {
+            let word = (*hash / 64) as usize % num_bloom;
+            bloom[word] |= 1 << (hash % 64);
+            bloom[word] |= 1 << ((hash >> Self::BLOOM_SHIFT) % 64);
+
+            let bucket = (hash % num_buckets) as usize;
+            if buckets[bucket] == 0 {
+                buckets[bucket] = (symoffset + i) as u32;
+            }
+            // The lowest bit marks the end of a chain
+            let is_last = hashes
+                .get(i + 1)
+                .is_none_or(|next| next % num_buckets != hash % num_buckets);
+            chains[i] = (hash & !1) | is_last as u32;
+        }
+
+        let mut content = vec![];
+        for value in [
+            num_buckets,
+            symoffset as u32,
+            num_bloom as u32,
+            Self::BLOOM_SHIFT,
+        ] {
+            content.extend_from_slice(&value.to_le_bytes());
+        }
+        for word in bloom {
+            content.extend_from_slice(&word.to_le_bytes());
+        }
+        for value in buckets.into_iter().chain(chains) {
+            content.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_GNU_HASH;
+        common.shdr.sh_flags = SHF_ALLOC as u64;
+        common.shdr.sh_addralign = 8;
+        common.shdr.sh_size = content.len() as u64;
+        GnuHash { common, content }
+    }
+
+    pub fn update_shdr(&mut self, dynsym_shndx: u32) {
+        self.common.shdr.sh_link = dynsym_shndx;
+    }
+
+    pub fn copy_buf(&self, buf: &mut [u8]) {
+        let offset = self.common.shdr.sh_offset as usize;
+        buf[offset..offset + self.content.len()].copy_from_slice(&self.content);
+    }
+}
+
+/// .rela.dyn or .rela.plt holding relocations applied by the dynamic linker. Entries are
+/// computed by Linker::get_rela_dyn_entries and Linker::get_rela_plt_entries.
+pub struct DynRela {
+    pub common: ChunkInfo,
+}
+
+impl DynRela {
+    const ENTRY_SIZE: u64 = 24;
+
+    /// `is_plt` is true for .rela.plt, whose relocations are applied to .got.plt
+    pub fn new(is_plt: bool) -> DynRela {
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_RELA;
+        common.shdr.sh_flags = SHF_ALLOC as u64;
+        if is_plt {
+            common.shdr.sh_flags |= SHF_INFO_LINK as u64;
+        }
+        common.shdr.sh_addralign = 8;
+        common.shdr.sh_entsize = Self::ENTRY_SIZE;
+        DynRela { common }
+    }
+
+    /// `info` is the section index of .got.plt for .rela.plt, 0 for .rela.dyn
+    pub fn update_shdr(&mut self, num_entries: usize, dynsym_shndx: u32, info: u32) {
+        self.common.shdr.sh_size = num_entries as u64 * Self::ENTRY_SIZE;
+        self.common.shdr.sh_link = dynsym_shndx;
+        self.common.shdr.sh_info = info;
+    }
+
+    pub fn copy_buf(&self, buf: &mut [u8], entries: &[Elf64_Rela]) {
+        let mut offset = self.common.shdr.sh_offset as usize;
+        for rela in entries {
+            offset += write_to(buf, offset, rela);
+        }
+    }
+}
+
+/// Hash function of the System V hash table, which is also used in .gnu.version_r
+pub fn elf_hash(name: &str) -> u32 {
+    name.bytes().fold(0u32, |h, c| {
+        let h = (h << 4).wrapping_add(c as u32);
+        let g = h & 0xf000_0000;
+        (h ^ (g >> 24)) & !g
+    })
+}
+
+/// .gnu.version holding the version index of each symbol in .dynsym
+pub struct Versym {
+    pub common: ChunkInfo,
+    indices: Vec<u16>,
+}
+
+impl Versym {
+    /// `indices` includes the entry for the null symbol
+    pub fn new(indices: Vec<u16>) -> Versym {
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_GNU_VERSYM;
+        common.shdr.sh_flags = SHF_ALLOC as u64;
+        common.shdr.sh_addralign = 2;
+        common.shdr.sh_entsize = 2;
+        common.shdr.sh_size = indices.len() as u64 * 2;
+        Versym { common, indices }
+    }
+
+    pub fn update_shdr(&mut self, dynsym_shndx: u32) {
+        self.common.shdr.sh_link = dynsym_shndx;
+    }
+
+    pub fn copy_buf(&self, buf: &mut [u8]) {
+        let mut offset = self.common.shdr.sh_offset as usize;
+        for index in &self.indices {
+            offset += write_to(buf, offset, index);
+        }
+    }
+}
+
+/// Version defined by the output
+pub struct DefinedVersion<'a> {
+    pub name: &'a str,
+    /// Offsets in .dynstr of `name` followed by those of the versions it inherits from
+    pub names: Vec<u32>,
+}
+
+/// .gnu.version_d listing versions defined by the output. The first one is the base
+/// version, which has index 1 and names the output itself.
+pub struct Verdef {
+    pub common: ChunkInfo,
+    content: Vec<u8>,
+}
+
+impl Verdef {
+    pub fn new(defs: &[DefinedVersion]) -> Verdef {
+        const VERDEF_SIZE: u32 = 20;
+        const VERDAUX_SIZE: u32 = 8;
+        let mut content = vec![];
+        for (i, def) in defs.iter().enumerate() {
+            let next = if i + 1 == defs.len() {
+                0
+            } else {
+                VERDEF_SIZE + def.names.len() as u32 * VERDAUX_SIZE
+            };
+            let flags = if i == 0 { VER_FLG_BASE } else { 0 };
+            // Elf64_Verdef: vd_version, vd_flags, vd_ndx, vd_cnt, vd_hash, vd_aux, vd_next
+            content.extend_from_slice(&VER_DEF_CURRENT.to_le_bytes());
+            content.extend_from_slice(&flags.to_le_bytes());
+            content.extend_from_slice(&(i as u16 + 1).to_le_bytes());
+            content.extend_from_slice(&(def.names.len() as u16).to_le_bytes());
+            content.extend_from_slice(&elf_hash(def.name).to_le_bytes());
+            content.extend_from_slice(&VERDEF_SIZE.to_le_bytes());
+            content.extend_from_slice(&next.to_le_bytes());
+            for (j, name) in def.names.iter().enumerate() {
+                let next = if j + 1 == def.names.len() {
+                    0
+                } else {
+                    VERDAUX_SIZE
+                };
+                // Elf64_Verdaux: vda_name, vda_next
+                content.extend_from_slice(&name.to_le_bytes());
+                content.extend_from_slice(&next.to_le_bytes());
+            }
+        }
+
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_GNU_VERDEF;
+        common.shdr.sh_flags = SHF_ALLOC as u64;
+        common.shdr.sh_addralign = 8;
+        common.shdr.sh_size = content.len() as u64;
+        // The number of Verdef entries
+        common.shdr.sh_info = defs.len() as u32;
+        Verdef { common, content }
+    }
+
+    pub fn update_shdr(&mut self, dynstr_shndx: u32) {
+        self.common.shdr.sh_link = dynstr_shndx;
+    }
+
+    pub fn copy_buf(&self, buf: &mut [u8]) {
+        let offset = self.common.shdr.sh_offset as usize;
+        buf[offset..offset + self.content.len()].copy_from_slice(&self.content);
+    }
+}
+
+/// Version required from a DSO
+pub struct NeededVersion<'a> {
+    pub index: u16,
+    pub name: &'a str,
+    /// Offset of `name` in .dynstr
+    pub name_offset: u32,
+}
+
+/// .gnu.version_r listing versions required from each DSO
+pub struct Verneed {
+    pub common: ChunkInfo,
+    content: Vec<u8>,
+}
+
+impl Verneed {
+    /// `needs` are pairs of the offset of a DSO name in .dynstr and versions required from
+    /// it
+    pub fn new(needs: &[(u32, Vec<NeededVersion>)]) -> Verneed {
+        const VERNEED_SIZE: u32 = 16;
+        const VERNAUX_SIZE: u32 = 16;
+        let mut content = vec![];
+        for (i, (file, versions)) in needs.iter().enumerate() {
+            let next = if i + 1 == needs.len() {
+                0
+            } else {
+                VERNEED_SIZE + versions.len() as u32 * VERNAUX_SIZE
+            };
+            // Elf64_Verneed: vn_version, vn_cnt, vn_file, vn_aux, vn_next
+            content.extend_from_slice(&VER_NEED_CURRENT.to_le_bytes());
+            content.extend_from_slice(&(versions.len() as u16).to_le_bytes());
+            content.extend_from_slice(&file.to_le_bytes());
+            content.extend_from_slice(&VERNEED_SIZE.to_le_bytes());
+            content.extend_from_slice(&next.to_le_bytes());
+            for (j, version) in versions.iter().enumerate() {
+                let next = if j + 1 == versions.len() {
+                    0
+                } else {
+                    VERNAUX_SIZE
+                };
+                // Elf64_Vernaux: vna_hash, vna_flags, vna_other, vna_name, vna_next
+                content.extend_from_slice(&elf_hash(version.name).to_le_bytes());
+                content.extend_from_slice(&0u16.to_le_bytes());
+                content.extend_from_slice(&version.index.to_le_bytes());
+                content.extend_from_slice(&version.name_offset.to_le_bytes());
+                content.extend_from_slice(&next.to_le_bytes());
+            }
+        }
+
+        let mut common = ChunkInfo::new();
+        common.shdr.sh_type = SHT_GNU_VERNEED;
+        common.shdr.sh_flags = SHF_ALLOC as u64;
+        common.shdr.sh_addralign = 8;
+        common.shdr.sh_size = content.len() as u64;
+        // The number of Verneed entries
+        common.shdr.sh_info = needs.len() as u32;
+        Verneed { common, content }
+    }
+
+    pub fn update_shdr(&mut self, dynstr_shndx: u32) {
+        self.common.shdr.sh_link = dynstr_shndx;
+    }
+
+    pub fn copy_buf(&self, buf: &mut [u8]) {
+        let offset = self.common.shdr.sh_offset as usize;
+        buf[offset..offset + self.content.len()].copy_from_slice(&self.content);
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Time spent in the phases of a link (--perf) and its counters (--stats). Each link has
+/// its own, which is returned along with the output.
+pub struct Perf {
+    /// Whether timers record anything (--perf)
+    enabled: bool,
+    /// Time and bytes spent in each phase, in the order the phases first finished
+    phases: Mutex<Vec<Phase>>,
+    /// Size of the input files
+    pub input_bytes: Counter,
+    /// Size of the input pages given back to the kernel after they are no longer needed
+    pub released_input_bytes: Counter,
+}
+
+struct Phase {
+    name: &'static str,
+    elapsed: Duration,
+    bytes: u64,
+}
+
+pub struct Counter {
+    name: &'static str,
+    value: AtomicU64,
+}
+
+impl Counter {
+    const fn new(name: &'static str) -> Counter {
+        Counter {
+            name,
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub fn add(&self, value: u64) {
+        self.value.fetch_add(value, Ordering::Relaxed);
+    }
+}
+
+impl Perf {
+    pub fn new(enabled: bool) -> Perf {
+        Perf {
+            enabled,
+            phases: Mutex::new(vec![]),
+            input_bytes: Counter::new("input_bytes"),
+            released_input_bytes: Counter::new("released_input_bytes"),
+        }
+    }
+
+    /// Start timing the phase `name` until the returned timer is dropped
+    pub fn timer(self: &Arc<Self>, name: &'static str) -> Timer {
+        Timer {
+            perf: Arc::clone(self),
+            name,
+            start: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    /// Returns the names and the values of the counters printed by --stats
+    pub fn counters(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        [&self.input_bytes, &self.released_input_bytes]
+            .into_iter()
+            .map(|counter| (counter.name, counter.value.load(Ordering::Relaxed)))
+    }
+
+    /// Returns the recorded phases, or an empty string unless --perf is given. There is
+    /// one line per phase with the time in milliseconds, the number of bytes and the
+    /// throughput in MB/s, or "-" for phases which don't count bytes.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        if !self.enabled {
+            return report;
+        }
+        writeln!(
+            report,
+            "{:>10} {:>12} {:>10}  Phase",
+            "Time(ms)", "Bytes", "MB/s"
+        )
+        .unwrap();
+        for phase in self.phases.lock().unwrap().iter() {
+            let secs = phase.elapsed.as_secs_f64();
+            let (bytes, throughput) = if phase.bytes == 0 {
+                ("-".to_owned(), "-".to_owned())
+            } else if secs == 0.0 {
+                (phase.bytes.to_string(), "-".to_owned())
+            } else {
+                (
+                    phase.bytes.to_string(),
+                    format!("{:.1}", phase.bytes as f64 / secs / 1e6),
+                )
+            };
+            writeln!(
+                report,
+                "{:>10.3} {:>12} {:>10}  {}",
+                secs * 1e3,
+                bytes,
+                throughput,
+                phase.name
+            )
+            .unwrap();
+        }
+        report
+    }
+
+    fn record(&self, name: &'static str, elapsed: Duration, bytes: u64) {
+        let mut phases = self.phases.lock().unwrap();
+        match phases.iter_mut().find(|phase| phase.name == name) {
+            Some(phase) => {
+                phase.elapsed += elapsed;
+                phase.bytes += bytes;
+            }
+            None => phases.push(Phase {
+                name,
+                elapsed,
+                bytes,
+            }),
+        }
+    }
+}
+
+/// Scoped timer of a phase of the link. The time until it is dropped is added to the
+/// phase, so a phase which is entered more than once is reported as the sum.
+pub struct Timer {
+    perf: Arc<Perf>,
+    name: &'static str,
+    start: Instant,
+    bytes: u64,
+}
+
+impl Timer {
+    /// Count bytes processed by the phase, which gives its throughput in the report
+    pub fn add_bytes(&mut self, bytes: u64) {
+        self.bytes += bytes;
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        if self.perf.enabled {
+            self.perf
+                .record(self.name, self.start.elapsed(), self.bytes);
+        }
+    }
+}
@@ -1,137 +1,118 @@
-use elf::{abi, relocation::Rela};
+use elf::relocation::Rela;
 
+use crate::target::Target;
+
+/// Value written to the contents of an input section
 #[derive(Debug)]
 pub struct RelValue {
-    pub file_ofs: usize,
+    /// Offset from the start of the input section
+    pub offset: usize,
     pub value: u64,
     pub size: usize,
 }
 
-pub fn relocation_value(symbol_addr: u64, isec_addr: u64, rela: &Rela) -> Option<u64> {
-    let s = symbol_addr;
-    let a = rela.r_addend;
-    let p = isec_addr + rela.r_offset;
-
-    match rela.r_type {
-        abi::R_X86_64_NONE => None,
-        abi::R_X86_64_PC32 | abi::R_X86_64_PLT32 => Some((s as i64 + a - p as i64) as u64),
-        abi::R_X86_64_8
-        | abi::R_X86_64_16
-        | abi::R_X86_64_32
-        | abi::R_X86_64_32S
-        | abi::R_X86_64_64 => Some((s as i64 + a) as u64),
-        abi::R_X86_64_GOTTPOFF | abi::R_X86_64_GOTPCRELX => {
-            log::warn!("{} is not supported, ignored", r_type_as_str(rela.r_type));
-            Some(0)
+/// Verify the values written by relocations to `data`, the contents of an input section in
+/// the output. It is an error if a value is outside the section, overlaps another one, or
+/// was overwritten.
+pub fn check_relocation_data(data: &[u8], values: &[RelValue]) -> Result<(), String> {
+    let mut ranges = values
+        .iter()
+        .map(|value| (value.offset, value.offset + value.size))
+        .collect::<Vec<_>>();
+    ranges.sort();
+    for pair in ranges.windows(2) {
+        let ((start1, end1), (start2, end2)) = (pair[0], pair[1]);
+        if start2 < end1 {
+            return Err(format!(
+                "values at [{:#x}, {:#x}) and [{:#x}, {:#x}) overlap",
+                start1, end1, start2, end2
+            ));
+        }
+    }
+    for value in values {
+        let end = value.offset + value.size;
+        let Some(written) = data.get(value.offset..end) else {
+            return Err(format!(
+                "value at [{:#x}, {:#x}) is outside the section of size {:#x}",
+                value.offset,
+                end,
+                data.len()
+            ));
+        };
+        if written != &value.value.to_le_bytes()[..value.size] {
+            return Err(format!(
+                "value at {:#x} is {:02x?}, expected {:#x}",
+                value.offset, written, value.value
+            ));
         }
-        _ => todo!("r_type: {} is not supported", r_type_as_str(rela.r_type)),
     }
+    Ok(())
 }
 
-pub fn relocation_size(rela: &Rela) -> usize {
-    match rela.r_type {
-        abi::R_X86_64_NONE => 0,
-        abi::R_X86_64_8 => 1,
-        abi::R_X86_64_16 => 2,
-        abi::R_X86_64_32 => 4,
-        abi::R_X86_64_32S => 4,
-        abi::R_X86_64_64 => 8,
-        abi::R_X86_64_PC32 => 4,
-        abi::R_X86_64_GOT32 => 4,
-        abi::R_X86_64_PLT32 => 4,
-        // FIXME: Not sure
-        abi::R_X86_64_GOTTPOFF => 4,
-        // FIXME: Not sure
-        abi::R_X86_64_GOTPCRELX => 4,
-        _ => todo!("r_type: {} is not supported", r_type_as_str(rela.r_type)),
-    }
+/// Values which a relocation is computed from
+pub struct RelocationInput {
+    /// S in the psABI. For TLS symbols, this is the offset in the TLS template.
+    pub symbol_addr: u64,
+    /// Z in the psABI, i.e. the size of the definition of the symbol
+    pub symbol_size: u64,
+    /// Address of the input section, which P is relative to
+    pub isec_addr: u64,
+    /// Address of the GOT entry which the relocation refers to, i.e. G + GOT in the psABI
+    pub got_entry_addr: Option<u64>,
+    /// Address of the PLT entry which calls go through, i.e. L in the psABI. It is None if
+    /// the symbol is called directly.
+    pub plt_entry_addr: Option<u64>,
+    /// GOT in the psABI, i.e. the address of _GLOBAL_OFFSET_TABLE_
+    pub got_addr: u64,
+    /// Offset of the thread pointer from the start of the TLS template
+    pub tp_offset: u64,
+    /// Offset of the address which DTPOFF relocations are relative to, i.e. 0 for the start
+    /// of the TLS block unless TLSLD is relaxed to local-exec
+    pub dtp_offset: u64,
+    /// Value of the R_RISCV_PCREL_HI20 relocation of the instruction which the symbol of a
+    /// R_RISCV_PCREL_LO12 relocation is at
+    pub pcrel_hi20_value: Option<u64>,
 }
 
-fn r_type_as_str(r_type: u32) -> &'static str {
-    match r_type {
-        abi::R_X86_64_NONE => "R_X86_64_NONE",
-        abi::R_X86_64_64 => "R_X86_64_64",
-        abi::R_X86_64_PC32 => "R_X86_64_PC32",
-        abi::R_X86_64_GOT32 => "R_X86_64_GOT32",
-        abi::R_X86_64_PLT32 => "R_X86_64_PLT32",
-        abi::R_X86_64_COPY => "R_X86_64_COPY",
-        abi::R_X86_64_GLOB_DAT => "R_X86_64_GLOB_DAT",
-        abi::R_X86_64_JUMP_SLOT => "R_X86_64_JUMP_SLOT",
-        abi::R_X86_64_RELATIVE => "R_X86_64_RELATIVE",
-        abi::R_X86_64_GOTPCREL => "R_X86_64_GOTPCREL",
-        abi::R_X86_64_32 => "R_X86_64_32",
-        abi::R_X86_64_16 => "R_X86_64_16",
-        abi::R_X86_64_8 => "R_X86_64_8",
-        abi::R_X86_64_PC8 => "R_X86_64_PC8",
-        abi::R_X86_64_PC16 => "R_X86_64_PC16",
-        abi::R_X86_64_32S => "R_X86_64_32S",
-        abi::R_X86_64_PC64 => "R_X86_64_PC64",
-        abi::R_X86_64_TLSGD => "R_X86_64_TLSGD",
-        abi::R_X86_64_TLSLD => "R_X86_64_TLSLD",
-        abi::R_X86_64_DTPOFF32 => "R_X86_64_DTPOFF32",
-        abi::R_X86_64_GOTTPOFF => "R_X86_64_GOTTPOFF",
-        abi::R_X86_64_TPOFF32 => "R_X86_64_TPOFF32",
-        abi::R_X86_64_GOTOFF64 => "R_X86_64_GOTOFF64",
-        abi::R_X86_64_GOTPC32 => "R_X86_64_GOTPC32",
-        abi::R_X86_64_GOT64 => "R_X86_64_GOT64",
-        abi::R_X86_64_GOTPCREL64 => "R_X86_64_GOTPCREL64",
-        abi::R_X86_64_GOTPC64 => "R_X86_64_GOTPC64",
-        abi::R_X86_64_PLTOFF64 => "R_X86_64_PLTOFF64",
-        abi::R_X86_64_SIZE32 => "R_X86_64_SIZE32",
-        abi::R_X86_64_SIZE64 => "R_X86_64_SIZE64",
-        abi::R_X86_64_GOTPC32_TLSDESC => "R_X86_64_GOTPC32_TLSDESC",
-        abi::R_X86_64_TLSDESC_CALL => "R_X86_64_TLSDESC_CALL",
-        abi::R_X86_64_TLSDESC => "R_X86_64_TLSDESC",
-        abi::R_X86_64_IRELATIVE => "R_X86_64_IRELATIVE",
-        abi::R_X86_64_RELATIVE64 => "R_X86_64_RELATIVE64",
-        abi::R_X86_64_GOTPCRELX => "R_X86_64_GOTPCRELX",
-        abi::R_X86_64_REX_GOTPCRELX => "R_X86_64_REX_GOTPCRELX",
-        _ => panic!("TODO: relocation type: {}", r_type),
-    }
+/// Instruction rewritten by the relaxation of GOTPCRELX, which refers to the symbol
+/// directly instead of loading its address from the GOT
+#[derive(Debug)]
+pub struct RelaxedInsn {
+    /// The new instruction, with a zero 32-bit PC-relative displacement
+    pub bytes: Vec<u8>,
+    /// Number of bytes of the instruction before the relocated field
+    pub start: usize,
+    /// Offset of the displacement in the instruction
+    pub disp: usize,
 }
 
-/*
-static u32 relax_gottpoff(u8 *loc) {
-    switch ((loc[0] << 16) | (loc[1] << 8) | loc[2]) {
-    case 0x488b05: return 0x48c7c0; // mov 0(%rip), %rax -> mov $0, %rax
-    case 0x488b0d: return 0x48c7c1; // mov 0(%rip), %rcx -> mov $0, %rcx
-    case 0x488b15: return 0x48c7c2; // mov 0(%rip), %rdx -> mov $0, %rdx
-    case 0x488b1d: return 0x48c7c3; // mov 0(%rip), %rbx -> mov $0, %rbx
-    case 0x488b25: return 0x48c7c4; // mov 0(%rip), %rsp -> mov $0, %rsp
-    case 0x488b2d: return 0x48c7c5; // mov 0(%rip), %rbp -> mov $0, %rbp
-    case 0x488b35: return 0x48c7c6; // mov 0(%rip), %rsi -> mov $0, %rsi
-    case 0x488b3d: return 0x48c7c7; // mov 0(%rip), %rdi -> mov $0, %rdi
-    case 0x4c8b05: return 0x49c7c0; // mov 0(%rip), %r8  -> mov $0, %r8
-    case 0x4c8b0d: return 0x49c7c1; // mov 0(%rip), %r9  -> mov $0, %r9
-    case 0x4c8b15: return 0x49c7c2; // mov 0(%rip), %r10 -> mov $0, %r10
-    case 0x4c8b1d: return 0x49c7c3; // mov 0(%rip), %r11 -> mov $0, %r11
-    case 0x4c8b25: return 0x49c7c4; // mov 0(%rip), %r12 -> mov $0, %r12
-    case 0x4c8b2d: return 0x49c7c5; // mov 0(%rip), %r13 -> mov $0, %r13
-    case 0x4c8b35: return 0x49c7c6; // mov 0(%rip), %r14 -> mov $0, %r14
-    case 0x4c8b3d: return 0x49c7c7; // mov 0(%rip), %r15 -> mov $0, %r15
+impl RelaxedInsn {
+    /// Returns the instruction referring to the address `s` with the addend of the original
+    /// relocation. The displacement keeps its distance to the end of the instruction.
+    pub fn encode(&self, s: u64, a: i64, insn_addr: u64) -> Vec<u8> {
+        let p = insn_addr + self.disp as u64;
+        let disp = (s as i64 + a - p as i64) as i32;
+        let mut bytes = self.bytes.clone();
+        bytes[self.disp..self.disp + 4].copy_from_slice(&disp.to_le_bytes());
+        bytes
     }
-    return 0;
-  }
+}
 
-  */
-fn relax_gottpoff(loc: &[u8]) -> u32 {
-    match (loc[0] as u32) << 16 | (loc[1] as u32) << 8 | loc[2] as u32 {
-        0x488b05 => 0x48c7c0, // mov 0(%rip), %rax -> mov $0, %rax
-        0x488b0d => 0x48c7c1, // mov 0(%rip), %rcx -> mov $0, %rcx
-        0x488b15 => 0x48c7c2, // mov 0(%rip), %rdx -> mov $0, %rdx
-        0x488b1d => 0x48c7c3, // mov 0(%rip), %rbx -> mov $0, %rbx
-        0x488b25 => 0x48c7c4, // mov 0(%rip), %rsp -> mov $0, %rsp
-        0x488b2d => 0x48c7c5, // mov 0(%rip), %rbp -> mov $0, %rbp
-        0x488b35 => 0x48c7c6, // mov 0(%rip), %rsi -> mov $0, %rsi
-        0x488b3d => 0x48c7c7, // mov 0(%rip), %rdi -> mov $0, %rdi
-        0x4c8b05 => 0x49c7c0, // mov 0(%rip), %r8  -> mov $0, %r8
-        0x4c8b0d => 0x49c7c1, // mov 0(%rip), %r9  -> mov $0, %r9
-        0x4c8b15 => 0x49c7c2, // mov 0(%rip), %r10 -> mov $0, %r10
-        0x4c8b1d => 0x49c7c3, // mov 0(%rip), %r11 -> mov $0, %r11
-        0x4c8b25 => 0x49c7c4, // mov 0(%rip), %r12 -> mov $0, %r12
-        0x4c8b2d => 0x49c7c5, // mov 0(%rip), %r13 -> mov $0, %r13
-        0x4c8b35 => 0x49c7c6, // mov 0(%rip), %r14 -> mov $0, %r14
-        0x4c8b3d => 0x49c7c7, // mov 0(%rip), %r15 -> mov $0, %r15
-        _ => panic!(),
+/// Returns the addend of a SHT_REL relocation, which is stored in the relocated field of
+/// `data` and sign-extended from the size of the field
+pub fn implicit_addend(target: &dyn Target, rela: &Rela, data: &[u8]) -> Result<i64, String> {
+    let size = target.relocation_size(rela.r_type)?;
+    if size == 0 {
+        return Ok(0);
     }
+    let offset = rela.r_offset as usize;
+    let field = data.get(offset..offset + size).ok_or(format!(
+        "relocation at {:#x} is outside the section of size {:#x}",
+        offset,
+        data.len()
+    ))?;
+    let mut bytes = [0; 8];
+    bytes[..size].copy_from_slice(field);
+    let shift = 64 - 8 * size as u32;
+    Ok(i64::from_le_bytes(bytes) << shift >> shift)
 }
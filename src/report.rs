@@ -0,0 +1,137 @@
+//! Machine-readable report of the layout of the output (--json-report)
+//!
+//! The report is a JSON object with these members:
+//!
+//! - `format`: always "bold-link-report"
+//! - `version`: version of the schema, which is incremented whenever a member is removed or
+//!   changes its meaning. Members may be added without changing it.
+//! - `entry`: address of the entry point
+//! - `sections`: sections in the order of their headers, each with `index`, `name`, `type`
+//!   (sh_type), `flags` (sh_flags), `address`, `offset`, `size`, `align` and `inputs`. The
+//!   inputs are the input sections the section is made of, each with `file`, `section`,
+//!   `address` and `size`.
+//! - `symbols`: symbols in .symtab in input order, each with `name`, `address`, `size`, `type`
+//!   (STT_*), `binding` (STB_*), `section` (index of the output section, or null for
+//!   absolute symbols) and `file` (the file defining the symbol)
+//! - `segments`: program headers, each with `type`, `flags`, `offset`, `address`,
+//!   `file_size`, `memory_size` and `align`
+//! - `stats`: counters printed by --stats
+//!
+//! Numbers are written in decimal.
+
+use std::fmt::{self, Display, Write};
+
+/// Version of the schema of the report
+pub const REPORT_VERSION: u64 = 1;
+
+/// JSON value. Members of objects are written in the order they are added.
+pub enum Json {
+    Null,
+    Number(u64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl From<u64> for Json {
+    fn from(value: u64) -> Json {
+        Json::Number(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Json {
+        Json::String(value.to_owned())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Json {
+        Json::String(value)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+impl Json {
+    /// Write the value indented by `level` levels. Arrays and objects put each element on
+    /// its own line, except that objects within arrays are written on a single line so
+    /// that reports of two links can be compared line by line.
+    fn write(&self, f: &mut fmt::Formatter<'_>, level: usize, inline: bool) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write_string(f, s),
+            Json::Array(elems) if elems.is_empty() => f.write_str("[]"),
+            Json::Object(members) if members.is_empty() => f.write_str("{}"),
+            Json::Array(elems) if inline => {
+                f.write_char('[')?;
+                for (i, elem) in elems.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    elem.write(f, level, true)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(members) if inline => {
+                f.write_char('{')?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write_string(f, key)?;
+                    f.write_str(": ")?;
+                    value.write(f, level, true)?;
+                }
+                f.write_char('}')
+            }
+            Json::Array(elems) => {
+                f.write_str("[\n")?;
+                for (i, elem) in elems.iter().enumerate() {
+                    write!(f, "{:1$}", "", (level + 1) * 2)?;
+                    elem.write(f, level + 1, matches!(elem, Json::Object(_)))?;
+                    f.write_str(if i + 1 < elems.len() { ",\n" } else { "\n" })?;
+                }
+                write!(f, "{:1$}]", "", level * 2)
+            }
+            Json::Object(members) => {
+                f.write_str("{\n")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    write!(f, "{:1$}", "", (level + 1) * 2)?;
+                    write_string(f, key)?;
+                    f.write_str(": ")?;
+                    value.write(f, level + 1, false)?;
+                    f.write_str(if i + 1 < members.len() { ",\n" } else { "\n" })?;
+                }
+                write!(f, "{:1$}}}", "", level * 2)
+            }
+        }
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0, false)?;
+        f.write_char('\n')
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
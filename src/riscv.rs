@@ -0,0 +1,423 @@
+//! Relocations of RISC-V (RV64). Like AArch64, most of them patch an immediate field of an
+//! instruction. A PCREL_LO12 relocation takes the low 12 bits of the value of the
+//! PCREL_HI20 relocation of its AUIPC, and R_RISCV_ALIGN padding is shrunk when the input
+//! is parsed since the code needs it even without relaxation.
+
+use elf::{
+    abi::{self, EF_RISCV_FLOAT_ABI_MASK, EF_RISCV_RVC, EF_RISCV_RVE, EF_RISCV_TSO},
+    relocation::Rela,
+};
+
+use crate::{input_section::ElfContents, relocation::RelocationInput, target::Target};
+
+/// Returns the value of the relocated field before it is encoded, or None if nothing is
+/// written. It is an error if the relocation type is not supported.
+pub fn relocation_value(input: &RelocationInput, rela: &Rela) -> Result<Option<u64>, String> {
+    let s = input.symbol_addr;
+    let a = rela.r_addend;
+    let p = input.isec_addr + rela.r_offset;
+
+    let value = match rela.r_type {
+        // Padding of R_RISCV_ALIGN is removed from the input section instead
+        abi::R_RISCV_NONE | abi::R_RISCV_RELAX | abi::R_RISCV_ALIGN => return Ok(None),
+        abi::R_RISCV_32
+        | abi::R_RISCV_64
+        | abi::R_RISCV_HI20
+        | abi::R_RISCV_LO12_I
+        | abi::R_RISCV_LO12_S
+        | abi::R_RISCV_ADD8
+        | abi::R_RISCV_ADD16
+        | abi::R_RISCV_ADD32
+        | abi::R_RISCV_ADD64
+        | abi::R_RISCV_SUB6
+        | abi::R_RISCV_SUB8
+        | abi::R_RISCV_SUB16
+        | abi::R_RISCV_SUB32
+        | abi::R_RISCV_SUB64
+        | abi::R_RISCV_SET6
+        | abi::R_RISCV_SET8
+        | abi::R_RISCV_SET16
+        | abi::R_RISCV_SET32 => s as i64 + a,
+        abi::R_RISCV_BRANCH
+        | abi::R_RISCV_JAL
+        | abi::R_RISCV_CALL
+        | abi::R_RISCV_CALL_PLT
+        | abi::R_RISCV_PCREL_HI20
+        | abi::R_RISCV_RVC_BRANCH
+        | abi::R_RISCV_RVC_JUMP
+        | abi::R_RISCV_32_PCREL => s as i64 + a - p as i64,
+        // The symbol is the label of the AUIPC
+        abi::R_RISCV_PCREL_LO12_I | abi::R_RISCV_PCREL_LO12_S => {
+            input.pcrel_hi20_value.ok_or(format!(
+                "{} does not refer to an instruction with R_RISCV_PCREL_HI20",
+                r_type_as_str(rela.r_type)
+            ))? as i64
+        }
+        r_type => return Err(unsupported_relocation(r_type)),
+    };
+    Ok(Some(value as u64))
+}
+
+fn unsupported_relocation(r_type: u32) -> String {
+    format!("unsupported relocation {}", r_type_as_str(r_type))
+}
+
+/// Returns the range of values which the relocation can hold. HI20 and LO12 are added with
+/// the low 12 bits sign-extended, so the high part is rounded.
+pub fn relocation_range(r_type: u32) -> Option<(i64, i64)> {
+    let bits = |n: u32| Some((-(1 << (n - 1)), (1 << (n - 1)) - 1));
+    match r_type {
+        abi::R_RISCV_32 => Some((i32::MIN as i64, u32::MAX as i64)),
+        abi::R_RISCV_32_PCREL => bits(32),
+        abi::R_RISCV_HI20 | abi::R_RISCV_PCREL_HI20 | abi::R_RISCV_CALL | abi::R_RISCV_CALL_PLT => {
+            Some((i32::MIN as i64 - 0x800, i32::MAX as i64 - 0x800))
+        }
+        abi::R_RISCV_BRANCH => bits(13),
+        abi::R_RISCV_JAL => bits(21),
+        abi::R_RISCV_RVC_BRANCH => bits(9),
+        abi::R_RISCV_RVC_JUMP => bits(12),
+        _ => None,
+    }
+}
+
+/// Returns the size of the relocated field. CALL covers both AUIPC and JALR.
+pub fn relocation_size(r_type: u32) -> Result<usize, String> {
+    let size = match r_type {
+        abi::R_RISCV_NONE | abi::R_RISCV_RELAX | abi::R_RISCV_ALIGN => 0,
+        abi::R_RISCV_64 | abi::R_RISCV_ADD64 | abi::R_RISCV_SUB64 => 8,
+        abi::R_RISCV_CALL | abi::R_RISCV_CALL_PLT => 8,
+        abi::R_RISCV_32
+        | abi::R_RISCV_32_PCREL
+        | abi::R_RISCV_ADD32
+        | abi::R_RISCV_SUB32
+        | abi::R_RISCV_SET32 => 4,
+        abi::R_RISCV_ADD16 | abi::R_RISCV_SUB16 | abi::R_RISCV_SET16 => 2,
+        abi::R_RISCV_ADD8
+        | abi::R_RISCV_SUB8
+        | abi::R_RISCV_SET8
+        | abi::R_RISCV_SUB6
+        | abi::R_RISCV_SET6 => 1,
+        abi::R_RISCV_RVC_BRANCH | abi::R_RISCV_RVC_JUMP => 2,
+        abi::R_RISCV_BRANCH
+        | abi::R_RISCV_JAL
+        | abi::R_RISCV_PCREL_HI20
+        | abi::R_RISCV_PCREL_LO12_I
+        | abi::R_RISCV_PCREL_LO12_S
+        | abi::R_RISCV_HI20
+        | abi::R_RISCV_LO12_I
+        | abi::R_RISCV_LO12_S => 4,
+        r_type => return Err(unsupported_relocation(r_type)),
+    };
+    Ok(size)
+}
+
+/// Returns bits `hi` to `lo` of `value` moved to bit `to`
+fn bits(value: u64, hi: u32, lo: u32, to: u32) -> u32 {
+    (((value >> lo) & ((1 << (hi - lo + 1)) - 1)) as u32) << to
+}
+
+/// High 20 bits for LUI or AUIPC, rounded so that adding the sign-extended low 12 bits
+/// gives the value
+fn hi20(value: u64) -> u32 {
+    (value.wrapping_add(0x800) as u32) & 0xfffff000
+}
+
+fn itype(value: u64) -> u32 {
+    bits(value, 11, 0, 20)
+}
+
+fn stype(value: u64) -> u32 {
+    bits(value, 11, 5, 25) | bits(value, 4, 0, 7)
+}
+
+fn btype(value: u64) -> u32 {
+    bits(value, 12, 12, 31) | bits(value, 10, 5, 25) | bits(value, 4, 1, 8) | bits(value, 11, 11, 7)
+}
+
+fn jtype(value: u64) -> u32 {
+    bits(value, 20, 20, 31)
+        | bits(value, 10, 1, 21)
+        | bits(value, 11, 11, 20)
+        | bits(value, 19, 12, 12)
+}
+
+fn cbtype(value: u64) -> u16 {
+    (bits(value, 8, 8, 12)
+        | bits(value, 4, 3, 10)
+        | bits(value, 7, 6, 5)
+        | bits(value, 2, 1, 3)
+        | bits(value, 5, 5, 2)) as u16
+}
+
+fn cjtype(value: u64) -> u16 {
+    (bits(value, 11, 11, 12)
+        | bits(value, 4, 4, 11)
+        | bits(value, 9, 8, 9)
+        | bits(value, 10, 10, 8)
+        | bits(value, 6, 6, 7)
+        | bits(value, 7, 7, 6)
+        | bits(value, 3, 1, 3)
+        | bits(value, 5, 5, 2)) as u16
+}
+
+/// Returns the bytes written for a relocation with `value`, as a little-endian integer.
+/// `field` holds the current contents of the relocated field, i.e. the instruction whose
+/// immediate is replaced, or the value which ADD and SUB relocations change.
+pub fn encode_relocation(r_type: u32, value: u64, field: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf[..field.len()].copy_from_slice(field);
+    let old = u64::from_le_bytes(buf);
+    let insn = old as u32;
+    match r_type {
+        abi::R_RISCV_ADD8 | abi::R_RISCV_ADD16 | abi::R_RISCV_ADD32 | abi::R_RISCV_ADD64 => {
+            old.wrapping_add(value)
+        }
+        abi::R_RISCV_SUB8 | abi::R_RISCV_SUB16 | abi::R_RISCV_SUB32 | abi::R_RISCV_SUB64 => {
+            old.wrapping_sub(value)
+        }
+        abi::R_RISCV_SUB6 => (old & 0xc0) | (old.wrapping_sub(value) & 0x3f),
+        abi::R_RISCV_SET6 => (old & 0xc0) | (value & 0x3f),
+        abi::R_RISCV_HI20 | abi::R_RISCV_PCREL_HI20 => ((insn & 0xfff) | hi20(value)) as u64,
+        abi::R_RISCV_LO12_I | abi::R_RISCV_PCREL_LO12_I => {
+            ((insn & 0x000fffff) | itype(value)) as u64
+        }
+        abi::R_RISCV_LO12_S | abi::R_RISCV_PCREL_LO12_S => {
+            ((insn & 0x01fff07f) | stype(value)) as u64
+        }
+        abi::R_RISCV_BRANCH => ((insn & 0x01fff07f) | btype(value)) as u64,
+        abi::R_RISCV_JAL => ((insn & 0xfff) | jtype(value)) as u64,
+        abi::R_RISCV_RVC_BRANCH => ((insn as u16 & 0xe383) | cbtype(value)) as u64,
+        abi::R_RISCV_RVC_JUMP => ((insn as u16 & 0xe003) | cjtype(value)) as u64,
+        // AUIPC followed by JALR
+        abi::R_RISCV_CALL | abi::R_RISCV_CALL_PLT => {
+            let auipc = (insn & 0xfff) | hi20(value);
+            let jalr = ((old >> 32) as u32 & 0x000fffff) | itype(value);
+            (jalr as u64) << 32 | auipc as u64
+        }
+        _ => value,
+    }
+}
+
+/// Range of padding which is removed from an input section
+#[derive(Debug, Clone, Copy)]
+pub struct Deletion {
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// Returns the padding removed at each R_RISCV_ALIGN relocation of a section, sorted by
+/// offset. The assembler emits NOPs of r_addend bytes, which is enough for any address of
+/// the padding, and the linker keeps only as many as needed for the alignment.
+///
+/// The section must be aligned at least as strictly as each padding, so that the padding
+/// depends only on offsets in the section and can be removed before layout.
+pub fn align_deletions(relas: &[Rela], sh_addralign: u64) -> Result<Vec<Deletion>, String> {
+    let mut aligns = relas
+        .iter()
+        .filter(|rela| rela.r_type == abi::R_RISCV_ALIGN)
+        .collect::<Vec<_>>();
+    aligns.sort_by_key(|rela| rela.r_offset);
+    let mut deletions = Vec::<Deletion>::new();
+    let mut removed = 0;
+    for rela in aligns {
+        let padding = rela.r_addend as u64;
+        let alignment = (padding + 1).next_power_of_two();
+        if alignment > sh_addralign.max(1) {
+            return Err(format!(
+                "R_RISCV_ALIGN at {:#x} needs alignment {} but the section is aligned to {}",
+                rela.r_offset, alignment, sh_addralign
+            ));
+        }
+        let loc = rela.r_offset - removed;
+        let kept = loc.next_multiple_of(alignment) - loc;
+        if kept < padding {
+            deletions.push(Deletion {
+                offset: rela.r_offset + kept,
+                size: padding - kept,
+            });
+            removed += padding - kept;
+        }
+    }
+    Ok(deletions)
+}
+
+/// Returns where `offset` in the original section is after the deletions
+pub fn offset_after_deletions(deletions: &[Deletion], offset: u64) -> u64 {
+    let mut removed = 0;
+    for deletion in deletions {
+        if offset <= deletion.offset {
+            break;
+        }
+        removed += (offset - deletion.offset).min(deletion.size);
+    }
+    offset - removed
+}
+
+/// Returns the contents of a section without the deleted padding. The remaining padding
+/// is rewritten with NOPs since it may end in the middle of a 4-byte NOP.
+pub fn delete_padding(data: &[u8], deletions: &[Deletion], relas: &[Rela]) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(data.len());
+    let mut pos = 0;
+    for deletion in deletions {
+        let start = deletion.offset as usize;
+        ret.extend_from_slice(&data[pos..start]);
+        pos = start + deletion.size as usize;
+    }
+    ret.extend_from_slice(&data[pos..]);
+    for rela in relas
+        .iter()
+        .filter(|rela| rela.r_type == abi::R_RISCV_ALIGN)
+    {
+        let start = offset_after_deletions(deletions, rela.r_offset) as usize;
+        let end = offset_after_deletions(deletions, rela.r_offset + rela.r_addend as u64) as usize;
+        let mut nops = ret[start..end].chunks_exact_mut(4);
+        for nop in &mut nops {
+            // addi x0, x0, 0
+            nop.copy_from_slice(&0x00000013u32.to_le_bytes());
+        }
+        let rest = nops.into_remainder();
+        if !rest.is_empty() {
+            // c.nop
+            rest.copy_from_slice(&0x0001u16.to_le_bytes());
+        }
+    }
+    ret
+}
+
+/// Returns e_flags of an output with objects whose e_flags are `flags` and `other`. The
+/// float ABI and RVE must match, and RVC and TSO are used if any object uses them.
+pub fn merge_e_flags(flags: u32, other: u32) -> Result<u32, String> {
+    let float_abi = |flags: u32| match flags & EF_RISCV_FLOAT_ABI_MASK {
+        abi::EF_RISCV_FLOAT_ABI_SOFT => "soft-float",
+        abi::EF_RISCV_FLOAT_ABI_SINGLE => "single-float",
+        abi::EF_RISCV_FLOAT_ABI_DOUBLE => "double-float",
+        _ => "quad-float",
+    };
+    if (flags ^ other) & EF_RISCV_FLOAT_ABI_MASK != 0 {
+        return Err(format!(
+            "{} object is incompatible with {} objects",
+            float_abi(other),
+            float_abi(flags)
+        ));
+    }
+    if (flags ^ other) & EF_RISCV_RVE != 0 {
+        return Err("RVE object is incompatible with RVI objects".to_owned());
+    }
+    Ok(flags | (other & (EF_RISCV_RVC | EF_RISCV_TSO)))
+}
+
+pub fn r_type_as_str(r_type: u32) -> String {
+    let name = match r_type {
+        abi::R_RISCV_NONE => "R_RISCV_NONE",
+        abi::R_RISCV_32 => "R_RISCV_32",
+        abi::R_RISCV_64 => "R_RISCV_64",
+        abi::R_RISCV_RELATIVE => "R_RISCV_RELATIVE",
+        abi::R_RISCV_COPY => "R_RISCV_COPY",
+        abi::R_RISCV_JUMP_SLOT => "R_RISCV_JUMP_SLOT",
+        abi::R_RISCV_BRANCH => "R_RISCV_BRANCH",
+        abi::R_RISCV_JAL => "R_RISCV_JAL",
+        abi::R_RISCV_CALL => "R_RISCV_CALL",
+        abi::R_RISCV_CALL_PLT => "R_RISCV_CALL_PLT",
+        abi::R_RISCV_GOT_HI20 => "R_RISCV_GOT_HI20",
+        abi::R_RISCV_TLS_GOT_HI20 => "R_RISCV_TLS_GOT_HI20",
+        abi::R_RISCV_TLS_GD_HI20 => "R_RISCV_TLS_GD_HI20",
+        abi::R_RISCV_PCREL_HI20 => "R_RISCV_PCREL_HI20",
+        abi::R_RISCV_PCREL_LO12_I => "R_RISCV_PCREL_LO12_I",
+        abi::R_RISCV_PCREL_LO12_S => "R_RISCV_PCREL_LO12_S",
+        abi::R_RISCV_HI20 => "R_RISCV_HI20",
+        abi::R_RISCV_LO12_I => "R_RISCV_LO12_I",
+        abi::R_RISCV_LO12_S => "R_RISCV_LO12_S",
+        abi::R_RISCV_TPREL_HI20 => "R_RISCV_TPREL_HI20",
+        abi::R_RISCV_TPREL_LO12_I => "R_RISCV_TPREL_LO12_I",
+        abi::R_RISCV_TPREL_LO12_S => "R_RISCV_TPREL_LO12_S",
+        abi::R_RISCV_TPREL_ADD => "R_RISCV_TPREL_ADD",
+        abi::R_RISCV_ADD8 => "R_RISCV_ADD8",
+        abi::R_RISCV_ADD16 => "R_RISCV_ADD16",
+        abi::R_RISCV_ADD32 => "R_RISCV_ADD32",
+        abi::R_RISCV_ADD64 => "R_RISCV_ADD64",
+        abi::R_RISCV_SUB8 => "R_RISCV_SUB8",
+        abi::R_RISCV_SUB16 => "R_RISCV_SUB16",
+        abi::R_RISCV_SUB32 => "R_RISCV_SUB32",
+        abi::R_RISCV_SUB64 => "R_RISCV_SUB64",
+        abi::R_RISCV_ALIGN => "R_RISCV_ALIGN",
+        abi::R_RISCV_RVC_BRANCH => "R_RISCV_RVC_BRANCH",
+        abi::R_RISCV_RVC_JUMP => "R_RISCV_RVC_JUMP",
+        abi::R_RISCV_RVC_LUI => "R_RISCV_RVC_LUI",
+        abi::R_RISCV_RELAX => "R_RISCV_RELAX",
+        abi::R_RISCV_SUB6 => "R_RISCV_SUB6",
+        abi::R_RISCV_SET6 => "R_RISCV_SET6",
+        abi::R_RISCV_SET8 => "R_RISCV_SET8",
+        abi::R_RISCV_SET16 => "R_RISCV_SET16",
+        abi::R_RISCV_SET32 => "R_RISCV_SET32",
+        abi::R_RISCV_32_PCREL => "R_RISCV_32_PCREL",
+        abi::R_RISCV_IRELATIVE => "R_RISCV_IRELATIVE",
+        _ => return format!("unknown ({})", r_type),
+    };
+    name.to_owned()
+}
+
+pub struct RiscV64;
+
+impl Target for RiscV64 {
+    fn name(&self) -> &'static str {
+        "riscv64"
+    }
+
+    fn e_machine(&self) -> u16 {
+        abi::EM_RISCV
+    }
+
+    fn emulations(&self) -> &'static [&'static str] {
+        &["elf64lriscv"]
+    }
+
+    fn merge_e_flags(&self, flags: Option<u32>, other: u32) -> Result<u32, String> {
+        match flags {
+            None => Ok(other),
+            Some(flags) => merge_e_flags(flags, other),
+        }
+    }
+
+    fn prepare_input(&self, contents: &mut ElfContents) -> Result<(), String> {
+        contents.remove_align_padding()
+    }
+
+    /// Attributes of inputs can't be concatenated and are not needed to run the output, so
+    /// they are dropped instead of being merged
+    fn is_dropped_section(&self, sh_type: u32) -> bool {
+        sh_type == abi::SHT_RISCV_ATTRIBUTES
+    }
+
+    fn relocation_value(
+        &self,
+        input: &RelocationInput,
+        rela: &Rela,
+    ) -> Result<Option<u64>, String> {
+        relocation_value(input, rela)
+    }
+
+    fn apply_relocation(&self, r_type: u32, value: u64, field: &[u8]) -> u64 {
+        encode_relocation(r_type, value, field)
+    }
+
+    fn relocation_range(&self, r_type: u32) -> Option<(i64, i64)> {
+        relocation_range(r_type)
+    }
+
+    fn relocation_size(&self, r_type: u32) -> Result<usize, String> {
+        relocation_size(r_type)
+    }
+
+    fn r_type_as_str(&self, r_type: u32) -> String {
+        r_type_as_str(r_type)
+    }
+
+    fn is_pcrel_hi(&self, r_type: u32) -> bool {
+        r_type == abi::R_RISCV_PCREL_HI20
+    }
+
+    /// e.g. ADD32 and SUB32 for the difference of two labels
+    fn composes_relocations(&self) -> bool {
+        true
+    }
+}
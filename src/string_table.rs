@@ -0,0 +1,76 @@
+// Builder of string tables such as .strtab, .shstrtab and .dynstr
+
+use std::collections::HashMap;
+
+/// A string table which stores each string once. The offset of a string never changes
+/// once it is added.
+pub struct StringTable {
+    content: Vec<u8>,
+    offsets: HashMap<String, u32>,
+}
+
+impl StringTable {
+    pub fn new() -> StringTable {
+        StringTable {
+            content: vec![0],
+            offsets: HashMap::new(),
+        }
+    }
+
+    /// Builds a table of all the strings at once. A string which is a suffix of another one
+    /// (e.g. .text of .rela.text) points into the longer one instead of being stored again.
+    pub fn with_suffix_sharing<'a>(strings: impl Iterator<Item = &'a str>) -> StringTable {
+        let mut strings = strings.collect::<Vec<_>>();
+        // In the descending order of the reversed strings, a string comes right after the
+        // longest one ending with it
+        strings.sort_by(|a, b| b.bytes().rev().cmp(a.bytes().rev()));
+        strings.dedup();
+
+        let mut table = StringTable::new();
+        let mut prev: Option<(&str, u32)> = None;
+        for string in strings {
+            let offset = match prev {
+                Some((prev, offset)) if prev.ends_with(string) => {
+                    offset + (prev.len() - string.len()) as u32
+                }
+                _ => {
+                    let offset = table.append(string);
+                    prev = Some((string, offset));
+                    offset
+                }
+            };
+            table.offsets.insert(string.to_owned(), offset);
+        }
+        table
+    }
+
+    /// Returns the offset of the string, adding it if it is not in the table yet
+    pub fn add(&mut self, string: &str) -> u32 {
+        if let Some(offset) = self.offsets.get(string) {
+            return *offset;
+        }
+        let offset = self.append(string);
+        self.offsets.insert(string.to_owned(), offset);
+        offset
+    }
+
+    /// Returns the offset of a string in the table
+    pub fn get(&self, string: &str) -> u32 {
+        self.offsets[string]
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    fn append(&mut self, string: &str) -> u32 {
+        // The empty string is the NUL byte at offset 0
+        if string.is_empty() {
+            return 0;
+        }
+        let offset = self.content.len() as u32;
+        self.content.extend_from_slice(string.as_bytes());
+        self.content.push(0);
+        offset
+    }
+}
@@ -0,0 +1,200 @@
+//! Interned symbol names. Each link has its own interner, which owns the names of the link
+//! and frees them when the link is done.
+
+use std::{
+    borrow::Borrow,
+    fmt,
+    hash::{BuildHasher, Hash, Hasher},
+    ops::Deref,
+    ptr::NonNull,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+use rustc_hash::{FxBuildHasher, FxHashMap};
+
+/// Name of a symbol. Equal names are interned to the same ID, so names are compared and
+/// hashed by the ID instead of their contents. The name is owned by the interner of the link,
+/// so it must not be used after the link.
+#[derive(Clone, Copy)]
+pub struct SymbolName {
+    id: u32,
+    name: NonNull<str>,
+}
+
+// SAFETY: Names are never modified, and the interner is shared by the threads of a link
+unsafe impl Send for SymbolName {}
+unsafe impl Sync for SymbolName {}
+
+impl SymbolName {
+    pub fn as_str(&self) -> &str {
+        // SAFETY: The interner outlives everything in the link which holds names
+        unsafe { self.name.as_ref() }
+    }
+}
+
+impl Deref for SymbolName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for SymbolName {
+    fn eq(&self, other: &SymbolName) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for SymbolName {}
+
+impl PartialEq<str> for SymbolName {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for SymbolName {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Hash for SymbolName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+/// Names are ordered by their contents, not by the order they were interned in
+impl PartialOrd for SymbolName {
+    fn partial_cmp(&self, other: &SymbolName) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SymbolName {
+    fn cmp(&self, other: &SymbolName) -> std::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl fmt::Display for SymbolName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for SymbolName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// Names are copied into large chunks so that interning doesn't allocate for each name.
+/// Chunks never grow beyond their capacity, so the names don't move until the arena is
+/// dropped.
+#[derive(Default)]
+struct Arena {
+    /// The last chunk is the one being filled
+    chunks: Vec<Vec<u8>>,
+}
+
+impl Arena {
+    const CHUNK_SIZE: usize = 1 << 16;
+
+    fn alloc(&mut self, name: &str) -> NonNull<str> {
+        let chunk = if name.len() > Self::CHUNK_SIZE / 4 {
+            // Long names have chunks of their own, which are not filled any further
+            let pos = self.chunks.len().saturating_sub(1);
+            self.chunks.insert(pos, Vec::with_capacity(name.len()));
+            &mut self.chunks[pos]
+        } else {
+            if self
+                .chunks
+                .last()
+                .is_none_or(|chunk| chunk.capacity() - chunk.len() < name.len())
+            {
+                self.chunks.push(Vec::with_capacity(Self::CHUNK_SIZE));
+            }
+            self.chunks.last_mut().unwrap()
+        };
+        let start = chunk.len();
+        chunk.extend_from_slice(name.as_bytes());
+        NonNull::from(std::str::from_utf8(&chunk[start..]).unwrap())
+    }
+}
+
+/// Name in an arena, which is hashed and compared by its contents
+struct ArenaStr(NonNull<str>);
+
+// SAFETY: Names are never modified
+unsafe impl Send for ArenaStr {}
+
+impl Borrow<str> for ArenaStr {
+    fn borrow(&self) -> &str {
+        // SAFETY: Keys are removed along with the arena
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl Hash for ArenaStr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        <str as Hash>::hash(self.borrow(), state);
+    }
+}
+
+impl PartialEq for ArenaStr {
+    fn eq(&self, other: &ArenaStr) -> bool {
+        <ArenaStr as Borrow<str>>::borrow(self) == <ArenaStr as Borrow<str>>::borrow(other)
+    }
+}
+
+impl Eq for ArenaStr {}
+
+#[derive(Default)]
+struct Shard {
+    /// Keys point into `arena`
+    ids: FxHashMap<ArenaStr, u32>,
+    arena: Arena,
+}
+
+/// Table of the names interned by a link. It is split into shards by the hash of names so
+/// that threads parsing different files rarely wait for each other. Dropping it frees the
+/// names.
+pub struct Interner {
+    shards: Vec<Mutex<Shard>>,
+    next_id: AtomicU32,
+}
+
+impl Default for Interner {
+    fn default() -> Interner {
+        Interner {
+            shards: (0..Self::NUM_SHARDS)
+                .map(|_| Mutex::new(Shard::default()))
+                .collect(),
+            next_id: AtomicU32::new(0),
+        }
+    }
+}
+
+impl Interner {
+    const NUM_SHARDS: usize = 16;
+
+    pub fn intern(&self, name: &str) -> SymbolName {
+        let hash = FxBuildHasher.hash_one(name);
+        let mut shard = self.shards[hash as usize % Self::NUM_SHARDS]
+            .lock()
+            .unwrap();
+        if let Some((name, &id)) = shard.ids.get_key_value(name) {
+            return SymbolName { id, name: name.0 };
+        }
+        let name = shard.arena.alloc(name);
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        shard.ids.insert(ArenaStr(name), id);
+        SymbolName { id, name }
+    }
+}
@@ -0,0 +1,294 @@
+//! Architectures which bold links for. The target is given by -m or is the machine of the
+//! input files, which must all be the same. Everything specific to the machine is behind
+//! the `Target` trait, which each architecture implements in its own module.
+
+use elf::relocation::Rela;
+
+use crate::{
+    aarch64::AArch64,
+    elf_class::ElfClass,
+    i386::I386,
+    input_section::ElfContents,
+    relocation::{RelaxedInsn, RelocationInput},
+    riscv::RiscV64,
+    x86_64::X86_64,
+};
+
+/// Targets in the order they are looked up
+const TARGETS: [&dyn Target; 4] = [&X86_64, &AArch64, &RiscV64, &I386];
+
+/// Returns the target of objects with `e_machine`, or None if it is not supported
+pub fn from_e_machine(e_machine: u16) -> Option<&'static dyn Target> {
+    TARGETS
+        .into_iter()
+        .find(|target| target.e_machine() == e_machine)
+}
+
+/// Returns the target of an emulation of GNU ld given by -m, or None if it is not supported
+pub fn from_emulation(emulation: &str) -> Option<&'static dyn Target> {
+    TARGETS
+        .into_iter()
+        .find(|target| target.emulations().contains(&emulation))
+}
+
+/// Relocations of TLS sequences, which need GOT entries unless the linker relaxes them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsReloc {
+    /// Loads the TP-relative offset of the symbol from the GOT (initial-exec)
+    GotTpOff,
+    /// Passes the GOT entries of the module ID and the offset of the symbol to
+    /// __tls_get_addr (general-dynamic)
+    Gd,
+    /// Passes the GOT entries of the module ID to __tls_get_addr (local-dynamic)
+    Ld,
+    /// Offset of the symbol in the TLS block of the module, which code adds to the result
+    /// of a local-dynamic sequence
+    LdOffset,
+    /// Computes the address of a TLS descriptor
+    Desc,
+    /// Calls the function of a TLS descriptor
+    DescCall,
+}
+
+/// Types of the dynamic relocations which the linker emits
+pub struct DynamicRelocTypes {
+    /// Absolute address of a symbol
+    pub absolute: u32,
+    /// Address relative to the load address of the output
+    pub relative: u32,
+    /// Address of a symbol stored in the GOT
+    pub glob_dat: u32,
+    /// Address of a function stored in .got.plt, which may be resolved lazily
+    pub jump_slot: u32,
+    /// TP-relative offset of a TLS symbol
+    pub tpoff: u32,
+    /// Module ID of a TLS symbol
+    pub dtpmod: u32,
+    /// Offset of a TLS symbol in the TLS block of its module
+    pub dtpoff: u32,
+    /// Copies the initial value of a data object from a DSO to the executable
+    pub copy: u32,
+}
+
+pub trait Target: Sync {
+    /// Name in diagnostics
+    fn name(&self) -> &'static str;
+
+    fn e_machine(&self) -> u16;
+
+    /// Names of the emulations of GNU ld which select the target
+    fn emulations(&self) -> &'static [&'static str];
+
+    /// Class of the inputs and the output
+    fn class(&self) -> ElfClass {
+        ElfClass::Elf64
+    }
+
+    /// Default maximum page size, which segments are aligned to
+    fn page_size(&self) -> u64 {
+        0x1000
+    }
+
+    /// Address of the first segment unless --image-base is given
+    fn default_image_base(&self) -> u64 {
+        0x400000
+    }
+
+    /// Returns the types of dynamic relocations, or None if dynamic linking is not
+    /// implemented for the target
+    fn dynamic_reloc_types(&self) -> Option<&'static DynamicRelocTypes> {
+        None
+    }
+
+    /// Returns true if dynamic linking is implemented, i.e. the GOT, PLT and dynamic
+    /// relocations
+    fn supports_dynamic_linking(&self) -> bool {
+        self.dynamic_reloc_types().is_some()
+    }
+
+    /// Rewrite the contents of an input file before input sections are created from them
+    fn prepare_input(&self, _contents: &mut ElfContents) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Returns true if input sections of the processor-specific type are dropped
+    fn is_dropped_section(&self, _sh_type: u32) -> bool {
+        false
+    }
+
+    /// Returns e_flags of the output after adding an object with `other`. `flags` is None
+    /// for the first object. It is an error if the objects can't be linked together.
+    fn merge_e_flags(&self, _flags: Option<u32>, _other: u32) -> Result<u32, String> {
+        Ok(0)
+    }
+
+    /// Returns the value of the relocated field, or None if nothing is written. It is an
+    /// error if the relocation type is not supported.
+    fn relocation_value(&self, input: &RelocationInput, rela: &Rela)
+        -> Result<Option<u64>, String>;
+
+    /// Returns the bytes written to the relocated field for `value`, as a little-endian
+    /// integer. `field` holds the current contents of the field. They are the value itself
+    /// unless it is encoded into an instruction.
+    fn apply_relocation(&self, _r_type: u32, value: u64, _field: &[u8]) -> u64 {
+        value
+    }
+
+    /// Returns the range of values which the relocation can hold if it is checked for
+    /// overflow
+    fn relocation_range(&self, r_type: u32) -> Option<(i64, i64)>;
+
+    /// Returns the size of the relocated field. It is an error if the relocation type is
+    /// not supported.
+    fn relocation_size(&self, r_type: u32) -> Result<usize, String>;
+
+    /// Returns an error if the linker can't apply relocations of the type
+    fn check_relocation_type(&self, r_type: u32) -> Result<(), String> {
+        self.relocation_size(r_type).map(|_| ())
+    }
+
+    fn r_type_as_str(&self, r_type: u32) -> String;
+
+    /// Returns true if the relocation needs a GOT entry holding the address of the symbol
+    fn needs_got(&self, _r_type: u32) -> bool {
+        false
+    }
+
+    /// Returns true if the relocation is relative to the GOT, which therefore has to exist
+    /// even without entries
+    fn is_got_relative(&self, _r_type: u32) -> bool {
+        false
+    }
+
+    /// Returns true if the relocation takes the absolute address of the symbol
+    fn is_absolute_relocation(&self, _r_type: u32) -> bool {
+        false
+    }
+
+    /// Returns true if the relocation is a call, which goes through the PLT if the function
+    /// may be bound elsewhere at runtime
+    fn is_call(&self, _r_type: u32) -> bool {
+        false
+    }
+
+    /// Returns the kind of the relocation if it belongs to a TLS sequence
+    fn tls_reloc(&self, _r_type: u32) -> Option<TlsReloc> {
+        None
+    }
+
+    /// Returns true if the relocation computes the upper bits of a PC-relative address,
+    /// whose lower bits are computed by relocations referring to the instruction
+    fn is_pcrel_hi(&self, _r_type: u32) -> bool {
+        false
+    }
+
+    /// Returns true if a relocation applies to the result of the previous one at the same
+    /// offset
+    fn composes_relocations(&self) -> bool {
+        false
+    }
+
+    fn got_entry_size(&self) -> u64 {
+        self.class().word_size()
+    }
+
+    // The PLT is only created by targets which support dynamic linking
+
+    fn plt_header_size(&self) -> u64 {
+        0
+    }
+
+    fn plt_entry_size(&self) -> u64 {
+        0
+    }
+
+    /// Write the first entry of the PLT, which calls the dynamic linker, to `buf`
+    fn write_plt_header(&self, _buf: &mut [u8], _plt_addr: u64, _got_plt_addr: u64) {
+        unreachable!("{} has no PLT", self.name());
+    }
+
+    /// Write the PLT entry at `idx` to `buf`, which jumps to the address stored at
+    /// `got_entry_addr`
+    fn write_plt_entry(
+        &self,
+        _buf: &mut [u8],
+        _entry_addr: u64,
+        _got_entry_addr: u64,
+        _plt_addr: u64,
+        _idx: usize,
+    ) {
+        unreachable!("{} has no PLT", self.name());
+    }
+
+    /// Returns the address which the .got.plt entry of the PLT entry at `entry_addr` holds
+    /// until the function is resolved lazily
+    fn plt_lazy_addr(&self, _entry_addr: u64) -> u64 {
+        unreachable!("{} has no PLT", self.name());
+    }
+
+    // Relaxation rewrites instructions so that they don't need the GOT or
+    // __tls_get_addr. The linker decides whether a relocation may be relaxed, and the
+    // target returns the new instructions, or None if they can't be relaxed. TLS
+    // relaxations take `data`, the contents of the input section, and return the offset of
+    // the new instructions in it along with them.
+
+    /// Relax an instruction loading the address of a symbol from the GOT. `insn` is the
+    /// section data up to the relocated field.
+    fn relax_gotpcrelx(&self, _r_type: u32, _insn: &[u8]) -> Option<RelaxedInsn> {
+        None
+    }
+
+    /// Relax an instruction loading a TP-relative offset from the GOT to load `tpoff` as an
+    /// immediate
+    fn relax_gottpoff(&self, _data: &[u8], _rela: &Rela, _tpoff: u64) -> Option<(usize, Vec<u8>)> {
+        None
+    }
+
+    /// Relax the instruction computing the address of a TLS descriptor to load `tpoff` as
+    /// an immediate (local-exec)
+    fn relax_tlsdesc_to_le(
+        &self,
+        _data: &[u8],
+        _rela: &Rela,
+        _tpoff: u64,
+    ) -> Option<(usize, Vec<u8>)> {
+        None
+    }
+
+    /// Relax the instruction computing the address of a TLS descriptor to load the
+    /// TP-relative offset from the GOT (initial-exec). `got_entry_addr` is the address of
+    /// the GOT entry and `p` is the address of the relocated field.
+    fn relax_tlsdesc_to_ie(
+        &self,
+        _data: &[u8],
+        _rela: &Rela,
+        _got_entry_addr: u64,
+        _p: u64,
+    ) -> Option<(usize, Vec<u8>)> {
+        None
+    }
+
+    /// Relax the call of a TLS descriptor, whose result is already computed by the relaxed
+    /// sequence
+    fn relax_tlsdesc_call(&self, _data: &[u8], _rela: &Rela) -> Option<(usize, Vec<u8>)> {
+        None
+    }
+
+    /// Relax a general-dynamic TLS sequence and its call to __tls_get_addr, the relocation
+    /// `call`, to local-exec
+    fn relax_tlsgd(
+        &self,
+        _data: &[u8],
+        _rela: &Rela,
+        _call: &Rela,
+        _tpoff: u64,
+    ) -> Option<(usize, Vec<u8>)> {
+        None
+    }
+
+    /// Relax a local-dynamic TLS sequence and its call to __tls_get_addr, the relocation
+    /// `call`, to local-exec
+    fn relax_tlsld(&self, _data: &[u8], _rela: &Rela, _call: &Rela) -> Option<(usize, Vec<u8>)> {
+        None
+    }
+}
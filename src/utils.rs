@@ -1,26 +1,20 @@
-use std::ops::Index;
+use std::ops::Range;
 
 #[macro_export]
 /// Create a zero-cleared value of a given type.
 macro_rules! dummy {
     ($name: ty) => {
-        unsafe { std::mem::transmute([0 as u8; std::mem::size_of::<$name>()]) }
+        unsafe {
+            std::mem::transmute::<[u8; std::mem::size_of::<$name>()], $name>(
+                [0 as u8; std::mem::size_of::<$name>()],
+            )
+        }
     };
 }
 
 pub fn align_to(val: u64, align: u64) -> u64 {
     debug_assert!(align.is_power_of_two());
-    return (val + align - 1) & !(align - 1);
-}
-
-// e.g. padding(0x1234, 0x1000) == 0x2000 - 0x1234
-// padding(0x1000, 0x100) = 0x1000 - 0x1000
-pub fn padding(val: u64, align: u64) -> u64 {
-    if val % align == 0 {
-        0
-    } else {
-        align - (val % align)
-    }
+    (val + align - 1) & !(align - 1)
 }
 
 pub fn write_to<T>(buf: &mut [u8], offset: usize, data: &T) -> usize {
@@ -31,9 +25,40 @@ pub fn write_to<T>(buf: &mut [u8], offset: usize, data: &T) -> usize {
     size
 }
 
+/// Split `buf` into mutable slices for `ranges`, which are returned in the same order.
+/// Panics if the ranges overlap.
+pub fn split_disjoint_mut<'a>(mut buf: &'a mut [u8], ranges: &[Range<usize>]) -> Vec<&'a mut [u8]> {
+    let mut order = (0..ranges.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| ranges[i].start);
+    let mut slices = ranges.iter().map(|_| None).collect::<Vec<_>>();
+    // Offset of the start of `buf` in the original buffer
+    let mut base = 0;
+    for i in order {
+        let Range { start, end } = ranges[i];
+        assert!(base <= start, "overlapping ranges: {:?}", ranges[i]);
+        let (_, rest) = std::mem::take(&mut buf).split_at_mut(start - base);
+        let (slice, rest) = rest.split_at_mut(end - start);
+        slices[i] = Some(slice);
+        buf = rest;
+        base = end;
+    }
+    slices.into_iter().map(|slice| slice.unwrap()).collect()
+}
+
 pub fn is_c_identifier(symbol: &str) -> bool {
     let chars: Vec<_> = symbol.chars().collect();
-    return chars.len() > 0
-        && !matches!(chars[0], '0'..='9')
-        && chars.iter().all(|c| c.is_alphanumeric());
+    !chars.is_empty() && !chars[0].is_ascii_digit() && chars.iter().all(|c| c.is_alphanumeric())
+}
+
+/// Lower bound of reserved section indices, which is missing in elf::abi
+pub const SHN_LORESERVE: u16 = 0xff00;
+
+/// Returns the value of st_shndx for a section index. Indices from SHN_LORESERVE on are
+/// reserved, so the real index is stored in .symtab_shndx instead.
+pub fn to_st_shndx(shndx: usize) -> u16 {
+    if shndx < SHN_LORESERVE as usize {
+        shndx as u16
+    } else {
+        elf::abi::SHN_XINDEX
+    }
 }
@@ -0,0 +1,564 @@
+//! Relocations, PLT and relaxations of x86-64, which is the only target supporting dynamic
+//! linking.
+
+use elf::{abi, relocation::Rela};
+
+use crate::{
+    relocation::{RelaxedInsn, RelocationInput},
+    target::{DynamicRelocTypes, Target, TlsReloc},
+    utils::write_to,
+};
+
+const DYNAMIC_RELOC_TYPES: DynamicRelocTypes = DynamicRelocTypes {
+    absolute: abi::R_X86_64_64,
+    relative: abi::R_X86_64_RELATIVE,
+    glob_dat: abi::R_X86_64_GLOB_DAT,
+    jump_slot: abi::R_X86_64_JUMP_SLOT,
+    tpoff: abi::R_X86_64_TPOFF64,
+    dtpmod: abi::R_X86_64_DTPMOD64,
+    dtpoff: abi::R_X86_64_DTPOFF64,
+    copy: abi::R_X86_64_COPY,
+};
+
+/// Returns the value of the relocated field, or None if nothing is written. It is an error
+/// if the relocation type is not supported.
+pub fn relocation_value(input: &RelocationInput, rela: &Rela) -> Result<Option<u64>, String> {
+    let s = input.symbol_addr;
+    let a = rela.r_addend;
+    let p = input.isec_addr + rela.r_offset;
+    let z = input.symbol_size;
+    let tp_offset = input.tp_offset;
+    let dtp_offset = input.dtp_offset;
+    let got_entry_addr = input.got_entry_addr;
+    let got = input.got_addr;
+    let l = input.plt_entry_addr.unwrap_or(s);
+    let g = || got_entry_addr.expect("GOT entry is not allocated");
+
+    let value = match rela.r_type {
+        abi::R_X86_64_NONE => return Ok(None),
+        abi::R_X86_64_PC8 | abi::R_X86_64_PC16 | abi::R_X86_64_PC32 | abi::R_X86_64_PC64 => {
+            s as i64 + a - p as i64
+        }
+        abi::R_X86_64_PLT32 => l as i64 + a - p as i64,
+        abi::R_X86_64_8
+        | abi::R_X86_64_16
+        | abi::R_X86_64_32
+        | abi::R_X86_64_32S
+        | abi::R_X86_64_64 => s as i64 + a,
+        abi::R_X86_64_SIZE32 | abi::R_X86_64_SIZE64 => z as i64 + a,
+        abi::R_X86_64_GOTPC32 | abi::R_X86_64_GOTPC64 => got as i64 + a - p as i64,
+        // L is the same as S because functions in DSOs already resolve to their PLT entries
+        abi::R_X86_64_GOTOFF64 | abi::R_X86_64_PLTOFF64 => s as i64 + a - got as i64,
+        abi::R_X86_64_GOT32 | abi::R_X86_64_GOT64 => g() as i64 + a - got as i64,
+        abi::R_X86_64_TPOFF32 | abi::R_X86_64_TPOFF64 => s as i64 + a - tp_offset as i64,
+        abi::R_X86_64_DTPOFF32 | abi::R_X86_64_DTPOFF64 => s as i64 + a - dtp_offset as i64,
+        abi::R_X86_64_GOTPCREL
+        | abi::R_X86_64_GOTPCRELX
+        | abi::R_X86_64_REX_GOTPCRELX
+        | abi::R_X86_64_GOTTPOFF
+        | abi::R_X86_64_TLSGD
+        | abi::R_X86_64_TLSLD
+        | abi::R_X86_64_GOTPCREL64 => g() as i64 + a - p as i64,
+        r_type => return Err(unsupported_relocation(r_type)),
+    };
+    Ok(Some(value as u64))
+}
+
+fn unsupported_relocation(r_type: u32) -> String {
+    format!("unsupported relocation {}", r_type_as_str(r_type))
+}
+
+/// Returns true if the relocation needs a GOT entry holding the address of the symbol
+pub fn needs_got(r_type: u32) -> bool {
+    matches!(
+        r_type,
+        abi::R_X86_64_GOT32
+            | abi::R_X86_64_GOT64
+            | abi::R_X86_64_GOTPCREL
+            | abi::R_X86_64_GOTPCRELX
+            | abi::R_X86_64_REX_GOTPCRELX
+            | abi::R_X86_64_GOTPCREL64
+    )
+}
+
+/// Returns true if the relocation is relative to the GOT, which therefore has to exist even
+/// without entries
+pub fn is_got_relative(r_type: u32) -> bool {
+    matches!(
+        r_type,
+        abi::R_X86_64_GOTPC32
+            | abi::R_X86_64_GOTPC64
+            | abi::R_X86_64_GOTOFF64
+            | abi::R_X86_64_PLTOFF64
+    )
+}
+
+/// Relax GOTPCRELX or REX_GOTPCRELX as GNU ld does. `insn` is the section data up to the
+/// relocated field. Returns None if the instruction can't be relaxed.
+pub fn relax_gotpcrelx(r_type: u32, insn: &[u8]) -> Option<RelaxedInsn> {
+    let n = insn.len();
+    // mod == 0 and r/m == 0b101, i.e. RIP-relative
+    let is_rip_relative = |modrm: u8| modrm & 0xc7 == 0x05;
+    match r_type {
+        abi::R_X86_64_GOTPCRELX if n >= 2 => match (insn[n - 2], insn[n - 1]) {
+            // call *foo@GOTPCREL(%rip) -> addr32 call foo
+            (0xff, 0x15) => Some(RelaxedInsn {
+                bytes: vec![0x67, 0xe8, 0, 0, 0, 0],
+                start: 2,
+                disp: 2,
+            }),
+            // jmp *foo@GOTPCREL(%rip) -> jmp foo; nop
+            (0xff, 0x25) => Some(RelaxedInsn {
+                bytes: vec![0xe9, 0, 0, 0, 0, 0x90],
+                start: 2,
+                disp: 1,
+            }),
+            // mov foo@GOTPCREL(%rip), %reg -> lea foo(%rip), %reg
+            (0x8b, modrm) if is_rip_relative(modrm) => Some(RelaxedInsn {
+                bytes: vec![0x8d, modrm, 0, 0, 0, 0],
+                start: 2,
+                disp: 2,
+            }),
+            _ => None,
+        },
+        abi::R_X86_64_REX_GOTPCRELX if n >= 3 => match (insn[n - 3], insn[n - 2], insn[n - 1]) {
+            // mov foo@GOTPCREL(%rip), %reg -> lea foo(%rip), %reg
+            (rex, 0x8b, modrm) if rex & 0xf0 == 0x40 && is_rip_relative(modrm) => {
+                Some(RelaxedInsn {
+                    bytes: vec![rex, 0x8d, modrm, 0, 0, 0, 0],
+                    start: 3,
+                    disp: 3,
+                })
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns true if `call` is the call to __tls_get_addr following the TLSGD or TLSLD
+/// relocation `rela`, whose field ends `len` bytes before the call's field
+fn is_tls_get_addr_call(rela: &Rela, call: &Rela, len: u64) -> bool {
+    matches!(call.r_type, abi::R_X86_64_PLT32 | abi::R_X86_64_PC32)
+        && call.r_offset == rela.r_offset + len
+}
+
+/// Relax a general-dynamic TLS sequence to local-exec as GNU ld does. Returns the offset of
+/// the sequence in `data` and the new instructions. The symbol of `call` is expected to be
+/// checked by the caller.
+pub fn relax_tlsgd(data: &[u8], rela: &Rela, call: &Rela, tpoff: u64) -> Option<(usize, Vec<u8>)> {
+    if !is_tls_get_addr_call(rela, call, 8) {
+        return None;
+    }
+    let start = (rela.r_offset as usize).checked_sub(4)?;
+    // data16 lea foo@tlsgd(%rip), %rdi
+    // data16 data16 rex.W call __tls_get_addr@PLT
+    let seq = data.get(start..start + 16)?;
+    if seq[..4] != [0x66, 0x48, 0x8d, 0x3d] || seq[8..12] != [0x66, 0x66, 0x48, 0xe8] {
+        return None;
+    }
+    // The addend is relative to the end of the field, which the immediate is not
+    let tpoff = tpoff as i64 + rela.r_addend + 4;
+    let mut insns = vec![
+        0x64, 0x48, 0x8b, 0x04, 0x25, 0, 0, 0, 0, // mov %fs:0, %rax
+        0x48, 0x8d, 0x80, 0, 0, 0, 0, // lea foo@tpoff(%rax), %rax
+    ];
+    insns[12..].copy_from_slice(&(tpoff as u32).to_le_bytes());
+    Some((start, insns))
+}
+
+/// Relax a local-dynamic TLS sequence to local-exec as GNU ld does. Returns the offset of
+/// the sequence in `data` and the new instructions.
+pub fn relax_tlsld(data: &[u8], rela: &Rela, call: &Rela) -> Option<(usize, Vec<u8>)> {
+    if !is_tls_get_addr_call(rela, call, 5) {
+        return None;
+    }
+    let start = (rela.r_offset as usize).checked_sub(3)?;
+    // lea foo@tlsld(%rip), %rdi
+    // call __tls_get_addr@PLT
+    let seq = data.get(start..start + 12)?;
+    if seq[..3] != [0x48, 0x8d, 0x3d] || seq[7] != 0xe8 {
+        return None;
+    }
+    Some((
+        start,
+        vec![
+            0x66, 0x66, 0x66, 0x64, 0x48, 0x8b, 0x04, 0x25, 0, 0, 0, 0, // mov %fs:0, %rax
+        ],
+    ))
+}
+
+/// Returns true if the relocation takes the absolute address of the symbol
+pub fn is_absolute_relocation(r_type: u32) -> bool {
+    matches!(
+        r_type,
+        abi::R_X86_64_64 | abi::R_X86_64_32 | abi::R_X86_64_32S
+    )
+}
+
+/// Returns the range of values which the relocation can hold if it is narrower than 64 bits
+pub fn relocation_range(r_type: u32) -> Option<(i64, i64)> {
+    match r_type {
+        // Like GNU ld, absolute 8-bit and 16-bit values may be either signed or unsigned
+        abi::R_X86_64_8 => Some((i8::MIN as i64, u8::MAX as i64)),
+        abi::R_X86_64_16 => Some((i16::MIN as i64, u16::MAX as i64)),
+        abi::R_X86_64_PC8 => Some((i8::MIN as i64, i8::MAX as i64)),
+        abi::R_X86_64_PC16 => Some((i16::MIN as i64, i16::MAX as i64)),
+        abi::R_X86_64_32 | abi::R_X86_64_SIZE32 => Some((0, u32::MAX as i64)),
+        abi::R_X86_64_32S
+        | abi::R_X86_64_PC32
+        | abi::R_X86_64_PLT32
+        | abi::R_X86_64_GOT32
+        | abi::R_X86_64_GOTPC32
+        | abi::R_X86_64_GOTPCREL
+        | abi::R_X86_64_GOTPCRELX
+        | abi::R_X86_64_REX_GOTPCRELX
+        | abi::R_X86_64_GOTTPOFF
+        | abi::R_X86_64_TLSGD
+        | abi::R_X86_64_TLSLD
+        | abi::R_X86_64_TPOFF32
+        | abi::R_X86_64_DTPOFF32 => Some((i32::MIN as i64, i32::MAX as i64)),
+        _ => None,
+    }
+}
+
+pub fn relocation_size(r_type: u32) -> Result<usize, String> {
+    let size = match r_type {
+        abi::R_X86_64_NONE => 0,
+        abi::R_X86_64_8 => 1,
+        abi::R_X86_64_16 => 2,
+        abi::R_X86_64_32 => 4,
+        abi::R_X86_64_32S => 4,
+        abi::R_X86_64_64 => 8,
+        abi::R_X86_64_PC8 => 1,
+        abi::R_X86_64_PC16 => 2,
+        abi::R_X86_64_PC32 => 4,
+        abi::R_X86_64_PC64 => 8,
+        abi::R_X86_64_GOT32 => 4,
+        abi::R_X86_64_PLT32 => 4,
+        abi::R_X86_64_GOTPCREL => 4,
+        abi::R_X86_64_GOTPCRELX => 4,
+        abi::R_X86_64_REX_GOTPCRELX => 4,
+        abi::R_X86_64_GOTTPOFF => 4,
+        abi::R_X86_64_TLSGD => 4,
+        abi::R_X86_64_TLSLD => 4,
+        abi::R_X86_64_TPOFF32 => 4,
+        abi::R_X86_64_TPOFF64 => 8,
+        abi::R_X86_64_DTPOFF32 => 4,
+        abi::R_X86_64_DTPOFF64 => 8,
+        abi::R_X86_64_SIZE32 => 4,
+        abi::R_X86_64_SIZE64 => 8,
+        abi::R_X86_64_GOTPC32 => 4,
+        abi::R_X86_64_GOTPC64 => 8,
+        abi::R_X86_64_GOTOFF64 => 8,
+        abi::R_X86_64_GOT64 => 8,
+        abi::R_X86_64_GOTPCREL64 => 8,
+        abi::R_X86_64_PLTOFF64 => 8,
+        r_type => return Err(unsupported_relocation(r_type)),
+    };
+    Ok(size)
+}
+
+pub fn r_type_as_str(r_type: u32) -> String {
+    let name = match r_type {
+        abi::R_X86_64_NONE => "R_X86_64_NONE",
+        abi::R_X86_64_64 => "R_X86_64_64",
+        abi::R_X86_64_PC32 => "R_X86_64_PC32",
+        abi::R_X86_64_GOT32 => "R_X86_64_GOT32",
+        abi::R_X86_64_PLT32 => "R_X86_64_PLT32",
+        abi::R_X86_64_COPY => "R_X86_64_COPY",
+        abi::R_X86_64_GLOB_DAT => "R_X86_64_GLOB_DAT",
+        abi::R_X86_64_JUMP_SLOT => "R_X86_64_JUMP_SLOT",
+        abi::R_X86_64_RELATIVE => "R_X86_64_RELATIVE",
+        abi::R_X86_64_GOTPCREL => "R_X86_64_GOTPCREL",
+        abi::R_X86_64_32 => "R_X86_64_32",
+        abi::R_X86_64_16 => "R_X86_64_16",
+        abi::R_X86_64_8 => "R_X86_64_8",
+        abi::R_X86_64_PC8 => "R_X86_64_PC8",
+        abi::R_X86_64_PC16 => "R_X86_64_PC16",
+        abi::R_X86_64_32S => "R_X86_64_32S",
+        abi::R_X86_64_PC64 => "R_X86_64_PC64",
+        abi::R_X86_64_TLSGD => "R_X86_64_TLSGD",
+        abi::R_X86_64_TLSLD => "R_X86_64_TLSLD",
+        abi::R_X86_64_DTPOFF32 => "R_X86_64_DTPOFF32",
+        abi::R_X86_64_GOTTPOFF => "R_X86_64_GOTTPOFF",
+        abi::R_X86_64_TPOFF32 => "R_X86_64_TPOFF32",
+        abi::R_X86_64_GOTOFF64 => "R_X86_64_GOTOFF64",
+        abi::R_X86_64_GOTPC32 => "R_X86_64_GOTPC32",
+        abi::R_X86_64_GOT64 => "R_X86_64_GOT64",
+        abi::R_X86_64_GOTPCREL64 => "R_X86_64_GOTPCREL64",
+        abi::R_X86_64_GOTPC64 => "R_X86_64_GOTPC64",
+        abi::R_X86_64_PLTOFF64 => "R_X86_64_PLTOFF64",
+        abi::R_X86_64_SIZE32 => "R_X86_64_SIZE32",
+        abi::R_X86_64_SIZE64 => "R_X86_64_SIZE64",
+        abi::R_X86_64_GOTPC32_TLSDESC => "R_X86_64_GOTPC32_TLSDESC",
+        abi::R_X86_64_TLSDESC_CALL => "R_X86_64_TLSDESC_CALL",
+        abi::R_X86_64_TLSDESC => "R_X86_64_TLSDESC",
+        abi::R_X86_64_IRELATIVE => "R_X86_64_IRELATIVE",
+        abi::R_X86_64_RELATIVE64 => "R_X86_64_RELATIVE64",
+        abi::R_X86_64_GOTPCRELX => "R_X86_64_GOTPCRELX",
+        abi::R_X86_64_REX_GOTPCRELX => "R_X86_64_REX_GOTPCRELX",
+        _ => return format!("unknown ({})", r_type),
+    };
+    name.to_owned()
+}
+
+/// Size of the PLT header and of each PLT entry
+const PLT_ENTRY_SIZE: u64 = 16;
+
+/// Write the PLT header, which pushes GOTPLT[1] and jumps to GOTPLT[2]
+fn write_plt_header(buf: &mut [u8], plt_addr: u64, got_plt_addr: u64) {
+    // push GOTPLT+8(%rip); jmp *GOTPLT+16(%rip); nop
+    const HEADER: [u8; 16] = [
+        0xff, 0x35, 0, 0, 0, 0, 0xff, 0x25, 0, 0, 0, 0, 0x0f, 0x1f, 0x40, 0x00,
+    ];
+    buf[..16].copy_from_slice(&HEADER);
+    write_to(
+        buf,
+        2,
+        &((got_plt_addr + 8).wrapping_sub(plt_addr + 6) as u32),
+    );
+    write_to(
+        buf,
+        8,
+        &((got_plt_addr + 16).wrapping_sub(plt_addr + 12) as u32),
+    );
+}
+
+/// Write the PLT entry at `idx`, which jumps to the address in its .got.plt entry
+fn write_plt_entry(
+    buf: &mut [u8],
+    entry_addr: u64,
+    got_entry_addr: u64,
+    plt_addr: u64,
+    idx: usize,
+) {
+    // jmp *GOTPLT[n](%rip); push $n; jmp PLT[0]
+    const ENTRY: [u8; 16] = [0xff, 0x25, 0, 0, 0, 0, 0x68, 0, 0, 0, 0, 0xe9, 0, 0, 0, 0];
+    buf[..16].copy_from_slice(&ENTRY);
+    write_to(
+        buf,
+        2,
+        &(got_entry_addr.wrapping_sub(entry_addr + 6) as u32),
+    );
+    write_to(buf, 7, &(idx as u32));
+    write_to(buf, 12, &(plt_addr.wrapping_sub(entry_addr + 16) as u32));
+}
+
+/// Relax GOTTPOFF to load `tpoff` as an immediate. Returns the offset of the instruction in
+/// `data` and the new instruction, or None if it can't be relaxed.
+pub fn relax_gottpoff(data: &[u8], rela: &Rela, tpoff: u64) -> Option<(usize, Vec<u8>)> {
+    let start = (rela.r_offset as usize).checked_sub(3)?;
+    let loc = data.get(start..start + 3)?;
+    let opcode = match (loc[0] as u32) << 16 | (loc[1] as u32) << 8 | loc[2] as u32 {
+        0x488b05 => 0x48c7c0, // mov 0(%rip), %rax -> mov $0, %rax
+        0x488b0d => 0x48c7c1, // mov 0(%rip), %rcx -> mov $0, %rcx
+        0x488b15 => 0x48c7c2, // mov 0(%rip), %rdx -> mov $0, %rdx
+        0x488b1d => 0x48c7c3, // mov 0(%rip), %rbx -> mov $0, %rbx
+        0x488b25 => 0x48c7c4, // mov 0(%rip), %rsp -> mov $0, %rsp
+        0x488b2d => 0x48c7c5, // mov 0(%rip), %rbp -> mov $0, %rbp
+        0x488b35 => 0x48c7c6, // mov 0(%rip), %rsi -> mov $0, %rsi
+        0x488b3d => 0x48c7c7, // mov 0(%rip), %rdi -> mov $0, %rdi
+        0x4c8b05 => 0x49c7c0, // mov 0(%rip), %r8  -> mov $0, %r8
+        0x4c8b0d => 0x49c7c1, // mov 0(%rip), %r9  -> mov $0, %r9
+        0x4c8b15 => 0x49c7c2, // mov 0(%rip), %r10 -> mov $0, %r10
+        0x4c8b1d => 0x49c7c3, // mov 0(%rip), %r11 -> mov $0, %r11
+        0x4c8b25 => 0x49c7c4, // mov 0(%rip), %r12 -> mov $0, %r12
+        0x4c8b2d => 0x49c7c5, // mov 0(%rip), %r13 -> mov $0, %r13
+        0x4c8b35 => 0x49c7c6, // mov 0(%rip), %r14 -> mov $0, %r14
+        0x4c8b3d => 0x49c7c7, // mov 0(%rip), %r15 -> mov $0, %r15
+        _ => return None,
+    };
+    let [_, a, b, c] = u32::to_be_bytes(opcode);
+    // mov $tpoff, %reg
+    Some((
+        start,
+        [a, b, c]
+            .into_iter()
+            .chain((tpoff as u32).to_le_bytes())
+            .collect(),
+    ))
+}
+
+/// Relax `lea x@tlsdesc(%rip), %rax` of a TLS descriptor sequence to `opcode` followed by
+/// the 32-bit `value`. Returns the offset of the instruction in `data` and the new
+/// instruction.
+fn relax_gotpc32_tlsdesc(
+    data: &[u8],
+    rela: &Rela,
+    opcode: [u8; 3],
+    value: i64,
+) -> Option<(usize, Vec<u8>)> {
+    let start = (rela.r_offset as usize).checked_sub(3)?;
+    if data.get(start..start + 3)? != [0x48, 0x8d, 0x05] {
+        return None;
+    }
+    Some((
+        start,
+        opcode
+            .into_iter()
+            .chain((value as u32).to_le_bytes())
+            .collect(),
+    ))
+}
+
+/// Relax `call *x@tlscall(%rax)` of a TLS descriptor sequence, whose result is already in
+/// %rax after relaxation, to a 2-byte nop (xchg %ax, %ax)
+pub fn relax_tlsdesc_call(data: &[u8], rela: &Rela) -> Option<(usize, Vec<u8>)> {
+    let start = rela.r_offset as usize;
+    (data.get(start..start + 2)? == [0xff, 0x10]).then(|| (start, vec![0x66, 0x90]))
+}
+
+pub struct X86_64;
+
+impl Target for X86_64 {
+    fn name(&self) -> &'static str {
+        "x86-64"
+    }
+
+    fn e_machine(&self) -> u16 {
+        abi::EM_X86_64
+    }
+
+    fn emulations(&self) -> &'static [&'static str] {
+        &["elf_x86_64"]
+    }
+
+    fn dynamic_reloc_types(&self) -> Option<&'static DynamicRelocTypes> {
+        Some(&DYNAMIC_RELOC_TYPES)
+    }
+
+    fn relocation_value(
+        &self,
+        input: &RelocationInput,
+        rela: &Rela,
+    ) -> Result<Option<u64>, String> {
+        relocation_value(input, rela)
+    }
+
+    fn relocation_range(&self, r_type: u32) -> Option<(i64, i64)> {
+        relocation_range(r_type)
+    }
+
+    fn relocation_size(&self, r_type: u32) -> Result<usize, String> {
+        relocation_size(r_type)
+    }
+
+    fn check_relocation_type(&self, r_type: u32) -> Result<(), String> {
+        match r_type {
+            // The instructions are rewritten instead
+            abi::R_X86_64_GOTPC32_TLSDESC | abi::R_X86_64_TLSDESC_CALL => Ok(()),
+            r_type => relocation_size(r_type).map(|_| ()),
+        }
+    }
+
+    fn r_type_as_str(&self, r_type: u32) -> String {
+        r_type_as_str(r_type)
+    }
+
+    fn needs_got(&self, r_type: u32) -> bool {
+        needs_got(r_type)
+    }
+
+    fn is_got_relative(&self, r_type: u32) -> bool {
+        is_got_relative(r_type)
+    }
+
+    fn is_absolute_relocation(&self, r_type: u32) -> bool {
+        is_absolute_relocation(r_type)
+    }
+
+    fn is_call(&self, r_type: u32) -> bool {
+        r_type == abi::R_X86_64_PLT32
+    }
+
+    fn tls_reloc(&self, r_type: u32) -> Option<TlsReloc> {
+        match r_type {
+            abi::R_X86_64_GOTTPOFF => Some(TlsReloc::GotTpOff),
+            abi::R_X86_64_TLSGD => Some(TlsReloc::Gd),
+            abi::R_X86_64_TLSLD => Some(TlsReloc::Ld),
+            abi::R_X86_64_DTPOFF32 => Some(TlsReloc::LdOffset),
+            abi::R_X86_64_GOTPC32_TLSDESC => Some(TlsReloc::Desc),
+            abi::R_X86_64_TLSDESC_CALL => Some(TlsReloc::DescCall),
+            _ => None,
+        }
+    }
+
+    fn plt_header_size(&self) -> u64 {
+        PLT_ENTRY_SIZE
+    }
+
+    fn plt_entry_size(&self) -> u64 {
+        PLT_ENTRY_SIZE
+    }
+
+    fn write_plt_header(&self, buf: &mut [u8], plt_addr: u64, got_plt_addr: u64) {
+        write_plt_header(buf, plt_addr, got_plt_addr);
+    }
+
+    fn write_plt_entry(
+        &self,
+        buf: &mut [u8],
+        entry_addr: u64,
+        got_entry_addr: u64,
+        plt_addr: u64,
+        idx: usize,
+    ) {
+        write_plt_entry(buf, entry_addr, got_entry_addr, plt_addr, idx);
+    }
+
+    /// The push instruction of the PLT entry
+    fn plt_lazy_addr(&self, entry_addr: u64) -> u64 {
+        entry_addr + 6
+    }
+
+    fn relax_gotpcrelx(&self, r_type: u32, insn: &[u8]) -> Option<RelaxedInsn> {
+        relax_gotpcrelx(r_type, insn)
+    }
+
+    fn relax_gottpoff(&self, data: &[u8], rela: &Rela, tpoff: u64) -> Option<(usize, Vec<u8>)> {
+        relax_gottpoff(data, rela, tpoff)
+    }
+
+    /// mov $tpoff, %rax
+    fn relax_tlsdesc_to_le(
+        &self,
+        data: &[u8],
+        rela: &Rela,
+        tpoff: u64,
+    ) -> Option<(usize, Vec<u8>)> {
+        // The addend is relative to the end of the field, which the immediate is not
+        let value = tpoff as i64 + rela.r_addend + 4;
+        relax_gotpc32_tlsdesc(data, rela, [0x48, 0xc7, 0xc0], value)
+    }
+
+    /// mov x@gottpoff(%rip), %rax
+    fn relax_tlsdesc_to_ie(
+        &self,
+        data: &[u8],
+        rela: &Rela,
+        got_entry_addr: u64,
+        p: u64,
+    ) -> Option<(usize, Vec<u8>)> {
+        let value = got_entry_addr as i64 + rela.r_addend - p as i64;
+        relax_gotpc32_tlsdesc(data, rela, [0x48, 0x8b, 0x05], value)
+    }
+
+    fn relax_tlsdesc_call(&self, data: &[u8], rela: &Rela) -> Option<(usize, Vec<u8>)> {
+        relax_tlsdesc_call(data, rela)
+    }
+
+    fn relax_tlsgd(
+        &self,
+        data: &[u8],
+        rela: &Rela,
+        call: &Rela,
+        tpoff: u64,
+    ) -> Option<(usize, Vec<u8>)> {
+        relax_tlsgd(data, rela, call, tpoff)
+    }
+
+    fn relax_tlsld(&self, data: &[u8], rela: &Rela, call: &Rela) -> Option<(usize, Vec<u8>)> {
+        relax_tlsld(data, rela, call)
+    }
+}
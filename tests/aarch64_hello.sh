@@ -0,0 +1,42 @@
+# A hello world of AArch64 objects links, and the relocated instructions refer to the
+# right places. The objects are assembled by llvm-mc, so the test passes without it.
+command -v llvm-mc > /dev/null || exit 0
+cat <<EOF2 | llvm-mc -triple=aarch64-linux-gnu -filetype=obj -o %aarch64_hello1.o
+  .text
+  .globl _start
+_start:
+  mov x0, #1
+  adrp x1, msg
+  add x1, x1, :lo12:msg
+  mov x2, #14
+  bl write
+  mov x0, #0
+  mov x8, #93
+  svc #0
+
+  .section .rodata
+msg:
+  .ascii "Hello, world!\n"
+EOF2
+cat <<EOF2 | llvm-mc -triple=aarch64-linux-gnu -filetype=obj -o %aarch64_hello2.o
+  .text
+  .globl write
+write:
+  mov x8, #64
+  svc #0
+  ret
+EOF2
+
+cargo run %aarch64_hello1.o %aarch64_hello2.o || exit 1
+[ "$(readelf -hW a.out | awk '/Machine:/ { print $2 }')" = AArch64 ] || exit 1
+# Segments are aligned to 64 KiB pages
+[ "$(readelf -lW a.out | awk '$1 == "LOAD" { print $NF }' | sort -u)" = 0x10000 ] || exit 1
+
+# msg is at 0x4000e8 and write at 0x410020, as GNU ld encodes them for this layout:
+# adrp x1, 0x400000; add x1, x1, #0xe8; bl 0x410020
+[ "$(readelf -sW a.out | awk '$8 == "msg" { print $2 }')" = 00000000004000e8 ] || exit 1
+[ "$(readelf -sW a.out | awk '$8 == "write" { print $2 }')" = 0000000000410020 ] || exit 1
+[ "$(od -An -tx4 -j $((0x10004)) -N 16 a.out | tr -s ' ')" = " 90ffff81 9103a021 d28001c2 94000004" ] \
+  || exit 1
+
+exit 0
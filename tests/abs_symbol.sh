@@ -0,0 +1,20 @@
+# Relocations against an absolute (SHN_ABS) symbol resolve to its value.
+cat <<EOF2 | cc -o %abs_symbol1.o -c -x assembler -
+  .globl myconst
+  .set myconst, 0xdeadbeef
+EOF2
+cat <<EOF2 | cc -o %abs_symbol2.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  jmp _start
+
+  .data
+  .quad myconst
+  .long myconst
+EOF2
+
+cargo run %abs_symbol1.o %abs_symbol2.o
+
+readelf -sW a.out | awk '$8 == "myconst" { print $2, $7 }' | grep -q "00000000deadbeef ABS" || exit 1
+objdump -s -j .data a.out | grep -q "efbeadde 00000000 efbeadde" || exit 1
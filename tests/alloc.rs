@@ -0,0 +1,87 @@
+//! Count the memory allocated by a link. This is the only test in its binary, so that the
+//! allocations of other tests running in parallel are not counted.
+
+mod support;
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use bold::Config;
+use elf::{
+    abi::{SHF_ALLOC, SHF_EXECINSTR},
+    endian::LittleEndian,
+    ElfBytes,
+};
+use support::{ObjectBuilder, DATA};
+
+/// Allocations of at least this size are counted. Small ones are made by the threads of
+/// the link and of the test harness all the time.
+const LARGE_ALLOC_SIZE: usize = 1 << 20;
+
+/// Bytes allocated by large allocations
+static LARGE_ALLOC_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts the bytes of large allocations
+struct CountingAllocator;
+
+impl CountingAllocator {
+    fn count(size: usize) {
+        if size >= LARGE_ALLOC_SIZE {
+            LARGE_ALLOC_BYTES.fetch_add(size, Ordering::Relaxed);
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        Self::count(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn section_data_is_not_copied() {
+    // Sections borrow their contents from the input, so a large section is allocated again
+    // only as part of the output. The input itself is copied once since `link` borrows it.
+    const SECTION_SIZE: usize = 0x400000;
+    let mut obj = ObjectBuilder::new();
+    let text = obj.section(".text", SHF_ALLOC | SHF_EXECINSTR, 1, &[0xeb, 0xfe]);
+    obj.global("_start", text, 0);
+    obj.section(".data", DATA, 8, &vec![7; SECTION_SIZE]);
+    let inputs = [obj.input("large.o")];
+
+    let before = LARGE_ALLOC_BYTES.load(Ordering::Relaxed);
+    let output = bold::link(&inputs, &Config::new()).unwrap();
+    let allocated = LARGE_ALLOC_BYTES.load(Ordering::Relaxed) - before;
+    // The input and the output hold the section once each. Another copy of it would exceed
+    // this.
+    assert!(
+        allocated < 3 * SECTION_SIZE,
+        "{} bytes allocated for a section of {} bytes",
+        allocated,
+        SECTION_SIZE
+    );
+
+    let file = ElfBytes::<LittleEndian>::minimal_parse(&output.data).unwrap();
+    let data = file.section_header_by_name(".data").unwrap().unwrap();
+    assert_eq!(data.sh_size, SECTION_SIZE as u64);
+}
@@ -0,0 +1,40 @@
+# .bss takes no space in the output file, which ends with the section header table
+cat <<EOF2 | cc -o %bss_file_size.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  movb \$1, buf+8388607(%rip)
+  movzbl buf+8388607(%rip), %edi
+  sub \$1, %edi
+  mov \$60, %eax
+  syscall
+  .data
+  .quad 1
+  .bss
+  .globl buf
+buf:
+  .zero 8388608
+EOF2
+
+cargo run %bss_file_size.o || exit 1
+./a.out || exit 1
+
+size=$(stat -c %s a.out)
+[ $size -lt 16384 ] || exit 1
+shoff=$(readelf -hW a.out | awk '/Start of section headers/ { print $5 }')
+shnum=$(readelf -hW a.out | awk '/Number of section headers/ { print $5 }')
+[ $size = $((shoff + shnum * 64)) ] || exit 1
+# Non-allocated sections follow the last PROGBITS byte without page padding
+data_end=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".data" { print $5, $6 }')
+symtab=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".symtab" { print $5 }')
+set -- $data_end
+[ $((0x$symtab)) -lt $((0x$1 + 0x$2 + 8)) ] || exit 1
+# The writable segment maps .data from the file and .bss only in memory
+rw=$(readelf -lW a.out | awk '$1 == "LOAD" && $7 == "RW"')
+set -- $rw
+[ $(($6 - $5)) -ge 8388608 ] || exit 1
+[ $(($5)) -lt 4096 ] || exit 1
+# .bss doesn't occupy the file range of the sections after it
+bss_ofs=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".bss" { print $5 }')
+[ $((0x$bss_ofs)) -le $((0x$symtab)) ] || exit 1
+exit 0
@@ -0,0 +1,71 @@
+# -Bsymbolic binds references in a shared object to its own definitions at link time, so
+# that they need fewer dynamic relocations
+cat <<EOF2 | cc -o %bsymbolic.o -c -x assembler -
+  .text
+  .globl foo, bar
+  .type foo, @function
+foo:
+  mov counter@GOTPCREL(%rip), %rax
+  mov (%rax), %eax
+  ret
+  .type bar, @function
+bar:
+  call foo@PLT
+  add \$1, %eax
+  ret
+
+  .data
+  .globl counter, fp
+  .type counter, @object
+  .size counter, 4
+counter:
+  .long 41
+  .type fp, @object
+  .size fp, 8
+fp:
+  .quad foo
+local_ptr:
+  .quad counter
+EOF2
+cat <<EOF2 | cc -o %bsymbolic_main.o -c -fPIC -x c -
+#include <stdio.h>
+int foo(void);
+int bar(void);
+extern int (*fp)(void);
+int main(void) { printf("%d %d %d\n", bar(), foo(), fp == foo); return 0; }
+EOF2
+
+relocs() {
+  readelf -rW %libbsymbolic.so | awk '$3 ~ /^R_X86_64_/ { print $3 }' | sort | uniq -c | xargs
+}
+run() {
+  cc -o %bsymbolic %bsymbolic_main.o %libbsymbolic.so || exit 1
+  [ "$(LD_LIBRARY_PATH=. ./%bsymbolic)" = "42 41 1" ] || exit 1
+}
+
+# Without the option, foo and counter may be preempted, so the PLT entry and the GOT entry
+# are bound at runtime and the pointers refer to the symbols
+cargo run -- -shared %bsymbolic.o || exit 1
+mv a.out %libbsymbolic.so
+[ "$(readelf -hW %libbsymbolic.so | awk '/Type:/ { print $2 }')" = DYN ] || exit 1
+[ "$(relocs)" = "2 R_X86_64_64 1 R_X86_64_GLOB_DAT 1 R_X86_64_JUMP_SLOT" ] || exit 1
+! readelf -dW %libbsymbolic.so | grep -q SYMBOLIC || exit 1
+run
+
+# With it, the call is direct, and only the load address is added to the addresses
+cargo run -- -shared -Bsymbolic %bsymbolic.o || exit 1
+mv a.out %libbsymbolic.so
+[ "$(relocs)" = "3 R_X86_64_RELATIVE" ] || exit 1
+! readelf -SW %libbsymbolic.so | grep -q "\.plt" || exit 1
+readelf -dW %libbsymbolic.so | grep -q "(SYMBOLIC)" || exit 1
+readelf -dW %libbsymbolic.so | grep -q "(FLAGS) *SYMBOLIC" || exit 1
+readelf -dW %libbsymbolic.so | grep -q "(RELACOUNT) *3" || exit 1
+run
+
+# -Bsymbolic-functions binds only foo
+cargo run -- -shared -Bsymbolic-functions %bsymbolic.o || exit 1
+mv a.out %libbsymbolic.so
+[ "$(relocs)" = "1 R_X86_64_64 1 R_X86_64_GLOB_DAT 1 R_X86_64_RELATIVE" ] || exit 1
+! readelf -dW %libbsymbolic.so | grep -q SYMBOLIC || exit 1
+run
+exit 0
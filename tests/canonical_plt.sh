@@ -0,0 +1,43 @@
+# Taking the address of a function in a shared object directly needs a canonical PLT entry,
+# which is the address of the function in the executable and the shared object alike
+cat <<EOF2 | cc -o %canonical_plt.so -shared -nostdlib -x assembler -
+  .text
+  .globl fn, check
+  .type fn, @function
+fn:
+  mov \$42, %eax
+  ret
+  .type check, @function
+check:
+  xor %eax, %eax
+  cmp fn@GOTPCREL(%rip), %rdi
+  je 1f
+  mov \$100, %eax
+1:
+  ret
+EOF2
+cat <<EOF2 | cc -o %canonical_plt.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov \$fn, %rdi
+  push %rdi
+  call *%rdi
+  pop %rdi
+  push %rax
+  call check@PLT
+  pop %rdi
+  add %rax, %rdi
+  mov \$60, %eax
+  syscall
+EOF2
+
+cargo run %canonical_plt.o %canonical_plt.so || exit 1
+readelf --dyn-syms -W a.out > %canonical_plt.dynsym
+value=$(awk '$8 == "fn" && $7 == "UND" { print $2 }' %canonical_plt.dynsym)
+[ -n "$value" ] && [ "$((16#$value))" != 0 ] || exit 1
+# The reference in the executable resolves to the value in .dynsym
+[ "$(objdump -d a.out | awk '/mov +\$0x[0-9a-f]+,%rdi/ { sub(/.*\$0x/, ""); sub(/,.*/, ""); print; exit }')" = "$(printf %x $((16#$value)))" ] || exit 1
+LD_LIBRARY_PATH=. ./a.out
+[ $? = 42 ] || exit 1
+exit 0
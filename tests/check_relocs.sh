@@ -0,0 +1,39 @@
+# --check-relocs verifies the values written by relocations after they are applied
+cat <<EOF2 | cc -o %check_relocs1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov \$60, %eax
+  mov foo(%rip), %rdi
+  syscall
+  .data
+  .globl foo
+foo:
+  .quad 42
+  .quad _start
+EOF2
+# Two relocations writing to the same bytes
+cat <<EOF2 | cc -o %check_relocs2.o -c -x assembler -
+  .section .rodata
+  .reloc ., R_X86_64_64, foo
+  .reloc .+4, R_X86_64_32, foo
+  .quad 0
+EOF2
+# A relocation extending past the end of its section
+cat <<EOF2 | cc -o %check_relocs3.o -c -x assembler -
+  .section .rodata.short, "a"
+  .reloc .+1, R_X86_64_64, foo
+  .short 0
+EOF2
+
+cargo run %check_relocs1.o --check-relocs || exit 1
+./a.out
+[ $? = 42 ] || exit 1
+
+cargo run %check_relocs1.o %check_relocs2.o || exit 1
+! cargo run %check_relocs1.o %check_relocs2.o --check-relocs 2> %check_relocs.log || exit 1
+grep -q "check-relocs: %check_relocs2.o:(.rodata): values at \[0x0, 0x8) and \[0x4, 0x8) overlap" %check_relocs.log || exit 1
+
+! cargo run %check_relocs1.o %check_relocs3.o 2> %check_relocs.log || exit 1
+grep -q "%check_relocs3.o:(.rodata.short+0x1): relocation is outside the section" %check_relocs.log || exit 1
+exit 0
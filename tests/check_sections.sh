@@ -0,0 +1,35 @@
+# Sections placed by --section-start are checked against the rest of the layout
+cat <<EOF2 | cc -o %check_sections.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov x(%rip), %edi
+  mov \$60, %eax
+  syscall
+  .data
+x:
+  .long 3
+EOF2
+
+# A section can be moved to a free address
+cargo run -- --section-start=.data=0x800000 %check_sections.o || exit 1
+[ "$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".data" { print $4 }')" = 0000000000800000 ] || exit 1
+./a.out
+[ $? = 3 ] || exit 1
+
+# Moving .data onto .text is an error naming both sections
+text=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".text" { print $4 }' | sed 's/^0*//')
+! cargo run -- --section-start=.data=0x$text %check_sections.o 2> %check_sections.log || exit 1
+grep -q "section .text address \[0x$text, 0x[0-9a-f]*\] overlaps section .data address \[0x$text, 0x[0-9a-f]*\]" %check_sections.log || exit 1
+
+# --no-check-sections produces the output anyway
+cargo run -- --no-check-sections --section-start=.data=0x$text %check_sections.o || exit 1
+
+# Moving .text onto the ELF header is also an error
+! cargo run -- --section-start=.text=0x400000 %check_sections.o 2> %check_sections.log || exit 1
+grep -q "ELF header address \[0x400000, 0x40003f\] overlaps section .text address \[0x400000," %check_sections.log || exit 1
+
+# Unknown sections are reported
+cargo run -- --section-start=.nosuch=0x800000 %check_sections.o 2> %check_sections.log || exit 1
+grep -q "no such section: .nosuch" %check_sections.log || exit 1
+exit 0
@@ -0,0 +1,46 @@
+# Merging rules of common symbols
+cat <<EOF2 | cc -o %common1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov c_c(%rip), %rax
+  mov c_def(%rip), %rax
+  mov c_weak(%rip), %rax
+  jmp _start
+  .comm c_c, 4, 4
+  .comm c_def, 8, 8
+  .comm c_weak, 8, 8
+EOF2
+cat <<EOF2 | cc -o %common2.o -c -x assembler -
+  .comm c_c, 16, 32
+
+  .data
+  .globl c_def
+  .type c_def, @object
+  .size c_def, 4
+c_def:
+  .long 1
+
+  .weak c_weak
+  .type c_weak, @object
+  .size c_weak, 2
+c_weak:
+  .short 2
+EOF2
+
+cargo run -- --warn-common %common1.o %common2.o 2> %common.log || exit 1
+
+sym() {
+  readelf -sW a.out | awk -v name="$1" '$8 == name { print $3, $7 }'
+}
+shndx() {
+  readelf -SW a.out | sed 's/\[ */[/' | awk -v name="$1" '$2 == name { print substr($1, 2, length($1) - 2) }'
+}
+# Two commons: the maximum size, allocated in .bss
+[ "$(sym c_c)" = "16 $(shndx .bss)" ] || exit 1
+# A common and a definition: the definition wins
+[ "$(sym c_def)" = "4 $(shndx .data)" ] || exit 1
+# A common and a weak definition: the definition wins
+[ "$(sym c_weak)" = "2 $(shndx .data)" ] || exit 1
+# Each symbol is written once
+[ "$(readelf -sW a.out | grep -c ' c_')" = 3 ] || exit 1
@@ -0,0 +1,56 @@
+# Direct references to data objects in shared objects refer to copies in the executable,
+# which the dynamic linker initializes with R_X86_64_COPY. The shared object refers to the
+# copy too, including by another name of the object.
+cat <<EOF2 | cc -o %copyrel.so -shared -nostdlib -x assembler -
+  .text
+  .globl get
+  .type get, @function
+get:
+  mov alias@GOTPCREL(%rip), %rax
+  mov (%rax), %rax
+  ret
+
+  .data
+  .balign 16
+  .globl var, alias, nosize
+  .type var, @object
+  .size var, 8
+  .type alias, @object
+  .size alias, 8
+var:
+alias:
+  .quad 42
+nosize:
+  .quad 0
+EOF2
+cat <<EOF2 | cc -o %copyrel.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  addq \$1, var
+  addq \$1, var(%rip)
+  call get@PLT
+  mov %rax, %rdi
+  mov \$60, %eax
+  syscall
+EOF2
+cat <<EOF2 | cc -o %copyrel_nosize.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov nosize, %rax
+EOF2
+
+cargo run %copyrel.o %copyrel.so || exit 1
+readelf -rW a.out | grep -q "R_X86_64_COPY .* var + 0" || exit 1
+readelf -SW a.out | grep -q "\.copyrel *NOBITS .* WA .* 16$" || exit 1
+readelf --dyn-syms -W a.out > %copyrel.dynsym
+[ "$(awk '$8 == "var" || $8 == "alias" { print $2 }' %copyrel.dynsym | uniq | wc -l)" = 1 ] || exit 1
+! grep -q "UND var" %copyrel.dynsym || exit 1
+LD_LIBRARY_PATH=. ./a.out
+[ $? = 44 ] || exit 1
+
+# The size of the copy has to be known
+! cargo run %copyrel_nosize.o %copyrel.so 2> %copyrel.log || exit 1
+grep -q "%copyrel_nosize.o:(.text+0x4): cannot create a copy relocation for symbol 'nosize' of unknown size; recompile with -fPIC" %copyrel.log || exit 1
+exit 0
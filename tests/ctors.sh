@@ -0,0 +1,55 @@
+# .ctors/.dtors are converted into .init_array/.fini_array
+cat <<EOF2 | cc -o %ctors_begin.o -c -x assembler -
+  .section .ctors,"aw",@progbits
+  .quad -1
+  .section .dtors,"aw",@progbits
+  .quad -1
+EOF2
+cat <<EOF2 | cc -o %ctors.o -c -x assembler -
+  .text
+  .globl _start, f0, f1, f2, f3, d1
+_start:
+  jmp _start
+f0:
+  ret
+f1:
+  ret
+f2:
+  ret
+f3:
+  ret
+d1:
+  ret
+  .section .init_array,"aw",@init_array
+  .quad f0
+  .section .ctors,"aw",@progbits
+  .quad f1
+  .quad f2
+  .section .ctors.00101,"aw",@progbits
+  .quad f3
+  .section .dtors,"aw",@progbits
+  .quad d1
+EOF2
+cat <<EOF2 | cc -o %ctors_end.o -c -x assembler -
+  .section .ctors,"aw",@progbits
+  .quad 0
+  .section .dtors,"aw",@progbits
+  .quad 0
+EOF2
+
+cargo run %ctors_begin.o %ctors.o %ctors_end.o || exit 1
+readelf -SW a.out | grep -q ctors && exit 1
+[ "$(readelf -SW a.out | grep -c ' \.init_array ')" = 1 ] || exit 1
+
+dump() {
+  sec=$(readelf -SW a.out | sed 's/\[ */[/' | awk -v name=$1 '$2 == name')
+  od -An -tx8 -v -j $((0x$(echo $sec | cut -d' ' -f5))) -N $((0x$(echo $sec | cut -d' ' -f6))) a.out | xargs
+}
+addrs() {
+  for name in "$@"; do
+    readelf -sW a.out | awk -v name=$name '$8 == name { print $2 }'
+  done | xargs
+}
+[ "$(dump .init_array)" = "$(addrs f3 f0 f2 f1)" ] || exit 1
+[ "$(dump .fini_array)" = "$(addrs d1)" ] || exit 1
+exit 0
@@ -0,0 +1,32 @@
+# Debug sections are concatenated into non-allocated output sections of the same names
+cat <<EOF2 | cc -o %debug_sections1.o -c -g -x c -
+int x = 3;
+int f(int a) { return a + x; }
+void _start(void) { for (;;) f(1); }
+EOF2
+cat <<EOF2 | cc -o %debug_sections2.o -c -g -x c -
+int g(int a) { return a * 2; }
+EOF2
+
+cargo run -- --verbose %debug_sections1.o %debug_sections2.o 2> %debug_sections.log || exit 1
+# They are not orphans
+! grep -q "orphan section: .debug" %debug_sections.log || exit 1
+
+section() {
+  readelf -SW $1 | sed 's/\[ */[/' | awk -v name=$2 '$2 == name { print $'$3' }'
+}
+for name in .debug_info .debug_abbrev .debug_line; do
+  [ -n "$(section a.out $name 2)" ] || exit 1
+  # Not allocated
+  [ "$(section a.out $name 4)" = 0000000000000000 ] || exit 1
+  [ "$(section a.out $name 8)" = 0 ] || exit 1
+done
+# Inputs are concatenated in file order
+size1=$((0x$(section %debug_sections1.o .debug_info 6)))
+size2=$((0x$(section %debug_sections2.o .debug_info 6)))
+[ $((0x$(section a.out .debug_info 6))) = $((size1 + size2)) ] || exit 1
+
+# Debug sections are placed after the last loadable segment
+set -- $(readelf -lW a.out | awk '$1 == "LOAD" { print $2, $5 }' | tail -1)
+[ $((0x$(section a.out .debug_info 5))) -ge $(($1 + $2)) ] || exit 1
+exit 0
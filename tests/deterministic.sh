@@ -0,0 +1,51 @@
+# Linking the same inputs twice produces identical outputs
+cat <<EOF2 | cc -o %deterministic.so -shared -nostdlib -x assembler -
+  .text
+  .globl foo, bar
+  .type foo, @function
+  .type bar, @function
+foo:
+  ret
+bar:
+  ret
+EOF2
+for i in 1 2 3 4 5 6; do
+  cat <<EOF2 | cc -o %deterministic$i.o -c -x assembler -
+  .text
+  .globl f$i
+f$i:
+  call foo@PLT
+  call bar@PLT
+  mov v$i@GOTPCREL(%rip), %rax
+  ret
+  .section .text.f$i,"ax",@progbits
+  ret
+  .section sec$i,"a",@progbits
+  .quad $i
+  .section .rodata.str1.1,"aMS",@progbits,1
+  .string "str$i"
+  .string "str"
+  .data
+  .globl v$i
+v$i:
+  .quad $i
+  .comm c$i, 8, 8
+EOF2
+done
+cat <<EOF2 | cc -o %deterministic.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  call f1
+  jmp _start
+EOF2
+ar rc %deterministic.a %deterministic5.o %deterministic6.o
+
+inputs="%deterministic.o %deterministic1.o %deterministic2.o %deterministic3.o %deterministic4.o %deterministic.a %deterministic.so"
+cargo run -- $inputs -u f6 || exit 1
+mv a.out %deterministic.out
+for i in 1 2 3; do
+  cargo run -- $inputs -u f6 || exit 1
+  cmp a.out %deterministic.out || exit 1
+done
+exit 0
@@ -0,0 +1,67 @@
+# Diagnostics are printed sorted by location without duplicates, followed by a summary
+cat <<EOF2 | cc -o %diagnostics1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  jmp _start
+  .comm c, 4, 4
+  .section mysec, "a"
+  .byte 1
+EOF2
+cat <<EOF2 | cc -o %diagnostics2.o -c -x assembler -
+  .comm c, 4, 4
+  .section mysec, "aw"
+  .byte 2
+EOF2
+echo '  .comm c, 4, 4' | cc -o %diagnostics3.o -c -x assembler -
+
+# "multiple common of c" is reported for both %diagnostics2.o and %diagnostics3.o
+cargo run -- --warn-common %diagnostics1.o %diagnostics2.o %diagnostics3.o 2> %diagnostics.log || exit 1
+cat <<EOF2 | diff - <(grep '^\[' %diagnostics.log) || exit 1
+[WARN  bold] multiple common of c
+[WARN  bold] %diagnostics2.o: mysec has different permissions from other inputs of mysec
+[WARN  bold] 0 errors, 2 warnings
+EOF2
+
+# Undefined symbols are sorted by name
+cat <<EOF2 | cc -o %diagnostics4.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  call zeta
+  call alpha
+  call zeta
+EOF2
+cargo run %diagnostics4.o 2> %diagnostics.log && exit 1
+cat <<EOF2 | diff - <(grep '^\[' %diagnostics.log) || exit 1
+[ERROR bold] undefined symbol: alpha
+[ERROR bold] undefined symbol: zeta
+[ERROR bold] 2 errors, 0 warnings
+EOF2
+
+# Relocation errors are sorted by file and offset
+cat <<EOF2 | cc -o %diagnostics5.o -c -x assembler -
+  .globl big
+  big = 0x1000
+  .text
+  .globl _start
+_start:
+  jmp _start
+EOF2
+printf '  .data\n  .byte big\n  .byte 0\n  .byte big\n' | cc -o %diagnostics6.o -c -x assembler -
+printf '  .data\n  .byte big\n' | cc -o %diagnostics7.o -c -x assembler -
+cargo run %diagnostics5.o %diagnostics7.o %diagnostics6.o 2> %diagnostics.log && exit 1
+cat <<EOF2 | diff - <(grep '^\[' %diagnostics.log) || exit 1
+[ERROR bold] %diagnostics6.o:(.data+0x0): relocation R_X86_64_8 out of range: symbol 'big' (0x1000): 4096 is not in [-128, 255]
+[ERROR bold] %diagnostics6.o:(.data+0x2): relocation R_X86_64_8 out of range: symbol 'big' (0x1000): 4096 is not in [-128, 255]
+[ERROR bold] %diagnostics7.o:(.data+0x0): relocation R_X86_64_8 out of range: symbol 'big' (0x1000): 4096 is not in [-128, 255]
+[ERROR bold] 3 errors, 0 warnings
+EOF2
+
+# Inputs which can't be read are reported the same way
+cargo run %diagnostics.nonexistent 2> %diagnostics.log && exit 1
+cat <<EOF2 | diff - <(grep '^\[' %diagnostics.log) || exit 1
+[ERROR bold] %diagnostics.nonexistent: No such file or directory (os error 2)
+[ERROR bold] 1 error, 0 warnings
+EOF2
+exit 0
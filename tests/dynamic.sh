@@ -0,0 +1,68 @@
+# Dynamically-linked output has .interp and .dynamic
+cat <<EOF2 | cc -o %dynamic.so -shared -nostdlib -Wl,-soname,libdynamic.so.1 -x assembler -
+  .text
+  .globl foo
+  .type foo, @function
+foo:
+  ret
+EOF2
+cat <<EOF2 | cc -o %dynamic2.so -shared -nostdlib -x assembler -
+  .text
+  .globl bar
+  .type bar, @function
+bar:
+  ret
+EOF2
+cat <<EOF2 | cc -o %dynamic.o -c -x assembler -
+  .text
+  .globl _start, _init
+_start:
+  call foo@PLT
+  lea _DYNAMIC(%rip), %rax
+  jmp _start
+_init:
+  ret
+  .section .init_array,"aw",@init_array
+  .quad _start
+  .quad _start
+EOF2
+
+cargo run -- %dynamic.o %dynamic.so %dynamic2.so -z now --dynamic-linker /foo/ld.so || exit 1
+
+readelf -dW a.out > %dynamic.dump
+section() {
+  readelf -SW a.out | sed 's/\[ */[/' | awk -v name=$1 '$2 == name { print $'$2' }'
+}
+entry() {
+  awk -v tag="($1)" '$2 == tag { print $3 }' %dynamic.dump
+}
+# DT_NEEDED holds DT_SONAME, or the file name if there is none, in command-line order
+[ "$(grep NEEDED %dynamic.dump | awk '{ print $5 }' | xargs)" = "[libdynamic.so.1] [%dynamic2.so]" ] || exit 1
+[ $(($(entry STRTAB))) = $((0x$(section .dynstr 4))) ] || exit 1
+[ "$(entry STRSZ)" = $((0x$(section .dynstr 6))) ] || exit 1
+[ $(($(entry PLTGOT))) = $((0x$(section .got.plt 4))) ] || exit 1
+[ $(($(entry INIT_ARRAY))) = $((0x$(section .init_array 4))) ] || exit 1
+[ "$(entry INIT_ARRAYSZ)" = 16 ] || exit 1
+init=$(readelf -sW a.out | awk '$8 == "_init" { print $2 }')
+[ $(($(entry INIT))) = $((0x$init)) ] || exit 1
+grep -q "(DEBUG)" %dynamic.dump || exit 1
+grep -q "(FLAGS) *BIND_NOW" %dynamic.dump || exit 1
+grep -q "(FLAGS_1) *Flags: NOW" %dynamic.dump || exit 1
+[ "$(tail -n 1 %dynamic.dump | awk '{ print $2 }')" = "(NULL)" ] || exit 1
+# sh_link of .dynamic points to .dynstr
+[ "$(section .dynamic 9)" = "$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".dynstr" { print $1 }' | tr -d '[]')" ] || exit 1
+
+# PT_DYNAMIC covers .dynamic, which is also the target of _DYNAMIC and the first entry of
+# .got.plt
+dynamic=$((0x$(section .dynamic 4)))
+[ $(($(readelf -lW a.out | awk '$1 == "DYNAMIC" { print $3 }'))) = $dynamic ] || exit 1
+objdump -d a.out | grep -q "lea .*# $(printf %x $dynamic)" || exit 1
+[ "$(od -An -tu8 -j $((0x$(section .got.plt 5))) -N 8 a.out | xargs)" = $dynamic ] || exit 1
+
+readelf -lW a.out | grep -q "Requesting program interpreter: /foo/ld.so" || exit 1
+
+# Statically-linked output has neither of them
+cargo run -- %dynamic.o -z undefs || exit 1
+! readelf -SW a.out | grep -q "\.dynamic\|\.interp" || exit 1
+! readelf -lW a.out | grep -q "DYNAMIC\|INTERP" || exit 1
+exit 0
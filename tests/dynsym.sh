@@ -0,0 +1,90 @@
+# .dynsym holds symbols imported from DSOs and exported ones
+cat <<EOF2 | cc -o %dynsym.so -shared -nostdlib -x assembler -
+  .text
+  .globl foo, bar
+  .type foo, @function
+  .type bar, @function
+foo:
+  call cb@PLT
+  ret
+bar:
+  ret
+EOF2
+cat <<EOF2 | cc -o %dynsym.o -c -x assembler -
+  .text
+  .globl _start, cb, hid, e1, e2, e3, e4, e5, e6, e7, e8, e9, e10, e11, e12
+  .hidden hid
+_start:
+  call foo@PLT
+  call bar@PLT
+  jmp _start
+cb:
+hid:
+e1:
+e2:
+e3:
+e4:
+e5:
+e6:
+e7:
+e8:
+e9:
+e10:
+e11:
+e12:
+  ret
+EOF2
+
+section() {
+  readelf -SW a.out | sed 's/\[ */[/' | awk -v name=$1 '$2 == name { print $'$2' }'
+}
+shndx() {
+  readelf -SW a.out | sed 's/\[ */[/' | awk -v name=$1 '$2 == name { print $1 }' | tr -d '[]'
+}
+dynsyms() {
+  readelf --dyn-syms -W a.out | awk '/^ *[0-9]+:/ && $8 != "" { print $7 ":" $8 }' | xargs
+}
+
+# Without --export-dynamic, only symbols referenced by DSOs are exported
+cargo run %dynsym.o %dynsym.so || exit 1
+[ "$(dynsyms)" = "UND:foo UND:bar $(shndx .text):cb" ] || exit 1
+# Only the null symbol is local
+[ "$(section .dynsym 10)" = 1 ] || exit 1
+[ "$(section .dynsym 9)" = "$(shndx .dynstr)" ] || exit 1
+[ "$(section .gnu.hash 9)" = "$(shndx .dynsym)" ] || exit 1
+readelf -dW a.out > %dynsym.dump
+grep -q "(SYMTAB) *0x$(section .dynsym 4 | sed 's/^0*//')" %dynsym.dump || exit 1
+grep -q "(SYMENT) *24 (bytes)" %dynsym.dump || exit 1
+grep -q "(GNU_HASH) *0x$(section .gnu.hash 4 | sed 's/^0*//')" %dynsym.dump || exit 1
+
+# With --export-dynamic, all visible symbols are exported after imported ones
+cargo run -- --export-dynamic %dynsym.o %dynsym.so || exit 1
+syms=$(dynsyms)
+[ $(echo $syms | wc -w) = 16 ] || exit 1
+echo "$syms" | grep -q "^UND:foo UND:bar " || exit 1
+for sym in _start cb e1 e12; do
+  echo "$syms" | grep -qw "$(shndx .text):$sym" || exit 1
+done
+! echo "$syms" | grep -qw hid || exit 1
+cb=$(readelf -sW a.out | awk '/.symtab/ { f = 1 } f && $8 == "cb" { print $2 }')
+[ "$(readelf --dyn-syms -W a.out | awk '$8 == "cb" { print $2 }')" = $cb ] || exit 1
+
+# Exported symbols are sorted by .gnu.hash bucket, which makes .gnu.hash usable to
+# enumerate all of them
+readelf --dyn-syms -W a.out | awk '/^ *[0-9]+:/ && $8 != "" { print $8 }' > %dynsym.names
+[ "$(readelf -D --dyn-syms -W a.out | awk '/^ *[0-9]+:/ && $8 != "" { print $8 }')" = "$(cat %dynsym.names)" ] || exit 1
+set -- $(od -An -tu4 -j $((0x$(section .gnu.hash 5))) -N 8 a.out)
+nbuckets=$1
+[ $2 = 3 ] || exit 1
+prev=0
+for name in $(tail -n +3 %dynsym.names); do
+  # Compute the GNU hash of the name
+  h=5381
+  for i in $(seq 0 $((${#name} - 1))); do
+    h=$(((h * 33 + $(printf %d "'${name:$i:1}")) & 0xffffffff))
+  done
+  bucket=$((h % nbuckets))
+  [ $bucket -ge $prev ] || exit 1
+  prev=$bucket
+done
+exit 0
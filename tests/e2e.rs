@@ -0,0 +1,309 @@
+//! Compile small programs with the C compiler, link them with bold and run them. Each
+//! program is also linked with GNU ld if it is installed, and both executables have to
+//! behave the same. Tests are skipped if the tools they need are missing.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// Flags for C sources. The programs don't use libc, so the compiler must not call it.
+const CFLAGS: &[&str] = &[
+    "-O1",
+    "-ffreestanding",
+    "-fno-builtin",
+    "-fno-stack-protector",
+    "-fno-tree-loop-distribute-patterns",
+];
+
+/// Entry point of C programs, which calls main and exits with its return value
+const START: &str = "
+  .text
+  .globl _start
+_start:
+  xor %ebp, %ebp
+  call main
+  mov %eax, %edi
+  mov $60, %eax
+  syscall
+";
+
+/// Directory of a test, where sources are compiled and linked. It is removed when the test
+/// passes and kept for inspection when it fails.
+struct Harness {
+    dir: PathBuf,
+    objects: Vec<PathBuf>,
+}
+
+impl Harness {
+    /// Returns None if one of the tools is not installed, in which case the test is skipped
+    fn new(name: &str, tools: &[&str]) -> Option<Harness> {
+        for tool in tools {
+            if !is_installed(tool) {
+                eprintln!("skipped: {} is not available", tool);
+                return None;
+            }
+        }
+        let dir = std::env::temp_dir().join(format!("bold-e2e-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        Some(Harness {
+            dir,
+            objects: vec![],
+        })
+    }
+
+    /// Compile a C or assembly source, depending on the extension of `name`, and add the
+    /// object to the inputs of the link
+    fn compile(&mut self, name: &str, source: &str) -> &mut Harness {
+        let obj = self.compile_only(name, source);
+        self.objects.push(obj);
+        self
+    }
+
+    fn compile_only(&self, name: &str, source: &str) -> PathBuf {
+        let src = self.dir.join(name);
+        let obj = src.with_extension("o");
+        std::fs::write(&src, source).unwrap();
+        let mut cmd = Command::new("cc");
+        if name.ends_with(".c") {
+            cmd.args(CFLAGS);
+        }
+        cmd.arg("-c").arg("-o").arg(&obj).arg(&src);
+        check(&mut cmd, "cc");
+        obj
+    }
+
+    /// Create an archive of the sources and add it to the inputs of the link
+    fn archive(&mut self, name: &str, sources: &[(&str, &str)]) -> &mut Harness {
+        let members = sources
+            .iter()
+            .map(|(name, source)| self.compile_only(name, source))
+            .collect::<Vec<_>>();
+        let path = self.dir.join(name);
+        check(Command::new("ar").arg("rc").arg(&path).args(&members), "ar");
+        self.objects.push(path);
+        self
+    }
+
+    /// Link the inputs with bold and return the path of the executable. The diagnostics are
+    /// printed if the link fails.
+    fn link(&self) -> PathBuf {
+        let output = Command::new(env!("CARGO_BIN_EXE_bold"))
+            .args(&self.objects)
+            .current_dir(&self.dir)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "bold failed in {}:\n{}",
+            self.dir.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        self.dir.join("a.out")
+    }
+
+    /// Link the inputs with GNU ld, or return None if it is not installed
+    fn link_control(&self) -> Option<PathBuf> {
+        if !is_installed("ld") {
+            return None;
+        }
+        let path = self.dir.join("control.out");
+        check(
+            Command::new("ld").arg("-o").arg(&path).args(&self.objects),
+            "ld",
+        );
+        Some(path)
+    }
+
+    /// Link the inputs, run the executable and check its exit code and output. The control
+    /// executable linked by GNU ld is checked as well.
+    fn run(&self, exit_code: i32, stdout: &str) {
+        let exe = self.link();
+        let control = self.link_control();
+        for exe in std::iter::once(exe).chain(control) {
+            let output = Command::new(&exe).output().unwrap();
+            assert_eq!(
+                output.status.code(),
+                Some(exit_code),
+                "{} exited with {}",
+                exe.display(),
+                output.status
+            );
+            assert_eq!(
+                String::from_utf8_lossy(&output.stdout),
+                stdout,
+                "output of {}",
+                exe.display()
+            );
+        }
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            eprintln!("files are kept in {}", self.dir.display());
+        } else {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+fn is_installed(tool: &str) -> bool {
+    Command::new(tool).arg("--version").output().is_ok()
+}
+
+/// Run a command and panic with its output if it fails
+fn check(cmd: &mut Command, name: &str) -> Output {
+    let output = cmd.output().unwrap();
+    assert!(
+        output.status.success(),
+        "{} failed:\n{}",
+        name,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+/// Size of a file
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).unwrap().len()
+}
+
+#[test]
+fn exit_code() {
+    let Some(mut h) = Harness::new("exit_code", &["cc"]) else {
+        return;
+    };
+    h.compile(
+        "start.s",
+        "
+  .text
+  .globl _start
+_start:
+  mov $60, %eax
+  mov $42, %edi
+  syscall
+",
+    );
+    h.run(42, "");
+}
+
+#[test]
+fn hello_world() {
+    let Some(mut h) = Harness::new("hello_world", &["cc"]) else {
+        return;
+    };
+    h.compile(
+        "hello.s",
+        r#"
+  .text
+  .globl _start
+_start:
+  mov $1, %eax
+  mov $1, %edi
+  lea msg(%rip), %rsi
+  mov $len, %edx
+  syscall
+  mov $60, %eax
+  xor %edi, %edi
+  syscall
+
+  .section .rodata
+msg:
+  .ascii "Hello, world!\n"
+  len = . - msg
+"#,
+    );
+    h.run(0, "Hello, world!\n");
+}
+
+#[test]
+fn multiple_objects() {
+    let Some(mut h) = Harness::new("multiple_objects", &["cc"]) else {
+        return;
+    };
+    h.compile("start.s", START)
+        .compile(
+            "main.c",
+            "
+extern int counter;
+int add(int a, int b);
+static int (*op)(int, int) = add;
+
+int main(void) {
+  counter += op(3, 4);
+  return counter;
+}
+",
+        )
+        .compile(
+            "lib.c",
+            "
+int counter = 10;
+
+int add(int a, int b) {
+  return a + b + counter;
+}
+",
+        );
+    h.run(27, "");
+}
+
+#[test]
+fn archive() {
+    let Some(mut h) = Harness::new("archive", &["cc", "ar"]) else {
+        return;
+    };
+    h.compile("start.s", START)
+        .compile(
+            "main.c",
+            "
+int square(int x);
+
+int main(void) {
+  return square(5);
+}
+",
+        )
+        .archive(
+            "libm.a",
+            &[
+                ("square.c", "int square(int x) { return x * x; }"),
+                // Not extracted, so its undefined reference doesn't matter
+                (
+                    "cube.c",
+                    "int missing(int); int cube(int x) { return missing(x); }",
+                ),
+            ],
+        );
+    h.run(25, "");
+}
+
+#[test]
+fn large_bss() {
+    let Some(mut h) = Harness::new("large_bss", &["cc"]) else {
+        return;
+    };
+    h.compile("start.s", START).compile(
+        "main.c",
+        "
+static unsigned char buf[16 << 20];
+long table[1 << 16];
+
+int main(void) {
+  for (unsigned long i = 0; i < sizeof(buf); i += 4096)
+    buf[i] = i >> 12;
+  for (unsigned long i = 0; i < sizeof(table) / sizeof(table[0]); i++)
+    table[i] = buf[(i & 0xff) << 12];
+  long sum = 0;
+  for (unsigned long i = 0; i < 256; i++)
+    sum += table[i];
+  return (sum == 255 * 256 / 2) ? 7 : 1;
+}
+",
+    );
+    h.run(7, "");
+    // .bss takes no space in the file
+    assert!(file_size(&h.dir.join("a.out")) < 1 << 20);
+}
@@ -0,0 +1,34 @@
+# .eh_frame_hdr holds a sorted table of FDEs and is covered by PT_GNU_EH_FRAME
+cat <<EOF2 | cc -o %eh_frame_hdr1.o -c -O0 -x c -
+int g(int);
+int f(int x) { return g(x) + 1; }
+EOF2
+cat <<EOF2 | cc -o %eh_frame_hdr2.o -c -O0 -x c -
+int f(int);
+int g(int x) { return x * 2; }
+void _start(void) { for (;;) f(1); }
+EOF2
+
+cargo run %eh_frame_hdr2.o %eh_frame_hdr1.o || exit 1
+
+hdr=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".eh_frame_hdr"')
+[ -n "$hdr" ] || exit 1
+hdr_addr=$((0x$(echo $hdr | cut -d' ' -f4)))
+hdr_ofs=$((0x$(echo $hdr | cut -d' ' -f5)))
+eh_frame_addr=$((0x$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".eh_frame" { print $4 }')))
+
+phdr=$(readelf -lW a.out | awk '$1 == "GNU_EH_FRAME"')
+[ $(($(echo $phdr | cut -d' ' -f3))) = $hdr_addr ] || exit 1
+
+# version, eh_frame_ptr_enc, fde_count_enc, table_enc
+[ "$(od -An -tx1 -j $hdr_ofs -N 4 a.out | xargs)" = "01 1b 03 3b" ] || exit 1
+set -- $(od -An -td4 -v -j $((hdr_ofs + 4)) -N 8 a.out)
+[ $((hdr_addr + 4 + $1)) = $eh_frame_addr ] || exit 1
+count=$2
+[ $count = 3 ] || exit 1
+
+# Initial locations in the table are sorted and match the FDEs
+expected=$(readelf -wf a.out | awk '$4 == "FDE" { split($6, pc, "[=.]"); print pc[2] }' | while read pc; do echo $((0x$pc)); done | sort -n | xargs)
+actual=$(od -An -td4 -v -j $((hdr_ofs + 12)) -N $((count * 8)) a.out | xargs -n 2 | while read loc fde; do echo $((hdr_addr + loc)); done | xargs)
+[ "$expected" = "$actual" ] || exit 1
+exit 0
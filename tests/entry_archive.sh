@@ -0,0 +1,16 @@
+# The entry symbol pulls its defining member out of an archive.
+echo '.globl _start; _start: call helper; jmp _start' | cc -o %entry_archive1.o -c -x assembler -
+echo '.globl helper; helper: ret' | cc -o %entry_archive2.o -c -x assembler -
+echo '.globl unused; unused: ret' | cc -o %entry_archive3.o -c -x assembler -
+rm -f %entry_archive.a
+ar rcs %entry_archive.a %entry_archive1.o %entry_archive2.o %entry_archive3.o
+
+cargo run %entry_archive.a || exit 1
+
+readelf -hW a.out | grep -q "Entry point address: *0x401000" || exit 1
+readelf -sW a.out | grep -q " helper$" || exit 1
+readelf -sW a.out | grep -q " unused$" && exit 1
+
+# A missing entry symbol is an error with --fatal-warnings
+cargo run -- --fatal-warnings --entry=nonexistent %entry_archive.a && exit 1
+exit 0
@@ -0,0 +1,33 @@
+# Without the entry symbol, the entry point defaults to the start of .text
+cat <<EOF2 | cc -o %entry_fallback1.o -c -x assembler -
+  .text
+  .globl main
+main:
+  mov \$60, %eax
+  mov \$5, %edi
+  syscall
+EOF2
+echo '.data; .globl x; x: .long 1' | cc -o %entry_fallback2.o -c -x assembler -
+
+cargo run %entry_fallback1.o 2> %entry_fallback.log || exit 1
+text=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".text" { print $4 }' | sed 's/^0*//')
+readelf -hW a.out | grep -q "Entry point address: *0x$text$" || exit 1
+grep -q "cannot find entry symbol _start; defaulting to 0x$text" %entry_fallback.log || exit 1
+./a.out
+[ $? = 5 ] || exit 1
+
+# The same applies to a symbol given by --entry
+cargo run -- --entry=nonexistent %entry_fallback1.o 2> %entry_fallback.log || exit 1
+grep -q "cannot find entry symbol nonexistent; defaulting to 0x$text" %entry_fallback.log || exit 1
+
+# The image base is used if .text is empty
+cargo run %entry_fallback2.o 2> %entry_fallback.log || exit 1
+readelf -hW a.out | grep -q "Entry point address: *0x400000$" || exit 1
+grep -q "cannot find entry symbol _start; defaulting to 0x400000" %entry_fallback.log || exit 1
+
+# --fatal-warnings makes it an error, and no output is written
+rm -f a.out
+! cargo run -- --fatal-warnings %entry_fallback1.o 2> %entry_fallback.log || exit 1
+grep -q "cannot find entry symbol _start" %entry_fallback.log || exit 1
+[ ! -e a.out ] || exit 1
+exit 0
@@ -0,0 +1,26 @@
+# Undefined-symbol errors are capped by --error-limit
+for i in $(seq 1 30); do echo "  call missing$i"; done > %error_limit.calls
+cat <<EOF2 | cc -o %error_limit.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+$(cat %error_limit.calls)
+  jmp _start
+EOF2
+
+cargo run %error_limit.o 2> %error_limit.log && exit 1
+[ "$(grep -c "undefined symbol" %error_limit.log)" = 20 ] || exit 1
+grep -q "too many errors emitted, stopping now (use --error-limit=0 to see all)" %error_limit.log || exit 1
+
+cargo run -- --error-limit=5 %error_limit.o 2> %error_limit.log && exit 1
+[ "$(grep -c "undefined symbol" %error_limit.log)" = 5 ] || exit 1
+
+cargo run -- --error-limit=0 %error_limit.o 2> %error_limit.log && exit 1
+[ "$(grep -c "undefined symbol" %error_limit.log)" = 30 ] || exit 1
+grep -q "too many errors" %error_limit.log && exit 1
+
+# Warnings have their own limit and do not abort the link
+cargo run -- --warn-unresolved-symbols --warning-limit=3 %error_limit.o 2> %error_limit.log || exit 1
+[ "$(grep -c "WARN.*undefined symbol:" %error_limit.log)" = 3 ] || exit 1
+grep -q "too many warnings emitted" %error_limit.log || exit 1
+exit 0
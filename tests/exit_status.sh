@@ -0,0 +1,45 @@
+# The exit status is 0 on success, 1 for link errors and 2 for usage errors, and failed
+# links end with a summary
+cat <<EOF2 | cc -o %exit_status1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  call foo
+EOF2
+cat <<EOF2 | cc -o %exit_status2.o -c -x assembler -
+  .text
+  .globl foo
+foo:
+  ret
+EOF2
+
+cargo run %exit_status1.o %exit_status2.o || exit 1
+
+# Undefined symbols
+cargo run %exit_status1.o 2> %exit_status.log
+[ $? -eq 1 ] || exit 1
+[ "$(tail -1 %exit_status.log)" = "bold: link failed: 1 symbols are not defined" ] || exit 1
+# Undefined symbols which are downgraded to warnings don't fail the link
+cargo run -- --warn-unresolved-symbols %exit_status1.o || exit 1
+
+# Duplicate symbols
+cargo run %exit_status1.o %exit_status2.o %exit_status2.o 2> %exit_status.log
+[ $? -eq 1 ] || exit 1
+[ "$(tail -1 %exit_status.log)" = "bold: link failed: 1 symbols are defined more than once" ] || exit 1
+
+# Invalid input
+echo junk > %exit_status3.o
+cargo run %exit_status3.o 2> %exit_status.log
+[ $? -eq 1 ] || exit 1
+tail -1 %exit_status.log | grep -q "^bold: link failed: %exit_status3.o: " || exit 1
+
+# Unknown option, missing input file and no inputs
+cargo run -- --no-such-option %exit_status1.o 2> %exit_status.log
+[ $? -eq 2 ] || exit 1
+grep -q "^bold: unknown option: --no-such-option$" %exit_status.log || exit 1
+cargo run %exit_status.nonexistent 2> %exit_status.log
+[ $? -eq 2 ] || exit 1
+tail -1 %exit_status.log | grep -q "^bold: link failed: %exit_status.nonexistent: " || exit 1
+cargo run 2> /dev/null
+[ $? -eq 2 ] || exit 1
+exit 0
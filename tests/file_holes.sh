@@ -0,0 +1,45 @@
+# Bytes of the output which don't belong to any header or section are zero. The file is
+# not cleared before chunks are written, so they must not be left with garbage.
+cat <<EOF2 | cc -o %file_holes.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov \$60, %eax
+  mov \$0, %edi
+  syscall
+  .data
+  .quad 1
+  .section .rodata
+  .p2align 12
+  .quad 2
+  .section .tdata,"awT",@progbits
+  .byte 3
+  .bss
+  .zero 4096
+  .ident "file_holes"
+EOF2
+
+cargo run %file_holes.o || exit 1
+./a.out || exit 1
+
+# Clear the headers and the sections, and the rest of the file must be zero
+cp a.out %file_holes.out
+clear() {
+  [ $2 -gt 0 ] || return 0
+  dd if=/dev/zero of=%file_holes.out bs=1 seek=$1 count=$2 conv=notrunc status=none
+}
+clear 0 64
+phoff=$(readelf -hW a.out | awk '/Start of program headers/ { print $5 }')
+phnum=$(readelf -hW a.out | awk '/Number of program headers/ { print $5 }')
+clear $phoff $((phnum * 56))
+shoff=$(readelf -hW a.out | awk '/Start of section headers/ { print $5 }')
+shnum=$(readelf -hW a.out | awk '/Number of section headers/ { print $5 }')
+clear $shoff $((shnum * 64))
+readelf -SW a.out | sed 's/\[ */[/' | awk '$1 ~ /^\[[0-9]/ && $3 != "NOBITS" { print $5, $6 }' > %file_holes.sections
+[ -s %file_holes.sections ] || exit 1
+while read offset size; do
+  clear $((0x$offset)) $((0x$size))
+done < %file_holes.sections
+size=$(stat -c %s a.out)
+cmp -n $size %file_holes.out /dev/zero || exit 1
+exit 0
@@ -0,0 +1,54 @@
+# GOT-generating relocations refer to entries in .got
+cat <<EOF2 | cc -o %got1.o -c -O1 -fPIC -fno-plt -x c -
+extern int printf(const char *, ...);
+extern int counter;
+void _start(void) { printf("x", counter); }
+EOF2
+cat <<EOF2 | cc -o %got2.o -c -x assembler -
+  .text
+  .globl printf
+printf:
+  # exit(counter)
+  mov %esi, %edi
+  mov \$60, %eax
+  syscall
+  .data
+  .globl counter
+counter:
+  .long 7
+EOF2
+
+readelf -rW %got1.o | grep -q "GOTPCRELX *[0-9a-f]* printf" || exit 1
+cargo run %got1.o %got2.o || exit 1
+./a.out
+[ $? = 7 ] || exit 1
+
+# .got holds the addresses of the symbols and is RELRO
+got=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".got" { print $4 }')
+[ -n "$got" ] || exit 1
+entries=$(od -An -tx8 -v -j $((0x$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".got" { print $5 }'))) -N 16 a.out | xargs)
+for sym in printf counter; do
+  addr=$(readelf -sW a.out | awk -v sym=$sym '$8 == sym { print $2 }')
+  echo "$entries" | grep -qw "$addr" || exit 1
+done
+relro=$(readelf -lW a.out | awk '$1 == "GNU_RELRO" { print $3 }')
+[ $((relro)) -le $((0x$got)) ] || exit 1
+
+# The GOT entry for GOTTPOFF holds the TP-relative offset of the variable
+cat <<EOF2 | cc -o %got3.o -c -O1 -fPIC -ftls-model=initial-exec -x c -
+extern __thread int tls_ie;
+int _start(void) { return tls_ie; }
+EOF2
+cat <<EOF2 | cc -o %got4.o -c -x assembler -
+  .section .tdata,"awT",@progbits
+  .globl tls_ie
+tls_ie:
+  .long 3
+  .long 4
+EOF2
+readelf -rW %got3.o | grep -q "GOTTPOFF" || exit 1
+cargo run %got3.o %got4.o || exit 1
+got_ofs=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".got" { print $5 }')
+# tls_ie is at the start of the 8-byte TLS block, whose end the thread pointer points to
+[ "$(od -An -td8 -j $((0x$got_ofs)) -N 8 a.out | xargs)" = "-8" ] || exit 1
+exit 0
@@ -0,0 +1,35 @@
+# Plain R_X86_64_GOTPCREL loads the address of the symbol from its GOT entry and the
+# instruction is never rewritten
+cat <<EOF2 | cc -o %gotpcrel1.o -c -Wa,-mrelax-relocations=no -x assembler -
+  .text
+  .globl _start
+_start:
+  movq value@GOTPCREL(%rip), %rax
+  movl (%rax), %edi
+  mov \$60, %eax
+  syscall
+EOF2
+cat <<EOF2 | cc -o %gotpcrel2.o -c -x assembler -
+  .data
+  .globl value
+  .long 0
+value:
+  .long 9
+EOF2
+
+readelf -rW %gotpcrel1.o | grep -q "R_X86_64_GOTPCREL " || exit 1
+cargo run %gotpcrel1.o %gotpcrel2.o || exit 1
+./a.out
+[ $? = 9 ] || exit 1
+
+# The displacement of the mov refers to the GOT entry, which holds the address of value
+got=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".got" { print $4, $5 }')
+text=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".text" { print $4, $5 }')
+set -- $got $text
+got_addr=$((0x$1)); got_ofs=$((0x$2)); text_addr=$((0x$3)); text_ofs=$((0x$4))
+[ "$(od -An -tx1 -j $text_ofs -N 3 a.out | xargs)" = "48 8b 05" ] || exit 1
+disp=$(od -An -td4 -j $((text_ofs + 3)) -N 4 a.out | xargs)
+[ $((text_addr + 7 + disp)) = $got_addr ] || exit 1
+value=$(readelf -sW a.out | awk '$8 == "value" { print $2 }')
+[ $(od -An -tx8 -j $got_ofs -N 8 a.out | xargs) = $value ] || exit 1
+exit 0
@@ -0,0 +1,31 @@
+# printf is called through the PLT since its address in libc is not known until runtime
+cat <<EOF2 | cc -o %hello_dynamic.o -c -x assembler -
+  .text
+  .globl main
+main:
+  sub \$8, %rsp
+  lea msg(%rip), %rdi
+  xor %eax, %eax
+  call printf@PLT
+  xor %eax, %eax
+  add \$8, %rsp
+  ret
+
+  .section .rodata
+msg:
+  .string "Hello world"
+EOF2
+
+cargo run /usr/lib/x86_64-linux-gnu/crt1.o \
+  /usr/lib/x86_64-linux-gnu/crti.o \
+  %hello_dynamic.o \
+  /lib/x86_64-linux-gnu/libc.so.6 \
+  /usr/lib/x86_64-linux-gnu/crtn.o || exit 1
+
+[ "$(./a.out)" = "Hello world" ] || exit 1
+
+# The call refers to the first PLT entry, which follows the 16-byte header
+plt=$((0x$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".plt" { print $4 }')))
+objdump -d a.out | awk '/<main>:/ { f = 1 } f && /call/ { print; exit }' |
+  grep -q "call *$(printf %x $((plt + 16))) " || exit 1
+exit 0
@@ -0,0 +1,49 @@
+# A hello world of i386 objects links into an ELF32 executable. Addends of the SHT_REL
+# relocations are read from the relocated fields, and R_386_GOTPC and R_386_GOTOFF are
+# relative to _GLOBAL_OFFSET_TABLE_.
+cat <<EOF2 | cc -m32 -o %i386_hello1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  call 1f
+1:
+  pop %ebx
+  addl \$_GLOBAL_OFFSET_TABLE_+[.-1b], %ebx
+  lea msg@GOTOFF(%ebx), %ecx
+  mov \$4, %eax
+  mov \$1, %ebx
+  mov \$14, %edx
+  call write
+  # Exit with 0 if ptr is msg + 7
+  mov ptr, %eax
+  sub \$msg, %eax
+  lea -7(%eax), %ebx
+  mov \$1, %eax
+  int \$0x80
+
+  .data
+msg:
+  .ascii "Hello, world!\n"
+ptr:
+  .long msg + 7
+EOF2
+cat <<EOF2 | cc -m32 -o %i386_hello2.o -c -x assembler -
+  .text
+  .globl write
+write:
+  int \$0x80
+  ret
+EOF2
+
+cargo run %i386_hello1.o %i386_hello2.o || exit 1
+[ "$(readelf -hW a.out | awk '/Class:/ { print $2 }')" = ELF32 ] || exit 1
+[ "$(readelf -hW a.out | awk '/Machine:/ { print $2 }')" = Intel ] || exit 1
+[ "$(./a.out)" = "Hello, world!" ] || exit 1
+
+# -m elf_i386 selects the target, so x86-64 objects are rejected
+echo '.globl _start; _start: ret' | cc -o %i386_hello3.o -c -x assembler -
+! cargo run -- -m elf_i386 %i386_hello3.o 2> %i386_hello.log || exit 1
+grep -q 'x86-64 object is incompatible with i386 output' %i386_hello.log || exit 1
+cargo run -- -melf_i386 %i386_hello1.o %i386_hello2.o || exit 1
+
+exit 0
@@ -0,0 +1,33 @@
+# .init_array and .fini_array are sorted by priority
+cat <<EOF2 | cc -o %init_array_priority1.o -c -x c -
+__attribute__((constructor(300))) void c300(void) {}
+__attribute__((constructor)) void c_default(void) {}
+__attribute__((constructor(101))) void c101(void) {}
+__attribute__((destructor(200))) void d200(void) {}
+__attribute__((destructor)) void d_default(void) {}
+EOF2
+cat <<EOF2 | cc -o %init_array_priority2.o -c -x c -
+__attribute__((constructor(200))) void c200(void) {}
+__attribute__((destructor(150))) void d150(void) {}
+EOF2
+cat <<EOF2 | cc -o %init_array_priority3.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  jmp _start
+EOF2
+
+cargo run %init_array_priority1.o %init_array_priority2.o %init_array_priority3.o || exit 1
+
+dump() {
+  sec=$(readelf -SW a.out | sed 's/\[ */[/' | awk -v name=$1 '$2 == name')
+  od -An -tx8 -v -j $((0x$(echo $sec | cut -d' ' -f5))) -N $((0x$(echo $sec | cut -d' ' -f6))) a.out | xargs
+}
+addrs() {
+  for name in "$@"; do
+    readelf -sW a.out | awk -v name=$name '$8 == name { print $2 }'
+  done | xargs
+}
+[ "$(dump .init_array)" = "$(addrs c101 c200 c300 c_default)" ] || exit 1
+[ "$(dump .fini_array)" = "$(addrs d150 d200 d_default)" ] || exit 1
+exit 0
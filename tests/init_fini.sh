@@ -0,0 +1,40 @@
+# Fragments of .init/.fini are concatenated in the command-line order
+cat <<EOF2 | cc -o %init_fini_crti.o -c -x assembler -
+  .section .init,"ax",@progbits
+  .globl _init
+  .type _init, @function
+_init:
+  push %rbp
+  .section .fini,"ax",@progbits
+  .globl _fini
+  .type _fini, @function
+_fini:
+  push %rbp
+EOF2
+cat <<EOF2 | cc -o %init_fini_main.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  call _init
+  jmp _start
+  .section .init,"ax",@progbits
+  nop
+  .section .fini,"ax",@progbits
+  nop
+EOF2
+cat <<EOF2 | cc -o %init_fini_crtn.o -c -x assembler -
+  .section .init,"ax",@progbits
+  pop %rbp
+  ret
+  .section .fini,"ax",@progbits
+  pop %rbp
+  ret
+EOF2
+
+cargo run %init_fini_crti.o %init_fini_main.o %init_fini_crtn.o || exit 1
+for sec in .init .fini; do
+  [ "$(readelf -SW a.out | grep -c " $sec ")" = 1 ] || exit 1
+  [ "$(objdump -d -j $sec a.out | awk -F'\t' 'NF >= 3 { print $3 }' | tr -s ' ' | xargs)" = "push %rbp nop pop %rbp ret" ] || exit 1
+done
+[ "$(readelf -sW a.out | awk '$8 == "_init" { print $2 }')" = "$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".init" { print $4 }')" ] || exit 1
+exit 0
@@ -0,0 +1,52 @@
+# Input sections are placed at their sh_addralign within output sections
+cat <<EOF2 | cc -o %isec_align1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  jmp _start
+  .section .rodata
+  .globl a1
+a1:
+  .byte 1
+EOF2
+i=2
+for align in 8 16 64 1 16; do
+  cat <<EOF2 | cc -o %isec_align$i.o -c -x assembler -
+  .text
+  .p2align 4
+  .globl f$i
+f$i:
+  ret
+  .section .rodata
+  .balign $align
+  .globl a$i
+a$i:
+  .byte $i
+EOF2
+  i=$((i + 1))
+done
+
+cargo run %isec_align1.o %isec_align2.o %isec_align3.o %isec_align4.o %isec_align5.o %isec_align6.o || exit 1
+
+check() {
+  addr=$(readelf -sW a.out | awk -v name=$1 '$8 == name { print $2 }')
+  [ -n "$addr" ] && [ $((0x$addr % $2)) = 0 ] || exit 1
+}
+check a2 8
+check a3 16
+check a4 64
+check a6 16
+check f2 16
+check f6 16
+# The maximum alignment is propagated to the output section
+[ "$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".rodata" { print $NF }')" = 64 ] || exit 1
+# Each input is placed after the previous one
+[ $((0x$(readelf -sW a.out | awk '$8 == "a5" { print $2 }'))) = $((0x$(readelf -sW a.out | awk '$8 == "a4" { print $2 }') + 1)) ] || exit 1
+# Padding in code is filled with int3
+objdump -d -j .text a.out | grep -q int3 || exit 1
+
+# The output section starts at the maximum alignment, even if the first input requires it
+cargo run %isec_align4.o %isec_align1.o || exit 1
+[ $((0x$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".rodata" { print $4 }') % 64)) = 0 ] || exit 1
+check a4 64
+exit 0
@@ -0,0 +1,46 @@
+# Only the members which are extracted from a large archive end up in the output. Member
+# names longer than 15 characters are stored in the table of long names.
+for i in $(seq 0 63); do
+  cat <<EOF2 | cc -o %large_archive_member_with_long_name$i.o -c -x assembler -
+  .data
+  .globl data$i
+data$i:
+  .byte $i
+  .zero 1048575
+EOF2
+done
+cat <<EOF2 | cc -o %large_archive.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  movzbl data42(%rip), %edi
+  mov \$60, %eax
+  syscall
+EOF2
+rm -f %large_archive.a
+ar rcs %large_archive.a %large_archive_member_with_long_name*.o
+[ $(stat -c %s %large_archive.a) -gt 67108864 ] || exit 1
+
+cargo run %large_archive.o %large_archive.a || exit 1
+./a.out
+[ $? = 42 ] || exit 1
+[ $(stat -c %s a.out) -lt 2097152 ] || exit 1
+readelf -sW a.out | grep -q " data42$" || exit 1
+readelf -sW a.out | grep -q " data41$" && exit 1
+
+# The pages of the members which are not extracted are released
+mv a.out %large_archive.out
+cargo run %large_archive.o %large_archive.a --stats > %large_archive.stats || exit 1
+cmp a.out %large_archive.out || exit 1
+released=$(sed -n 's/^released_input_bytes=//p' %large_archive.stats)
+[ "$released" -gt 62914560 ] || exit 1
+
+# The BSD format stores long names at the start of the member data
+if command -v llvm-ar > /dev/null; then
+  rm -f %large_archive.a
+  llvm-ar --format=bsd rcs %large_archive.a %large_archive_member_with_long_name4*.o
+  cargo run %large_archive.o %large_archive.a || exit 1
+  ./a.out
+  [ $? = 42 ] || exit 1
+fi
+exit 0
@@ -0,0 +1,565 @@
+//! Link objects in memory through the library API
+
+use std::{
+    io::ErrorKind,
+    os::unix::fs::PermissionsExt,
+    path::PathBuf,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use bold::{Config, Input, LinkError, LinkOutput};
+use elf::{endian::LittleEndian, ElfBytes};
+
+/// Assemble a source with the C compiler, or return None if there is none
+fn assemble(name: &str, source: &str) -> Option<Vec<u8>> {
+    // Tests run in parallel, so each call has its own directory
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "bold-link-api-{}-{}",
+        std::process::id(),
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let src = dir.join(format!("{}.s", name));
+    let obj = dir.join(format!("{}.o", name));
+    std::fs::write(&src, source).unwrap();
+    let status = Command::new("cc")
+        .arg("-c")
+        .arg("-o")
+        .arg(&obj)
+        .arg(&src)
+        .status()
+        .ok()?;
+    assert!(status.success());
+    let data = std::fs::read(&obj).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    Some(data)
+}
+
+#[test]
+fn link_in_memory() {
+    let Some(main) = assemble(
+        "main",
+        "  .text\n  .globl _start\n_start:\n  mov value(%rip), %edi\n  mov $60, %eax\n  syscall\n",
+    ) else {
+        eprintln!("skipped: cc is not available");
+        return;
+    };
+    let value = assemble("value", "  .data\n  .globl value\nvalue:\n  .long 42\n").unwrap();
+    let inputs = [
+        Input::Bytes {
+            name: "main.o".to_owned(),
+            data: main,
+        },
+        Input::Bytes {
+            name: "value.o".to_owned(),
+            data: value,
+        },
+    ];
+    let output = bold::link(&inputs, &Config::new()).unwrap();
+    assert!(output.warnings.is_empty());
+
+    let file = ElfBytes::<LittleEndian>::minimal_parse(&output.data).unwrap();
+    assert_eq!(file.ehdr.e_type, elf::abi::ET_EXEC);
+    let (symtab, strtab) = file.symbol_table().unwrap().unwrap();
+    let addr_of = |name: &str| {
+        symtab
+            .iter()
+            .find(|sym| strtab.get(sym.st_name as usize).unwrap() == name)
+            .unwrap()
+            .st_value
+    };
+    assert_eq!(file.ehdr.e_entry, addr_of("_start"));
+
+    // mov value(%rip), %edi refers to value, which holds 42
+    let text = file.section_header_by_name(".text").unwrap().unwrap();
+    let code = &output.data[text.sh_offset as usize..];
+    assert_eq!(code[..2], [0x8b, 0x3d]);
+    let disp = i32::from_le_bytes(code[2..6].try_into().unwrap());
+    assert_eq!(
+        text.sh_addr.wrapping_add_signed(6 + disp as i64),
+        addr_of("value")
+    );
+    let data = file.section_header_by_name(".data").unwrap().unwrap();
+    let offset = (addr_of("value") - data.sh_addr + data.sh_offset) as usize;
+    assert_eq!(output.data[offset..offset + 4], 42u32.to_le_bytes());
+
+    // Links in the same process give the same output
+    assert_eq!(
+        bold::link(&inputs, &Config::new()).unwrap().data,
+        output.data
+    );
+}
+
+/// Link the sources assembled into objects, or return None if there is no C compiler
+fn link_sources(sources: &[&str], config: &Config) -> Option<Result<LinkOutput, LinkError>> {
+    let mut inputs = vec![];
+    for (i, source) in sources.iter().enumerate() {
+        let name = format!("input{}", i);
+        let data = assemble(&name, source)?;
+        inputs.push(Input::Bytes {
+            name: format!("{}.o", name),
+            data,
+        });
+    }
+    Some(bold::link(&inputs, config))
+}
+
+const START: &str = "  .text\n  .globl _start\n_start:\n  jmp _start\n";
+
+#[test]
+fn io_error() {
+    let inputs = [Input::Path(PathBuf::from("/nonexistent/input.o"))];
+    match bold::link(&inputs, &Config::new()) {
+        Err(LinkError::Io { path, error }) => {
+            assert_eq!(path, PathBuf::from("/nonexistent/input.o"));
+            assert_eq!(error.kind(), ErrorKind::NotFound);
+        }
+        _ => panic!("the link must fail"),
+    }
+}
+
+#[test]
+fn parse_error() {
+    let inputs = [Input::Bytes {
+        name: "garbage.o".to_owned(),
+        data: b"not an object file".to_vec(),
+    }];
+    match bold::link(&inputs, &Config::new()) {
+        Err(LinkError::Parse { file, .. }) => assert_eq!(file, "garbage.o"),
+        _ => panic!("the link must fail"),
+    }
+
+    // The header of the only member is cut off
+    let inputs = [Input::Archive {
+        name: "truncated.a".to_owned(),
+        data: b"!<arch>\nfoo.o/".to_vec(),
+    }];
+    match bold::link(&inputs, &Config::new()) {
+        Err(LinkError::Parse { file, msg }) => {
+            assert_eq!(file, "truncated.a");
+            assert!(msg.starts_with("malformed archive"), "{}", msg);
+        }
+        _ => panic!("the link must fail"),
+    }
+}
+
+#[test]
+fn undefined_symbols() {
+    let Some(result) = link_sources(
+        &["  .text\n  .globl _start\n_start:\n  call missing\n"],
+        &Config::new(),
+    ) else {
+        eprintln!("skipped: cc is not available");
+        return;
+    };
+    match result {
+        Err(LinkError::UndefinedSymbols(names)) => assert_eq!(names, ["missing"]),
+        _ => panic!("the link must fail"),
+    }
+}
+
+#[test]
+fn duplicate_symbols() {
+    let Some(result) = link_sources(
+        &[START, "  .data\n  .globl _start\n_start:\n  .long 0\n"],
+        &Config::new(),
+    ) else {
+        eprintln!("skipped: cc is not available");
+        return;
+    };
+    match result {
+        Err(LinkError::DuplicateSymbols(names)) => assert_eq!(names, ["_start"]),
+        _ => panic!("the link must fail"),
+    }
+}
+
+#[test]
+fn unsupported() {
+    let Some(result) = link_sources(
+        &["  .text\n  .globl _start\n_start:\n  nop\n  .reloc ., R_X86_64_COPY, _start\n  nop\n"],
+        &Config::new(),
+    ) else {
+        eprintln!("skipped: cc is not available");
+        return;
+    };
+    match result {
+        Err(LinkError::Unsupported(errors)) => assert_eq!(
+            errors,
+            ["input0.o:(.text+0x1): unsupported relocation R_X86_64_COPY"]
+        ),
+        _ => panic!("the link must fail"),
+    }
+}
+
+#[test]
+fn layout_error() {
+    // Put .text onto the ELF header
+    let mut config = Config::new();
+    config.section_start.insert(".text".to_owned(), 0x400000);
+    let Some(result) = link_sources(&[START], &config) else {
+        eprintln!("skipped: cc is not available");
+        return;
+    };
+    match result {
+        Err(LinkError::Layout(errors)) => {
+            assert!(
+                errors[0]
+                    .starts_with("ELF header address [0x400000, 0x40003f] overlaps section .text"),
+                "{:?}",
+                errors
+            );
+        }
+        _ => panic!("the link must fail"),
+    }
+}
+
+/// Object file with a `_start` which exits with status 42, so that linking it needs no
+/// assembler
+#[rustfmt::skip]
+const EXIT_42: &[u8] = &[
+    // ELF header: ELFCLASS64, ET_REL, EM_X86_64, 4 section headers at 0xa0
+    0x7f, 0x45, 0x4c, 0x46, 0x02, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x3e, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xa0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x04, 0x00, 0x03, 0x00,
+    // .text: mov $60, %eax; mov $42, %edi; syscall
+    0xb8, 0x3c, 0x00, 0x00, 0x00, 0xbf, 0x2a, 0x00, 0x00, 0x00, 0x0f, 0x05,
+    // .shstrtab, also used as the string table of .symtab:
+    // "\0.text\0.symtab\0.shstrtab\0_start\0"
+    0x00, 0x2e, 0x74, 0x65, 0x78, 0x74, 0x00, 0x2e, 0x73, 0x79, 0x6d, 0x74, 0x61, 0x62, 0x00, 0x2e,
+    0x73, 0x68, 0x73, 0x74, 0x72, 0x74, 0x61, 0x62, 0x00, 0x5f, 0x73, 0x74, 0x61, 0x72, 0x74, 0x00,
+    // Padding
+    0x00, 0x00, 0x00, 0x00,
+    // .symtab: the null symbol, and _start (STB_GLOBAL, STT_FUNC) at the start of .text
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x19, 0x00, 0x00, 0x00, 0x12, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // Section header: null
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // Section header: .text
+    0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x0c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // Section header: .symtab
+    0x07, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x70, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+    0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // Section header: .shstrtab
+    0x0f, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Write an executable to a file and run it, returning the exit status
+fn run_executable(name: &str, data: &[u8]) -> i32 {
+    let path = std::env::temp_dir().join(format!("bold-link-api-{}-{}", std::process::id(), name));
+    std::fs::write(&path, data).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let status = Command::new(&path).status().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    status.code().unwrap()
+}
+
+#[test]
+fn object_from_bytes() {
+    let inputs = [Input::Bytes {
+        name: "exit42.o".to_owned(),
+        data: EXIT_42.to_vec(),
+    }];
+    let output = bold::link(&inputs, &Config::new()).unwrap();
+    assert_eq!(run_executable("object", &output.data), 42);
+
+    // The same object as the only member of an archive, which is linked because it
+    // defines the entry symbol
+    let mut archive = b"!<arch>\n".to_vec();
+    archive.extend(format!("{:<48}{:<10}`\n", "exit42.o/", EXIT_42.len()).as_bytes());
+    archive.extend(EXIT_42);
+    let inputs = [Input::Archive {
+        name: "libexit42.a".to_owned(),
+        data: archive,
+    }];
+    let output = bold::link(&inputs, &Config::new()).unwrap();
+    assert_eq!(run_executable("archive", &output.data), 42);
+}
+
+#[test]
+fn json_report() {
+    let Some(main) = assemble(
+        "main",
+        "  .text\n  .globl _start\n_start:\n  mov value(%rip), %edi\n  mov $60, %eax\n  syscall\n",
+    ) else {
+        eprintln!("skipped: cc is not available");
+        return;
+    };
+    let value = assemble("value", "  .data\n  .globl value\nvalue:\n  .long 42\n").unwrap();
+    let inputs = [
+        Input::Bytes {
+            name: "main.o".to_owned(),
+            data: main,
+        },
+        Input::Bytes {
+            name: "value.o".to_owned(),
+            data: value,
+        },
+    ];
+    let path =
+        std::env::temp_dir().join(format!("bold-link-api-{}-report.json", std::process::id()));
+    let mut config = Config::new();
+    config.json_report = Some(path.to_string_lossy().into_owned());
+    let output = bold::link(&inputs, &config).unwrap();
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(report["format"], "bold-link-report");
+    assert_eq!(report["version"], 1);
+
+    let file = ElfBytes::<LittleEndian>::minimal_parse(&output.data).unwrap();
+    assert_eq!(report["entry"], file.ehdr.e_entry);
+
+    // Sections are listed in the order of their headers
+    let shdrs = file.section_headers().unwrap();
+    let sections = report["sections"].as_array().unwrap();
+    assert_eq!(sections.len(), shdrs.len() - 1);
+    let text = sections.iter().find(|s| s["name"] == ".text").unwrap();
+    let shdr = shdrs.get(text["index"].as_u64().unwrap() as usize).unwrap();
+    assert_eq!(text["address"], shdr.sh_addr);
+    assert_eq!(text["offset"], shdr.sh_offset);
+    assert_eq!(text["size"], shdr.sh_size);
+    assert_eq!(text["inputs"][0]["file"], "main.o");
+    assert_eq!(text["inputs"][0]["address"], shdr.sh_addr);
+
+    let (symtab, strtab) = file.symbol_table().unwrap().unwrap();
+    let sym = symtab
+        .iter()
+        .find(|sym| strtab.get(sym.st_name as usize).unwrap() == "value")
+        .unwrap();
+    let symbols = report["symbols"].as_array().unwrap();
+    let value = symbols.iter().find(|s| s["name"] == "value").unwrap();
+    assert_eq!(value["address"], sym.st_value);
+    assert_eq!(value["section"], sym.st_shndx);
+    assert_eq!(value["file"], "value.o");
+
+    let phdrs = file.segments().unwrap();
+    let segments = report["segments"].as_array().unwrap();
+    assert_eq!(segments.len(), phdrs.len());
+    for (segment, phdr) in segments.iter().zip(phdrs.iter()) {
+        assert_eq!(segment["type"], phdr.p_type);
+        assert_eq!(segment["address"], phdr.p_vaddr);
+        assert_eq!(segment["file_size"], phdr.p_filesz);
+    }
+}
+
+#[test]
+fn output_permissions() {
+    let inputs = [Input::Bytes {
+        name: "exit42.o".to_owned(),
+        data: EXIT_42.to_vec(),
+    }];
+    let dir = std::env::temp_dir();
+    // Permissions of a new file, which the umask is applied to
+    let plain = dir.join(format!("bold-link-api-{}-plain", std::process::id()));
+    std::fs::write(&plain, b"").unwrap();
+    let mode = std::fs::metadata(&plain).unwrap().permissions().mode() & 0o777;
+    std::fs::remove_file(&plain).unwrap();
+
+    // Executables get an execute bit for each read bit
+    let path = dir.join(format!("bold-link-api-{}-exec", std::process::id()));
+    bold::link_to_file(&inputs, &Config::new(), &path).unwrap();
+    let exec_mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(exec_mode, mode | (mode & 0o444) >> 2, "{:o}", exec_mode);
+
+    // Shared objects are not executable
+    let path = dir.join(format!("bold-link-api-{}-shared", std::process::id()));
+    let mut config = Config::new();
+    config.shared = true;
+    bold::link_to_file(&inputs, &config, &path).unwrap();
+    let shared_mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(shared_mode, mode, "{:o}", shared_mode);
+}
+
+#[test]
+fn failed_link_keeps_output() {
+    // R_X86_64_32 overflows with the image base above 4 GiB. The error is found while
+    // relocations are applied, after the output has been created.
+    let Some(obj) = assemble(
+        "overflow",
+        "  .text\n  .globl _start\n_start:\n  mov $foo, %edi\n  .data\nfoo:\n  .long 0\n",
+    ) else {
+        eprintln!("skipped: cc is not available");
+        return;
+    };
+    let inputs = [Input::Bytes {
+        name: "overflow.o".to_owned(),
+        data: obj,
+    }];
+    let dir = std::env::temp_dir().join(format!("bold-link-api-{}-keep", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("a.out");
+    std::fs::write(&path, b"original").unwrap();
+
+    let mut config = Config::new();
+    config.image_base = Some(0x100000000);
+    assert!(matches!(
+        bold::link_to_file(&inputs, &config, &path),
+        Err(LinkError::Failed(_))
+    ));
+    // The old output is untouched and the temporary file is removed
+    assert_eq!(std::fs::read(&path).unwrap(), b"original");
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    // A successful link replaces it
+    bold::link_to_file(&inputs, &Config::new(), &path).unwrap();
+    assert_eq!(&std::fs::read(&path).unwrap()[..4], b"\x7fELF");
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn x86_64_target_selection() {
+    // GOT loads, TLS and absolute data exercise the machine-specific parts of the linker
+    let sources = [
+        "  .text\n  .globl _start\n_start:\n  mov value@GOTPCREL(%rip), %rax\n  mov %fs:counter@tpoff, %edi\n  call exit\n  .data\n  .quad value\n",
+        "  .text\n  .globl exit\nexit:\n  mov $60, %eax\n  syscall\n  .data\n  .globl value\nvalue:\n  .long 42\n  .section .tbss,\"awT\",@nobits\n  .globl counter\ncounter:\n  .zero 4\n",
+    ];
+    let Some(output) = link_sources(&sources, &Config::new()) else {
+        eprintln!("skipped: cc is not available");
+        return;
+    };
+    let output = output.unwrap().data;
+
+    // -m elf_x86_64 selects the same target as the machine of the inputs, and the default
+    // image base is that of x86-64
+    let mut config = Config::new();
+    config.emulation = Some("elf_x86_64".to_owned());
+    assert_eq!(
+        link_sources(&sources, &config).unwrap().unwrap().data,
+        output
+    );
+    let mut config = Config::new();
+    config.image_base = Some(0x400000);
+    assert_eq!(
+        link_sources(&sources, &config).unwrap().unwrap().data,
+        output
+    );
+
+    let file = ElfBytes::<LittleEndian>::minimal_parse(&output).unwrap();
+    assert_eq!(file.ehdr.e_machine, elf::abi::EM_X86_64);
+    let first_load = file
+        .segments()
+        .unwrap()
+        .iter()
+        .find(|phdr| phdr.p_type == elf::abi::PT_LOAD)
+        .unwrap();
+    assert_eq!(first_load.p_vaddr, 0x400000);
+}
+
+#[test]
+fn concurrent_links() {
+    // Both programs define the same symbols, with different values
+    let programs = [42, 7].map(|value| {
+        let source = format!(
+            "  .text\n  .globl _start\n_start:\n  mov value(%rip), %edi\n  mov $60, %eax\n  syscall\n\
+             \n  .data\n  .globl value\nvalue:\n  .long {}\n",
+            value
+        );
+        assemble(&format!("program{}", value), &source)
+    });
+    let [Some(program42), Some(program7)] = programs else {
+        eprintln!("skipped: cc is not available");
+        return;
+    };
+    let inputs = [program42, program7].map(|data| {
+        [Input::Bytes {
+            name: "program.o".to_owned(),
+            data,
+        }]
+    });
+    let expected = inputs
+        .each_ref()
+        .map(|inputs| bold::link(inputs, &Config::new()).unwrap().data);
+    assert_eq!(run_executable("concurrent42", &expected[0]), 42);
+    assert_eq!(run_executable("concurrent7", &expected[1]), 7);
+
+    // Links running at the same time don't see each other's symbols or IDs
+    std::thread::scope(|scope| {
+        for (inputs, expected) in inputs.iter().zip(&expected) {
+            for _ in 0..4 {
+                scope.spawn(move || {
+                    for _ in 0..8 {
+                        let output = bold::link(inputs, &Config::new()).unwrap();
+                        assert!(output.warnings.is_empty());
+                        assert_eq!(&output.data, expected);
+                    }
+                });
+            }
+        }
+    });
+}
+
+#[test]
+fn perf_per_link() {
+    let Some(start) = assemble("start", START) else {
+        eprintln!("skipped: cc is not available");
+        return;
+    };
+    let input_bytes = start.len() as u64;
+    let inputs = [Input::Bytes {
+        name: "start.o".to_owned(),
+        data: start,
+    }];
+    let config = Config {
+        perf: true,
+        ..Config::new()
+    };
+    let output = bold::link(&inputs, &config).unwrap();
+    let report = output.perf.report();
+    assert!(report.starts_with("  Time(ms)"), "{}", report);
+    assert!(report.lines().any(|line| line.ends_with("  total")));
+
+    // Each link counts its own input, and records phases only with --perf
+    for output in [output, bold::link(&inputs, &Config::new()).unwrap()] {
+        let counters = output.perf.counters().collect::<Vec<_>>();
+        assert!(counters.contains(&("input_bytes", input_bytes)));
+    }
+    let output = bold::link(&inputs, &Config::new()).unwrap();
+    assert_eq!(output.perf.report(), "");
+}
+
+#[test]
+fn listing_in_output() {
+    let config = Config {
+        print_sections: true,
+        print_symbols: true,
+        ..Config::new()
+    };
+    let Some(output) = link_sources(&[START], &config) else {
+        eprintln!("skipped: cc is not available");
+        return;
+    };
+    let listing = output.unwrap().listing;
+    assert!(
+        listing.lines().any(|line| line.contains(" .text ")),
+        "{}",
+        listing
+    );
+    assert!(
+        listing.lines().any(|line| line.contains(" _start ")),
+        "{}",
+        listing
+    );
+
+    // Nothing is listed unless it is asked for
+    let output = link_sources(&[START], &Config::new()).unwrap().unwrap();
+    assert_eq!(output.listing, "");
+}
@@ -0,0 +1,63 @@
+# -T lays out the output sections by the SECTIONS command of a linker script
+cat <<EOF2 | cc -o %linker_script.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov value(%rip), %edi
+  mov \$60, %eax
+  syscall
+  .section .text.boot,"ax"
+  nop
+  .data
+  .globl value
+value:
+  .long 42
+  .section .rodata
+  .ascii "hi"
+  .bss
+  .zero 16
+EOF2
+
+cat <<EOF2 > %linker_script.ld
+/* Kernel-style layout */
+SECTIONS
+{
+  . = 0x80000;
+  .text : { *(.text.boot) *(.text*) }
+  .rodata : { *(.rodata*) }
+  .data : ALIGN(4096) { *(.data*) }
+  .bss : { *(.bss*) }
+}
+EOF2
+
+RUST_LOG=off cargo run -- -T %linker_script.ld --print-sections %linker_script.o \
+  > %linker_script.txt || exit 1
+./a.out
+[ $? -eq 42 ] || exit 1
+
+# .text.boot comes first as it matches the first description
+cat <<EOF2 | diff - %linker_script.txt || exit 1
+Nr  Name       Type      Flags  Address           Offset            Size              Align  Inputs
+ 0             NULL             0000000000000000  0000000000000000  0000000000000000      0       -
+ 1  .text      PROGBITS  AX     0000000000080000  0000000000001000  000000000000000e      1       2
+ 2  .rodata    PROGBITS  A      0000000000081000  0000000000002000  0000000000000002      1       1
+ 3  .data      PROGBITS  WA     0000000000082000  0000000000003000  0000000000000004      1       1
+ 4  .bss       NOBITS    WA     0000000000082004  0000000000003004  0000000000000010      1       1
+ 5  .symtab    SYMTAB           0000000000000000  0000000000003008  0000000000000048      8       -
+ 6  .strtab    STRTAB           0000000000000000  0000000000003050  000000000000000e      1       -
+ 7  .shstrtab  STRTAB           0000000000000000  000000000000305e  0000000000000034      1       -
+EOF2
+readelf -h a.out | grep -q "Entry point address: *0x80001" || exit 1
+
+# Unsupported commands are errors rather than ignored
+cat <<EOF2 > %linker_script_bad.ld
+SECTIONS
+{
+  .text : { *(.text) }
+}
+MEMORY { }
+EOF2
+! cargo run -- -T %linker_script_bad.ld %linker_script.o 2> %linker_script.log || exit 1
+grep -q "line 5: unsupported command: MEMORY" %linker_script.log || exit 1
+
+exit 0
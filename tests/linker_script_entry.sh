@@ -0,0 +1,75 @@
+# The entry point is --entry, ENTRY() of the linker script, _start or start, or the start
+# of .text, in this order
+cat <<EOF2 | cc -o %linker_script_entry1.o -c -x assembler -
+  .text
+  .globl _start, Reset_Handler, other
+  mov \$1, %edi
+  jmp 1f
+_start:
+  mov \$2, %edi
+  jmp 1f
+Reset_Handler:
+  mov \$3, %edi
+  jmp 1f
+other:
+  mov \$4, %edi
+1:
+  mov \$60, %eax
+  syscall
+EOF2
+cat <<EOF2 | cc -o %linker_script_entry2.o -c -x assembler -
+  .text
+  .globl start
+  mov \$1, %edi
+  jmp 1f
+start:
+  mov \$5, %edi
+1:
+  mov \$60, %eax
+  syscall
+EOF2
+
+cat <<EOF2 > %linker_script_entry.ld
+ENTRY(Reset_Handler)
+SECTIONS
+{
+  . = 0x80000;
+  .text : { *(.text) }
+}
+EOF2
+sed '/ENTRY/d' %linker_script_entry.ld > %linker_script_entry_noentry.ld
+sed 's/Reset_Handler/0x8000e/' %linker_script_entry.ld > %linker_script_entry_addr.ld
+
+check() {
+  ./a.out
+  [ $? -eq $1 ] || exit 1
+}
+
+# --entry overrides ENTRY()
+cargo run -- -T %linker_script_entry.ld --entry=other %linker_script_entry1.o || exit 1
+check 4
+# ENTRY() overrides _start
+cargo run -- -T %linker_script_entry.ld %linker_script_entry1.o || exit 1
+readelf -hW a.out | grep -q "Entry point address: *0x8000e$" || exit 1
+check 3
+# _start without ENTRY()
+cargo run -- -T %linker_script_entry_noentry.ld %linker_script_entry1.o || exit 1
+check 2
+# start if _start is not defined
+cargo run -- --fatal-warnings %linker_script_entry2.o || exit 1
+check 5
+# The start of .text if the entry symbol is not defined
+cargo run -- -T %linker_script_entry.ld %linker_script_entry2.o \
+  2> %linker_script_entry.log || exit 1
+grep -q "cannot find entry symbol Reset_Handler; defaulting to 0x80000" \
+  %linker_script_entry.log || exit 1
+check 1
+
+# Numbers are addresses, both in ENTRY() and --entry
+cargo run -- --fatal-warnings -T %linker_script_entry_addr.ld %linker_script_entry1.o || exit 1
+check 3
+cargo run -- --fatal-warnings -T %linker_script_entry_noentry.ld --entry=0x80015 \
+  %linker_script_entry1.o || exit 1
+check 4
+
+exit 0
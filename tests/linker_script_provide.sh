@@ -0,0 +1,67 @@
+# Linker scripts define symbols by assignments, PROVIDE() and PROVIDE_HIDDEN()
+cat <<EOF2 | cc -o %linker_script_provide.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  lea _stack_top(%rip), %rsp
+  push \$42
+  pop %rdi
+  mov \$60, %eax
+  syscall
+  .data
+  .globl __data_marker
+__data_marker:
+  .quad __data_marker
+  .quad __bss_end
+  .quad __hidden_sym
+  .section .bss.stack,"aw",@nobits
+  .zero 4096
+EOF2
+
+cat <<EOF2 > %linker_script_provide.ld
+SECTIONS
+{
+  . = 0x80000;
+  .text : { *(.text*) }
+  .data : { *(.data*) PROVIDE(__data_marker = 1); }
+  .bss : { __bss_start = .; *(.bss*) __bss_end = .; }
+  PROVIDE(_stack_top = .);
+  PROVIDE(_unused = 1);
+  PROVIDE_HIDDEN(__hidden_sym = _stack_top - 16);
+  _image_end = ALIGN(0x1000);
+}
+EOF2
+
+RUST_LOG=off cargo run -- -T %linker_script_provide.ld --print-symbols \
+  %linker_script_provide.o > %linker_script_provide.txt || exit 1
+# The startup code runs on the stack at _stack_top
+./a.out
+[ $? -eq 42 ] || exit 1
+
+# __data_marker is defined by the input, and _unused is not referenced. Symbols assigned
+# in output sections are relative to them.
+cat <<EOF2 | diff - %linker_script_provide.txt || exit 1
+Value             Size  Bind    Type    Section  Name           File
+0000000000080000     0  GLOBAL  NOTYPE  .text    _start         %linker_script_provide.o
+0000000000081000     0  GLOBAL  NOTYPE  .data    __data_marker  %linker_script_provide.o
+0000000000082018     0  GLOBAL  NOTYPE  .bss     __bss_end
+0000000000081018     0  GLOBAL  NOTYPE  .bss     __bss_start
+0000000000082008     0  GLOBAL  NOTYPE  ABS      __hidden_sym
+0000000000083000     0  GLOBAL  NOTYPE  ABS      _image_end
+0000000000082018     0  GLOBAL  NOTYPE  ABS      _stack_top
+EOF2
+readelf -sW a.out | grep -q "HIDDEN *ABS __hidden_sym" || exit 1
+readelf -x .data a.out | grep -q "00100800 00000000 18200800 00000000" || exit 1
+
+# Addresses are computed before symbols have values
+cat <<EOF2 > %linker_script_provide_bad.ld
+SECTIONS
+{
+  . = _stack_top;
+}
+EOF2
+! cargo run -- -T %linker_script_provide_bad.ld %linker_script_provide.o \
+  2> %linker_script_provide.log || exit 1
+grep -q "line 3: unsupported symbol in an address: _stack_top" %linker_script_provide.log || exit 1
+
+exit 0
@@ -0,0 +1,28 @@
+# Section indices from SHN_LORESERVE (0xff00) on are stored out of line
+awk 'BEGIN {
+  print "  .text"
+  print "  .globl _start"
+  print "_start:"
+  print "  jmp _start"
+  for (i = 0; i < 70000; i++) {
+    printf "  .section s%d,\"\",@progbits\n", i
+    print "  .byte 1"
+  }
+  print "  .globl last"
+  print "last:"
+  print "  .byte 2"
+}' | cc -o %many_sections.o -c -x assembler -
+
+cargo run %many_sections.o || exit 1
+
+# e_shnum and e_shstrndx are in the first section header
+readelf -hW a.out > %many_sections.h || exit 1
+grep -Eq "Number of section headers: +0 \([0-9]+\)" %many_sections.h || exit 1
+grep -Eq "Section header string table index: +65535 \([0-9]+\)" %many_sections.h || exit 1
+[ "$(readelf -SW a.out | grep -c ' s[0-9]* ')" = 70000 ] || exit 1
+[ "$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".shstrtab" { print $2 }')" = .shstrtab ] || exit 1
+
+# The index of the section of `last` is in .symtab_shndx
+shndx=$(readelf -SW a.out | sed 's/\[ */[/;s/]//' | awk '$2 == "s69999" { print $1 }' | tr -d '[')
+[ "$(readelf -sW a.out | awk '$8 == "last" { print $7 }')" = "$shndx" ] || exit 1
+exit 0
@@ -0,0 +1,27 @@
+# Identical constants in fixed-size SHF_MERGE sections are stored once
+cat <<EOF2 | cc -o %merge_constants1.o -c -O1 -fno-pic -fno-asynchronous-unwind-tables -x c -
+int scale2(double x);
+void _start(void) {
+  volatile double x = 4.0;
+  int ret = (int)(x * 2.5) + scale2(x);
+  __asm__ volatile("syscall" : : "a"(60), "D"(ret));
+}
+EOF2
+cat <<EOF2 | cc -o %merge_constants2.o -c -O1 -fpie -fno-asynchronous-unwind-tables -x c -
+int scale2(double x) {
+  return (int)(x * 2.5 + 0.75);
+}
+EOF2
+
+cargo run %merge_constants1.o %merge_constants2.o || exit 1
+./a.out
+[ $? = 20 ] || exit 1
+
+readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".rodata" { print $5, $6 }' > %merge_constants.secs
+count=0
+while read ofs size; do
+  n=$(od -An -tx8 -v -j $((0x$ofs)) -N $((0x$size)) a.out | tr ' ' '\n' | grep -c '^4004000000000000$')
+  count=$((count + n))
+done < %merge_constants.secs
+[ $count = 1 ] || exit 1
+exit 0
@@ -0,0 +1,29 @@
+# Identical strings in SHF_MERGE|SHF_STRINGS sections are stored once
+cat <<EOF2 > %merge_strings.h
+static inline void print(const char *s) {
+  long n = 0;
+  while (s[n])
+    n++;
+  __asm__ volatile("syscall" : : "a"(1), "D"(1), "S"(s), "d"(n) : "rcx", "r11", "memory");
+}
+EOF2
+cat <<EOF2 | cc -o %merge_strings1.o -c -O1 -fno-pic -fno-asynchronous-unwind-tables -include ./%merge_strings.h -x c -
+void f2(void);
+void _start(void) {
+  print("hello\n");
+  f2();
+  __asm__ volatile("syscall" : : "a"(60), "D"(0));
+}
+EOF2
+cat <<EOF2 | cc -o %merge_strings2.o -c -O1 -fpie -fno-asynchronous-unwind-tables -include ./%merge_strings.h -x c -
+void f2(void) {
+  print("hello\n");
+  print("world\n");
+  print("hello\n" + 1);
+}
+EOF2
+
+cargo run %merge_strings1.o %merge_strings2.o || exit 1
+[ "$(./a.out)" = "$(printf 'hello\nhello\nworld\nello')" ] || exit 1
+[ "$(grep -ao hello a.out | wc -l)" = 1 ] || exit 1
+exit 0
@@ -0,0 +1,43 @@
+# References to merged string literals resolve to the deduplicated copies. Code built without
+# -fpie refers to them by section symbols and addends, and with -fpie by local symbols.
+cat <<EOF2 | cc -o %merge_strings_printf1.o -c -O1 -fno-pic -x c -
+#include <stdio.h>
+void f2(void);
+void f3(void);
+int main(void) {
+  printf("hello %s\n", "world");
+  puts("hello world");
+  f2();
+  f3();
+  return 0;
+}
+EOF2
+cat <<EOF2 | cc -o %merge_strings_printf2.o -c -O1 -fpie -x c -
+#include <stdio.h>
+void f2(void) {
+  printf("hello %s\n", "again");
+}
+EOF2
+# The addend points into the middle of "hello world", which is deduplicated
+cat <<EOF2 | cc -o %merge_strings_printf3.o -c -x assembler -
+  .section .rodata.str1.1,"aMS",@progbits,1
+  .string "unique"
+  .string "hello world"
+  .text
+  .globl f3
+f3:
+  mov \$.rodata.str1.1 + 13, %edi
+  jmp puts@PLT
+EOF2
+readelf -rW %merge_strings_printf3.o | grep -q "R_X86_64_32 .* \.rodata\.str1\.1 + d" || exit 1
+
+cargo run /usr/lib/x86_64-linux-gnu/crt1.o \
+  /usr/lib/x86_64-linux-gnu/crti.o \
+  %merge_strings_printf1.o %merge_strings_printf2.o %merge_strings_printf3.o \
+  /lib/x86_64-linux-gnu/libc.so.6 \
+  /usr/lib/x86_64-linux-gnu/crtn.o || exit 1
+
+[ "$(./a.out)" = "$(printf 'hello world\nhello world\nhello again\nworld')" ] || exit 1
+[ "$(grep -ao 'hello world' a.out | wc -l)" = 1 ] || exit 1
+[ "$(grep -ao 'hello %s' a.out | wc -l)" = 1 ] || exit 1
+exit 0
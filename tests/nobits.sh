@@ -0,0 +1,45 @@
+# NOBITS inputs are laid out in .bss without taking space in the file
+cat <<EOF2 | cc -o %nobits.o -c -x assembler - 2>/dev/null
+  .text
+  .globl _start
+_start:
+  jmp _start
+  .section .bss.a,"aw",@nobits
+  .globl a
+a:
+  .zero 20
+  .section .bss.b,"aw",@nobits
+  .p2align 4
+  .globl b
+b:
+  .zero 16
+  .section .bss.c,"aw",@progbits
+  .globl c
+c:
+  .quad 5
+  .data
+  .globl d
+d:
+  .quad 7
+EOF2
+cat <<EOF2 | cc -o %nobits2.o -c -x assembler -
+  .section .bss.e,"aw",@nobits
+  .globl e
+e:
+  .zero 4096
+EOF2
+
+cargo run %nobits.o %nobits2.o || exit 1
+# There is exactly one .bss, and it is NOBITS
+[ "$(readelf -SW a.out | grep -c ' \.bss ')" = 1 ] || exit 1
+bss=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".bss"')
+[ "$(echo $bss | cut -d' ' -f3)" = NOBITS ] || exit 1
+[ $((0x$(echo $bss | cut -d' ' -f6))) = $((48 + 4096)) ] || exit 1
+sym() {
+  echo $((0x$(readelf -sW a.out | awk -v name=$1 '$8 == name { print $2 }')))
+}
+[ $(sym b) = $(($(sym a) + 32)) ] || exit 1
+[ $(sym e) = $(($(sym b) + 16)) ] || exit 1
+# A PROGBITS input named .bss.* goes to .data
+[ "$(readelf -sW a.out | awk '$8 == "c" { print $7 }')" = "$(readelf -sW a.out | awk '$8 == "d" { print $7 }')" ] || exit 1
+exit 0
@@ -0,0 +1,22 @@
+# Non-alloc sections don't take up address space
+cat <<EOF2 | cc -o %nonalloc_vaddr.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  jmp _start
+  .section .data,"aw"
+  .quad 1
+  .section mycomment,"",@progbits
+  .zero 0x2000
+  .section mysec,"aw",@progbits
+  .quad 2
+EOF2
+
+cargo run %nonalloc_vaddr.o || exit 1
+
+section() {
+  readelf -SW a.out | sed 's/\[ */[/' | awk -v name=$1 '$2 == name { print $'$2' }'
+}
+[ $((0x$(section mycomment 4))) = 0 ] || exit 1
+[ $((0x$(section mysec 4))) = $((0x$(section .data 4) + 8)) ] || exit 1
+exit 0
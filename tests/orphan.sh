@@ -0,0 +1,34 @@
+# Sections with unknown names are placed in output sections of their own names
+cat <<EOF2 | cc -o %orphan1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  lea foo(%rip), %rax
+  jmp _start
+  .section my_section,"aw",@progbits
+  .globl foo
+foo:
+  .quad 1
+  .section .gcc_except_table,"a",@progbits
+  .byte 0xff, 0xff, 0x01, 0x00
+EOF2
+cat <<EOF2 | cc -o %orphan2.o -c -x assembler -
+  .section my_section,"aw",@progbits
+  .quad 2
+EOF2
+
+cargo run -- --verbose %orphan1.o %orphan2.o 2> %orphan.log || exit 1
+grep -q "orphan section: my_section" %orphan.log || exit 1
+grep -q "orphan section: .gcc_except_table" %orphan.log || exit 1
+# Inputs of the same name are grouped
+[ "$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == "my_section" { print $6 }')" = 000010 ] || exit 1
+# Orphans are placed among sections with the same permissions
+[ "$(readelf -SW a.out | sed 's/\[ */[/' | awk '/^ *\[[1-9]/ && $6 != "000000" { printf "%s ", $2 }')" = ".gcc_except_table .text my_section .symtab .strtab .shstrtab " ] || exit 1
+[ "$(readelf -x my_section a.out | awk '/0x/ { print $2 $3 $4 $5 }')" = "01000000000000000200000000000000" ] || exit 1
+# foo points to the beginning of my_section
+[ "$(readelf -sW a.out | awk '$8 == "foo" { print $2 }')" = "$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == "my_section" { print $4 }')" ] || exit 1
+
+# Without --verbose, orphans are not reported
+cargo run %orphan1.o %orphan2.o 2> %orphan.log || exit 1
+! grep -q "orphan section" %orphan.log || exit 1
+exit 0
@@ -0,0 +1,40 @@
+# Segments are aligned to -z max-page-size, and their addresses and offsets are congruent
+cat <<EOF2 | cc -o %page_size.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov x(%rip), %edi
+  mov \$60, %eax
+  syscall
+  .data
+x:
+  .long 3
+EOF2
+
+check() {
+  readelf -lW a.out | awk '$1 ~ /^[A-Z_]+$/ && $2 ~ /^0x/ { print $1, $2, $3, $NF }' |
+    while read type offset vaddr align; do
+      if [ $type = LOAD ] && [ $align != $1 ]; then
+        exit 1
+      fi
+      if [ $((align)) -gt 1 ] && [ $(((vaddr - offset) % align)) != 0 ]; then
+        exit 1
+      fi
+    done
+}
+
+cargo run %page_size.o || exit 1
+check 0x1000 || exit 1
+./a.out
+[ $? = 3 ] || exit 1
+# The first segment starts at the image base with the ELF header
+[ "$(readelf -lW a.out | awk '$1 == "LOAD" { print $2, $3; exit }')" = "0x000000 0x0000000000400000" ] || exit 1
+
+cargo run -- -z max-page-size=0x200000 %page_size.o || exit 1
+check 0x200000 || exit 1
+./a.out
+[ $? = 3 ] || exit 1
+
+! cargo run -- -z max-page-size=0x3000 %page_size.o 2> %page_size.log || exit 1
+grep -q "invalid -z max-page-size: 0x3000" %page_size.log || exit 1
+exit 0
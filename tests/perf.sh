@@ -0,0 +1,27 @@
+# --perf prints a table of phases to stderr without changing the output
+cat <<EOF2 | cc -o %perf.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov \$60, %eax
+  mov \$3, %edi
+  syscall
+EOF2
+
+cargo run %perf.o || exit 1
+mv a.out %perf.out
+cargo run %perf.o --perf 2> %perf.log || exit 1
+cmp a.out %perf.out || exit 1
+./a.out
+[ $? -eq 3 ] || exit 1
+
+grep -Eq "^ *Time\(ms\) +Bytes +MB/s  Phase$" %perf.log || exit 1
+for phase in read parse resolve_symbols bin_input_sections assign_offsets update_shdr \
+  copy_buf relocation write total; do
+  grep -Eq "^ *[0-9.]+ +([0-9]+|-) +([0-9.]+|-)  $phase\$" %perf.log || exit 1
+done
+
+# Nothing is printed without --perf
+cargo run %perf.o 2> %perf.log || exit 1
+! grep -q "Phase" %perf.log || exit 1
+exit 0
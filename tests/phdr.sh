@@ -0,0 +1,28 @@
+# The file headers are mapped by the first PT_LOAD and described by PT_PHDR
+cat <<EOF2 | cc -o %phdr.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov \$60, %eax
+  xor %edi, %edi
+  syscall
+  .data
+  .quad 1
+EOF2
+
+cargo run %phdr.o || exit 1
+./a.out || exit 1
+
+[ "$(readelf -lW a.out | awk '$1 == "PHDR" || $1 == "LOAD" { print $1 }' | head -2 | xargs)" = "PHDR LOAD" ] || exit 1
+phdr=$(readelf -lW a.out | awk '$1 == "PHDR"')
+load=$(readelf -lW a.out | awk '$1 == "LOAD"' | head -1)
+[ "$(echo $phdr | cut -d' ' -f2)" = "$(readelf -hW a.out | awk '/Start of program headers/ { printf "0x%06x", $5 }')" ] || exit 1
+# The ELF header and the program headers are inside the first segment
+[ $(($(echo $load | cut -d' ' -f2))) = 0 ] || exit 1
+[ $(($(echo $phdr | cut -d' ' -f2) + $(echo $phdr | cut -d' ' -f5))) -le $(($(echo $load | cut -d' ' -f5))) ] || exit 1
+[ $(($(echo $phdr | cut -d' ' -f3) - $(echo $phdr | cut -d' ' -f2))) = $(($(echo $load | cut -d' ' -f3))) ] || exit 1
+# p_offset and p_vaddr are congruent modulo p_align
+readelf -lW a.out | awk '$1 == "LOAD"' | while read type ofs vaddr paddr filesz memsz rest; do
+  [ $((ofs % 4096)) = $((vaddr % 4096)) ] || exit 1
+done || exit 1
+exit 0
@@ -0,0 +1,60 @@
+# Calls to functions in a DSO go through .plt and .got.plt
+cat <<EOF2 | cc -o %plt.so -shared -nostdlib -x assembler -
+  .text
+  .globl foo, bar, baz
+  .type foo, @function
+  .type bar, @function
+  .type baz, @function
+foo:
+  ret
+bar:
+  ret
+baz:
+  ret
+EOF2
+cat <<EOF2 | cc -o %plt1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  call foo@PLT
+  call bar@PLT
+  call foo@PLT
+  call baz@PLT
+  jmp _start
+EOF2
+# baz is defined in an object file, which overrides the DSO
+cat <<EOF2 | cc -o %plt2.o -c -x assembler -
+  .text
+  .globl baz
+  .type baz, @function
+baz:
+  ret
+EOF2
+
+cargo run %plt1.o %plt2.o %plt.so || exit 1
+
+section() {
+  readelf -SW a.out | sed 's/\[ */[/' | awk -v name=$1 '$2 == name { print $'$2' }'
+}
+plt=$((0x$(section .plt 4)))
+got_plt=$((0x$(section .got.plt 4)))
+# Header and two entries
+[ $((0x$(section .plt 6))) = 48 ] || exit 1
+[ $((0x$(section .got.plt 6))) = 40 ] || exit 1
+
+# Calls refer to the PLT entries in order of first use, and baz is called directly
+calls=$(objdump -d a.out | awk '/<_start>:/ { f = 1 } f && /call/ { print $(NF - 1) }' | xargs)
+baz=$(readelf -sW a.out | awk '$8 == "baz" { print $2 }' | sed 's/^0*//')
+[ "$calls" = "$(printf '%x %x %x %s' $((plt + 16)) $((plt + 32)) $((plt + 16)) $baz)" ] || exit 1
+
+# jmp *GOTPLT[3 + n](%rip); push $n; jmp PLT[0]
+objdump -d -j .plt a.out > %plt.dis
+grep -q "jmp  *\*0x[0-9a-f]*(%rip) *# $(printf %x $((got_plt + 24)))" %plt.dis || exit 1
+grep -q "jmp  *\*0x[0-9a-f]*(%rip) *# $(printf %x $((got_plt + 32)))" %plt.dis || exit 1
+grep -q "push  *\$0x1" %plt.dis || exit 1
+[ $(grep -c "jmp  *$(printf %x $plt) " %plt.dis) = 2 ] || exit 1
+
+# .got.plt entries initially point to the push instructions of the PLT entries
+set -- $(od -An -tu8 -v -j $((0x$(section .got.plt 5) + 24)) -N 16 a.out)
+[ $1 = $((plt + 22)) ] && [ $2 = $((plt + 38)) ] || exit 1
+exit 0
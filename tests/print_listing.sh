@@ -0,0 +1,50 @@
+# --print-sections and --print-symbols print tables to stdout regardless of the log level
+cat <<EOF2 | cc -o %print_listing.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  call foo
+  mov \$60, %eax
+  syscall
+foo:
+  ret
+  .data
+  .globl var
+  .type var, @object
+  .size var, 8
+var:
+  .quad 1
+  .comm buf, 64, 16
+EOF2
+
+cargo run %print_listing.o || exit 1
+mv a.out %print_listing.out
+RUST_LOG=off cargo run -- --print-sections --print-symbols %print_listing.o \
+  > %print_listing.txt || exit 1
+# The output doesn't change
+cmp a.out %print_listing.out || exit 1
+
+cat <<EOF2 | diff - %print_listing.txt || exit 1
+Nr  Name       Type      Flags  Address           Offset            Size              Align  Inputs
+ 0             NULL             0000000000000000  0000000000000000  0000000000000000      0       -
+ 1  .text      PROGBITS  AX     0000000000401000  0000000000001000  000000000000000d      1       1
+ 2  .data      PROGBITS  WA     0000000000402000  0000000000002000  0000000000000008      1       1
+ 3  .bss       NOBITS    WA     0000000000402010  0000000000002010  0000000000000040     16       2
+ 4  .symtab    SYMTAB           0000000000000000  0000000000002008  0000000000000078      8       -
+ 5  .strtab    STRTAB           0000000000000000  0000000000002080  0000000000000014      1       -
+ 6  .shstrtab  STRTAB           0000000000000000  0000000000002094  000000000000002c      1       -
+
+Value             Size  Bind    Type    Section  Name    File
+000000000040100c     0  LOCAL   NOTYPE  .text    foo     %print_listing.o
+0000000000401000     0  GLOBAL  NOTYPE  .text    _start  %print_listing.o
+0000000000402000     8  GLOBAL  OBJECT  .data    var     %print_listing.o
+0000000000402010    64  GLOBAL  OBJECT  .bss     buf     %print_listing.o
+EOF2
+
+# The tables can be printed separately
+cargo run -- --print-symbols %print_listing.o > %print_listing.txt || exit 1
+head -1 %print_listing.txt | grep -q "^Value " || exit 1
+# Nothing is printed without the options
+cargo run %print_listing.o > %print_listing.txt || exit 1
+[ ! -s %print_listing.txt ] || exit 1
+exit 0
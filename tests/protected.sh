@@ -0,0 +1,22 @@
+# A call to a protected function binds directly to the definition.
+cat <<EOF2 | cc -o %protected1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  call func@PLT
+  jmp _start
+EOF2
+cat <<EOF2 | cc -o %protected2.o -c -x assembler -
+  .text
+  .globl func
+  .protected func
+  .type func, @function
+func:
+  ret
+EOF2
+
+cargo run %protected1.o %protected2.o
+
+readelf -sW a.out | awk '$8 == "func" { print $6 }' | grep -q PROTECTED || exit 1
+func=$(readelf -sW a.out | awk '$8 == "func" { print $2 }' | sed 's/^0*//')
+objdump -d a.out | grep -q "call *$func <func>" || exit 1
@@ -0,0 +1,89 @@
+# References to symbols in a DSO are resolved through .rela.dyn and .rela.plt
+cat <<EOF2 | cc -o %rela_dyn.so -shared -nostdlib -x assembler -
+  .text
+  .globl foo, bar
+  .type foo, @function
+  .type bar, @function
+foo:
+  ret
+bar:
+  ret
+  .data
+  .globl var
+  .type var, @object
+var:
+  .quad 1
+  .section .tdata,"awT",@progbits
+  .globl tvar
+  .type tvar, @object
+tvar:
+  .quad 2
+EOF2
+cat <<EOF2 | cc -o %rela_dyn.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  call foo@PLT
+  call bar@PLT
+  mov var@GOTPCREL(%rip), %rax
+  mov tvar@GOTTPOFF(%rip), %rax
+  jmp _start
+EOF2
+
+cargo run %rela_dyn.o %rela_dyn.so || exit 1
+
+section() {
+  readelf -SW a.out | sed 's/\[ */[/' | awk -v name=$1 '$2 == name { print $'$2' }'
+}
+got=$((0x$(section .got 4)))
+got_plt=$((0x$(section .got.plt 4)))
+dynsym=$(section .dynsym 1 | tr -d "[]")
+
+# Both sections refer to .dynsym, and .rela.plt applies to .got.plt
+[ "$(section .rela.dyn 3) $(section .rela.dyn 7) $(section .rela.dyn 8)" = "RELA 18 A" ] || exit 1
+[ "$(section .rela.plt 3) $(section .rela.plt 7) $(section .rela.plt 8)" = "RELA 18 AI" ] || exit 1
+[ $(section .rela.dyn 9) = $dynsym ] && [ $(section .rela.plt 9) = $dynsym ] || exit 1
+[ $(section .rela.plt 10) = $(section .got.plt 1 | tr -d "[]") ] || exit 1
+
+readelf -rW a.out | awk '/^[0-9a-f]+ / { print $1, $3, $5 }' > %rela_dyn.dump
+cat <<EOF2 | diff - %rela_dyn.dump || exit 1
+$(printf %016x $got) R_X86_64_GLOB_DAT var
+$(printf %016x $((got + 8))) R_X86_64_TPOFF64 tvar
+$(printf %016x $((got_plt + 24))) R_X86_64_JUMP_SLOT foo
+$(printf %016x $((got_plt + 32))) R_X86_64_JUMP_SLOT bar
+EOF2
+
+# GOT entries are filled by the dynamic linker
+[ "$(od -An -tu8 -v -j $((0x$(section .got 5))) -N 16 a.out | xargs)" = "0 0" ] || exit 1
+
+readelf -dW a.out > %rela_dyn.dyn
+entry() {
+  awk -v tag="($1)" '$2 == tag { print $3 }' %rela_dyn.dyn
+}
+[ $(entry RELA) = 0x$(section .rela.dyn 4 | sed 's/^0*//') ] || exit 1
+[ $(entry RELASZ) = 48 ] && [ $(entry RELAENT) = 24 ] || exit 1
+[ $(entry JMPREL) = 0x$(section .rela.plt 4 | sed 's/^0*//') ] || exit 1
+[ $(entry PLTRELSZ) = 48 ] && [ $(entry PLTREL) = RELA ] || exit 1
+# There are no R_X86_64_RELATIVE relocations
+! grep -q RELACOUNT %rela_dyn.dyn || exit 1
+
+# A program calling libc through the PLT runs
+libc=/lib/x86_64-linux-gnu/libc.so.6
+[ -f $libc ] || exit 0
+cat <<EOF2 | cc -o %rela_dyn_hello.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  lea msg(%rip), %rdi
+  call puts@PLT
+  xor %edi, %edi
+  call exit@PLT
+  .section .rodata
+msg:
+  .string "Hello, world"
+EOF2
+for z in lazy now; do
+  cargo run %rela_dyn_hello.o $libc -z $z || exit 1
+  [ "$(./a.out)" = "Hello, world" ] || exit 1
+done
+exit 0
@@ -0,0 +1,53 @@
+# With --relax, GOTPCRELX and REX_GOTPCRELX against symbols defined in the output are
+# rewritten to refer to them directly, in the same way as GNU ld does
+cat <<EOF2 | cc -o %relax_gotpcrelx.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  movq value@GOTPCREL(%rip), %rax
+  movq value@GOTPCREL(%rip), %r12
+  movl value@GOTPCREL(%rip), %ecx
+  movq weak@GOTPCREL(%rip), %rdx
+  call *func@GOTPCREL(%rip)
+  jmp *exit@GOTPCREL(%rip)
+  .weak weak
+func:
+  ret
+exit:
+  mov (%r12), %edi
+  mov \$60, %eax
+  syscall
+  .data
+value:
+  .long 6
+EOF2
+
+# Instructions of _start without addresses and displacements. The undefined weak symbol is
+# left out because it is resolved differently.
+disasm() {
+  objdump -dw --no-show-raw-insn $1 | sed -n '/<_start>:/,/^$/p' | grep -v '%rdx' |
+    sed -e 's/^ *[0-9a-f]*:\s*//' -e 's/0x[0-9a-f]*/X/g' -e 's/[0-9a-f]* </</' -e 's/ \+/ /g'
+}
+
+readelf -rW %relax_gotpcrelx.o | grep -q "R_X86_64_REX_GOTPCRELX" || exit 1
+readelf -rW %relax_gotpcrelx.o | grep -q "R_X86_64_GOTPCRELX" || exit 1
+
+# Without --relax, the GOT is used
+cargo run %relax_gotpcrelx.o || exit 1
+./a.out
+[ $? = 6 ] || exit 1
+disasm a.out | grep -q "^lea" && exit 1
+[ -n "$(readelf -SW a.out | grep ' .got ')" ] || exit 1
+
+cargo run -- --relax %relax_gotpcrelx.o || exit 1
+./a.out
+[ $? = 6 ] || exit 1
+# GNU ld rewrites mov to lea only for PIC, so compare with a PIE
+ld -pie --no-dynamic-linker -o %relax_gotpcrelx.ld %relax_gotpcrelx.o || exit 1
+[ "$(disasm a.out)" = "$(disasm %relax_gotpcrelx.ld)" ] || exit 1
+disasm a.out | grep -q "^lea X(%rip),%r12 # <value>" || exit 1
+disasm a.out | grep -q "^addr32 call <func>" || exit 1
+disasm a.out | grep -q "^jmp <exit>" || exit 1
+# The undefined weak symbol still goes through the GOT
+objdump -dw a.out | grep -q "mov .*(%rip),%rdx" || exit 1
+exit 0
@@ -0,0 +1,42 @@
+# With --relax, initial-exec TLS accesses in an executable load the TP-relative offset as an
+# immediate instead of from the GOT
+cat <<EOF2 | cc -o %relax_gottpoff.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  movq a@gottpoff(%rip), %rax
+  movq b@gottpoff(%rip), %r9
+  movl %fs:(%rax), %edi
+  addl %fs:(%r9), %edi
+  mov \$60, %eax
+  syscall
+
+  .section .tdata,"awT",@progbits
+  .globl a, b
+a:
+  .long 0
+  .long 3
+b:
+  .quad 4
+EOF2
+
+readelf -rW %relax_gottpoff.o | grep -q "R_X86_64_GOTTPOFF .* b " || exit 1
+[ "$(readelf -rW %relax_gottpoff.o | grep -c R_X86_64_GOTTPOFF)" = 2 ] || exit 1
+
+# The movs of _start
+movs() {
+  objdump -dw $1 | sed -n '/<_start>:/,/^$/p' | grep -E ',%(rax|r9)\b' | cut -f2- | sed -e 's/ *#.*//' -e 's/\s\+/ /g'
+}
+
+# Without --relax, the offsets are loaded from the GOT
+cargo run %relax_gottpoff.o || exit 1
+movs a.out | grep -q "(%rip)" || exit 1
+
+cargo run -- --relax %relax_gottpoff.o || exit 1
+# The TLS block is 16 bytes, so a is at -16 and b at -8 from the thread pointer
+[ "$(movs a.out)" = "$(printf '%s\n' '48 c7 c0 f0 ff ff ff mov $0xfffffffffffffff0,%rax' '49 c7 c1 f8 ff ff ff mov $0xfffffffffffffff8,%r9')" ] || exit 1
+ld -o %relax_gottpoff.ld %relax_gottpoff.o || exit 1
+[ "$(movs a.out)" = "$(movs %relax_gottpoff.ld)" ] || exit 1
+# No GOT is needed
+readelf -SW a.out | grep -q ' .got ' && exit 1
+exit 0
@@ -0,0 +1,43 @@
+# With --relax, general-dynamic and local-dynamic TLS sequences in an executable are
+# relaxed to local-exec, and the calls to __tls_get_addr disappear
+cat <<EOF2 | cc -o %relax_tls1.o -c -O1 -fPIC -x c -
+__thread int a = 3;
+extern __thread int c;
+int gd(void) { return a + c; }
+static __thread int x, y;
+int ld(int v) { x += v; y += v; return x + y; }
+EOF2
+cat <<EOF2 | cc -o %relax_tls2.o -c -x assembler -
+  .section .tdata,"awT",@progbits
+  .globl c
+c:
+  .long 5
+  .text
+  .globl _start
+_start:
+  ret
+EOF2
+
+readelf -rW %relax_tls1.o | grep -q "R_X86_64_TLSGD .* a " || exit 1
+readelf -rW %relax_tls1.o | grep -q "R_X86_64_TLSLD" || exit 1
+readelf -rW %relax_tls1.o | grep -q "R_X86_64_DTPOFF32 .* x " || exit 1
+
+# Instructions of the functions without addresses
+disasm() {
+  objdump -dw $1 | sed -n '/<gd>:/,/^$/p; /<ld>:/,/^$/p' | grep -E '^ +[0-9a-f]+:' | cut -f2- |
+    sed -e 's/ *#.*//' -e 's/\s\+/ /g'
+}
+
+# Without --relax, __tls_get_addr is needed
+! cargo run %relax_tls1.o %relax_tls2.o 2> %relax_tls.log || exit 1
+grep -q "undefined symbol: __tls_get_addr" %relax_tls.log || exit 1
+
+cargo run -- --relax %relax_tls1.o %relax_tls2.o || exit 1
+ld -o %relax_tls.ld %relax_tls1.o %relax_tls2.o || exit 1
+disasm a.out | grep -q "__tls_get_addr" && exit 1
+disasm a.out | grep -q "^64 48 8b 04 25 00 00 00 00 mov %fs:0x0,%rax" || exit 1
+disasm a.out | grep -q "^66 66 66 64 48 8b 04 25 00 00 00 00 data16 data16 data16 mov %fs:0x0,%rax" || exit 1
+[ "$(disasm a.out)" = "$(disasm %relax_tls.ld)" ] || exit 1
+# No GOT entries are needed either
+readelf -SW a.out | grep -q ' .got ' && exit 1
+exit 0
@@ -0,0 +1,62 @@
+# Relocations of the large code model and other GOT-relative ones
+cat <<EOF2 | cc -o %reloc_large_model.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+1:
+  # %rbx = _GLOBAL_OFFSET_TABLE_
+  lea 1b(%rip), %rbx
+  movabs \$_GLOBAL_OFFSET_TABLE_-1b, %r11
+  add %r11, %rbx
+  leaq _GLOBAL_OFFSET_TABLE_(%rip), %rcx
+  cmp %rbx, %rcx
+  jne fail
+  movabs \$value@GOTOFF, %rax
+  mov (%rbx,%rax), %edi
+  movabs \$value@GOT, %rax
+  mov (%rbx,%rax), %rax
+  add (%rax), %edi
+  movabs \$func@PLTOFF, %rax
+  add %rbx, %rax
+  call *%rax
+  # GOTPCREL64 points at the same GOT entry as GOT64
+  lea slot(%rip), %rcx
+  add slot(%rip), %rcx
+  movabs \$value@GOT, %rax
+  add %rbx, %rax
+  cmp %rax, %rcx
+  jne fail
+  mov \$60, %eax
+  syscall
+fail:
+  mov \$60, %eax
+  mov \$1, %edi
+  syscall
+func:
+  add \$1, %edi
+  ret
+  .data
+value:
+  .long 20
+slot:
+  .quad value@GOTPCREL
+EOF2
+
+for r_type in GOTPC64 GOTOFF64 GOT64 PLTOFF64 GOTPC32 GOTPCREL64; do
+  readelf -rW %reloc_large_model.o | grep -q "R_X86_64_$r_type " || exit 1
+done
+
+cargo run %reloc_large_model.o || exit 1
+./a.out
+[ $? = 41 ] || exit 1
+# GOT64 refers to an entry holding the address of value
+got=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".got" { print $5 }')
+value=$(readelf -sW a.out | awk '$8 == "value" { print $2 }')
+[ "$(od -An -tx8 -j $((0x$got)) -N 8 a.out | xargs)" = $value ] || exit 1
+
+# _GLOBAL_OFFSET_TABLE_ alone is enough to create the GOT
+echo '.globl _start; _start: lea _GLOBAL_OFFSET_TABLE_(%rip), %rax; ret' |
+  cc -o %reloc_large_model2.o -c -x assembler -
+cargo run %reloc_large_model2.o || exit 1
+readelf -SW a.out | grep -q ' .got ' || exit 1
+exit 0
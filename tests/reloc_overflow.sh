@@ -0,0 +1,46 @@
+# Relocations narrower than 64 bits are checked for overflow
+cat <<EOF2 | cc -o %reloc_overflow1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov \$foo, %edi
+  mov \$60, %eax
+  syscall
+  .data
+  .globl foo
+foo:
+  .long 0
+EOF2
+
+# The addresses fit in 32 bits with the default image base
+cargo run %reloc_overflow1.o || exit 1
+./a.out
+
+# R_X86_64_32 can't hold an address above 4 GiB
+! cargo run -- --image-base=0x100000000 %reloc_overflow1.o 2> %reloc_overflow.log || exit 1
+grep -q "%reloc_overflow1.o:(.text+0x1): relocation R_X86_64_32 out of range: symbol 'foo' (0x10000[0-9a-f]*): [0-9]* is not in \[0, 4294967295\]" %reloc_overflow.log || exit 1
+
+# PC32 can't reach a symbol more than 2 GiB away
+cat <<EOF2 | cc -o %reloc_overflow2.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov far(%rip), %edi
+  mov \$60, %eax
+  syscall
+  .globl far
+  .set far, 0x90000000
+EOF2
+! cargo run %reloc_overflow2.o 2> %reloc_overflow.log || exit 1
+grep -q "relocation R_X86_64_PC32 out of range: symbol 'far' (0x90000000): .* is not in \[-2147483648, 2147483647\]" %reloc_overflow.log || exit 1
+
+# R_X86_64_8 accepts both signed and unsigned bytes
+echo '.data; .byte v1; .byte v2' | cc -o %reloc_overflow3.o -c -x assembler -
+echo '.data; .byte v3' | cc -o %reloc_overflow4.o -c -x assembler -
+echo '.globl v1, v2, v3; .set v1, 0xff; .set v2, -0x80; .set v3, 0x100' |
+  cc -o %reloc_overflow5.o -c -x assembler -
+readelf -rW %reloc_overflow4.o | grep -q "R_X86_64_8 .* v3" || exit 1
+cargo run %reloc_overflow1.o %reloc_overflow3.o %reloc_overflow5.o || exit 1
+! cargo run %reloc_overflow1.o %reloc_overflow4.o %reloc_overflow5.o 2> %reloc_overflow.log || exit 1
+grep -q "relocation R_X86_64_8 out of range: symbol 'v3' (0x100): 256 is not in \[-128, 255\]" %reloc_overflow.log || exit 1
+exit 0
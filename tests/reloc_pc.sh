@@ -0,0 +1,50 @@
+# R_X86_64_PC8, PC16 and PC64 are resolved to S + A - P, and the small ones are checked for
+# overflow
+cat <<EOF2 | cc -o %reloc_pc1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov \$60, %eax
+  xor %edi, %edi
+  syscall
+  .data
+  .globl p8, p16, p64
+p8:
+  .byte near - .
+p16:
+  .word near - . + 0x100
+p64:
+  .quad far - .
+EOF2
+cat <<EOF2 | cc -o %reloc_pc2.o -c -x assembler -
+  .data
+  .globl near
+  .long 0
+near:
+  .long 0
+  .globl far
+  .set far, 0x123456789
+EOF2
+echo '.data; .byte far - .' | cc -o %reloc_pc3.o -c -x assembler -
+
+cargo run %reloc_pc1.o %reloc_pc2.o || exit 1
+./a.out || exit 1
+
+sym() {
+  readelf -sW a.out | awk -v name=$1 '$8 == name { print $2 }'
+}
+# Reads a little-endian signed value of $2 bytes at the address $1
+read_at() {
+  readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".data" { print $4, $5 }' | {
+    read addr offset
+    od -An -t d$2 -j $((0x$offset + 0x$1 - 0x$addr)) -N $2 a.out | tr -d ' '
+  }
+}
+[ $(read_at $(sym p8) 1) = $((0x$(sym near) - 0x$(sym p8))) ] || exit 1
+[ $(read_at $(sym p16) 2) = $((0x$(sym near) - 0x$(sym p16) + 0x100)) ] || exit 1
+[ $(read_at $(sym p64) 8) = $((0x123456789 - 0x$(sym p64))) ] || exit 1
+
+# The displacement to far doesn't fit in 8 bits
+! cargo run %reloc_pc1.o %reloc_pc2.o %reloc_pc3.o 2> %reloc_pc.log || exit 1
+grep -q "%reloc_pc3.o:(.data+0x0): relocation R_X86_64_PC8 out of range: .* is not in \[-128, 127\]" %reloc_pc.log || exit 1
+exit 0
@@ -0,0 +1,56 @@
+# R_X86_64_SIZE32 and SIZE64 resolve to the size of the definition plus the addend
+cat <<EOF2 | cc -o %reloc_size1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov \$60, %eax
+  xor %edi, %edi
+  syscall
+  .data
+  .globl sizes
+sizes:
+  .quad obj@SIZE
+  .long obj@SIZE + 1
+  .quad abs@SIZE
+  .long common@SIZE
+  .quad func@SIZE - 2
+  .comm common, 8, 8
+EOF2
+cat <<EOF2 | cc -o %reloc_size2.o -c -x assembler -
+  .data
+  .globl obj
+  .type obj, @object
+obj:
+  .zero 24
+  .size obj, 24
+  .globl abs
+  .set abs, 0x1000
+  .size abs, 7
+  .comm common, 32, 8
+  .text
+  .globl func
+  .type func, @function
+func:
+  ret
+  .size func, 3
+EOF2
+echo '.data; .long obj@SIZE - 25' | cc -o %reloc_size3.o -c -x assembler -
+
+readelf -rW %reloc_size1.o | grep -q "R_X86_64_SIZE64 .* obj " || exit 1
+readelf -rW %reloc_size1.o | grep -q "R_X86_64_SIZE32 .* obj " || exit 1
+
+cargo run %reloc_size1.o %reloc_size2.o || exit 1
+./a.out || exit 1
+# The size of common is the largest of its declarations
+readelf -sW a.out | grep -q " 32 OBJECT .* common$" || exit 1
+ofs=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".data" { print $5 }')
+[ "$(od -An -td8 -j $((0x$ofs)) -N 8 a.out | xargs)" = 24 ] || exit 1
+[ "$(od -An -td4 -j $((0x$ofs + 8)) -N 4 a.out | xargs)" = 25 ] || exit 1
+[ "$(od -An -td8 -j $((0x$ofs + 12)) -N 8 a.out | xargs)" = 7 ] || exit 1
+[ "$(od -An -td4 -j $((0x$ofs + 20)) -N 4 a.out | xargs)" = 32 ] || exit 1
+[ "$(od -An -td8 -j $((0x$ofs + 24)) -N 8 a.out | xargs)" = 1 ] || exit 1
+
+# SIZE32 is unsigned
+! cargo run %reloc_size1.o %reloc_size2.o %reloc_size3.o 2> %reloc_size.log || exit 1
+grep -q "relocation R_X86_64_SIZE32 out of range: symbol 'obj' (0x[0-9a-f]*): -1 is not in \[0, 4294967295\]" %reloc_size.log || exit 1
+exit 0
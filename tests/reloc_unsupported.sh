@@ -0,0 +1,31 @@
+# Unsupported relocations are reported with their location instead of aborting the link
+cat <<EOF2 | cc -o %reloc_unsupported1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  nop
+  .reloc ., R_X86_64_TLSDESC, foo
+  nop
+  .reloc ., R_X86_64_COPY, foo
+  nop
+  .data
+  .globl foo
+foo:
+  .quad 0
+EOF2
+cat <<EOF2 | cc -o %reloc_unsupported2.o -c -x assembler -
+  .data
+  .quad foo
+EOF2
+# Turn R_X86_64_64 into an unknown relocation type
+rela=$((0x$(readelf -SW %reloc_unsupported2.o | sed 's/\[ */[/' | awk '$2 == ".rela.data" { print $5 }')))
+printf '\143' | dd of=%reloc_unsupported2.o bs=1 seek=$((rela + 8)) conv=notrunc 2> /dev/null
+readelf -rW %reloc_unsupported2.o | grep -q "unrecognized: 63" || exit 1
+
+! cargo run %reloc_unsupported1.o %reloc_unsupported2.o 2> %reloc_unsupported.log || exit 1
+# All of them are reported at once
+grep -q "%reloc_unsupported1.o:(.text+0x1): unsupported relocation R_X86_64_TLSDESC" %reloc_unsupported.log || exit 1
+grep -q "%reloc_unsupported1.o:(.text+0x2): unsupported relocation R_X86_64_COPY" %reloc_unsupported.log || exit 1
+grep -q "%reloc_unsupported2.o:(.data+0x0): unsupported relocation unknown (99)" %reloc_unsupported.log || exit 1
+! grep -q panicked %reloc_unsupported.log || exit 1
+exit 0
@@ -0,0 +1,38 @@
+# RELRO sections are grouped and covered by PT_GNU_RELRO
+cat <<EOF2 | cc -o %relro.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  jmp _start
+  .data
+  .quad 1
+  .section .init_array,"aw",@init_array
+  .quad _start
+  .section .data.rel.ro,"aw",@progbits
+  .quad 2
+  .bss
+  .zero 8
+EOF2
+
+cargo run %relro.o || exit 1
+relro=$(readelf -lW a.out | awk '$1 == "GNU_RELRO"')
+[ -n "$relro" ] || exit 1
+start=$(($(echo $relro | cut -d' ' -f3)))
+end=$((start + $(echo $relro | cut -d' ' -f6)))
+[ $((end % 4096)) = 0 ] || exit 1
+addr() {
+  echo $((0x$(readelf -SW a.out | sed 's/\[ */[/' | awk -v name=$1 '$2 == name { print $4 }')))
+}
+size() {
+  echo $((0x$(readelf -SW a.out | sed 's/\[ */[/' | awk -v name=$1 '$2 == name { print $6 }')))
+}
+# The segment starts at the first RELRO section and covers all of them
+[ $start = $(addr .init_array) ] || exit 1
+[ $(($(addr .data.rel.ro) + $(size .data.rel.ro))) -le $end ] || exit 1
+# Other writable sections come after the padded end
+[ $(addr .data) -ge $end ] || exit 1
+[ $(addr .bss) -ge $end ] || exit 1
+
+cargo run -- -z norelro %relro.o || exit 1
+readelf -lW a.out | grep -q GNU_RELRO && exit 1
+exit 0
@@ -0,0 +1,43 @@
+# --verify-reproducible links twice and checks that the outputs are the same
+cat <<EOF2 | cc -o %reproducible1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  call f
+  mov v@GOTPCREL(%rip), %rax
+  mov \$60, %eax
+  mov \$7, %edi
+  syscall
+  .section .rodata.str1.1,"aMS",@progbits,1
+  .string "str"
+  .comm c, 8, 8
+EOF2
+cat <<EOF2 | cc -o %reproducible2.o -c -x assembler -
+  .text
+  .globl f
+f:
+  ret
+  .section sec,"a",@progbits
+  .quad 1
+  .section .rodata.str1.1,"aMS",@progbits,1
+  .string "str"
+  .data
+  .globl v
+v:
+  .quad 2
+EOF2
+
+cargo run %reproducible1.o %reproducible2.o || exit 1
+mv a.out %reproducible.out
+cargo run %reproducible1.o %reproducible2.o --verify-reproducible 2> %reproducible.log || exit 1
+cmp a.out %reproducible.out || exit 1
+# Diagnostics of the second link are not printed
+! grep -q '^\[' %reproducible.log || exit 1
+./a.out
+[ $? -eq 7 ] || exit 1
+
+# Warnings are printed once
+cargo run %reproducible1.o %reproducible2.o --verify-reproducible --warn-common \
+  --section-start=.nonexistent=0x800000 2> %reproducible.log || exit 1
+[ "$(grep -c 'no such section' %reproducible.log)" -eq 1 ] || exit 1
+exit 0
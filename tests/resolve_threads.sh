@@ -0,0 +1,39 @@
+# The definition which a symbol is bound to doesn't depend on the number of threads. Each
+# file defines 400 symbols, some weak and some strong, and each symbol has a strong
+# definition in at most one file. sym$j is strong in file (j % 13) if j is a multiple of 3.
+for i in $(seq 0 15); do
+  seq 0 399 | awk -v i=$i '
+    BEGIN { print "  .data" }
+    {
+      if ($1 % 3 == 0 && $1 % 13 == i)
+        printf "  .globl sym%d\n", $1
+      else
+        printf "  .weak sym%d\n", $1
+      printf "sym%d:\n  .quad %d\n", $1, i
+      printf "  .quad sym%d\n", ($1 + i) % 400
+    }
+  ' | cc -o %resolve_threads$i.o -c -x assembler -
+done
+# sym39 is strong in file 0 and sym40 has only weak definitions, the first of which wins
+cat <<EOF2 | cc -o %resolve_threads.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov sym39(%rip), %rdi
+  add sym40(%rip), %rdi
+  add sym42(%rip), %rdi
+  mov \$60, %eax
+  syscall
+EOF2
+files="%resolve_threads.o $(seq -f %%resolve_threads%g.o 0 15)"
+
+cargo run $files --no-threads || exit 1
+mv a.out %resolve_threads.out
+./%resolve_threads.out
+# sym39 (file 0) + sym40 (file 0) + sym42 (file 3)
+[ $? = 3 ] || exit 1
+for i in $(seq 5); do
+  cargo run $files --threads=8 || exit 1
+  cmp a.out %resolve_threads.out || exit 1
+done
+exit 0
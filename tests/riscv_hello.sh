@@ -0,0 +1,58 @@
+# A hello world of RISC-V objects links with linker relaxation enabled, and the relocated
+# instructions refer to the right places. The objects are assembled by llvm-mc, so the test
+# passes without it.
+command -v llvm-mc > /dev/null || exit 0
+cat <<EOF2 | llvm-mc -triple=riscv64-linux-gnu -mattr=+c,+relax -filetype=obj -o %riscv_hello1.o
+  .attribute arch, "rv64i2p0_m2p0_a2p0_f2p0_d2p0_c2p0"
+  .text
+  .globl _start
+_start:
+  li a0, 1
+  lla a1, msg
+  li a2, 14
+  call write
+  .p2align 3
+  beqz a0, 1f
+1:
+  li a0, 0
+  li a7, 93
+  ecall
+
+  .section .rodata
+msg:
+  .ascii "Hello, world!\n"
+
+  .data
+  .word 2f - 1b
+2:
+EOF2
+cat <<EOF2 | llvm-mc -triple=riscv64-linux-gnu -mattr=+c,+relax -filetype=obj -o %riscv_hello2.o
+  .attribute arch, "rv64i2p0_m2p0_a2p0_f2p0_d2p0_c2p0"
+  .text
+  .globl write
+write:
+  li a7, 64
+  ecall
+  ret
+EOF2
+
+cargo run %riscv_hello1.o %riscv_hello2.o || exit 1
+[ "$(readelf -hW a.out | awk '/Machine:/ { print $2 }')" = RISC-V ] || exit 1
+readelf -hW a.out | grep -q 'Flags:.*RVC' || exit 1
+# Attributes of the inputs are not concatenated
+readelf -SW a.out | grep -q riscv.attributes && exit 1
+
+# msg is at 0x400120 and write at 0x401024. .p2align 3 is padded with 6 bytes by the
+# assembler, and 2 of them are deleted so that beqz stays at an 8-byte boundary.
+[ "$(readelf -sW a.out | awk '$8 == "msg" { print $2 }')" = 0000000000400120 ] || exit 1
+[ "$(readelf -sW a.out | awk '$8 == "write" { print $2 }')" = 0000000000401024 ] || exit 1
+# auipc a1, -1; addi a1, a1, 286
+[ "$(od -An -tx4 -j $((0x1002)) -N 8 a.out | tr -s ' ')" = " fffff597 11e58593" ] || exit 1
+# auipc ra, 0; jalr 24(ra); nop; beqz a0, 0x40101a
+[ "$(od -An -tx4 -j $((0x100c)) -N 12 a.out | tr -s ' ')" = " 00000097 018080e7 00000013" ] \
+  || exit 1
+[ "$(od -An -tx2 -j $((0x1018)) -N 2 a.out | tr -s ' ')" = " c109" ] || exit 1
+# .word 2f - 1b is relocated by a pair of R_RISCV_ADD32 and R_RISCV_SUB32
+[ "$(od -An -tx4 -j $((0x2000)) -N 4 a.out | tr -s ' ')" = " 00000fea" ] || exit 1
+
+exit 0
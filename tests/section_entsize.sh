@@ -0,0 +1,53 @@
+# sh_entsize, sh_link and sh_info of output sections are consistent with their contents
+cat <<EOF2 | cc -o %section_entsize1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov foo@GOTPCREL(%rip), %rax
+  jmp _start
+  .local bar
+bar:
+  .section mystr,"aMS",@progbits,1
+  .string "foo"
+  .section mymix,"aM",@progbits,8
+  .quad 1
+  .section .init_array,"aw"
+  .quad _start
+  .section .comment,"MS",@progbits,1
+  .string "comment"
+EOF2
+cat <<EOF2 | cc -o %section_entsize2.o -c -x assembler -
+  .globl foo
+  .section .data,"aw"
+foo:
+  .quad 2
+  .section mystr,"aMS",@progbits,1
+  .string "bar"
+  .section mymix,"aMS",@progbits,1
+  .string "baz"
+  .section .ctors,"aw",@progbits
+  .quad _start
+EOF2
+
+cargo run %section_entsize1.o %section_entsize2.o || exit 1
+
+section() {
+  readelf -SW a.out | sed 's/\[ */[/' | awk -v name=$1 '$2 == name { print $'$2' }'
+}
+# Inputs agreeing on the entry size keep it
+[ "$(section mystr 7)" = 01 ] || exit 1
+[ "$(section mystr 8)" = AMS ] || exit 1
+[ "$(section .comment 7)" = 01 ] || exit 1
+# Entries of different sizes cannot be merged
+[ "$(section mymix 7)" = 00 ] || exit 1
+[ "$(section mymix 8)" = A ] || exit 1
+# Arrays of pointers, including ones converted from .ctors
+[ "$(section .init_array 7)" = 08 ] || exit 1
+[ "$(section .got 7)" = 08 ] || exit 1
+# .symtab links to .strtab and sh_info is the index of the first non-local symbol. It has
+# no flags, so the fields are counted from the end.
+strtab=$(readelf -SW a.out | sed 's/\[ */[/;s/]//' | awk '$2 == ".strtab" { print $1 }' | tr -d '[')
+[ "$(section .symtab "(NF-2)")" = "$strtab" ] || exit 1
+first_global=$(readelf -sW a.out | awk '$5 == "LOCAL" { n++ } END { print n }')
+[ "$(section .symtab "(NF-1)")" = "$first_global" ] || exit 1
+exit 0
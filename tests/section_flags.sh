@@ -0,0 +1,50 @@
+# Inputs of the same output section with different flags are merged into one section
+cat <<EOF2 | cc -o %section_flags1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  jmp _start
+  .section .rodata,"a",@progbits
+  .quad 1
+  .section .rodata.str1.1,"aMS",@progbits,1
+  .string "foo"
+  .section .rodata.cst8,"aM",@progbits,8
+  .quad 2
+  .section .data.a,"aw",@progbits,unique,1
+  .quad 3
+  .section mysec,"aw",@progbits
+  .quad 3
+  .section .tdata,"awT",@progbits
+  .quad 4
+EOF2
+cat <<EOF2 | cc -o %section_flags2.o -c -x assembler -
+  .section .rodata.str1.1,"aMS",@progbits,1
+  .string "bar"
+  .section .data.a,"aw",@progbits
+  .quad 5
+  .section mysec,"a",@progbits
+  .quad 6
+  .section .data.c,"awG",@progbits,grp,comdat
+  .quad 7
+EOF2
+
+cargo run %section_flags1.o %section_flags2.o 2> %section_flags.log || exit 1
+
+section() {
+  readelf -SW a.out | sed 's/\[ */[/' | awk -v name=$1 '$2 == name { print $'$2' }'
+}
+# One output section per name. MERGE and STRINGS are dropped unless all inputs have them.
+[ "$(section .rodata 2 | wc -l)" = 1 ] || exit 1
+[ "$(section .rodata 8)" = A ] || exit 1
+[ $((0x$(section .rodata 6))) = $((8 + 4 + 8 + 4)) ] || exit 1
+# Group flags are dropped
+[ "$(section .data 2 | wc -l)" = 1 ] || exit 1
+[ "$(section .data 8)" = WA ] || exit 1
+# Permissions are merged with a warning
+[ "$(section mysec 2 | wc -l)" = 1 ] || exit 1
+[ "$(section mysec 8)" = WA ] || exit 1
+grep -q "%section_flags2.o: mysec has different permissions from other inputs of mysec" %section_flags.log || exit 1
+[ $(grep -c "has different permissions" %section_flags.log) = 1 ] || exit 1
+# TLS sections are never merged with non-TLS ones
+[ "$(section .tdata 8)" = WAT ] || exit 1
+exit 0
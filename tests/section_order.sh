@@ -0,0 +1,24 @@
+# Output sections are sorted by permissions
+cat <<EOF2 | cc -o %section_order.o -c -x assembler -
+  .bss
+  .zero 8
+  .data
+  .quad 1
+  .section .tbss,"awT",@nobits
+  .zero 8
+  .section .tdata,"awT",@progbits
+  .quad 2
+  .text
+  .globl _start
+_start:
+  jmp _start
+  .section .rodata
+  .quad 3
+EOF2
+
+cargo run %section_order.o || exit 1
+[ "$(readelf -SW a.out | sed 's/\[ */[/' | awk '/^ *\[[1-9]/ { printf "%s ", $2 }')" = ".rodata .text .data .tdata .tbss .bss .symtab .strtab .shstrtab " ] || exit 1
+# The section header table is at the end of the file
+shstrtab_ofs=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".shstrtab" { print $5 }')
+[ "$(readelf -hW a.out | awk '/Start of section headers/ { print $5 }')" -gt $((0x$shstrtab_ofs)) ] || exit 1
+exit 0
@@ -0,0 +1,74 @@
+# Sections with the same permissions are sorted in the conventional order regardless of
+# the input order, and orphans keep their input order after the well-known ones
+cat <<EOF2 | cc -o %section_rank.so -shared -nostdlib -x assembler -
+  .text
+  .globl foo
+  .type foo, @function
+foo:
+  ret
+  .data
+  .globl var
+  .type var, @object
+var:
+  .quad 1
+EOF2
+cat <<EOF2 | cc -o %section_rank.o -c -x assembler -
+  .section .comment
+  .string "comment"
+  .bss
+  .zero 8
+  .data
+  .quad 1
+  .section .fini_array,"aw",@fini_array
+  .quad 0
+  .section .init_array,"aw",@init_array
+  .quad 0
+  .section .data.rel.ro,"aw",@progbits
+  .quad 2
+  .section my_text2,"ax",@progbits
+  ret
+  .section .fini,"ax",@progbits
+  ret
+  .text
+  .globl _start
+_start:
+  call foo@PLT
+  mov var@GOTPCREL(%rip), %rax
+  jmp _start
+  .section my_text1,"ax",@progbits
+  ret
+  .section .init,"ax",@progbits
+  ret
+  .section .rodata
+  .quad 3
+EOF2
+
+cargo run %section_rank.o %section_rank.so || exit 1
+cat <<EOF2 | diff - <(readelf -SW a.out | sed 's/\[ */[/' | awk '/^ *\[[1-9]/ { print $2 }') || exit 1
+.interp
+.gnu.hash
+.dynsym
+.dynstr
+.rela.dyn
+.rela.plt
+.rodata
+.init
+.plt
+.text
+.fini
+my_text2
+my_text1
+.init_array
+.fini_array
+.data.rel.ro
+.dynamic
+.got
+.got.plt
+.data
+.bss
+.comment
+.symtab
+.strtab
+.shstrtab
+EOF2
+exit 0
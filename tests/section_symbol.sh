@@ -0,0 +1,56 @@
+# References to local labels are relocated against section symbols, with the offset of the
+# label in the addend
+cat <<EOF2 | cc -o %section_symbol1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov ptrs(%rip), %rax
+  mov (%rax), %rdi
+  mov ptrs + 8(%rip), %rax
+  add (%rax), %rdi
+  lea .Lthree(%rip), %rax
+  add (%rax), %rdi
+  mov \$60, %eax
+  syscall
+  .data
+  .quad 0
+.Lthree:
+  .quad 3
+EOF2
+cat <<EOF2 | cc -o %section_symbol2.o -c -x assembler -
+  .section .rodata
+  .quad 0, 0
+.Lone:
+  .quad 1
+.Ltwo:
+  .quad 2
+  .data
+  .globl ptrs
+ptrs:
+  .quad .Lone
+  .quad .Ltwo
+  # Relocated against the null symbol
+  .reloc ., R_X86_64_64, 0x1234
+  .quad 0
+EOF2
+readelf -rW %section_symbol2.o | grep -q "R_X86_64_64 .* \.rodata + 18" || exit 1
+
+cargo run %section_symbol1.o %section_symbol2.o || exit 1
+./a.out
+[ $? = 6 ] || exit 1
+ptrs=$(readelf -sW a.out | awk '$8 == "ptrs" { print $2 }')
+data=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".data" { print $4, $5 }')
+ofs=$((0x${data#* } + 0x$ptrs - 0x${data% *}))
+[ "$(od -An -tx8 -j $((ofs + 16)) -N 8 a.out | xargs)" = 0000000000001234 ] || exit 1
+
+# References to sections which are not linked are errors
+cat <<EOF2 | cc -o %section_symbol3.o -c -x assembler -
+  .section .gnu.warning.foo
+.Lwarning:
+  .string "foo is deprecated"
+  .data
+  .quad .Lwarning
+EOF2
+! cargo run %section_symbol1.o %section_symbol2.o %section_symbol3.o 2> %section_symbol.log || exit 1
+grep -q "%section_symbol3.o:(.data+0x0): relocation R_X86_64_64 refers to a symbol in a discarded section" %section_symbol.log || exit 1
+exit 0
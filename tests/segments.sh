@@ -0,0 +1,36 @@
+# Sections with the same permissions share a PT_LOAD segment
+for i in 1 2; do
+  cat <<EOF2 | cc -o %segments$i.o -c -x assembler -
+  .text
+  .globl f$i
+f$i:
+  ret
+  .section .rodata.$i,"a",@progbits
+  .quad $i
+  .section .data.rel.ro,"aw",@progbits
+  .quad $i
+  .data
+  .quad $i
+  .bss
+  .zero 16
+EOF2
+done
+cat <<EOF2 | cc -o %segments_start.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov \$60, %eax
+  xor %edi, %edi
+  syscall
+  .section .rodata.str,"a",@progbits
+  .asciz "hello"
+EOF2
+
+cargo run %segments_start.o %segments1.o %segments2.o || exit 1
+./a.out || exit 1
+
+[ "$(readelf -lW a.out | awk '$1 == "LOAD" { print $7 $8 }' | sed 's/0x1000$//' | xargs)" = "R RE RW" ] || exit 1
+# The headers are in the first segment, and .bss is only in memory
+[ "$(readelf -lW a.out | awk '$1 == "LOAD" { print $2 }' | head -1)" = 0x000000 ] || exit 1
+[ "$(readelf -lW a.out | awk '$1 == "LOAD" && $7 == "RW" { print ($5 != $6) }')" = 1 ] || exit 1
+exit 0
@@ -0,0 +1,31 @@
+# Symbols which a shared object references but doesn't define are imported at runtime.
+# Calls go through the PLT, and addresses and TLS variables through GOT entries which the
+# dynamic linker fills.
+cat <<EOF2 | cc -o %shared_undefined.o -c -fPIC -x c -
+#include <stdio.h>
+extern int answer;
+extern __thread int counter;
+void report(void) { printf("%d %d\n", answer, ++counter); }
+EOF2
+cat <<EOF2 | cc -o %shared_undefined_main.o -c -fPIC -x c -
+int answer = 42;
+__thread int counter = 1;
+void report(void);
+int main(void) { report(); return 0; }
+EOF2
+
+cargo run -- -shared %shared_undefined.o || exit 1
+mv a.out %libshared_undefined.so
+readelf --dyn-syms -W %libshared_undefined.so > %shared_undefined.dynsym
+for name in printf answer counter __tls_get_addr; do
+  grep -qE " UND $name$" %shared_undefined.dynsym || exit 1
+done
+readelf -rW %libshared_undefined.so > %shared_undefined.rel
+grep -qE "R_X86_64_JUMP_SLOT .* printf \+ 0$" %shared_undefined.rel || exit 1
+grep -qE "R_X86_64_JUMP_SLOT .* __tls_get_addr \+ 0$" %shared_undefined.rel || exit 1
+grep -qE "R_X86_64_GLOB_DAT .* answer \+ 0$" %shared_undefined.rel || exit 1
+grep -qE "R_X86_64_DTPMOD64 .* counter \+ 0$" %shared_undefined.rel || exit 1
+
+cc -o %shared_undefined %shared_undefined_main.o %libshared_undefined.so || exit 1
+[ "$(LD_LIBRARY_PATH=. ./%shared_undefined)" = "42 2" ] || exit 1
+exit 0
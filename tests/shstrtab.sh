@@ -0,0 +1,59 @@
+# Section names are looked up by exact offsets in .shstrtab, even if a name is a suffix of
+# another one
+cat <<EOF2 | cc -o %shstrtab.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  jmp _start
+  .section xfoo,"aw",@progbits
+  .quad 1
+  .section foo,"a",@progbits
+  .quad 2
+  .section oo,"a",@progbits
+  .quad 3
+  .section bar,"a",@progbits
+  .quad 4
+EOF2
+cat <<EOF2 | cc -o %shstrtab2.o -c -x assembler -
+  .section bar,"awT",@progbits
+  .quad 5
+EOF2
+
+cargo run %shstrtab.o %shstrtab2.o || exit 1
+
+names=$(readelf -SW a.out | sed 's/\[ */[/' | awk '/^ *\[[1-9]/ { print $2 }')
+for name in xfoo foo oo .text .symtab .strtab .shstrtab; do
+  [ $(echo "$names" | grep -cx -- "$name") = 1 ] || exit 1
+done
+# Output sections with the same name share the string
+[ $(echo "$names" | grep -cx bar) = 2 ] || exit 1
+[ "$(readelf -x foo a.out | awk '/0x/ { print $2 $3 }')" = 0200000000000000 ] || exit 1
+[ "$(readelf -x oo a.out | awk '/0x/ { print $2 $3 }')" = 0300000000000000 ] || exit 1
+
+# Each distinct name is stored once, after the leading NUL, and names which are suffixes of
+# other ones share their tails
+size=$(echo "$names" | sort -u | awk '
+  { names[NR] = $0 }
+  END {
+    n = 1
+    for (i in names) {
+      shared = 0
+      for (j in names) {
+        long = names[j]
+        if (i != j && length(long) > length(names[i]) &&
+            substr(long, length(long) - length(names[i]) + 1) == names[i])
+          shared = 1
+      }
+      if (!shared)
+        n += length(names[i]) + 1
+    }
+    print n
+  }')
+[ $((0x$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".shstrtab" { print $6 }'))) = $size ] || exit 1
+shoff=$(readelf -hW a.out | awk '/Start of section headers/ { print $5 }')
+sh_name() {
+  idx=$(readelf -SW a.out | sed 's/\[ */[/' | awk -v name=$1 '$2 == name { print $1 }' | tr -d '[]')
+  od -An -tu4 -j $((shoff + idx * 64)) -N 4 a.out | xargs
+}
+[ $(sh_name oo) = $(($(sh_name xfoo) + 2)) ] || exit 1
+exit 0
@@ -0,0 +1,35 @@
+# --sort-symbols=address sorts locals and globals by address separately.
+cat <<EOF2 | cc -o %sort_symbols1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  call zzz
+local_b:
+  jmp _start
+EOF2
+cat <<EOF2 | cc -o %sort_symbols2.o -c -x assembler -
+  .text
+local_a:
+  nop
+  .globl zzz
+zzz:
+  ret
+  .globl aaa
+aaa:
+  ret
+EOF2
+
+cargo run -- --sort-symbols=address %sort_symbols1.o %sort_symbols2.o || exit 1
+
+# Print "<bind> <value>" of each symbol except the null one
+readelf -sW a.out | awk '$1 ~ /^[1-9][0-9]*:$/ { print $5, $2 }' > %sort_symbols.txt
+# Locals come first, and sh_info points at the first global
+first_global=$(awk '$1 == "GLOBAL" { print NR; exit }' %sort_symbols.txt)
+awk 'NR >= '"$first_global"' && $1 == "LOCAL" { exit 1 }' %sort_symbols.txt || exit 1
+info=$(readelf -SW a.out | awk '/ \.symtab / { print $(NF-1) }')
+[ "$info" = "$first_global" ] || exit 1
+# Values are monotonic within each partition
+for bind in LOCAL GLOBAL; do
+  awk -v bind=$bind '$1 == bind { print $2 }' %sort_symbols.txt > %sort_symbols_values.txt
+  sort -c %sort_symbols_values.txt || exit 1
+done
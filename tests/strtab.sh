@@ -0,0 +1,25 @@
+# Symbol names are stored once in .strtab, and names which are suffixes of other ones share
+# their tails
+cat <<EOF2 | cc -o %strtab1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  jmp _start
+counter:
+  .globl xcounter
+xcounter:
+  nop
+EOF2
+cat <<EOF2 | cc -o %strtab2.o -c -x assembler -
+  .text
+counter:
+  nop
+EOF2
+
+cargo run %strtab1.o %strtab2.o || exit 1
+
+[ "$(readelf -sW a.out | awk '$8 == "counter"' | wc -l)" = 2 ] || exit 1
+strtab=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".strtab" { print $5, $6 }')
+set -- $strtab
+[ "$(od -An -c -j $((0x$1)) -N $((0x$2)) a.out | tr -d ' \n')" = '\0_start\0xcounter\0' ] || exit 1
+exit 0
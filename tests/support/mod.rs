@@ -0,0 +1,482 @@
+//! Builder of minimal relocatable objects for tests, so that they don't depend on the
+//! assembler of the host.
+//!
+//! ```ignore
+//! let mut obj = ObjectBuilder::new();
+//! let text = obj.section(".text", SHF_ALLOC | SHF_EXECINSTR, 1, &[0xe8, 0, 0, 0, 0]);
+//! obj.global("_start", text, 0);
+//! obj.rela(text, 1, R_X86_64_PLT32, "foo", -4);
+//! let data = obj.build();
+//! ```
+
+// Each test binary uses a different part of this module
+#![allow(dead_code)]
+
+use bold::Input;
+use elf::{
+    abi::{
+        SHF_ALLOC, SHF_INFO_LINK, SHF_TLS, SHF_WRITE, SHN_ABS, SHN_COMMON, SHN_UNDEF, SHT_NOBITS,
+        SHT_PROGBITS, SHT_RELA, SHT_STRTAB, SHT_SYMTAB, STB_GLOBAL, STB_LOCAL, STB_WEAK,
+        STT_NOTYPE, STT_OBJECT, STT_TLS,
+    },
+    endian::LittleEndian,
+    ElfBytes,
+};
+
+const EHDR_SIZE: usize = 64;
+const SHDR_SIZE: usize = 64;
+const SYM_SIZE: usize = 24;
+const RELA_SIZE: usize = 24;
+
+struct Section {
+    name: String,
+    sh_type: u32,
+    flags: u64,
+    align: u64,
+    data: Vec<u8>,
+    /// Size of a NOBITS section, which has no data
+    size: u64,
+    relas: Vec<Rela>,
+}
+
+struct Rela {
+    offset: u64,
+    r_type: u32,
+    symbol: String,
+    addend: i64,
+}
+
+struct Symbol {
+    name: String,
+    bind: u8,
+    sym_type: u8,
+    /// Index in the section header table, or SHN_*
+    shndx: u16,
+    value: u64,
+    size: u64,
+}
+
+/// Relocatable object under construction, which is for x86-64 unless another machine is
+/// set. Sections are referred to by the index returned when they are added, which is also
+/// their index in the section header table.
+pub struct ObjectBuilder {
+    e_machine: u16,
+    e_flags: u32,
+    sections: Vec<Section>,
+    symbols: Vec<Symbol>,
+}
+
+impl Default for ObjectBuilder {
+    fn default() -> ObjectBuilder {
+        ObjectBuilder {
+            e_machine: elf::abi::EM_X86_64,
+            e_flags: 0,
+            sections: vec![],
+            symbols: vec![],
+        }
+    }
+}
+
+impl ObjectBuilder {
+    pub fn new() -> ObjectBuilder {
+        ObjectBuilder::default()
+    }
+
+    /// Set e_machine of the object
+    pub fn machine(&mut self, e_machine: u16) -> &mut ObjectBuilder {
+        self.e_machine = e_machine;
+        self
+    }
+
+    /// Set e_flags of the object
+    pub fn flags(&mut self, e_flags: u32) -> &mut ObjectBuilder {
+        self.e_flags = e_flags;
+        self
+    }
+
+    /// Add a PROGBITS section and return its index
+    pub fn section(&mut self, name: &str, flags: u32, align: u64, data: &[u8]) -> u16 {
+        self.add_section(
+            name,
+            SHT_PROGBITS,
+            flags,
+            align,
+            data.to_vec(),
+            data.len() as u64,
+        )
+    }
+
+    /// Add a NOBITS section such as .bss and return its index
+    pub fn nobits(&mut self, name: &str, flags: u32, align: u64, size: u64) -> u16 {
+        self.add_section(name, SHT_NOBITS, flags, align, vec![], size)
+    }
+
+    fn add_section(
+        &mut self,
+        name: &str,
+        sh_type: u32,
+        flags: u32,
+        align: u64,
+        data: Vec<u8>,
+        size: u64,
+    ) -> u16 {
+        self.sections.push(Section {
+            name: name.to_owned(),
+            sh_type,
+            flags: flags as u64,
+            align,
+            data,
+            size,
+            relas: vec![],
+        });
+        // Index 0 is the null section
+        self.sections.len() as u16
+    }
+
+    /// Add a symbol with any attributes
+    pub fn symbol(
+        &mut self,
+        name: &str,
+        bind: u8,
+        sym_type: u8,
+        shndx: u16,
+        value: u64,
+        size: u64,
+    ) -> &mut ObjectBuilder {
+        self.symbols.push(Symbol {
+            name: name.to_owned(),
+            bind,
+            sym_type,
+            shndx,
+            value,
+            size,
+        });
+        self
+    }
+
+    pub fn local(&mut self, name: &str, section: u16, value: u64) -> &mut ObjectBuilder {
+        self.symbol(name, STB_LOCAL, STT_NOTYPE, section, value, 0)
+    }
+
+    pub fn global(&mut self, name: &str, section: u16, value: u64) -> &mut ObjectBuilder {
+        self.symbol(name, STB_GLOBAL, STT_NOTYPE, section, value, 0)
+    }
+
+    pub fn weak(&mut self, name: &str, section: u16, value: u64) -> &mut ObjectBuilder {
+        self.symbol(name, STB_WEAK, STT_NOTYPE, section, value, 0)
+    }
+
+    /// Add a global symbol at an absolute address
+    pub fn abs(&mut self, name: &str, value: u64) -> &mut ObjectBuilder {
+        self.symbol(name, STB_GLOBAL, STT_NOTYPE, SHN_ABS, value, 0)
+    }
+
+    /// Add a common symbol. Its value is the alignment.
+    pub fn common(&mut self, name: &str, size: u64, align: u64) -> &mut ObjectBuilder {
+        self.symbol(name, STB_GLOBAL, STT_OBJECT, SHN_COMMON, align, size)
+    }
+
+    /// Add a global TLS variable at `value` in a TLS section
+    pub fn tls(&mut self, name: &str, section: u16, value: u64, size: u64) -> &mut ObjectBuilder {
+        assert!(self.sections[section as usize - 1].flags & SHF_TLS as u64 != 0);
+        self.symbol(name, STB_GLOBAL, STT_TLS, section, value, size)
+    }
+
+    /// Add an undefined reference, which is only needed for symbols which no relocation
+    /// refers to
+    pub fn undefined(&mut self, name: &str) -> &mut ObjectBuilder {
+        self.symbol(name, STB_GLOBAL, STT_NOTYPE, SHN_UNDEF, 0, 0)
+    }
+
+    /// Add a relocation at `offset` in `section`. The symbol is added as an undefined global
+    /// if it is not defined when the object is built. An empty name refers to the null
+    /// symbol.
+    pub fn rela(
+        &mut self,
+        section: u16,
+        offset: u64,
+        r_type: u32,
+        symbol: &str,
+        addend: i64,
+    ) -> &mut ObjectBuilder {
+        self.sections[section as usize - 1].relas.push(Rela {
+            offset,
+            r_type,
+            symbol: symbol.to_owned(),
+            addend,
+        });
+        self
+    }
+
+    /// Returns the contents of the object file
+    pub fn build(&self) -> Vec<u8> {
+        // Local symbols must precede global ones
+        let mut symbols = self
+            .symbols
+            .iter()
+            .filter(|sym| sym.bind == STB_LOCAL)
+            .chain(self.symbols.iter().filter(|sym| sym.bind != STB_LOCAL))
+            .collect::<Vec<_>>();
+        let first_global = 1 + self
+            .symbols
+            .iter()
+            .filter(|sym| sym.bind == STB_LOCAL)
+            .count();
+        let mut undefined = vec![];
+        for rela in self.sections.iter().flat_map(|sec| &sec.relas) {
+            let name = &rela.symbol;
+            if !name.is_empty()
+                && !self.symbols.iter().any(|sym| sym.name == *name)
+                && !undefined.contains(name)
+            {
+                undefined.push(name.clone());
+            }
+        }
+        let undefined = undefined
+            .into_iter()
+            .map(|name| Symbol {
+                name,
+                bind: STB_GLOBAL,
+                sym_type: STT_NOTYPE,
+                shndx: SHN_UNDEF,
+                value: 0,
+                size: 0,
+            })
+            .collect::<Vec<_>>();
+        symbols.extend(&undefined);
+        let symbol_index = |name: &str| match name {
+            "" => 0,
+            name => 1 + symbols.iter().position(|sym| sym.name == name).unwrap() as u64,
+        };
+
+        let mut strtab = StringTable::new();
+        let mut symtab = vec![0; SYM_SIZE];
+        for sym in &symbols {
+            symtab.extend(strtab.add(&sym.name).to_le_bytes());
+            symtab.push((sym.bind << 4) | sym.sym_type);
+            symtab.push(0);
+            symtab.extend(sym.shndx.to_le_bytes());
+            symtab.extend(sym.value.to_le_bytes());
+            symtab.extend(sym.size.to_le_bytes());
+        }
+
+        // Sections after the ones added by the user: .rela.* for each section with
+        // relocations, .symtab, .strtab and .shstrtab
+        let mut shstrtab = StringTable::new();
+        let mut headers = vec![Header::default()];
+        let mut contents = vec![];
+        for sec in &self.sections {
+            headers.push(Header {
+                name: shstrtab.add(&sec.name),
+                sh_type: sec.sh_type,
+                flags: sec.flags,
+                size: sec.size,
+                align: sec.align,
+                ..Header::default()
+            });
+            contents.push(sec.data.clone());
+        }
+        let num_user = headers.len();
+        let symtab_index = num_user + self.sections.iter().filter(|s| !s.relas.is_empty()).count();
+        for (i, sec) in self.sections.iter().enumerate() {
+            if sec.relas.is_empty() {
+                continue;
+            }
+            let mut data = vec![];
+            for rela in &sec.relas {
+                let info = (symbol_index(&rela.symbol) << 32) | rela.r_type as u64;
+                data.extend(rela.offset.to_le_bytes());
+                data.extend(info.to_le_bytes());
+                data.extend(rela.addend.to_le_bytes());
+            }
+            headers.push(Header {
+                name: shstrtab.add(&format!(".rela{}", sec.name)),
+                sh_type: SHT_RELA,
+                flags: SHF_INFO_LINK as u64,
+                size: data.len() as u64,
+                link: symtab_index as u32,
+                info: (i + 1) as u32,
+                align: 8,
+                entsize: RELA_SIZE as u64,
+                ..Header::default()
+            });
+            contents.push(data);
+        }
+        headers.push(Header {
+            name: shstrtab.add(".symtab"),
+            sh_type: SHT_SYMTAB,
+            size: symtab.len() as u64,
+            link: symtab_index as u32 + 1,
+            info: first_global as u32,
+            align: 8,
+            entsize: SYM_SIZE as u64,
+            ..Header::default()
+        });
+        contents.push(symtab);
+        headers.push(Header {
+            name: shstrtab.add(".strtab"),
+            sh_type: SHT_STRTAB,
+            size: strtab.data.len() as u64,
+            align: 1,
+            ..Header::default()
+        });
+        contents.push(strtab.data);
+        headers.push(Header {
+            name: shstrtab.add(".shstrtab"),
+            sh_type: SHT_STRTAB,
+            align: 1,
+            ..Header::default()
+        });
+        let shstrndx = headers.len() - 1;
+        headers[shstrndx].size = shstrtab.data.len() as u64;
+        contents.push(shstrtab.data);
+
+        // Contents are placed after the ELF header in the order of the section headers
+        let mut buf = vec![0; EHDR_SIZE];
+        for (header, data) in headers.iter_mut().skip(1).zip(&contents) {
+            let align = header.align.max(1) as usize;
+            buf.resize(buf.len().next_multiple_of(align), 0);
+            header.offset = buf.len() as u64;
+            buf.extend(data);
+        }
+        buf.resize(buf.len().next_multiple_of(8), 0);
+        let shoff = buf.len() as u64;
+        for header in &headers {
+            header.write(&mut buf);
+        }
+
+        let ehdr = &mut buf[..EHDR_SIZE];
+        ehdr[..16].copy_from_slice(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+        ehdr[16..18].copy_from_slice(&elf::abi::ET_REL.to_le_bytes());
+        ehdr[18..20].copy_from_slice(&self.e_machine.to_le_bytes());
+        ehdr[20..24].copy_from_slice(&1u32.to_le_bytes());
+        ehdr[40..48].copy_from_slice(&shoff.to_le_bytes());
+        ehdr[48..52].copy_from_slice(&self.e_flags.to_le_bytes());
+        ehdr[52..54].copy_from_slice(&(EHDR_SIZE as u16).to_le_bytes());
+        ehdr[58..60].copy_from_slice(&(SHDR_SIZE as u16).to_le_bytes());
+        ehdr[60..62].copy_from_slice(&(headers.len() as u16).to_le_bytes());
+        ehdr[62..64].copy_from_slice(&(shstrndx as u16).to_le_bytes());
+        buf
+    }
+
+    /// Returns the object as an input of a link
+    pub fn input(&self, name: &str) -> Input {
+        Input::Bytes {
+            name: name.to_owned(),
+            data: self.build(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Header {
+    name: u32,
+    sh_type: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+
+impl Header {
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend(self.name.to_le_bytes());
+        buf.extend(self.sh_type.to_le_bytes());
+        buf.extend(self.flags.to_le_bytes());
+        // sh_addr
+        buf.extend(0u64.to_le_bytes());
+        buf.extend(self.offset.to_le_bytes());
+        buf.extend(self.size.to_le_bytes());
+        buf.extend(self.link.to_le_bytes());
+        buf.extend(self.info.to_le_bytes());
+        buf.extend(self.align.to_le_bytes());
+        buf.extend(self.entsize.to_le_bytes());
+    }
+}
+
+struct StringTable {
+    data: Vec<u8>,
+}
+
+impl StringTable {
+    fn new() -> StringTable {
+        StringTable { data: vec![0] }
+    }
+
+    fn add(&mut self, s: &str) -> u32 {
+        if s.is_empty() {
+            return 0;
+        }
+        let offset = self.data.len() as u32;
+        self.data.extend(s.as_bytes());
+        self.data.push(0);
+        offset
+    }
+}
+
+/// Flags of .data
+pub const DATA: u32 = SHF_ALLOC | SHF_WRITE;
+
+/// Parsed output of a link
+pub struct Output<'a> {
+    pub data: &'a [u8],
+    pub file: ElfBytes<'a, LittleEndian>,
+}
+
+impl Output<'_> {
+    pub fn new(data: &[u8]) -> Output<'_> {
+        Output {
+            data,
+            file: ElfBytes::minimal_parse(data).unwrap(),
+        }
+    }
+
+    /// Returns the address of a symbol in .symtab
+    pub fn symbol_addr(&self, name: &str) -> Option<u64> {
+        let (symtab, strtab) = self.file.symbol_table().unwrap()?;
+        symtab
+            .iter()
+            .find(|sym| strtab.get(sym.st_name as usize).unwrap() == name)
+            .map(|sym| sym.st_value)
+    }
+
+    /// Returns the names of the sections in the order of their headers
+    pub fn section_names(&self) -> Vec<String> {
+        let (shdrs, strtab) = self.file.section_headers_with_strtab().unwrap();
+        let strtab = strtab.unwrap();
+        shdrs
+            .unwrap()
+            .iter()
+            .skip(1)
+            .map(|shdr| strtab.get(shdr.sh_name as usize).unwrap().to_owned())
+            .collect()
+    }
+
+    /// Returns the bytes at an address in the image
+    pub fn read(&self, addr: u64, len: usize) -> &[u8] {
+        let phdr = self
+            .file
+            .segments()
+            .unwrap()
+            .iter()
+            .find(|phdr| {
+                phdr.p_type == elf::abi::PT_LOAD
+                    && phdr.p_vaddr <= addr
+                    && addr + len as u64 <= phdr.p_vaddr + phdr.p_filesz
+            })
+            .expect("the address is not in the file");
+        let offset = (addr - phdr.p_vaddr + phdr.p_offset) as usize;
+        &self.data[offset..offset + len]
+    }
+
+    pub fn read_u32(&self, addr: u64) -> u32 {
+        u32::from_le_bytes(self.read(addr, 4).try_into().unwrap())
+    }
+
+    pub fn read_u64(&self, addr: u64) -> u64 {
+        u64::from_le_bytes(self.read(addr, 8).try_into().unwrap())
+    }
+}
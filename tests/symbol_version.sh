@@ -0,0 +1,63 @@
+# Versions of symbols imported from DSOs are recorded in .gnu.version and .gnu.version_r
+cat <<EOF2 > %symbol_version.ver
+VER_1 { global: bar; local: *; };
+VER_2 { global: foo; } VER_1;
+EOF2
+cat <<EOF2 | cc -o %symbol_version.so -shared -nostdlib -Wl,--version-script=%symbol_version.ver -Wl,-soname,libver.so -x assembler -
+  .text
+  .globl foo_old, foo, bar
+  .type foo_old, @function
+  .type foo, @function
+  .type bar, @function
+  .symver foo_old, foo@VER_1
+foo_old:
+  ret
+foo:
+  ret
+bar:
+  ret
+EOF2
+cat <<EOF2 | cc -o %symbol_version2.so -shared -nostdlib -x assembler -
+  .text
+  .globl baz
+  .type baz, @function
+baz:
+  ret
+EOF2
+cat <<EOF2 | cc -o %symbol_version.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  call baz@PLT
+  call foo@PLT
+  call bar@PLT
+  jmp _start
+EOF2
+
+cargo run %symbol_version.o %symbol_version.so %symbol_version2.so || exit 1
+
+# Unversioned symbols get index 1, and versions are numbered from 2 in order of first use.
+# References bind to the default version foo@@VER_2.
+readelf -W --dyn-syms a.out | awk '$8 ~ /^(foo|bar|baz)/ { print $8, $9 }' | sed "s/ *$//" | sort > %symbol_version.syms
+cat <<EOF2 | diff - %symbol_version.syms || exit 1
+bar@VER_1 (3)
+baz
+foo@VER_2 (2)
+EOF2
+
+readelf -VW a.out > %symbol_version.dump
+grep -q "File: libver.so  Cnt: 2" %symbol_version.dump || exit 1
+grep -q "Name: VER_2  Flags: none  Version: 2" %symbol_version.dump || exit 1
+grep -q "Name: VER_1  Flags: none  Version: 3" %symbol_version.dump || exit 1
+# DSOs without versioned symbols are not listed
+! grep -q "File: %symbol_version2.so" %symbol_version.dump || exit 1
+
+readelf -dW a.out > %symbol_version.dyn
+grep -q "(VERSYM) *0x$(readelf -SW a.out | awk '$2 == ".gnu.version" { print $4 }' | sed 's/^0*//')" %symbol_version.dyn || exit 1
+grep -q "(VERNEEDNUM) *1$" %symbol_version.dyn || exit 1
+
+# There are no versions to record without versioned symbols
+cargo run %symbol_version.o %symbol_version2.so -z undefs || exit 1
+! readelf -SW a.out | grep -q "\.gnu\.version" || exit 1
+! readelf -dW a.out | grep -q VER || exit 1
+exit 0
@@ -0,0 +1,43 @@
+# st_size, st_info and st_other of defined symbols must survive into the output.
+cat <<EOF2 | cc -o %symtab_attrs1.o -c -x assembler -
+  .text
+  .globl _start
+  .type _start, @function
+_start:
+  call func
+  mov obj(%rip), %rax
+  jmp _start
+  .size _start, .-_start
+EOF2
+cat <<EOF2 | cc -o %symtab_attrs2.o -c -x assembler -
+  .text
+  .globl func
+  .type func, @function
+  .protected func
+func:
+  xor %eax, %eax
+  ret
+  .size func, .-func
+
+  .data
+  .globl obj
+  .type obj, @object
+  .size obj, 24
+obj:
+  .zero 24
+EOF2
+
+cargo run %symtab_attrs1.o %symtab_attrs2.o
+
+# Size, type, bind and visibility columns of `readelf -s`
+attrs() {
+  readelf -sW "$1" | awk -v name="$2" '$8 == name && $7 != "UND" { print $3, $4, $5, $6 }'
+}
+for sym in func obj; do
+  expected=$(attrs %symtab_attrs2.o $sym)
+  actual=$(attrs a.out $sym)
+  if [ "$expected" != "$actual" ]; then
+    echo "$sym: expected '$expected', got '$actual'"
+    exit 1
+  fi
+done
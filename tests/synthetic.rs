@@ -0,0 +1,378 @@
+//! Link objects made by the builder in the support module, so that the tests don't need an
+//! assembler
+
+mod support;
+
+use bold::{Config, Input, LinkError};
+use elf::abi::{
+    EF_RISCV_FLOAT_ABI_DOUBLE, EF_RISCV_RVC, EM_AARCH64, EM_RISCV, R_AARCH64_ABS64,
+    R_AARCH64_ADD_ABS_LO12_NC, R_AARCH64_ADR_GOT_PAGE, R_AARCH64_ADR_PREL_PG_HI21,
+    R_AARCH64_CALL26, R_AARCH64_CONDBR19, R_AARCH64_JUMP26, R_AARCH64_LD64_GOT_LO12_NC,
+    R_AARCH64_LDST64_ABS_LO12_NC, R_RISCV_ADD32, R_RISCV_ALIGN, R_RISCV_BRANCH, R_RISCV_CALL,
+    R_RISCV_JAL, R_RISCV_PCREL_HI20, R_RISCV_PCREL_LO12_I, R_RISCV_RELAX, R_RISCV_SUB32,
+    R_X86_64_PC32, SHF_ALLOC, SHF_EXECINSTR, SHF_TLS, SHF_WRITE,
+};
+use support::{ObjectBuilder, Output, DATA};
+
+const TEXT: u32 = SHF_ALLOC | SHF_EXECINSTR;
+
+/// Object defining _start, which is required by every link
+fn start() -> ObjectBuilder {
+    let mut obj = ObjectBuilder::new();
+    let text = obj.section(".text", TEXT, 1, &[0xeb, 0xfe]);
+    obj.global("_start", text, 0);
+    obj
+}
+
+/// Object defining `name` as a weak or global symbol on a u64 holding `value`
+fn define(name: &str, weak: bool, value: u64) -> ObjectBuilder {
+    let mut obj = ObjectBuilder::new();
+    let data = obj.section(".data", DATA, 8, &value.to_le_bytes());
+    if weak {
+        obj.weak(name, data, 0);
+    } else {
+        obj.global(name, data, 0);
+    }
+    obj
+}
+
+fn link(inputs: &[Input]) -> Vec<u8> {
+    bold::link(inputs, &Config::new()).unwrap().data
+}
+
+#[test]
+fn strong_definition_overrides_weak() {
+    for strong_first in [false, true] {
+        let weak = define("foo", true, 1).input("weak.o");
+        let strong = define("foo", false, 2).input("strong.o");
+        let inputs = if strong_first {
+            [start().input("start.o"), strong, weak]
+        } else {
+            [start().input("start.o"), weak, strong]
+        };
+        let data = link(&inputs);
+        let out = Output::new(&data);
+        assert_eq!(out.read_u64(out.symbol_addr("foo").unwrap()), 2);
+    }
+}
+
+#[test]
+fn first_weak_definition_wins() {
+    let data = link(&[
+        start().input("start.o"),
+        define("foo", true, 1).input("weak1.o"),
+        define("foo", true, 2).input("weak2.o"),
+    ]);
+    let out = Output::new(&data);
+    assert_eq!(out.read_u64(out.symbol_addr("foo").unwrap()), 1);
+}
+
+#[test]
+fn definition_overrides_common() {
+    let mut common = start();
+    common.common("foo", 16, 8);
+    let data = link(&[
+        common.input("common.o"),
+        define("foo", false, 3).input("def.o"),
+    ]);
+    let out = Output::new(&data);
+    assert_eq!(out.read_u64(out.symbol_addr("foo").unwrap()), 3);
+
+    // Without a definition, the common symbol is allocated in .bss
+    let mut common = start();
+    common.common("foo", 16, 8);
+    let data = link(&[common.input("common.o")]);
+    let out = Output::new(&data);
+    let bss = out.file.section_header_by_name(".bss").unwrap().unwrap();
+    let addr = out.symbol_addr("foo").unwrap();
+    assert!(bss.sh_addr <= addr && addr + 16 <= bss.sh_addr + bss.sh_size);
+    assert_eq!(addr % 8, 0);
+}
+
+#[test]
+fn section_binning() {
+    let mut obj = start();
+    obj.section(".text.a", TEXT, 1, &[0x90]);
+    obj.section(".text.b", TEXT, 1, &[0xc3]);
+    obj.section(".rodata.x", SHF_ALLOC, 1, &[1]);
+    obj.section(".data.rel.ro.y", DATA, 1, &[2]);
+    obj.section("custom", DATA, 1, &[3]);
+    let data = link(&[obj.input("sections.o")]);
+    let out = Output::new(&data);
+    let names = out.section_names();
+    for name in [".text", ".rodata", ".data.rel.ro", "custom"] {
+        assert!(
+            names.iter().any(|n| n == name),
+            "{} is missing: {:?}",
+            name,
+            names
+        );
+    }
+    assert!(
+        !names.iter().any(|n| n.starts_with(".text.")),
+        "{:?}",
+        names
+    );
+
+    // Inputs are placed in input order
+    let text = out.file.section_header_by_name(".text").unwrap().unwrap();
+    assert_eq!(out.read(text.sh_addr, 4), [0xeb, 0xfe, 0x90, 0xc3]);
+}
+
+#[test]
+fn pc32_relocation() {
+    let mut obj = ObjectBuilder::new();
+    let text = obj.section(".text", TEXT, 16, &[0; 8]);
+    obj.global("_start", text, 0);
+    obj.rela(text, 4, R_X86_64_PC32, "target", -4);
+    let data = link(&[
+        obj.input("main.o"),
+        define("target", false, 0).input("target.o"),
+    ]);
+    let out = Output::new(&data);
+    let place = out.symbol_addr("_start").unwrap() + 4;
+    let target = out.symbol_addr("target").unwrap();
+    assert_eq!(
+        out.read_u32(place) as i32 as i64,
+        target as i64 - place as i64 - 4
+    );
+}
+
+#[test]
+fn absolute_and_tls_symbols() {
+    let mut obj = start();
+    obj.abs("abs", 0x1234);
+    let tdata = obj.section(".tdata", SHF_ALLOC | SHF_WRITE | SHF_TLS, 8, &[0; 8]);
+    obj.tls("tls", tdata, 0, 8);
+    let data = link(&[obj.input("symbols.o")]);
+    let out = Output::new(&data);
+    assert_eq!(out.symbol_addr("abs"), Some(0x1234));
+    // TLS symbols are relative to the TLS segment
+    let tls = out
+        .file
+        .segments()
+        .unwrap()
+        .iter()
+        .find(|phdr| phdr.p_type == elf::abi::PT_TLS)
+        .unwrap();
+    assert!(out.symbol_addr("tls").unwrap() < tls.p_memsz);
+}
+
+/// Object for AArch64 whose code refers to a string with ADRP and ADD, loads a pointer to it
+/// and branches to `func` at offset 24
+fn aarch64_object() -> ObjectBuilder {
+    let insns: [u32; 7] = [
+        0x90000000, // adrp x0, msg
+        0x91000000, // add x0, x0, :lo12:msg
+        0xf9400001, // ldr x1, [x0, :lo12:ptr]
+        0x94000000, // bl func
+        0x14000000, // b func
+        0x54000000, // b.eq func
+        0xd65f03c0, // func: ret
+    ];
+    let code = insns
+        .iter()
+        .flat_map(|insn| insn.to_le_bytes())
+        .collect::<Vec<_>>();
+    let mut obj = ObjectBuilder::new();
+    obj.machine(EM_AARCH64);
+    let text = obj.section(".text", TEXT, 4, &code);
+    let rodata = obj.section(".rodata", SHF_ALLOC, 1, b"...hello\0");
+    let data = obj.section(".data", DATA, 8, &[0; 8]);
+    obj.local("msg", rodata, 3)
+        .global("_start", text, 0)
+        .global("func", text, 24)
+        .global("ptr", data, 0)
+        .rela(text, 0, R_AARCH64_ADR_PREL_PG_HI21, "msg", 0)
+        .rela(text, 4, R_AARCH64_ADD_ABS_LO12_NC, "msg", 0)
+        .rela(text, 8, R_AARCH64_LDST64_ABS_LO12_NC, "ptr", 0)
+        .rela(text, 12, R_AARCH64_CALL26, "func", 0)
+        .rela(text, 16, R_AARCH64_JUMP26, "func", 0)
+        .rela(text, 20, R_AARCH64_CONDBR19, "func", 0)
+        .rela(data, 0, R_AARCH64_ABS64, "msg", 0);
+    obj
+}
+
+#[test]
+fn aarch64_relocations() {
+    let data = link(&[aarch64_object().input("main.o")]);
+    let out = Output::new(&data);
+    assert_eq!(out.file.ehdr.e_machine, EM_AARCH64);
+    // Segments are aligned to 64 KiB pages
+    for phdr in out.file.segments().unwrap() {
+        if phdr.p_type == elf::abi::PT_LOAD {
+            assert_eq!(phdr.p_align, 0x10000);
+        }
+    }
+
+    let start = out.symbol_addr("_start").unwrap();
+    let msg = out.symbol_addr("msg").unwrap();
+    let ptr = out.symbol_addr("ptr").unwrap();
+    let pages = ((msg & !0xfff) as i64 - (start & !0xfff) as i64) >> 12;
+    let adrp = 0x90000000 | ((pages & 3) as u32) << 29 | ((pages >> 2) as u32 & 0x7ffff) << 5;
+    assert_eq!(out.read_u32(start), adrp);
+    assert_eq!(
+        out.read_u32(start + 4),
+        0x91000000 | ((msg & 0xfff) as u32) << 10
+    );
+    assert_eq!(
+        out.read_u32(start + 8),
+        0xf9400001 | ((ptr & 0xfff) as u32 >> 3) << 10
+    );
+    // Branches to func are 3, 2 and 1 instructions ahead
+    assert_eq!(out.read_u32(start + 12), 0x94000003);
+    assert_eq!(out.read_u32(start + 16), 0x14000002);
+    assert_eq!(out.read_u32(start + 20), 0x54000020);
+    assert_eq!(out.read_u64(ptr), msg);
+}
+
+#[test]
+fn aarch64_got() {
+    let insns: [u32; 3] = [
+        0x90000000, // adrp x0, :got:msg
+        0xf9400000, // ldr x0, [x0, :got_lo12:msg]
+        0xd65f03c0, // ret
+    ];
+    let code = insns
+        .iter()
+        .flat_map(|insn| insn.to_le_bytes())
+        .collect::<Vec<_>>();
+    let mut obj = ObjectBuilder::new();
+    obj.machine(EM_AARCH64);
+    let text = obj.section(".text", TEXT, 4, &code);
+    let rodata = obj.section(".rodata", SHF_ALLOC, 1, b"...hello\0");
+    obj.global("_start", text, 0)
+        .global("msg", rodata, 3)
+        .rela(text, 0, R_AARCH64_ADR_GOT_PAGE, "msg", 0)
+        .rela(text, 4, R_AARCH64_LD64_GOT_LO12_NC, "msg", 0);
+    let data = link(&[obj.input("main.o")]);
+    let out = Output::new(&data);
+
+    let start = out.symbol_addr("_start").unwrap();
+    let got = out.file.section_header_by_name(".got").unwrap().unwrap();
+    let pages = ((got.sh_addr & !0xfff) as i64 - (start & !0xfff) as i64) >> 12;
+    let adrp = 0x90000000 | ((pages & 3) as u32) << 29 | ((pages >> 2) as u32 & 0x7ffff) << 5;
+    assert_eq!(out.read_u32(start), adrp);
+    let ldr = out.read_u32(start + 4);
+    let got_entry_addr = (got.sh_addr & !0xfff) + (((ldr >> 10) & 0xfff) as u64) * 8;
+    assert_eq!(
+        out.read_u64(got_entry_addr),
+        out.symbol_addr("msg").unwrap()
+    );
+}
+
+#[test]
+fn aarch64_misaligned_load() {
+    let mut obj = ObjectBuilder::new();
+    obj.machine(EM_AARCH64);
+    let text = obj.section(".text", TEXT, 4, &0xf9400001u32.to_le_bytes());
+    let rodata = obj.section(".rodata", SHF_ALLOC, 8, b"...hello\0");
+    obj.global("_start", text, 0)
+        .local("msg", rodata, 3)
+        // ldr x1, [x0, :lo12:msg] can't encode an offset which is not a multiple of 8
+        .rela(text, 0, R_AARCH64_LDST64_ABS_LO12_NC, "msg", 0);
+    match bold::link(&[obj.input("main.o")], &Config::new()) {
+        Err(LinkError::Failed(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert!(errors[0].contains("is not aligned to 8 bytes"));
+        }
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("a misaligned offset was truncated"),
+    }
+}
+
+#[test]
+fn mixed_machines() {
+    let inputs = [start().input("x86.o"), aarch64_object().input("arm.o")];
+    match bold::link(&inputs, &Config::new()) {
+        Err(LinkError::Unsupported(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert!(errors[0].contains("aarch64 object is incompatible with x86-64 output"));
+        }
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("objects for different machines were linked"),
+    }
+}
+
+/// Object for RISC-V whose code refers to a string with AUIPC and ADDI, calls `func` and
+/// jumps and branches to it. The padding of the alignment at offset 20 is 6 bytes as the
+/// assembler emits it, and 2 bytes of it are deleted.
+fn riscv_object(e_flags: u32) -> ObjectBuilder {
+    let code = [
+        0x00000517u32.to_le_bytes().as_slice(), // .Lhi: auipc a0, %pcrel_hi(msg)
+        &0x00050513u32.to_le_bytes(),           // addi a0, a0, %pcrel_lo(.Lhi)
+        &0x00000097u32.to_le_bytes(),           // auipc ra, 0 (call func)
+        &0x000080e7u32.to_le_bytes(),           // jalr ra
+        &0x00000013u32.to_le_bytes(),           // nop
+        &0x00000013u32.to_le_bytes(),           // .p2align 3
+        &0x0001u16.to_le_bytes(),               // c.nop
+        &0x0000006fu32.to_le_bytes(),           // j func
+        &0x00a50063u32.to_le_bytes(),           // beq a0, a0, func
+        &0x00008067u32.to_le_bytes(),           // func: ret
+    ]
+    .concat();
+    let mut obj = ObjectBuilder::new();
+    obj.machine(EM_RISCV).flags(e_flags);
+    let text = obj.section(".text", TEXT, 8, &code);
+    let rodata = obj.section(".rodata", SHF_ALLOC, 1, b"...hello\0");
+    let data = obj.section(".data", DATA, 4, &[0; 4]);
+    obj.local("msg", rodata, 3)
+        .local(".Lhi", text, 0)
+        .global("_start", text, 0)
+        .global("func", text, 34)
+        .rela(text, 0, R_RISCV_PCREL_HI20, "msg", 0)
+        .rela(text, 0, R_RISCV_RELAX, "", 0)
+        .rela(text, 4, R_RISCV_PCREL_LO12_I, ".Lhi", 0)
+        .rela(text, 4, R_RISCV_RELAX, "", 0)
+        .rela(text, 8, R_RISCV_CALL, "func", 0)
+        .rela(text, 8, R_RISCV_RELAX, "", 0)
+        .rela(text, 20, R_RISCV_ALIGN, "", 6)
+        .rela(text, 26, R_RISCV_JAL, "func", 0)
+        .rela(text, 30, R_RISCV_BRANCH, "func", 0)
+        .rela(data, 0, R_RISCV_ADD32, "func", 0)
+        .rela(data, 0, R_RISCV_SUB32, "_start", 0);
+    obj
+}
+
+#[test]
+fn riscv_relocations() {
+    let data = link(&[riscv_object(EF_RISCV_RVC).input("main.o")]);
+    let out = Output::new(&data);
+    assert_eq!(out.file.ehdr.e_machine, EM_RISCV);
+    assert_eq!(out.file.ehdr.e_flags, EF_RISCV_RVC);
+
+    let start = out.symbol_addr("_start").unwrap();
+    let msg = out.symbol_addr("msg").unwrap();
+    let func = out.symbol_addr("func").unwrap();
+    // 2 bytes of the padding are deleted, so func is 8-byte aligned
+    assert_eq!(func, start + 32);
+    let offset = msg as i64 - start as i64;
+    let hi = (offset + 0x800) >> 12;
+    let lo = offset - (hi << 12);
+    assert_eq!(out.read_u32(start), 0x00000517 | (hi as u32) << 12);
+    assert_eq!(out.read_u32(start + 4), 0x00050513 | (lo as u32) << 20);
+    // func is 24, 8 and 4 bytes ahead of the call, the jump and the branch
+    assert_eq!(out.read_u32(start + 8), 0x00000097);
+    assert_eq!(out.read_u32(start + 12), 0x018080e7);
+    assert_eq!(out.read_u32(start + 20), 0x00000013);
+    assert_eq!(out.read_u32(start + 24), 0x0080006f);
+    assert_eq!(out.read_u32(start + 28), 0x00a50263);
+    let data = out.file.section_header_by_name(".data").unwrap().unwrap();
+    assert_eq!(out.read_u32(data.sh_addr), 32);
+}
+
+#[test]
+fn riscv_float_abi_mismatch() {
+    let inputs = [
+        riscv_object(EF_RISCV_RVC).input("soft.o"),
+        riscv_object(EF_RISCV_FLOAT_ABI_DOUBLE).input("double.o"),
+    ];
+    match bold::link(&inputs, &Config::new()) {
+        Err(LinkError::Unsupported(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert!(errors[0].contains("double-float object is incompatible with soft-float"));
+        }
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("objects with different float ABIs were linked"),
+    }
+}
@@ -0,0 +1,50 @@
+# Absolute references to symbols in a DSO are resolved by the dynamic linker. Those in
+# read-only sections are text relocations, which are rejected unless -z notext is given.
+cat <<EOF2 | cc -o %textrel.so -shared -nostdlib -x assembler -
+  .data
+  .globl var
+  .type var, @object
+  .size var, 8
+var:
+  .quad 42
+EOF2
+cat <<EOF2 | cc -o %textrel1.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov ptr, %rax
+  mov (%rax), %rdi
+  mov \$60, %eax
+  syscall
+  .data
+ptr:
+  .quad var
+EOF2
+cat <<EOF2 | cc -o %textrel2.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  movabs \$var + 8, %rax
+  mov -8(%rax), %rdi
+  mov \$60, %eax
+  syscall
+EOF2
+
+# A relocation in a writable section is not a text relocation
+cargo run %textrel1.o %textrel.so || exit 1
+readelf -rW a.out | grep -q "R_X86_64_64 .* var + 0" || exit 1
+! readelf -dW a.out | grep -q TEXTREL || exit 1
+LD_LIBRARY_PATH=. ./a.out
+[ $? = 42 ] || exit 1
+
+! cargo run %textrel2.o %textrel.so 2> %textrel.log || exit 1
+grep -q "%textrel2.o:(.text+0x2): relocation R_X86_64_64 against symbol 'var' cannot be used in read-only section .text; recompile with -fPIC" %textrel.log || exit 1
+
+cargo run %textrel2.o %textrel.so -z notext || exit 1
+readelf -rW a.out | grep -q "R_X86_64_64 .* var + 8" || exit 1
+readelf -dW a.out > %textrel.dyn
+grep -q "(TEXTREL)" %textrel.dyn || exit 1
+grep -q "(FLAGS) *TEXTREL" %textrel.dyn || exit 1
+LD_LIBRARY_PATH=. ./a.out
+[ $? = 42 ] || exit 1
+exit 0
@@ -0,0 +1,30 @@
+# The output doesn't depend on the number of threads. Each file defines 2500 symbols and
+# refers to those of the next file, so that 20000 symbols are shared between files.
+for i in $(seq 0 7); do
+  seq 0 2499 | awk -v i=$i '
+    BEGIN { print "  .data" }
+    { printf "  .globl sym%d_%d\nsym%d_%d:\n  .quad sym%d_%d\n", i, $1, i, $1, (i + 1) % 8, $1 }
+  ' | cc -o %threads$i.o -c -x assembler -
+done
+cat <<EOF2 | cc -o %threads.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  mov sym7_2499, %rax
+  lea sym0_2499, %rdx
+  sub %rdx, %rax
+  mov %rax, %rdi
+  mov \$60, %eax
+  syscall
+EOF2
+
+cargo run %threads.o %threads?.o --threads=4 || exit 1
+mv a.out %threads.out
+cargo run %threads.o %threads?.o --no-threads || exit 1
+cmp a.out %threads.out || exit 1
+# sym7_2499 holds the address of sym0_2499
+./a.out || exit 1
+
+! cargo run %threads.o --threads=0 2> %threads.log || exit 1
+grep -q "invalid argument to --threads: 0" %threads.log || exit 1
+exit 0
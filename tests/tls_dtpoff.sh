@@ -0,0 +1,51 @@
+# R_X86_64_DTPOFF32 and DTPOFF64 are resolved to offsets in the TLS block, or to TP-relative
+# offsets in code whose TLSLD sequence is relaxed
+cat <<EOF2 | cc -o %tls_dtpoff1.o -c -g -O1 -fPIC -x c -
+static __thread int x, y;
+int ld(int v) { x += v; y += v; return x + y; }
+EOF2
+cat <<EOF2 | cc -o %tls_dtpoff2.o -c -x assembler -
+  .section .tdata,"awT",@progbits
+  .quad 1
+z:
+  .quad 2
+  .data
+  .quad z@dtpoff
+  .text
+  .globl _start, __tls_get_addr
+_start:
+__tls_get_addr:
+  ret
+EOF2
+
+readelf -rW %tls_dtpoff1.o | grep -q "R_X86_64_DTPOFF32 .* x " || exit 1
+readelf -rW %tls_dtpoff2.o | grep -q "R_X86_64_DTPOFF64 .* z " || exit 1
+
+# Locations of the TLS variables in the debug info
+locations() {
+  readelf -wi $1 | grep -o "DW_OP_const8u: [0-9]*; DW_OP_form_tls_address"
+}
+data() {
+  objdump -s -j .data $1 | sed -n 5p | awk '{ print $2, $3 }'
+}
+# Displacements from %rax or %rbp in ld()
+disps() {
+  objdump -dw $1 | sed -n '/<ld>:/,/^$/p' | grep -o -- '-*0x[0-9a-f]*(%r[ab][xp])' | sort -u | xargs
+}
+
+# Without relaxation, the offsets are those in the TLS block: the 16 bytes of .tdata are
+# followed by y and x
+cargo run %tls_dtpoff1.o %tls_dtpoff2.o || exit 1
+[ "$(disps a.out)" = "0x10(%rbp) 0x14(%rax) 0x14(%rbp)" ] || exit 1
+[ "$(locations a.out | xargs)" = "$(printf 'DW_OP_const8u: 20; DW_OP_form_tls_address DW_OP_const8u: 16; DW_OP_form_tls_address')" ] || exit 1
+[ "$(data a.out)" = "08000000 00000000" ] || exit 1
+
+# With relaxation, code refers to TP-relative offsets like GNU ld does, while the debug info
+# and data keep the offsets in the TLS block
+cargo run -- --relax %tls_dtpoff1.o %tls_dtpoff2.o || exit 1
+ld -o %tls_dtpoff.ld %tls_dtpoff1.o %tls_dtpoff2.o || exit 1
+[ "$(disps a.out)" = "$(disps %tls_dtpoff.ld)" ] || exit 1
+disps a.out | grep -q -- "-0x8(%rbp)" || exit 1
+[ "$(locations a.out)" = "$(locations %tls_dtpoff.ld)" ] || exit 1
+[ "$(data a.out)" = "$(data %tls_dtpoff.ld)" ] || exit 1
+exit 0
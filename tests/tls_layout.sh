@@ -0,0 +1,42 @@
+# TLS symbols are offsets in the TLS template, and TP-relative offsets match GNU ld
+cat <<EOF2 | cc -o %tls_layout1.o -c -O1 -fPIC -ftls-model=initial-exec -x c -
+extern __thread int a;
+extern __thread long b;
+long _start(void) { return a + b; }
+EOF2
+cat <<EOF2 | cc -o %tls_layout2.o -c -x c -
+__thread int a = 1;
+_Alignas(32) __thread long b;
+long c;
+EOF2
+
+cargo run %tls_layout1.o %tls_layout2.o || exit 1
+ld -o %tls_layout.ld %tls_layout1.o %tls_layout2.o || exit 1
+
+value() {
+  readelf -sW $1 | awk -v sym=$2 '$8 == sym { print $2 }'
+}
+for sym in a b; do
+  [ "$(value a.out $sym)" = "$(value %tls_layout.ld $sym)" ] || exit 1
+done
+
+# The thread pointer is at the end of the template rounded up to its alignment
+tls() {
+  readelf -lW $1 | awk '$1 == "TLS" { print $'$2' }'
+}
+[ "$(tls a.out 6)" = "$(tls %tls_layout.ld 6)" ] || exit 1
+[ "$(tls a.out 8)" = "$(tls %tls_layout.ld 8)" ] || exit 1
+align=$(($(tls a.out 8)))
+tp_offset=$(((($(tls a.out 6) + align - 1) / align) * align))
+[ $(($(tls a.out 3) % align)) = 0 ] || exit 1
+got=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".got" { print $5, $6 }')
+entries=$(od -An -td8 -j $((0x${got% *})) -N $((0x${got#* })) a.out)
+for sym in a b; do
+  echo "$entries" | grep -qw -- "$((0x$(value a.out $sym) - tp_offset))" || exit 1
+done
+
+# .tbss doesn't take up the address space of the following sections
+bss=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".bss" { print $4 }')
+tbss=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".tbss" { print $4, $6 }')
+[ $((0x$bss)) -lt $((0x${tbss% *} + 0x${tbss#* })) ] || exit 1
+exit 0
@@ -0,0 +1,35 @@
+# Local-exec TLS accesses are resolved to TP-relative offsets by R_X86_64_TPOFF32
+cat <<EOF2 | cc -o %tls_local_exec.o -c -O1 -ftls-model=local-exec -x c -
+__thread int a = 3;
+__thread long b __attribute__((aligned(16)));
+__thread char c;
+long _start(void) { return a + b + c; }
+EOF2
+echo '.section .tdata,"awT",@progbits; .globl x; x: .quad 1; .text; movabs $x@tpoff, %rax' |
+  cc -o %tls_local_exec2.o -c -x assembler -
+
+readelf -rW %tls_local_exec.o | grep -q "R_X86_64_TPOFF32 .* b " || exit 1
+readelf -rW %tls_local_exec2.o | grep -q "R_X86_64_TPOFF64 .* x " || exit 1
+
+# Instructions of the object without addresses
+disasm() {
+  objdump -dw $1 | grep -E '^ +[0-9a-f]+:' | cut -f2- | sed 's/\s\+/ /g'
+}
+
+cargo run %tls_local_exec.o %tls_local_exec2.o || exit 1
+ld -o %tls_local_exec.ld %tls_local_exec.o %tls_local_exec2.o || exit 1
+disasm a.out | grep -q "%fs:0xffffffffffff" || exit 1
+[ "$(disasm a.out)" = "$(disasm %tls_local_exec.ld)" ] || exit 1
+
+# An offset which doesn't fit in 32 bits is an error
+cat <<EOF2 | cc -o %tls_local_exec3.o -c -x assembler -
+  .section .tbss,"awT",@nobits
+  .globl big
+big:
+  .zero 0x80000000
+  .text
+  mov %fs:big@tpoff, %eax
+EOF2
+! cargo run %tls_local_exec.o %tls_local_exec3.o 2> %tls_local_exec.log || exit 1
+grep -q "relocation R_X86_64_TPOFF32 out of range" %tls_local_exec.log || exit 1
+exit 0
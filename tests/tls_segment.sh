@@ -0,0 +1,30 @@
+# .tdata and .tbss are covered by PT_TLS
+cat <<EOF2 | cc -o %tls_segment1.o -c -x c -
+__thread int x = 42;
+__thread long y;
+_Alignas(16) __thread char z[3] = "ab";
+EOF2
+cat <<EOF2 | cc -o %tls_segment2.o -c -x assembler -
+  .text
+  .globl _start
+_start:
+  jmp _start
+EOF2
+
+cargo run %tls_segment1.o %tls_segment2.o || exit 1
+# The program header table has room for PT_TLS
+[ -z "$(readelf -lW a.out 2>&1 >/dev/null)" ] || exit 1
+phdr=$(readelf -lW a.out | awk '$1 == "PHDR" { print $5 }')
+[ $((phdr)) = $(($(readelf -hW a.out | awk '/Number of program headers/ { print $5 }') * 56)) ] || exit 1
+[ "$(readelf -lW a.out | grep -c TLS)" = 1 ] || exit 1
+tls=$(readelf -lW a.out | awk '$1 == "TLS"')
+tdata=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".tdata"')
+tbss=$(readelf -SW a.out | sed 's/\[ */[/' | awk '$2 == ".tbss"')
+# p_offset and p_vaddr are those of .tdata
+[ $(($(echo $tls | cut -d' ' -f2))) = $((0x$(echo $tdata | cut -d' ' -f5))) ] || exit 1
+[ $(($(echo $tls | cut -d' ' -f3))) = $((0x$(echo $tdata | cut -d' ' -f4))) ] || exit 1
+# p_filesz is the size of .tdata, and p_memsz extends to the end of .tbss
+[ $(($(echo $tls | cut -d' ' -f5))) = $((0x$(echo $tdata | cut -d' ' -f6))) ] || exit 1
+[ $(($(echo $tls | cut -d' ' -f3) + $(echo $tls | cut -d' ' -f6))) = $((0x$(echo $tbss | cut -d' ' -f4) + 0x$(echo $tbss | cut -d' ' -f6))) ] || exit 1
+[ "$(echo $tls | awk '{ print $NF }')" = 0x10 ] || exit 1
+exit 0
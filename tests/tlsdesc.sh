@@ -0,0 +1,68 @@
+# TLS descriptor sequences (-mtls-dialect=gnu2) are relaxed to local-exec for variables
+# defined in the executable and to initial-exec for those in DSOs
+cat <<EOF2 | cc -o %tlsdesc.so -shared -nostdlib -x assembler -
+  .section .tdata,"awT",@progbits
+  .globl ext
+  .type ext, @object
+ext:
+  .long 37
+EOF2
+cat <<EOF2 | cc -o %tlsdesc1.o -c -O1 -fPIC -mtls-dialect=gnu2 -x c -
+__thread int a = 3;
+static __thread int b = 2;
+extern __thread int c;
+int get(void) { return a + b + c; }
+EOF2
+cat <<EOF2 | cc -o %tlsdesc2.o -c -x assembler -
+  .section .tdata,"awT",@progbits
+  .globl c
+c:
+  .long 5
+  .text
+  .globl _start
+_start:
+  call get
+  mov %eax, %edi
+  mov \$60, %eax
+  syscall
+EOF2
+cat <<EOF2 | cc -o %tlsdesc3.o -c -x assembler -
+  .section .tdata,"awT",@progbits
+  .globl c
+c:
+  .long 5
+  .text
+  .globl _start
+_start:
+  call get
+  mov %eax, %edi
+  lea ext@tlsdesc(%rip), %rax
+  call *ext@tlscall(%rax)
+  add %fs:(%rax), %edi
+  mov \$60, %eax
+  syscall
+EOF2
+
+readelf -rW %tlsdesc1.o | grep -q "R_X86_64_GOTPC32_TLSDESC .* a " || exit 1
+readelf -rW %tlsdesc1.o | grep -q "R_X86_64_TLSDESC_CALL .* a " || exit 1
+
+# Instructions of the function without addresses
+disasm() {
+  objdump -dw $1 | sed -n '/<get>:/,/^$/p' | grep -E '^ +[0-9a-f]+:' | cut -f2- |
+    sed -e 's/ *#.*//' -e 's/\s\+/ /g'
+}
+
+cargo run %tlsdesc1.o %tlsdesc2.o || exit 1
+ld -o %tlsdesc.ld %tlsdesc1.o %tlsdesc2.o || exit 1
+disasm a.out | grep -q "^48 c7 c0 [0-9a-f ]* mov \$0x[0-9a-f]*,%rax" || exit 1
+disasm a.out | grep -q "^66 90 xchg %ax,%ax" || exit 1
+[ "$(disasm a.out)" = "$(disasm %tlsdesc.ld)" ] || exit 1
+
+# ext is accessed through a GOT entry filled by R_X86_64_TPOFF64
+cargo run %tlsdesc1.o %tlsdesc3.o %tlsdesc.so || exit 1
+objdump -dw a.out | sed -n '/<_start>:/,/^$/p' | sed 's/\s\+/ /g' |
+  grep -q "48 8b 05 [0-9a-f ]* mov 0x[0-9a-f]*(%rip),%rax" || exit 1
+readelf -rW a.out | grep -q "R_X86_64_TPOFF64 .* ext + 0" || exit 1
+LD_LIBRARY_PATH=. ./a.out
+[ $? = 47 ] || exit 1
+exit 0
@@ -0,0 +1,45 @@
+# Undefined symbols are errors by default, and can be downgraded to warnings.
+cat <<EOF2 | cc -o %unresolved.o -c -x assembler -
+  .text
+  .globl _start
+  .weak maybe
+_start:
+  call missing
+  mov maybe(%rip), %rax
+  jmp _start
+EOF2
+
+rm -f a.out
+cargo run %unresolved.o 2> %unresolved.log && exit 1
+grep -q "undefined symbol: missing" %unresolved.log || exit 1
+# Weak references are not reported
+grep -q "undefined symbol: maybe" %unresolved.log && exit 1
+[ -e a.out ] && exit 1
+
+cargo run -- --warn-unresolved-symbols %unresolved.o 2> %unresolved.log || exit 1
+grep -q "WARN.*undefined symbol: missing" %unresolved.log || exit 1
+grep -q "1 undefined symbols were resolved to 0" %unresolved.log || exit 1
+[ -e a.out ] || exit 1
+
+# --warn-unresolved-symbols takes precedence over -z defs
+cargo run -- -z defs --warn-unresolved-symbols %unresolved.o || exit 1
+
+# The last option wins
+cargo run -- --warn-unresolved-symbols --error-unresolved-symbols %unresolved.o && exit 1
+
+# -z undefs does not report them at all
+cargo run -- -z undefs %unresolved.o 2> %unresolved.log || exit 1
+grep -q "undefined symbol" %unresolved.log && exit 1
+
+# Shared objects may leave symbols undefined unless -z defs is given
+cat <<EOF2 | cc -o %unresolved_pic.o -c -x assembler -
+  .text
+  .globl foo
+foo:
+  jmp missing@PLT
+EOF2
+cargo run -- -shared %unresolved_pic.o 2> %unresolved.log || exit 1
+grep -q "undefined symbol" %unresolved.log && exit 1
+cargo run -- -shared -z defs %unresolved_pic.o 2> %unresolved.log && exit 1
+grep -q "undefined symbol: missing" %unresolved.log || exit 1
+exit 0
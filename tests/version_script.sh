@@ -0,0 +1,68 @@
+# A version script assigns versions to the symbols exported from a shared object, which are
+# defined in .gnu.version_d, and hides the others
+cat <<EOF2 | cc -o %version_script.o -c -fPIC -x c -
+int foo(void) { return 1; }
+int bar(void) { return foo() + 1; }
+int baz(void) { return 40; }
+int hidden(void) { return 0; }
+EOF2
+cat <<EOF2 > %version_script.map
+VERS_1 {
+  global:
+    foo;
+    ba*;
+  local:
+    *;
+};
+/* baz moved to a newer version */
+VERS_2 {
+  global: baz;
+} VERS_1;
+EOF2
+cat <<EOF2 | cc -o %version_script_main.o -c -fPIC -x c -
+#include <stdio.h>
+int foo(void);
+int bar(void);
+int baz(void);
+int main(void) { printf("%d\n", foo() + bar() + baz()); return 0; }
+EOF2
+
+cargo run -- -shared -soname %libversion_script.so --version-script %version_script.map \
+  %version_script.o || exit 1
+mv a.out %libversion_script.so
+readelf -dW %libversion_script.so > %version_script.dyn
+grep -q "(SONAME) *Library soname: \[%libversion_script.so\]" %version_script.dyn || exit 1
+grep -q "(VERDEFNUM) *3" %version_script.dyn || exit 1
+readelf -VW %libversion_script.so > %version_script.ver
+grep -q "Rev: 1  Flags: BASE  Index: 1  Cnt: 1  Name: %libversion_script.so" %version_script.ver || exit 1
+grep -q "Rev: 1  Flags: none  Index: 2  Cnt: 1  Name: VERS_1" %version_script.ver || exit 1
+grep -q "Rev: 1  Flags: none  Index: 3  Cnt: 2  Name: VERS_2" %version_script.ver || exit 1
+grep -q "Parent 1: VERS_1" %version_script.ver || exit 1
+readelf --dyn-syms -W %libversion_script.so > %version_script.dynsym
+grep -q " foo@@VERS_1$" %version_script.dynsym || exit 1
+grep -q " bar@@VERS_1$" %version_script.dynsym || exit 1
+grep -q " baz@@VERS_2$" %version_script.dynsym || exit 1
+! grep -q hidden %version_script.dynsym || exit 1
+
+# Programs linked against it require the versions
+cc -o %version_script %version_script_main.o %libversion_script.so || exit 1
+readelf -VW %version_script | grep -q "Name: VERS_2  Flags: none" || exit 1
+[ "$(LD_LIBRARY_PATH=. ./%version_script)" = 43 ] || exit 1
+
+# Anonymous versions can't be combined with named ones
+cat <<EOF2 > %version_script_bad.map
+{ global: foo; };
+VERS_1 { global: bar; };
+EOF2
+! cargo run -- -shared --version-script %version_script_bad.map %version_script.o \
+  2> %version_script.log || exit 1
+grep -q "%version_script_bad.map: line 2: anonymous version cannot be combined with other versions" %version_script.log || exit 1
+
+# Versions can only inherit from those defined before them
+cat <<EOF2 > %version_script_bad.map
+VERS_2 { global: foo; } VERS_1;
+EOF2
+! cargo run -- -shared --version-script %version_script_bad.map %version_script.o \
+  2> %version_script.log || exit 1
+grep -q "%version_script_bad.map: line 1: undefined version: VERS_1" %version_script.log || exit 1
+exit 0
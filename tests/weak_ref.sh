@@ -0,0 +1,42 @@
+# References keep their own attributes while binding to the definition.
+cat <<EOF2 | cc -o %weak_ref1.o -c -x assembler -
+  .text
+  .globl _start
+  .weak func
+  .weak data
+_start:
+  call func
+  mov data(%rip), %rax
+  jmp _start
+EOF2
+cat <<EOF2 | cc -o %weak_ref2.o -c -x assembler -
+  .text
+  .weak func
+  .type func, @function
+func:
+  ud2
+  .size func, .-func
+EOF2
+cat <<EOF2 | cc -o %weak_ref3.o -c -x assembler -
+  .text
+  .globl func
+  .type func, @function
+func:
+  ret
+  .size func, .-func
+
+  .data
+  .globl data
+  .type data, @object
+  .size data, 8
+data:
+  .quad 42
+EOF2
+
+cargo run %weak_ref1.o %weak_ref2.o %weak_ref3.o
+
+# The strong definition wins over the weak one, and is the only one written
+[ "$(readelf -sW a.out | awk '$8 == "func" { print $3, $4, $5 }')" = "1 FUNC GLOBAL" ] || exit 1
+[ "$(readelf -sW a.out | awk '$8 == "data" { print $3, $4, $5 }')" = "8 OBJECT GLOBAL" ] || exit 1
+func=$(readelf -sW a.out | awk '$8 == "func" { print $2 }' | sed 's/^0*//')
+objdump -d a.out | grep -q "call *$func <func>" || exit 1